@@ -0,0 +1,59 @@
+use std::convert::Infallible;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use tx::chain::TxChain;
+use tx::prelude::*;
+
+// Chains of eight trivial steps, run on a context without a database, so that what is
+// measured is what the chain itself costs.
+
+struct Counter(u64);
+
+fn step(n: u64) -> impl Tx<Counter, Item = u64, Err = Infallible, Mode = AsyncMode> {
+    with_tx_async(move |ctx: &mut Counter| {
+        Box::pin(async move {
+            ctx.0 += 1;
+            Ok(n + 1)
+        })
+    })
+}
+
+fn nested() -> impl Tx<Counter, Item = u64, Err = Infallible, Mode = AsyncMode> {
+    step(0)
+        .and_then(step)
+        .and_then(step)
+        .and_then(step)
+        .and_then(step)
+        .and_then(step)
+        .and_then(step)
+        .and_then(step)
+}
+
+fn boxed() -> TxChain<Counter, u64, Infallible> {
+    TxChain::new(step(0))
+        .and_then(step)
+        .and_then(step)
+        .and_then(step)
+        .and_then(step)
+        .and_then(step)
+        .and_then(step)
+        .and_then(step)
+}
+
+fn bench(c: &mut Criterion) {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let mut group = c.benchmark_group("eight steps");
+    group.bench_function("nested combinators", |b| {
+        b.iter(|| rt.block_on(nested().run(&mut Counter(0))).map(black_box))
+    });
+    group.bench_function("TxChain", |b| {
+        b.iter(|| rt.block_on(boxed().run(&mut Counter(0))).map(black_box))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
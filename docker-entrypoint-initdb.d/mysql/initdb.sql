@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS todos
+(
+    id          BIGINT PRIMARY KEY AUTO_INCREMENT,
+    description TEXT    NOT NULL,
+    done        BOOLEAN NOT NULL DEFAULT FALSE
+);
@@ -0,0 +1,6 @@
+CREATE TABLE todo_notes
+(
+    id      BIGINT PRIMARY KEY,
+    todo_id BIGINT NOT NULL REFERENCES todos (id),
+    body    TEXT   NOT NULL
+);
@@ -0,0 +1,101 @@
+use actix_web::http::StatusCode;
+use actix_web::{middleware, test, web, App, HttpResponse};
+use sqlx::{query, Postgres};
+
+use tx::prelude::*;
+use tx::runner::{self, ActixRequestTx, PgCtx};
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+fn insert_todo_tx(id: i64) -> impl Tx<PgCtx, Item = (), Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(move |transaction: &mut PgCtx| {
+        Box::pin(async move {
+            query!(
+                r#"INSERT INTO todos (id, description) VALUES ( $1, $2 )"#,
+                id,
+                "todo from a request"
+            )
+            .execute(&mut **transaction)
+            .await?;
+            Ok(())
+        })
+    })
+}
+
+async fn create_todo(
+    id: web::Path<i64>,
+    tx: ActixRequestTx<Postgres>,
+) -> actix_web::Result<HttpResponse> {
+    tx.run(insert_todo_tx(*id))
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Created().finish())
+}
+
+// inserts like `create_todo`, then blows up; the insert is rolled back all the same
+async fn panic_todo(
+    id: web::Path<i64>,
+    tx: ActixRequestTx<Postgres>,
+) -> actix_web::Result<HttpResponse> {
+    tx.run(insert_todo_tx(*id))
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    panic!("handler failed after its insert");
+}
+
+async fn exists(pool: &sqlx::PgPool, id: i64) -> Result<bool, sqlx::Error> {
+    let todo = query!(r#"SELECT id FROM todos WHERE id = $1"#, id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(todo.is_some())
+}
+
+async fn run_app() -> Result<(), Error> {
+    let conn_str =
+        std::env::var("DATABASE_URL").expect("Env var DATABASE_URL is required for this example.");
+    let pool = sqlx::PgPool::connect(&conn_str).await?;
+
+    let test_id = 22;
+
+    query!(
+        r#"DELETE FROM todos WHERE id BETWEEN $1 AND $2"#,
+        test_id,
+        test_id + 1
+    )
+    .execute(&pool)
+    .await?;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .wrap(middleware::from_fn(
+                runner::actix_transaction_per_request::<Postgres, _>,
+            ))
+            .route("/todos/{id}", web::post().to(create_todo))
+            .route("/todos/{id}/panic", web::post().to(panic_todo)),
+    )
+    .await;
+
+    let request = test::TestRequest::post()
+        .uri(&format!("/todos/{}", test_id))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert!(exists(&pool, test_id).await?);
+
+    let request = test::TestRequest::post()
+        .uri(&format!("/todos/{}/panic", test_id + 1))
+        .to_request();
+    let response = test::try_call_service(&app, request).await;
+    assert!(response.is_err());
+    assert!(!exists(&pool, test_id + 1).await?);
+
+    Ok(())
+}
+
+pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    // actix-web runs on its own single threaded runtime
+    tokio::task::spawn_blocking(|| actix_web::rt::System::new().block_on(run_app()))
+        .await?
+        .map_err(|e| e as Box<dyn std::error::Error>)
+}
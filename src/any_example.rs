@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use tx::prelude::*;
+use tx::runner::{self, AnyCtx};
+
+// A chain compiled once and run against whatever `DATABASE_URL` points to. The bind placeholder
+// syntax differs between the databases (`$1` vs `?`), so the id is formatted into the SQL.
+
+fn insert_and_verify_tx(
+    test_id: i64,
+) -> impl Tx<AnyCtx, Item = (), Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(move |transaction: &mut AnyCtx| {
+        Box::pin(async move {
+            sqlx::query(&format!(
+                "INSERT INTO todos (id, description) VALUES ( {}, 'test todo' )",
+                test_id
+            ))
+            .execute(&mut **transaction)
+            .await?;
+
+            // check that inserted todo can be fetched inside the uncommitted transaction
+            let _ = sqlx::query(&format!("SELECT id FROM todos WHERE id = {}", test_id))
+                .fetch_one(&mut **transaction)
+                .await?;
+
+            Ok(())
+        })
+    })
+}
+
+async fn exists(pool: &sqlx::AnyPool, test_id: i64) -> Result<bool, sqlx::Error> {
+    let todo = sqlx::query(&format!("SELECT id FROM todos WHERE id = {}", test_id))
+        .fetch_optional(pool)
+        .await?;
+    Ok(todo.is_some())
+}
+
+pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    sqlx::any::install_default_drivers();
+
+    let conn_str =
+        std::env::var("DATABASE_URL").expect("Env var DATABASE_URL is required for this example.");
+    let pool = sqlx::AnyPool::connect(&conn_str).await?;
+
+    let test_id = 13;
+
+    sqlx::query(&format!("DELETE FROM todos WHERE id = {}", test_id))
+        .execute(&pool)
+        .await?;
+
+    // whatever the database cannot honour is dropped instead of failing the transaction
+    let options = runner::TxOptions::new()
+        .isolation_level(runner::IsolationLevel::RepeatableRead)
+        .statement_timeout(Duration::from_secs(5));
+    let chain = insert_and_verify_tx(test_id).and_then(|()| {
+        with_tx_async(|transaction: &mut AnyCtx| {
+            Box::pin(async move { Ok(transaction.capabilities()) })
+        })
+    });
+    let capabilities = runner::run_tx_with(&pool, options, chain).await?;
+
+    assert!(capabilities.savepoints);
+    assert!(exists(&pool, test_id).await?);
+
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use std::fmt::Display;
+
+use serde::Serialize;
+use sqlx::types::Json;
+use sqlx::PgPool;
+
+use crate::combinator::{AsyncMode, BoxFuture, Description, Tx};
+use crate::runner::{self, PgCtx};
+
+pub async fn create_table(pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"CREATE TABLE IF NOT EXISTS audit_log
+           (
+               id          BIGSERIAL PRIMARY KEY,
+               actor       TEXT        NOT NULL,
+               action      TEXT        NOT NULL,
+               outcome     TEXT        NOT NULL,
+               detail      JSONB       NOT NULL,
+               recorded_at TIMESTAMPTZ NOT NULL DEFAULT clock_timestamp()
+           )"#,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// How the item of an audited chain ends up in the `detail` of its audit record.
+pub trait AuditSerializer<T> {
+    fn serialize(&self, item: &T) -> serde_json::Value;
+}
+impl<T, F> AuditSerializer<T> for F
+where
+    F: Fn(&T) -> serde_json::Value,
+{
+    fn serialize(&self, item: &T) -> serde_json::Value {
+        self(item)
+    }
+}
+
+// The item as it is, for items which are `Serialize`.
+pub struct AsJson;
+impl<T: Serialize> AuditSerializer<T> for AsJson {
+    fn serialize(&self, item: &T) -> serde_json::Value {
+        serde_json::to_value(item).unwrap_or(serde_json::Value::Null)
+    }
+}
+
+// Nothing of the item, for items which must not be kept.
+pub struct Redacted;
+impl<T> AuditSerializer<T> for Redacted {
+    fn serialize(&self, _: &T) -> serde_json::Value {
+        serde_json::Value::Null
+    }
+}
+
+// Runs `tx` in a savepoint, then records who did what, and how it went, in `audit_log` in the
+// same transaction. The item or error is passed on untouched. A failure is recorded as well,
+// but the record only lasts if the surrounding chain recovers from the error: otherwise it is
+// rolled back with everything else.
+#[derive(Debug, Clone)]
+pub struct Audited<X, S> {
+    tx: X,
+    actor: String,
+    action: String,
+    serializer: S,
+}
+impl<X, S> Tx<PgCtx> for Audited<X, S>
+where
+    X: Tx<PgCtx, Mode = AsyncMode> + Send,
+    X::Item: Send,
+    X::Err: From<sqlx::Error> + Display + Send,
+    S: AuditSerializer<X::Item> + Send,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut PgCtx) -> BoxFuture<'a, Result<Self::Item, Self::Err>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            let result = runner::savepoint(self.tx).run(&mut *ctx).await;
+            let (outcome, detail) = match &result {
+                Ok(t) => ("ok", self.serializer.serialize(t)),
+                Err(e) => ("error", serde_json::Value::String(e.to_string())),
+            };
+            sqlx::query(
+                "INSERT INTO audit_log (actor, action, outcome, detail) VALUES ($1, $2, $3, $4)",
+            )
+            .bind(&self.actor)
+            .bind(&self.action)
+            .bind(outcome)
+            .bind(Json(detail))
+            .execute(&mut **ctx)
+            .await?;
+            result
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("audited", vec![self.tx.describe()])
+    }
+}
+
+pub trait AuditedExt: Tx<PgCtx, Mode = AsyncMode> {
+    fn audited(self, actor: impl Into<String>, action: impl Into<String>) -> Audited<Self, AsJson>
+    where
+        Self::Item: Serialize,
+        Self: Sized,
+    {
+        self.audited_with(actor, action, AsJson)
+    }
+    fn audited_with<S>(
+        self,
+        actor: impl Into<String>,
+        action: impl Into<String>,
+        serializer: S,
+    ) -> Audited<Self, S>
+    where
+        S: AuditSerializer<Self::Item>,
+        Self: Sized,
+    {
+        Audited {
+            tx: self,
+            actor: actor.into(),
+            action: action.into(),
+            serializer,
+        }
+    }
+}
+impl<X> AuditedExt for X where X: Tx<PgCtx, Mode = AsyncMode> {}
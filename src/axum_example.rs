@@ -0,0 +1,96 @@
+use axum::body::Body;
+use axum::extract::Path;
+use axum::http::{Request, StatusCode};
+use axum::routing::post;
+use axum::Router;
+use sqlx::{query, Postgres};
+use tower::ServiceExt;
+
+use tx::prelude::*;
+use tx::runner::{self, PgCtx, RequestTx};
+
+fn insert_todo_tx(id: i64) -> impl Tx<PgCtx, Item = (), Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(move |transaction: &mut PgCtx| {
+        Box::pin(async move {
+            query!(
+                r#"INSERT INTO todos (id, description) VALUES ( $1, $2 )"#,
+                id,
+                "todo from a request"
+            )
+            .execute(&mut **transaction)
+            .await?;
+            Ok(())
+        })
+    })
+}
+
+async fn create_todo(
+    Path(id): Path<i64>,
+    tx: RequestTx<Postgres>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    tx.run(insert_todo_tx(id))
+        .await
+        .map_err(|e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(StatusCode::CREATED)
+}
+
+// inserts like `create_todo`, but then turns the request down, so the insert is rolled back
+async fn reject_todo(
+    Path(id): Path<i64>,
+    tx: RequestTx<Postgres>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    tx.run(insert_todo_tx(id))
+        .await
+        .map_err(|e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(StatusCode::CONFLICT)
+}
+
+async fn exists(pool: &sqlx::PgPool, id: i64) -> Result<bool, sqlx::Error> {
+    let todo = query!(r#"SELECT id FROM todos WHERE id = $1"#, id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(todo.is_some())
+}
+
+pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let conn_str =
+        std::env::var("DATABASE_URL").expect("Env var DATABASE_URL is required for this example.");
+    let pool = sqlx::PgPool::connect(&conn_str).await?;
+
+    let app = Router::new()
+        .route("/todos/:id", post(create_todo))
+        .route("/todos/:id/reject", post(reject_todo))
+        .layer(axum::middleware::from_fn_with_state(
+            pool.clone(),
+            runner::transaction_per_request::<Postgres>,
+        ));
+
+    let test_id = 20;
+
+    query!(
+        r#"DELETE FROM todos WHERE id BETWEEN $1 AND $2"#,
+        test_id,
+        test_id + 1
+    )
+    .execute(&pool)
+    .await?;
+
+    let response = app
+        .clone()
+        .oneshot(Request::post(format!("/todos/{}", test_id)).body(Body::empty())?)
+        .await?;
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    // committed, as the response was a success
+    assert!(exists(&pool, test_id).await?);
+
+    let response = app
+        .oneshot(Request::post(format!("/todos/{}/reject", test_id + 1)).body(Body::empty())?)
+        .await?;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    // rolled back, as it was not
+    assert!(!exists(&pool, test_id + 1).await?);
+
+    Ok(())
+}
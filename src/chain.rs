@@ -0,0 +1,147 @@
+use std::any::Any;
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::combinator::{AsyncMode, BoxFuture, Description, IntoTx, Tx};
+
+// A chain of steps boxed one by one, for call sites where the nested types of the
+// combinators cost too much to compile: `a.and_then(f).and_then(g)` is a type of its own,
+// growing with every step and compiled anew for every context, while
+//
+//     TxChain::new(a).and_then(f).and_then(g)
+//
+// is a `TxChain<Ctx, T, E>` whatever its steps. The price is paid when it runs: a box for
+// each step, its future and the value it hands over, and a downcast of the latter;
+// `cargo bench --bench chain` compares both with chains of trivial steps, where that cost is
+// all there is. Mix them freely, a `TxChain` being a step like any other, in `AsyncMode`.
+pub struct TxChain<Ctx, T, E> {
+    steps: Vec<BoxStep<Ctx, E>>,
+    description: Vec<Description>,
+    item: PhantomData<fn() -> T>,
+}
+
+// A step of the chain, handed the value of the one before it.
+type BoxStep<Ctx, E> =
+    Box<dyn for<'c> FnOnce(&'c mut Ctx, Value) -> BoxFuture<'c, Result<Value, E>> + Send>;
+type Value = Box<dyn Any + Send>;
+
+impl<Ctx, T, E> TxChain<Ctx, T, E>
+where
+    Ctx: Send + 'static,
+    T: Send + 'static,
+    E: Send + 'static,
+{
+    pub fn new<X>(tx: X) -> Self
+    where
+        X: Tx<Ctx, Item = T, Err = E, Mode = AsyncMode> + Send + 'static,
+    {
+        let description = vec![tx.describe()];
+        Self {
+            steps: vec![boxed(move |()| tx)],
+            description,
+            item: PhantomData,
+        }
+    }
+
+    pub fn and_then<I, F>(self, f: F) -> TxChain<Ctx, <I::Tx as Tx<Ctx>>::Item, E>
+    where
+        I: IntoTx<Ctx>,
+        I::Tx: Tx<Ctx, Err = E, Mode = AsyncMode> + Send + 'static,
+        <I::Tx as Tx<Ctx>>::Item: Send + 'static,
+        F: FnOnce(T) -> I + Send + 'static,
+    {
+        self.push(Description::leaf("and_then"), move |item| f(item).into_tx())
+    }
+
+    pub fn map<U, F>(self, f: F) -> TxChain<Ctx, U, E>
+    where
+        U: Send + 'static,
+        F: FnOnce(T) -> U + Send + 'static,
+    {
+        let mut chain = self.retype();
+        chain.description.push(Description::leaf("map"));
+        chain.steps.push(Box::new(move |_, value| {
+            let value: Value = Box::new(f(downcast(value)));
+            Box::pin(async move { Ok(value) })
+        }));
+        chain
+    }
+
+    fn push<X, F>(self, description: Description, f: F) -> TxChain<Ctx, X::Item, E>
+    where
+        X: Tx<Ctx, Err = E, Mode = AsyncMode> + Send + 'static,
+        X::Item: Send + 'static,
+        F: FnOnce(T) -> X + Send + 'static,
+    {
+        let mut chain = self.retype();
+        chain.description.push(description);
+        chain.steps.push(boxed(f));
+        chain
+    }
+
+    fn retype<U>(self) -> TxChain<Ctx, U, E> {
+        TxChain {
+            steps: self.steps,
+            description: self.description,
+            item: PhantomData,
+        }
+    }
+}
+
+fn boxed<Ctx, T, E, X, F>(f: F) -> BoxStep<Ctx, E>
+where
+    Ctx: 'static,
+    T: 'static,
+    E: 'static,
+    X: Tx<Ctx, Err = E, Mode = AsyncMode> + Send + 'static,
+    X::Item: Send + 'static,
+    F: FnOnce(T) -> X + Send + 'static,
+{
+    Box::new(move |ctx, value| {
+        let run = f(downcast(value)).run(ctx);
+        Box::pin(async move { run.await.map(|item| Box::new(item) as Value) })
+    })
+}
+
+// The steps are only pushed with the type of the value of the one before them.
+fn downcast<T: 'static>(value: Value) -> T {
+    match value.downcast() {
+        Ok(value) => *value,
+        Err(_) => unreachable!("a step of a TxChain handed a value of another type"),
+    }
+}
+
+impl<Ctx, T, E> Tx<Ctx> for TxChain<Ctx, T, E>
+where
+    Ctx: Send,
+    T: 'static,
+{
+    type Item = T;
+    type Err = E;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> BoxFuture<'a, Result<Self::Item, Self::Err>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            let mut value: Value = Box::new(());
+            for step in self.steps {
+                value = step(ctx, value).await?;
+            }
+            Ok(downcast(value))
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("chain", self.description.clone())
+    }
+}
+
+impl<Ctx, T, E> fmt::Debug for TxChain<Ctx, T, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TxChain")
+            .field("steps", &self.steps.len())
+            .finish_non_exhaustive()
+    }
+}
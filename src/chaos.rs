@@ -0,0 +1,162 @@
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::combinator::{Chain, Description, Output, Tx};
+use crate::rt::TimedOut;
+use crate::runner::SERIALIZATION_FAILURE;
+
+// Fault injection, to exercise the retry and rollback paths of a chain without waiting for
+// the database to fail: a step wrapped by `inject_fault` randomly reports one of the policy's
+// faults instead of its success, as the database or the network would.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    ConnectionLost,
+    SerializationFailure,
+    Timeout,
+}
+impl Fault {
+    // The error the fault is reported as, classified like the real one by `SqlState`.
+    pub fn error(self) -> sqlx::Error {
+        match self {
+            Fault::ConnectionLost => sqlx::Error::Io(std::io::Error::new(
+                std::io::ErrorKind::ConnectionReset,
+                "injected fault: connection lost",
+            )),
+            Fault::SerializationFailure => sqlx::Error::Database(Box::new(InjectedDbError {
+                code: SERIALIZATION_FAILURE,
+                message: "injected fault: could not serialize access".to_string(),
+            })),
+            Fault::Timeout => TimedOut {
+                after: Duration::ZERO,
+            }
+            .into(),
+        }
+    }
+}
+
+// Which faults to inject, and how often. Clones share the random number generator, so a
+// seeded policy draws the same sequence of faults across the attempts of a retried chain,
+// and a failing CI run can be replayed.
+#[derive(Debug, Clone)]
+pub struct FaultPolicy {
+    rate: f64,
+    faults: Vec<Fault>,
+    rng: Arc<Mutex<StdRng>>,
+}
+impl FaultPolicy {
+    // Fails a `rate` share of the successes, 0.0 to 1.0, with any of the faults.
+    pub fn new(rate: f64) -> Self {
+        Self {
+            rate: rate.clamp(0.0, 1.0),
+            faults: vec![
+                Fault::ConnectionLost,
+                Fault::SerializationFailure,
+                Fault::Timeout,
+            ],
+            rng: Arc::new(Mutex::new(StdRng::from_entropy())),
+        }
+    }
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.rng = Arc::new(Mutex::new(StdRng::seed_from_u64(seed)));
+        self
+    }
+    // The faults to pick from, evenly; none disables the injection.
+    pub fn faults(mut self, faults: impl IntoIterator<Item = Fault>) -> Self {
+        self.faults = faults.into_iter().collect();
+        self
+    }
+
+    // The fault to inject into the next success, if any.
+    pub fn draw(&self) -> Option<Fault> {
+        if self.faults.is_empty() {
+            return None;
+        }
+        let mut rng = self.rng.lock().unwrap();
+        if rng.gen_bool(self.rate) {
+            Some(self.faults[rng.gen_range(0..self.faults.len())])
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InjectFault<X> {
+    tx: X,
+    policy: FaultPolicy,
+}
+impl<Ctx, X> Tx<Ctx> for InjectFault<X>
+where
+    X: Tx<Ctx>,
+    X::Mode: Chain<Ctx, X, FaultPolicy>,
+    X::Err: From<sqlx::Error>,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = X::Mode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        X::Mode::map(self.tx, ctx, self.policy, |policy, r| {
+            match (r, policy.draw()) {
+                (Ok(_), Some(fault)) => Err(fault.error().into()),
+                (r, _) => r,
+            }
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("inject_fault", vec![self.tx.describe()])
+    }
+}
+
+pub trait FaultExt<Ctx>: Tx<Ctx> {
+    // Runs the step, then turns its success into a fault drawn from `policy`; the work
+    // is done all the same, so the surrounding transaction must roll it back.
+    fn inject_fault(self, policy: FaultPolicy) -> InjectFault<Self>
+    where
+        Self: Sized,
+    {
+        InjectFault { tx: self, policy }
+    }
+}
+impl<Ctx, X: Tx<Ctx>> FaultExt<Ctx> for X {}
+
+#[derive(Debug)]
+struct InjectedDbError {
+    code: &'static str,
+    message: String,
+}
+impl std::fmt::Display for InjectedDbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+impl std::error::Error for InjectedDbError {}
+impl sqlx::error::DatabaseError for InjectedDbError {
+    fn message(&self) -> &str {
+        &self.message
+    }
+    fn code(&self) -> Option<Cow<'_, str>> {
+        Some(self.code.into())
+    }
+    fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+        self
+    }
+    fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+        self
+    }
+    fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+        self
+    }
+    fn kind(&self) -> sqlx::error::ErrorKind {
+        sqlx::error::ErrorKind::Other
+    }
+}
@@ -0,0 +1,207 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+// The command line of the example binary. Without a command it runs every example, as
+// `cargo run` always did; the commands run one part of them against `DATABASE_URL`, on a pool
+// sized and configured by the flags below or their environment variables.
+//
+//     cargo run -- demo rollback --id 7
+//     DATABASE_ISOLATION=serializable cargo run -- bench --transactions 500
+
+#[derive(Debug, Parser)]
+#[command(name = "sqlx-test", about = "Examples of composable sqlx transactions")]
+pub struct Cli {
+    #[command(flatten)]
+    pub pool: PoolArgs,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Runs one scenario of the Postgres example on the todo `id`.
+    #[cfg(feature = "postgres")]
+    Demo {
+        #[arg(value_enum)]
+        scenario: Scenario,
+        #[arg(long, default_value_t = 1)]
+        id: i64,
+    },
+    /// Applies the pending migrations.
+    #[cfg(feature = "postgres")]
+    Migrate,
+    /// Migrates the database and seeds it with a data set of `src/seeds.rs`.
+    #[cfg(feature = "postgres")]
+    Seed { set: String },
+    /// Times read-only transactions, one after another.
+    #[cfg(feature = "postgres")]
+    Bench {
+        #[arg(long, default_value_t = 1000)]
+        transactions: u32,
+    },
+    /// Fails unless the server answers a `SELECT 1` within the timeout.
+    #[cfg(feature = "postgres")]
+    Health {
+        #[arg(long, default_value_t = 1000)]
+        timeout_ms: u64,
+    },
+    /// Prints the JSON reports `tests/golden.rs` compares with the golden files.
+    #[cfg(all(feature = "postgres", feature = "tracing"))]
+    Report,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Scenario {
+    Commit,
+    Rollback,
+    Implicit,
+}
+
+#[derive(Debug, Args)]
+pub struct PoolArgs {
+    /// The most connections the pool opens.
+    #[arg(long, env = "DATABASE_MAX_CONNECTIONS", default_value_t = 10)]
+    pub max_connections: u32,
+    /// The isolation level of every transaction on the pool, the server default otherwise.
+    #[arg(long, env = "DATABASE_ISOLATION", value_enum)]
+    pub isolation: Option<Isolation>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Isolation {
+    ReadCommitted,
+    RepeatableRead,
+    Serializable,
+}
+impl Isolation {
+    #[cfg(feature = "postgres")]
+    fn as_sql(self) -> &'static str {
+        match self {
+            Isolation::ReadCommitted => "READ COMMITTED",
+            Isolation::RepeatableRead => "REPEATABLE READ",
+            Isolation::Serializable => "SERIALIZABLE",
+        }
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl PoolArgs {
+    // A pool on `DATABASE_URL`. The isolation level is set as the default of the sessions, so
+    // that it holds for the transactions begun with `pool.begin()` as for those of the runner.
+    pub async fn connect(&self) -> Result<sqlx::PgPool, sqlx::Error> {
+        let conn_str = std::env::var("DATABASE_URL")
+            .expect("Env var DATABASE_URL is required for this example.");
+        let isolation = self.isolation;
+        sqlx::postgres::PgPoolOptions::new()
+            .max_connections(self.max_connections)
+            .after_connect(move |conn, _| {
+                Box::pin(async move {
+                    if let Some(isolation) = isolation {
+                        let sql = format!(
+                            "SET SESSION CHARACTERISTICS AS TRANSACTION ISOLATION LEVEL {}",
+                            isolation.as_sql()
+                        );
+                        sqlx::query(&sql).execute(conn).await?;
+                    }
+                    Ok(())
+                })
+            })
+            .connect(&conn_str)
+            .await
+    }
+}
+
+#[cfg(feature = "postgres")]
+pub mod commands {
+    use std::time::{Duration, Instant};
+
+    use sqlx::PgPool;
+    use tx::prelude::*;
+    use tx::runner::{self, health_check, ReadTx, TxCtx};
+    use tx::{migrations, schema};
+
+    use super::Scenario;
+    use crate::postgres_example::{
+        commit_example, explicit_rollback_example, implicit_rollback_example,
+    };
+
+    pub async fn demo(
+        pool: &PgPool,
+        scenario: Scenario,
+        id: i64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        migrations::run(pool).await?;
+        sqlx::query("DELETE FROM todos WHERE id = $1")
+            .bind(id)
+            .execute(pool)
+            .await?;
+        match scenario {
+            Scenario::Commit => commit_example(pool, id).await?,
+            Scenario::Rollback => explicit_rollback_example(pool, id).await?,
+            Scenario::Implicit => implicit_rollback_example(pool, id).await?,
+        }
+        let (kept,): (bool,) = sqlx::query_as("SELECT EXISTS (SELECT FROM todos WHERE id = $1)")
+            .bind(id)
+            .fetch_one(pool)
+            .await?;
+        let outcome = if kept { "committed" } else { "rolled back" };
+        println!("{:?}: todo {} {}", scenario, id, outcome);
+        Ok(())
+    }
+
+    pub async fn migrate(pool: &PgPool) -> Result<(), Box<dyn std::error::Error>> {
+        migrations::run(pool).await?;
+        let version = run_tx(pool, schema::current_schema_version::<ReadTx>()).await?;
+        match version {
+            Some(version) => println!("migrated to version {}", version),
+            None => println!("no migrations"),
+        }
+        Ok(())
+    }
+
+    pub async fn bench(pool: &PgPool, transactions: u32) -> Result<(), Box<dyn std::error::Error>> {
+        let count = || {
+            with_tx_async(|ctx: &mut TxCtx<sqlx::Postgres, ReadTx>| {
+                Box::pin(async move {
+                    let (count,): (i64,) = sqlx::query_as("SELECT count(*) FROM todos")
+                        .fetch_one(&mut **ctx)
+                        .await?;
+                    Ok::<_, sqlx::Error>(count)
+                })
+            })
+        };
+        migrations::run(pool).await?;
+        let mut latencies = Vec::with_capacity(transactions as usize);
+        let started = Instant::now();
+        for _ in 0..transactions {
+            let begun = Instant::now();
+            run_tx(pool, count()).await?;
+            latencies.push(begun.elapsed());
+        }
+        let elapsed = started.elapsed();
+        latencies.sort();
+        let percentile = |p: usize| {
+            let at = (latencies.len() * p / 100).min(latencies.len().saturating_sub(1));
+            latencies.get(at).copied().unwrap_or_default()
+        };
+        println!(
+            "{} transactions in {:?}, {:.0}/s, p50 {:?}, p99 {:?}",
+            transactions,
+            elapsed,
+            transactions as f64 / elapsed.as_secs_f64(),
+            percentile(50),
+            percentile(99)
+        );
+        Ok(())
+    }
+
+    pub async fn health(
+        pool: &PgPool,
+        timeout: Duration,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let options = runner::TxOptions::new().read_only();
+        let check = health_check::<_, ReadTx>(timeout);
+        runner::run_tx_with(pool, options, check).await?;
+        println!("ok");
+        Ok(())
+    }
+}
@@ -0,0 +1,1268 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+
+use crate::{observer, watchdog};
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+// What `Tx::run` hands back: the result itself for `SyncMode`, a future of it for `AsyncMode`.
+pub type Output<'a, M, T, E> = <M as Mode>::Output<'a, T, E>;
+
+pub trait Mode: Sized {
+    type Output<'a, T, E>
+    where
+        T: 'a,
+        E: 'a;
+
+    // Runs the step `name`, telling a `Watchdog` watching the transaction which step it is in
+    // and the `TxObserver`s when it starts and ends; with the `tracing` feature, in a span of
+    // its own, entered while the step runs and, for `AsyncMode`, while its future is polled.
+    fn in_step<'a, T, E>(
+        name: &'static str,
+        run: impl FnOnce() -> Self::Output<'a, T, E>,
+    ) -> Self::Output<'a, T, E>
+    where
+        T: 'a,
+        E: 'a,
+    {
+        let _ = name;
+        run()
+    }
+}
+
+// The two primitives every combinator is built from: running the step `Tx1` over `Ctx`, then
+// `f` with its result and `kept`, what the rest of the combinator needs of its own. Each mode
+// implements them once, so the combinator structs below are shared between the sync and the
+// async world. `AsyncMode` has them only for a `Send` step, context and `kept`, as its futures
+// may move between threads; `f` captures nothing, which makes it `Send` in either mode.
+pub trait Chain<Ctx, Tx1, K>: Mode {
+    fn map<'a, F, T, E>(tx1: Tx1, ctx: &'a mut Ctx, kept: K, f: F) -> Self::Output<'a, T, E>
+    where
+        Tx1: Tx<Ctx, Mode = Self> + 'a,
+        K: 'a,
+        F: FnOnce(K, Result<Tx1::Item, Tx1::Err>) -> Result<T, E> + Send + 'a;
+
+    fn then<'a, Tx2, F>(
+        tx1: Tx1,
+        ctx: &'a mut Ctx,
+        kept: K,
+        f: F,
+    ) -> Self::Output<'a, Tx2::Item, Tx2::Err>
+    where
+        Tx1: Tx<Ctx, Mode = Self> + 'a,
+        Tx2: Tx<Ctx, Mode = Self>,
+        K: 'a,
+        F: FnOnce(K, Result<Tx1::Item, Tx1::Err>) -> Next<Tx2, Tx2::Item, Tx2::Err> + Send + 'a;
+}
+
+// A step which finishes with `result` at once; `AsyncMode` has it for a `Send` result only.
+pub trait Yield<T, E>: Mode {
+    fn ready<'a>(result: Result<T, E>) -> Self::Output<'a, T, E>
+    where
+        T: 'a,
+        E: 'a;
+}
+
+pub enum Next<Tx2, T, E> {
+    Run(Tx2),
+    Done(Result<T, E>),
+}
+
+// Bounds of the chaining combinators, in place of `Err = Self::Err` and `Mode = Self::Mode`,
+// which the compiler reports as mismatched projections deep inside the combinator types.
+#[diagnostic::on_unimplemented(
+    message = "the step added to the chain fails with `{Self}`, but the chain fails with `{E}`",
+    label = "this step fails with `{Self}`",
+    note = "convert its error with `.map_err(...)` first, or chain it with `and_then_into` when `{E}: From<{Self}>`"
+)]
+pub trait SameErr<E> {}
+impl<E> SameErr<E> for E {}
+
+#[diagnostic::on_unimplemented(
+    message = "a `{Self}` step cannot be chained with a `{M}` one",
+    label = "this step runs in `{Self}`",
+    note = "`with_tx` steps return their result, `with_tx_async` steps a future of it: build every step of the chain the same way"
+)]
+pub trait SameMode<M> {}
+impl<M> SameMode<M> for M {}
+
+pub struct SyncMode;
+impl Mode for SyncMode {
+    type Output<'a, T, E>
+        = Result<T, E>
+    where
+        T: 'a,
+        E: 'a;
+
+    fn in_step<'a, T, E>(name: &'static str, run: impl FnOnce() -> Result<T, E>) -> Result<T, E>
+    where
+        T: 'a,
+        E: 'a,
+    {
+        #[cfg(feature = "tracing")]
+        let run = || tracing::info_span!("step", name).in_scope(run);
+        watchdog::sync_step(name, || observer::sync_step(name, run))
+    }
+}
+impl<Ctx, Tx1, K> Chain<Ctx, Tx1, K> for SyncMode {
+    fn map<'a, F, T, E>(tx1: Tx1, ctx: &'a mut Ctx, kept: K, f: F) -> Result<T, E>
+    where
+        Tx1: Tx<Ctx, Mode = Self> + 'a,
+        K: 'a,
+        F: FnOnce(K, Result<Tx1::Item, Tx1::Err>) -> Result<T, E> + Send + 'a,
+    {
+        f(kept, tx1.run(ctx))
+    }
+
+    fn then<'a, Tx2, F>(tx1: Tx1, ctx: &'a mut Ctx, kept: K, f: F) -> Result<Tx2::Item, Tx2::Err>
+    where
+        Tx1: Tx<Ctx, Mode = Self> + 'a,
+        Tx2: Tx<Ctx, Mode = Self>,
+        K: 'a,
+        F: FnOnce(K, Result<Tx1::Item, Tx1::Err>) -> Next<Tx2, Tx2::Item, Tx2::Err> + Send + 'a,
+    {
+        match f(kept, tx1.run(&mut *ctx)) {
+            Next::Run(tx2) => tx2.run(ctx),
+            Next::Done(r) => r,
+        }
+    }
+}
+impl<T, E> Yield<T, E> for SyncMode {
+    fn ready<'a>(result: Result<T, E>) -> Result<T, E>
+    where
+        T: 'a,
+        E: 'a,
+    {
+        result
+    }
+}
+
+pub struct AsyncMode;
+impl Mode for AsyncMode {
+    type Output<'a, T, E>
+        = BoxFuture<'a, Result<T, E>>
+    where
+        T: 'a,
+        E: 'a;
+
+    fn in_step<'a, T, E>(
+        name: &'static str,
+        run: impl FnOnce() -> BoxFuture<'a, Result<T, E>>,
+    ) -> BoxFuture<'a, Result<T, E>>
+    where
+        T: 'a,
+        E: 'a,
+    {
+        #[cfg(feature = "tracing")]
+        let run = || -> BoxFuture<'a, Result<T, E>> {
+            let span = tracing::info_span!("step", name);
+            Box::pin(tracing::Instrument::instrument(span.in_scope(run), span))
+        };
+        watchdog::async_step(name, || observer::async_step(name, run))
+    }
+}
+impl<Ctx, Tx1, K> Chain<Ctx, Tx1, K> for AsyncMode
+where
+    Ctx: Send,
+    Tx1: Send,
+    K: Send,
+{
+    fn map<'a, F, T, E>(tx1: Tx1, ctx: &'a mut Ctx, kept: K, f: F) -> BoxFuture<'a, Result<T, E>>
+    where
+        Tx1: Tx<Ctx, Mode = Self> + 'a,
+        K: 'a,
+        F: FnOnce(K, Result<Tx1::Item, Tx1::Err>) -> Result<T, E> + Send + 'a,
+    {
+        Box::pin(async move { f(kept, tx1.run(ctx).await) })
+    }
+
+    fn then<'a, Tx2, F>(
+        tx1: Tx1,
+        ctx: &'a mut Ctx,
+        kept: K,
+        f: F,
+    ) -> BoxFuture<'a, Result<Tx2::Item, Tx2::Err>>
+    where
+        Tx1: Tx<Ctx, Mode = Self> + 'a,
+        Tx2: Tx<Ctx, Mode = Self>,
+        K: 'a,
+        F: FnOnce(K, Result<Tx1::Item, Tx1::Err>) -> Next<Tx2, Tx2::Item, Tx2::Err> + Send + 'a,
+    {
+        Box::pin(async move {
+            let tx2 = match f(kept, tx1.run(&mut *ctx).await) {
+                Next::Run(tx2) => tx2,
+                Next::Done(r) => return r,
+            };
+            tx2.run(ctx).await
+        })
+    }
+}
+impl<T, E> Yield<T, E> for AsyncMode
+where
+    T: Send,
+    E: Send,
+{
+    fn ready<'a>(result: Result<T, E>) -> BoxFuture<'a, Result<T, E>>
+    where
+        T: 'a,
+        E: 'a,
+    {
+        Box::pin(async move { result })
+    }
+}
+
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a transaction step over `{Ctx}`",
+    label = "not a `Tx<{Ctx}>`",
+    note = "steps are built with `with_tx`, `with_tx_async` or the combinators, and a plain closure is one when it is `FnOnce(&mut {Ctx}) -> Result<T, E>`",
+    note = "if this is a step, check that it runs over `{Ctx}`: a read-only step cannot be used where a writing one is expected"
+)]
+pub trait Tx<Ctx> {
+    type Item;
+    type Err;
+    type Mode: Mode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+    where
+        Self: 'a;
+
+    // The structure of the step as a tree of step names, for logs and error reports.
+    fn describe(&self) -> Description {
+        Description::leaf(short_type_name::<Self>())
+    }
+
+    fn map<F, T>(self, f: F) -> Map<Self, F>
+    where
+        F: FnOnce(Self::Item) -> T,
+        Self: Sized,
+    {
+        Map { tx1: self, f }
+    }
+    fn and_then<I, F>(self, f: F) -> AndThen<Self, F>
+    where
+        I: IntoTx<Ctx>,
+        <I::Tx as Tx<Ctx>>::Err: SameErr<Self::Err>,
+        <I::Tx as Tx<Ctx>>::Mode: SameMode<Self::Mode>,
+        F: FnOnce(Self::Item) -> I,
+        Self: Sized,
+    {
+        AndThen { tx1: self, f }
+    }
+    // `and_then` for a step failing with an error the chain's error converts from.
+    fn and_then_into<I, F>(self, f: F) -> AndThenInto<Self, F>
+    where
+        I: IntoTx<Ctx>,
+        Self::Err: From<<I::Tx as Tx<Ctx>>::Err>,
+        <I::Tx as Tx<Ctx>>::Mode: SameMode<Self::Mode>,
+        F: FnOnce(Self::Item) -> I,
+        Self: Sized,
+    {
+        AndThenInto { tx1: self, f }
+    }
+    fn then<I, F>(self, f: F) -> Then<Self, F>
+    where
+        I: IntoTx<Ctx>,
+        <I::Tx as Tx<Ctx>>::Err: SameErr<Self::Err>,
+        <I::Tx as Tx<Ctx>>::Mode: SameMode<Self::Mode>,
+        F: FnOnce(Result<Self::Item, Self::Err>) -> I,
+        Self: Sized,
+    {
+        Then { tx1: self, f }
+    }
+    fn or_else<I, F>(self, f: F) -> OrElse<Self, F>
+    where
+        I: IntoTx<Ctx>,
+        I::Tx: Tx<Ctx, Item = Self::Item>,
+        <I::Tx as Tx<Ctx>>::Err: SameErr<Self::Err>,
+        <I::Tx as Tx<Ctx>>::Mode: SameMode<Self::Mode>,
+        F: FnOnce(Self::Err) -> I,
+        Self: Sized,
+    {
+        OrElse { tx1: self, f }
+    }
+    fn join<I2>(self, tx2: I2) -> Join<Self, I2::Tx>
+    where
+        I2: IntoTx<Ctx>,
+        <I2::Tx as Tx<Ctx>>::Err: SameErr<Self::Err>,
+        <I2::Tx as Tx<Ctx>>::Mode: SameMode<Self::Mode>,
+        Self: Sized,
+    {
+        Join {
+            tx1: self,
+            tx2: tx2.into_tx(),
+        }
+    }
+    fn join3<I2, I3>(self, tx2: I2, tx3: I3) -> Join3<Self, I2::Tx, I3::Tx>
+    where
+        I2: IntoTx<Ctx>,
+        <I2::Tx as Tx<Ctx>>::Err: SameErr<Self::Err>,
+        <I2::Tx as Tx<Ctx>>::Mode: SameMode<Self::Mode>,
+        I3: IntoTx<Ctx>,
+        <I3::Tx as Tx<Ctx>>::Err: SameErr<Self::Err>,
+        <I3::Tx as Tx<Ctx>>::Mode: SameMode<Self::Mode>,
+        Self: Sized,
+    {
+        Join3 {
+            tx1: self,
+            tx2: tx2.into_tx(),
+            tx3: tx3.into_tx(),
+        }
+    }
+    fn join4<I2, I3, I4>(self, tx2: I2, tx3: I3, tx4: I4) -> Join4<Self, I2::Tx, I3::Tx, I4::Tx>
+    where
+        I2: IntoTx<Ctx>,
+        <I2::Tx as Tx<Ctx>>::Err: SameErr<Self::Err>,
+        <I2::Tx as Tx<Ctx>>::Mode: SameMode<Self::Mode>,
+        I3: IntoTx<Ctx>,
+        <I3::Tx as Tx<Ctx>>::Err: SameErr<Self::Err>,
+        <I3::Tx as Tx<Ctx>>::Mode: SameMode<Self::Mode>,
+        I4: IntoTx<Ctx>,
+        <I4::Tx as Tx<Ctx>>::Err: SameErr<Self::Err>,
+        <I4::Tx as Tx<Ctx>>::Mode: SameMode<Self::Mode>,
+        Self: Sized,
+    {
+        Join4 {
+            tx1: self,
+            tx2: tx2.into_tx(),
+            tx3: tx3.into_tx(),
+            tx4: tx4.into_tx(),
+        }
+    }
+    fn map_err<F, E>(self, f: F) -> MapErr<Self, F>
+    where
+        F: FnOnce(Self::Err) -> E,
+        Self: Sized,
+    {
+        MapErr { tx1: self, f }
+    }
+    fn try_map<F, T, E>(self, f: F) -> TryMap<Self, F>
+    where
+        F: FnOnce(Self::Item) -> Result<T, E>,
+        Self: Sized,
+    {
+        TryMap { tx1: self, f }
+    }
+    fn recover<F>(self, f: F) -> Recover<Self, F>
+    where
+        F: FnOnce(Self::Err) -> Self::Item,
+        Self: Sized,
+    {
+        Recover { tx1: self, f }
+    }
+    fn try_recover<F>(self, f: F) -> TryRecover<Self, F>
+    where
+        F: FnOnce(Self::Err) -> Result<Self::Item, Self::Err>,
+        Self: Sized,
+    {
+        TryRecover { tx1: self, f }
+    }
+    fn abort<F>(self, f: F) -> Abort<Self, F>
+    where
+        F: FnOnce(Self::Item) -> Self::Err,
+        Self: Sized,
+    {
+        Abort { tx1: self, f }
+    }
+    fn try_abort<F>(self, f: F) -> TryAbort<Self, F>
+    where
+        F: FnOnce(Self::Item) -> Result<Self::Item, Self::Err>,
+        Self: Sized,
+    {
+        TryAbort { tx1: self, f }
+    }
+    // Labels the step in `describe`, and in a span of its own with the `tracing` feature.
+    fn named(self, name: &'static str) -> Named<Self>
+    where
+        Self: Sized,
+    {
+        Named { tx1: self, name }
+    }
+}
+
+impl<Ctx, T, E, F> Tx<Ctx> for F
+where
+    F: FnOnce(&mut Ctx) -> Result<T, E>,
+{
+    type Item = T;
+    type Err = E;
+    type Mode = SyncMode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> Result<Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        self(ctx)
+    }
+
+    fn describe(&self) -> Description {
+        Description::leaf("closure")
+    }
+}
+
+// What the combinators take where they expect a step: any `Tx`, and whatever converts
+// into one.
+pub trait IntoTx<Ctx> {
+    type Tx: Tx<Ctx>;
+
+    fn into_tx(self) -> Self::Tx;
+}
+impl<Ctx, X: Tx<Ctx>> IntoTx<Ctx> for X {
+    type Tx = X;
+
+    fn into_tx(self) -> X {
+        self
+    }
+}
+
+// A step which touches nothing and finishes with `result`, in either mode.
+pub fn ready<T, E, M>(result: Result<T, E>) -> Ready<T, E, M> {
+    Ready {
+        result,
+        mode: PhantomData,
+    }
+}
+#[derive(Debug, Clone, Copy)]
+pub struct Ready<T, E, M> {
+    result: Result<T, E>,
+    mode: PhantomData<fn() -> M>,
+}
+impl<Ctx, T, E, M> Tx<Ctx> for Ready<T, E, M>
+where
+    M: Yield<T, E>,
+{
+    type Item = T;
+    type Err = E;
+    type Mode = M;
+
+    fn run<'a>(self, _ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        M::ready(self.result)
+    }
+
+    fn describe(&self) -> Description {
+        Description::leaf("ready")
+    }
+}
+
+// `result.into_tx()` for `ready(result)`, e.g. to end an `and_then` branch without a query.
+// `Result` cannot be `IntoTx` itself: next to the impl for every `Tx`, that would conflict
+// with a `Tx` impl for `Result` which another crate is allowed to write.
+pub trait ResultExt<T, E> {
+    fn into_tx<M>(self) -> Ready<T, E, M>;
+}
+impl<T, E> ResultExt<T, E> for Result<T, E> {
+    fn into_tx<M>(self) -> Ready<T, E, M> {
+        ready(self)
+    }
+}
+
+// A tree of step names, as returned by `Tx::describe`. Its `Display` prints one step per
+// line, each indented under the step it belongs to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Description {
+    pub name: Cow<'static, str>,
+    pub children: Vec<Description>,
+}
+impl Description {
+    pub fn new(name: impl Into<Cow<'static, str>>, children: Vec<Description>) -> Self {
+        Self {
+            name: name.into(),
+            children,
+        }
+    }
+    pub fn leaf(name: impl Into<Cow<'static, str>>) -> Self {
+        Self::new(name, vec![])
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        writeln!(f, "{:indent$}{}", "", self.name, indent = depth * 2)?;
+        self.children
+            .iter()
+            .try_for_each(|child| child.write(f, depth + 1))
+    }
+}
+impl fmt::Display for Description {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+// `Savepoint` for `tx::runner::Savepoint<...>`: what a step is called when it does not
+// describe itself.
+fn short_type_name<T: ?Sized>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    let path = name.split('<').next().unwrap_or(name);
+    path.rsplit("::").next().unwrap_or(path)
+}
+
+// The closures of the combinators are left out of their `Debug` output.
+macro_rules! debug_without_closure {
+    ($($name:ident),*) => {$(
+        impl<Tx1: fmt::Debug, F> fmt::Debug for $name<Tx1, F> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_struct(stringify!($name))
+                    .field("tx1", &self.tx1)
+                    .finish_non_exhaustive()
+            }
+        }
+    )*};
+}
+debug_without_closure!(
+    Map,
+    AndThen,
+    AndThenInto,
+    Then,
+    OrElse,
+    MapErr,
+    TryMap,
+    Recover,
+    TryRecover,
+    Abort,
+    TryAbort
+);
+
+#[derive(Clone, Copy)]
+pub struct Map<Tx1, F> {
+    tx1: Tx1,
+    f: F,
+}
+impl<Ctx, Tx1, T, F> Tx<Ctx> for Map<Tx1, F>
+where
+    Tx1: Tx<Ctx>,
+    Tx1::Mode: Chain<Ctx, Tx1, F>,
+    F: FnOnce(Tx1::Item) -> T,
+{
+    type Item = T;
+    type Err = Tx1::Err;
+    type Mode = Tx1::Mode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        Tx1::Mode::map(self.tx1, ctx, self.f, |f, r| r.map(f))
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("map", vec![self.tx1.describe()])
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct AndThen<Tx1, F> {
+    tx1: Tx1,
+    f: F,
+}
+impl<Ctx, Tx1, I, F> Tx<Ctx> for AndThen<Tx1, F>
+where
+    Tx1: Tx<Ctx>,
+    Tx1::Mode: Chain<Ctx, Tx1, F>,
+    I: IntoTx<Ctx>,
+    I::Tx: Tx<Ctx, Err = Tx1::Err, Mode = Tx1::Mode>,
+    F: FnOnce(Tx1::Item) -> I,
+{
+    type Item = <I::Tx as Tx<Ctx>>::Item;
+    type Err = Tx1::Err;
+    type Mode = Tx1::Mode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        Tx1::Mode::then(self.tx1, ctx, self.f, |f, r| match r {
+            Ok(x) => Next::Run(f(x).into_tx()),
+            Err(e) => Next::Done(Err(e)),
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("and_then", vec![self.tx1.describe()])
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct AndThenInto<Tx1, F> {
+    tx1: Tx1,
+    f: F,
+}
+impl<Ctx, Tx1, I, F> Tx<Ctx> for AndThenInto<Tx1, F>
+where
+    Tx1: Tx<Ctx>,
+    Tx1::Err: From<<I::Tx as Tx<Ctx>>::Err>,
+    Tx1::Mode: Chain<Ctx, Tx1, F> + Chain<Ctx, I::Tx, ()>,
+    I: IntoTx<Ctx>,
+    I::Tx: Tx<Ctx, Mode = Tx1::Mode>,
+    F: FnOnce(Tx1::Item) -> I,
+{
+    type Item = <I::Tx as Tx<Ctx>>::Item;
+    type Err = Tx1::Err;
+    type Mode = Tx1::Mode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        Tx1::Mode::then(self.tx1, ctx, self.f, |f, r| match r {
+            Ok(x) => Next::Run(Finish {
+                tx1: f(x).into_tx(),
+                kept: (),
+                f: |(), r: Result<_, _>| r.map_err(Tx1::Err::from),
+            }),
+            Err(e) => Next::Done(Err(e)),
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("and_then_into", vec![self.tx1.describe()])
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Then<Tx1, F> {
+    tx1: Tx1,
+    f: F,
+}
+impl<Ctx, Tx1, I, F> Tx<Ctx> for Then<Tx1, F>
+where
+    Tx1: Tx<Ctx>,
+    Tx1::Mode: Chain<Ctx, Tx1, F>,
+    I: IntoTx<Ctx>,
+    I::Tx: Tx<Ctx, Err = Tx1::Err, Mode = Tx1::Mode>,
+    F: FnOnce(Result<Tx1::Item, Tx1::Err>) -> I,
+{
+    type Item = <I::Tx as Tx<Ctx>>::Item;
+    type Err = Tx1::Err;
+    type Mode = Tx1::Mode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        Tx1::Mode::then(self.tx1, ctx, self.f, |f, r| Next::Run(f(r).into_tx()))
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("then", vec![self.tx1.describe()])
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct OrElse<Tx1, F> {
+    tx1: Tx1,
+    f: F,
+}
+impl<Ctx, Tx1, I, F> Tx<Ctx> for OrElse<Tx1, F>
+where
+    Tx1: Tx<Ctx>,
+    Tx1::Mode: Chain<Ctx, Tx1, F>,
+    I: IntoTx<Ctx>,
+    I::Tx: Tx<Ctx, Item = Tx1::Item, Err = Tx1::Err, Mode = Tx1::Mode>,
+    F: FnOnce(Tx1::Err) -> I,
+{
+    type Item = Tx1::Item;
+    type Err = Tx1::Err;
+    type Mode = Tx1::Mode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        Tx1::Mode::then(self.tx1, ctx, self.f, |f, r| match r {
+            Ok(t) => Next::Done(Ok(t)),
+            Err(e) => Next::Run(f(e).into_tx()),
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("or_else", vec![self.tx1.describe()])
+    }
+}
+
+// Runs `tx1` and feeds its whole result, with `kept`, to `f`; the building block of the `Join*`
+// family.
+struct Finish<Tx1, K, F> {
+    tx1: Tx1,
+    kept: K,
+    f: F,
+}
+impl<Ctx, Tx1, K, F, T, E> Tx<Ctx> for Finish<Tx1, K, F>
+where
+    Tx1: Tx<Ctx>,
+    Tx1::Mode: Chain<Ctx, Tx1, K>,
+    F: FnOnce(K, Result<Tx1::Item, Tx1::Err>) -> Result<T, E> + Send,
+{
+    type Item = T;
+    type Err = E;
+    type Mode = Tx1::Mode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        Tx1::Mode::map(self.tx1, ctx, self.kept, self.f)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Join<Tx1, Tx2> {
+    tx1: Tx1,
+    tx2: Tx2,
+}
+impl<Ctx, Tx1, Tx2> Tx<Ctx> for Join<Tx1, Tx2>
+where
+    Tx1: Tx<Ctx>,
+    Tx2: Tx<Ctx, Err = Tx1::Err, Mode = Tx1::Mode>,
+    Tx1::Mode: Chain<Ctx, Tx1, Tx2> + Chain<Ctx, Tx2, Result<Tx1::Item, Tx1::Err>>,
+{
+    type Item = (Tx1::Item, Tx2::Item);
+    type Err = Tx1::Err;
+    type Mode = Tx1::Mode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        Tx1::Mode::then(self.tx1, ctx, self.tx2, |tx2, r1| {
+            Next::Run(Finish {
+                tx1: tx2,
+                kept: r1,
+                f: |r1, r2| match (r1, r2) {
+                    (Ok(t), Ok(u)) => Ok((t, u)),
+                    (Err(e), _) | (_, Err(e)) => Err(e),
+                },
+            })
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("join", vec![self.tx1.describe(), self.tx2.describe()])
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Join3<Tx1, Tx2, Tx3> {
+    tx1: Tx1,
+    tx2: Tx2,
+    tx3: Tx3,
+}
+impl<Ctx, Tx1, Tx2, Tx3> Tx<Ctx> for Join3<Tx1, Tx2, Tx3>
+where
+    Tx1: Tx<Ctx>,
+    Tx2: Tx<Ctx, Err = Tx1::Err, Mode = Tx1::Mode>,
+    Tx3: Tx<Ctx, Err = Tx1::Err, Mode = Tx1::Mode>,
+    Tx1::Mode: Chain<Ctx, Tx1, Tx2>
+        + Chain<Ctx, Tx2, Result<Tx1::Item, Tx1::Err>>
+        + Chain<Ctx, Join<Tx1, Tx2>, Tx3>
+        + Chain<Ctx, Tx3, Result<(Tx1::Item, Tx2::Item), Tx1::Err>>,
+{
+    type Item = (Tx1::Item, Tx2::Item, Tx3::Item);
+    type Err = Tx1::Err;
+    type Mode = Tx1::Mode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        let tx12 = Join {
+            tx1: self.tx1,
+            tx2: self.tx2,
+        };
+        Tx1::Mode::then(tx12, ctx, self.tx3, |tx3, r12| {
+            Next::Run(Finish {
+                tx1: tx3,
+                kept: r12,
+                f: |r12, r3| match (r12, r3) {
+                    (Ok((t, u)), Ok(v)) => Ok((t, u, v)),
+                    (Err(e), _) | (_, Err(e)) => Err(e),
+                },
+            })
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::new(
+            "join3",
+            vec![
+                self.tx1.describe(),
+                self.tx2.describe(),
+                self.tx3.describe(),
+            ],
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Join4<Tx1, Tx2, Tx3, Tx4> {
+    tx1: Tx1,
+    tx2: Tx2,
+    tx3: Tx3,
+    tx4: Tx4,
+}
+impl<Ctx, Tx1, Tx2, Tx3, Tx4> Tx<Ctx> for Join4<Tx1, Tx2, Tx3, Tx4>
+where
+    Tx1: Tx<Ctx>,
+    Tx2: Tx<Ctx, Err = Tx1::Err, Mode = Tx1::Mode>,
+    Tx3: Tx<Ctx, Err = Tx1::Err, Mode = Tx1::Mode>,
+    Tx4: Tx<Ctx, Err = Tx1::Err, Mode = Tx1::Mode>,
+    Tx1::Mode: Chain<Ctx, Tx1, Tx2>
+        + Chain<Ctx, Tx2, Result<Tx1::Item, Tx1::Err>>
+        + Chain<Ctx, Join<Tx1, Tx2>, Tx3>
+        + Chain<Ctx, Tx3, Result<(Tx1::Item, Tx2::Item), Tx1::Err>>
+        + Chain<Ctx, Join3<Tx1, Tx2, Tx3>, Tx4>
+        + Chain<Ctx, Tx4, Result<(Tx1::Item, Tx2::Item, Tx3::Item), Tx1::Err>>,
+{
+    type Item = (Tx1::Item, Tx2::Item, Tx3::Item, Tx4::Item);
+    type Err = Tx1::Err;
+    type Mode = Tx1::Mode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        let tx123 = Join3 {
+            tx1: self.tx1,
+            tx2: self.tx2,
+            tx3: self.tx3,
+        };
+        Tx1::Mode::then(tx123, ctx, self.tx4, |tx4, r123| {
+            Next::Run(Finish {
+                tx1: tx4,
+                kept: r123,
+                f: |r123, r4| match (r123, r4) {
+                    (Ok((t, u, v)), Ok(w)) => Ok((t, u, v, w)),
+                    (Err(e), _) | (_, Err(e)) => Err(e),
+                },
+            })
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::new(
+            "join4",
+            vec![
+                self.tx1.describe(),
+                self.tx2.describe(),
+                self.tx3.describe(),
+                self.tx4.describe(),
+            ],
+        )
+    }
+}
+
+// Runs `acquire`, then the step `use_fn` builds with the resource it yields, then the step
+// `release` builds with the resource, on the same context whatever the outcome of the use:
+// the general form of creating a temp table, taking an advisory lock or `SET LOCAL`ing some
+// state for a few steps, and undoing it after. The chain fails with the error of the use
+// if any, then with that of the release; when `acquire` fails nothing else runs. On Postgres a
+// failed statement aborts the transaction, so the use goes in a `savepoint` for the release to
+// run. A chain dropped midway, e.g. by `timeout`, does not release: its rollback has to.
+pub fn bracket<Ctx, Acq, U, R, I1, I2>(acquire: Acq, use_fn: U, release: R) -> Bracket<Acq, U, R>
+where
+    Acq: Tx<Ctx>,
+    U: FnOnce(Acq::Item) -> I1,
+    I1: IntoTx<Ctx>,
+    R: FnOnce(Acq::Item) -> I2,
+    I2: IntoTx<Ctx>,
+{
+    Bracket {
+        acquire,
+        use_fn,
+        release,
+    }
+}
+#[derive(Clone, Copy)]
+pub struct Bracket<Acq, U, R> {
+    acquire: Acq,
+    use_fn: U,
+    release: R,
+}
+impl<Acq: fmt::Debug, U, R> fmt::Debug for Bracket<Acq, U, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Bracket")
+            .field("acquire", &self.acquire)
+            .finish_non_exhaustive()
+    }
+}
+impl<Ctx, Acq, U, R, I1, I2> Tx<Ctx> for Bracket<Acq, U, R>
+where
+    Acq: Tx<Ctx>,
+    Acq::Item: Clone,
+    Acq::Mode: Chain<Ctx, Acq, (U, R)>
+        + Chain<Ctx, I1::Tx, (Acq::Item, R)>
+        + Chain<Ctx, I2::Tx, Result<<I1::Tx as Tx<Ctx>>::Item, Acq::Err>>,
+    U: FnOnce(Acq::Item) -> I1,
+    I1: IntoTx<Ctx>,
+    I1::Tx: Tx<Ctx, Err = Acq::Err, Mode = Acq::Mode>,
+    R: FnOnce(Acq::Item) -> I2,
+    I2: IntoTx<Ctx>,
+    I2::Tx: Tx<Ctx, Err = Acq::Err, Mode = Acq::Mode>,
+{
+    type Item = <I1::Tx as Tx<Ctx>>::Item;
+    type Err = Acq::Err;
+    type Mode = Acq::Mode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        let kept = (self.use_fn, self.release);
+        Acq::Mode::then(
+            self.acquire,
+            ctx,
+            kept,
+            |(use_fn, release), acquired| match acquired {
+                Ok(resource) => Next::Run(Using {
+                    used: use_fn(resource.clone()).into_tx(),
+                    resource,
+                    release,
+                }),
+                Err(e) => Next::Done(Err(e)),
+            },
+        )
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("bracket", vec![self.acquire.describe()])
+    }
+}
+
+// The use of an acquired resource, then its release.
+struct Using<X, T, R> {
+    used: X,
+    resource: T,
+    release: R,
+}
+impl<Ctx, X, T, R, I2> Tx<Ctx> for Using<X, T, R>
+where
+    X: Tx<Ctx>,
+    X::Mode: Chain<Ctx, X, (T, R)> + Chain<Ctx, I2::Tx, Result<X::Item, X::Err>>,
+    R: FnOnce(T) -> I2,
+    I2: IntoTx<Ctx>,
+    I2::Tx: Tx<Ctx, Err = X::Err, Mode = X::Mode>,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = X::Mode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        let kept = (self.resource, self.release);
+        X::Mode::then(self.used, ctx, kept, |(resource, release), used| {
+            Next::Run(Finish {
+                tx1: release(resource).into_tx(),
+                kept: used,
+                f: |used, released| match (used, released) {
+                    (Ok(t), Ok(_)) => Ok(t),
+                    (Err(e), _) | (_, Err(e)) => Err(e),
+                },
+            })
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct MapErr<Tx1, F> {
+    tx1: Tx1,
+    f: F,
+}
+impl<Ctx, Tx1, F, E> Tx<Ctx> for MapErr<Tx1, F>
+where
+    Tx1: Tx<Ctx>,
+    Tx1::Mode: Chain<Ctx, Tx1, F>,
+    F: FnOnce(Tx1::Err) -> E,
+{
+    type Item = Tx1::Item;
+    type Err = E;
+    type Mode = Tx1::Mode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        Tx1::Mode::map(self.tx1, ctx, self.f, |f, r| r.map_err(f))
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("map_err", vec![self.tx1.describe()])
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct TryMap<Tx1, F> {
+    tx1: Tx1,
+    f: F,
+}
+impl<Ctx, Tx1, F, T> Tx<Ctx> for TryMap<Tx1, F>
+where
+    Tx1: Tx<Ctx>,
+    Tx1::Mode: Chain<Ctx, Tx1, F>,
+    F: FnOnce(Tx1::Item) -> Result<T, Tx1::Err>,
+{
+    type Item = T;
+    type Err = Tx1::Err;
+    type Mode = Tx1::Mode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        Tx1::Mode::map(self.tx1, ctx, self.f, |f, r| r.and_then(f))
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("try_map", vec![self.tx1.describe()])
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Recover<Tx1, F> {
+    tx1: Tx1,
+    f: F,
+}
+impl<Ctx, Tx1, F> Tx<Ctx> for Recover<Tx1, F>
+where
+    Tx1: Tx<Ctx>,
+    Tx1::Mode: Chain<Ctx, Tx1, F>,
+    F: FnOnce(Tx1::Err) -> Tx1::Item,
+{
+    type Item = Tx1::Item;
+    type Err = Tx1::Err;
+    type Mode = Tx1::Mode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        Tx1::Mode::map(self.tx1, ctx, self.f, |f, r| Ok(r.unwrap_or_else(f)))
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("recover", vec![self.tx1.describe()])
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct TryRecover<Tx1, F> {
+    tx1: Tx1,
+    f: F,
+}
+impl<Ctx, Tx1, F, E> Tx<Ctx> for TryRecover<Tx1, F>
+where
+    Tx1: Tx<Ctx>,
+    Tx1::Mode: Chain<Ctx, Tx1, F>,
+    F: FnOnce(Tx1::Err) -> Result<Tx1::Item, E>,
+{
+    type Item = Tx1::Item;
+    type Err = E;
+    type Mode = Tx1::Mode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        Tx1::Mode::map(self.tx1, ctx, self.f, |f, r| r.or_else(f))
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("try_recover", vec![self.tx1.describe()])
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Abort<Tx1, F> {
+    tx1: Tx1,
+    f: F,
+}
+impl<Ctx, Tx1, F> Tx<Ctx> for Abort<Tx1, F>
+where
+    Tx1: Tx<Ctx>,
+    Tx1::Mode: Chain<Ctx, Tx1, F>,
+    F: FnOnce(Tx1::Item) -> Tx1::Err,
+{
+    type Item = Tx1::Item;
+    type Err = Tx1::Err;
+    type Mode = Tx1::Mode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        Tx1::Mode::map(self.tx1, ctx, self.f, |f, r| r.and_then(|t| Err(f(t))))
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("abort", vec![self.tx1.describe()])
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct TryAbort<Tx1, F> {
+    tx1: Tx1,
+    f: F,
+}
+impl<Ctx, Tx1, F> Tx<Ctx> for TryAbort<Tx1, F>
+where
+    Tx1: Tx<Ctx>,
+    Tx1::Mode: Chain<Ctx, Tx1, F>,
+    F: FnOnce(Tx1::Item) -> Result<Tx1::Item, Tx1::Err>,
+{
+    type Item = Tx1::Item;
+    type Err = Tx1::Err;
+    type Mode = Tx1::Mode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        Tx1::Mode::map(self.tx1, ctx, self.f, |f, r| r.and_then(f))
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("try_abort", vec![self.tx1.describe()])
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Named<Tx1> {
+    tx1: Tx1,
+    name: &'static str,
+}
+impl<Ctx, Tx1> Tx<Ctx> for Named<Tx1>
+where
+    Tx1: Tx<Ctx>,
+{
+    type Item = Tx1::Item;
+    type Err = Tx1::Err;
+    type Mode = Tx1::Mode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        let tx1 = self.tx1;
+        Tx1::Mode::in_step(self.name, move || tx1.run(ctx))
+    }
+
+    fn describe(&self) -> Description {
+        Description::new(self.name, vec![self.tx1.describe()])
+    }
+}
+
+pub fn with_tx<Ctx, F, T, E>(f: F) -> WithTx<F>
+where
+    F: FnOnce(&mut Ctx) -> Result<T, E>,
+{
+    WithTx { f }
+}
+#[derive(Clone, Copy)]
+pub struct WithTx<F> {
+    f: F,
+}
+impl<F> fmt::Debug for WithTx<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WithTx").finish_non_exhaustive()
+    }
+}
+impl<Ctx, F, T, E> Tx<Ctx> for WithTx<F>
+where
+    F: FnOnce(&mut Ctx) -> Result<T, E>,
+{
+    type Item = T;
+    type Err = E;
+    type Mode = SyncMode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> Result<Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        (self.f)(ctx)
+    }
+
+    fn describe(&self) -> Description {
+        Description::leaf("with_tx")
+    }
+}
+
+pub fn with_tx_async<Ctx, F, T, E>(f: F) -> WithTxAsync<F>
+where
+    F: for<'c> FnOnce(&'c mut Ctx) -> BoxFuture<'c, Result<T, E>>,
+{
+    WithTxAsync { f }
+}
+#[derive(Clone, Copy)]
+pub struct WithTxAsync<F> {
+    f: F,
+}
+impl<F> fmt::Debug for WithTxAsync<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WithTxAsync").finish_non_exhaustive()
+    }
+}
+impl<Ctx, F, T, E> Tx<Ctx> for WithTxAsync<F>
+where
+    F: for<'c> FnOnce(&'c mut Ctx) -> BoxFuture<'c, Result<T, E>>,
+{
+    type Item = T;
+    type Err = E;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> BoxFuture<'a, Result<Self::Item, Self::Err>>
+    where
+        Self: 'a,
+    {
+        (self.f)(ctx)
+    }
+
+    fn describe(&self) -> Description {
+        Description::leaf("with_tx_async")
+    }
+}
+
+// Do-notation over the combinators, for chains whose steps use the items of several earlier
+// ones. `let p <- step;` runs a step and binds its item, `let p = expr;` binds a plain
+// value, `step;` runs a step for its effect only, and `ret expr` ends the chain with a
+// value; otherwise the last step gives the item:
+//
+//     tx! {
+//         let user <- find_user(id);
+//         let _ <- charge(user.id, amount);
+//         ret Receipt::new(user)
+//     }
+//
+// Every binding is moved into the rest of the chain, as with `and_then(move |p| ...)`.
+#[macro_export]
+macro_rules! tx {
+    // `step` binding `p`, followed by plain `let`s in `lets`; the rest not scanned yet
+    (@bind [$p:tt] [$tx:expr] [$($lets:tt)*] let $q:tt = $e:expr; $($rest:tt)+) => {
+        $crate::tx!(@bind [$p] [$tx] [$($lets)* let $q = $e;] $($rest)+)
+    };
+    (@bind [$p:tt] [$tx:expr] [$($lets:tt)*] ret $e:expr $(;)?) => {
+        $crate::combinator::Tx::map($tx, move |$p| { $($lets)* $e })
+    };
+    (@bind [$p:tt] [$tx:expr] [$($lets:tt)*] $($rest:tt)+) => {
+        $crate::combinator::Tx::and_then($tx, move |$p| { $($lets)* $crate::tx!($($rest)+) })
+    };
+    (ret $e:expr $(;)?) => {
+        compile_error!("tx! needs a step before `ret`")
+    };
+    (let $p:tt <- $tx:expr; $($rest:tt)+) => {
+        $crate::tx!(@bind [$p] [$tx] [] $($rest)+)
+    };
+    (let $p:tt = $e:expr; $($rest:tt)+) => {{
+        let $p = $e;
+        $crate::tx!($($rest)+)
+    }};
+    ($tx:expr; $($rest:tt)+) => {
+        $crate::tx!(@bind [_] [$tx] [] $($rest)+)
+    };
+    ($tx:expr $(;)?) => {
+        $tx
+    };
+}
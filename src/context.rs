@@ -0,0 +1,307 @@
+use std::borrow::Cow;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::time::{Duration, Instant};
+
+use sqlx::{Database, Transaction};
+
+use crate::combinator::BoxFuture;
+use crate::env::{Env, EnvCtx};
+use crate::error::{DeadlineExceeded, StatementBudgetExceeded};
+use crate::observer;
+use crate::runner::{OverBudget, Plan, TxOptions};
+use crate::sqlcomment;
+
+// The context handed to every step: an open transaction plus how many savepoints deep we are,
+// the deadline, if any, and the hooks to run when it ends. It derefs to the connection,
+// so steps keep writing `&mut **ctx` as with a bare `Transaction`.
+pub struct TxCtx<DB: Database, A = WriteTx> {
+    pub(crate) transaction: Transaction<'static, DB>,
+    pub(crate) depth: usize,
+    pub(crate) deadline: Option<Instant>,
+    pub(crate) before_commit: Vec<BeforeCommit<DB>>,
+    pub(crate) hooks: Hooks,
+    pub(crate) env: Env,
+    pub(crate) sql_comments: bool,
+    // the name the chain describes itself with, when something reports it
+    pub(crate) name: Option<Cow<'static, str>>,
+    // how many times the connection was taken out, against the budget of the options
+    pub(crate) statements: u32,
+    pub(crate) statement_budget: Option<(u32, OverBudget)>,
+    // the writes put off by a dry run
+    pub(crate) plan: Option<Plan>,
+    // in an `explained` step
+    pub(crate) explain: bool,
+    // whether the statements of the SQL helpers are kept in the statement cache
+    pub(crate) persistent: bool,
+    pub(crate) access: PhantomData<A>,
+}
+
+pub(crate) type BeforeCommit<DB> = Box<
+    dyn for<'c> FnOnce(
+            &'c mut <DB as Database>::Connection,
+        ) -> BoxFuture<'c, Result<(), sqlx::Error>>
+        + Send,
+>;
+impl<DB: Database, A> TxCtx<DB, A> {
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+    // What is left of the budget; `None` without a deadline.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+    // For steps about to start something expensive; the runner enforces the deadline anyway.
+    pub fn check_deadline(&self) -> Result<(), DeadlineExceeded> {
+        match self.remaining() {
+            Some(remaining) if remaining.is_zero() => Err(DeadlineExceeded),
+            _ => Ok(()),
+        }
+    }
+    // How many statements the steps issued so far, counted as the times they took the
+    // connection with `&mut **ctx`.
+    pub fn statements(&self) -> u32 {
+        self.statements
+    }
+    pub(crate) fn check_statement_budget<T, E>(&self, result: Result<T, E>) -> Result<T, E>
+    where
+        E: From<sqlx::Error>,
+    {
+        let exceeded = match self.statement_budget {
+            Some((budget, over)) if self.statements > budget => (
+                StatementBudgetExceeded {
+                    budget,
+                    statements: self.statements,
+                },
+                over,
+            ),
+            _ => return result,
+        };
+        observer::statement_budget_exceeded(exceeded.0.budget, exceeded.0.statements);
+        match exceeded {
+            (e, OverBudget::Fail) if result.is_ok() => Err(sqlx::Error::from(e).into()),
+            (e, _) => {
+                #[cfg(feature = "tracing")]
+                {
+                    let name = self.name.as_deref().unwrap_or("?");
+                    tracing::warn!(target: "tx::statement_budget", transaction = name, "{}", e);
+                }
+                #[cfg(not(feature = "tracing"))]
+                let _ = e;
+                result
+            }
+        }
+    }
+    // `sql` with the sqlcommenter comment of the transaction, when it runs with
+    // `TxOptions::sql_comments`, for statements of the chain's own to carry it as well.
+    pub fn commented<'s>(&self, sql: &'s str) -> Cow<'s, str> {
+        if self.sql_comments {
+            sqlcomment::apply(sql, self.name.as_deref())
+        } else {
+            Cow::Borrowed(sql)
+        }
+    }
+    // Runs `f` once the transaction has committed, outside of it, e.g. to send an email or to
+    // invalidate a cache. Never run if the transaction, or the savepoint `f` was registered in,
+    // is rolled back.
+    pub fn after_commit(&mut self, f: impl FnOnce() + Send + 'static) {
+        self.hooks.after_commit(Box::new(f));
+    }
+    // Runs `f` once the transaction, or the savepoint `f` was registered in, has been rolled
+    // back; in the latter case only when the transaction is over, whichever way it ended.
+    pub fn after_rollback(&mut self, f: impl FnOnce() + Send + 'static) {
+        self.hooks.after_rollback(Box::new(f));
+    }
+
+    // Runs `f` in the transaction right before it commits, after every step. When it fails, the
+    // transaction is rolled back instead. Never run if the savepoint `f` was registered in is
+    // rolled back.
+    pub fn before_commit<F>(&mut self, f: F)
+    where
+        F: for<'c> FnOnce(&'c mut DB::Connection) -> BoxFuture<'c, Result<(), sqlx::Error>>
+            + Send
+            + 'static,
+    {
+        self.before_commit.push(Box::new(f));
+    }
+
+    pub(crate) async fn run_before_commit(&mut self) -> Result<(), sqlx::Error> {
+        for f in std::mem::take(&mut self.before_commit) {
+            f(&mut self.transaction).await?;
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn commit(mut self) -> Result<(), sqlx::Error> {
+        if let Err(e) = self.run_before_commit().await {
+            let _ = self.rollback().await;
+            return Err(e);
+        }
+        let mut hooks = self.hooks;
+        match self.transaction.commit().await {
+            Ok(()) => {
+                hooks.run_committed();
+                Ok(())
+            }
+            Err(e) => {
+                hooks.run_rolled_back();
+                Err(e)
+            }
+        }
+    }
+    // The hooks run even if the rollback fails: the work is lost either way.
+    pub(crate) async fn rollback(self) -> Result<(), sqlx::Error> {
+        let mut hooks = self.hooks;
+        let result = self.transaction.rollback().await;
+        hooks.run_rolled_back();
+        result
+    }
+}
+
+pub(crate) type Hook = Box<dyn FnOnce() + Send>;
+
+#[derive(Default)]
+pub(crate) struct Hooks {
+    after_commit: Vec<Hook>,
+    after_rollback: Vec<Hook>,
+    // `after_rollback` hooks of rolled back savepoints, due whatever becomes of the transaction
+    undone: Vec<Hook>,
+}
+impl Hooks {
+    pub(crate) fn after_commit(&mut self, hook: Hook) {
+        self.after_commit.push(hook);
+    }
+    pub(crate) fn after_rollback(&mut self, hook: Hook) {
+        self.after_rollback.push(hook);
+    }
+    pub(crate) fn mark(&self) -> (usize, usize) {
+        (self.after_commit.len(), self.after_rollback.len())
+    }
+    // A savepoint taken at `mark` was rolled back.
+    pub(crate) fn undo_to(&mut self, (commit, rollback): (usize, usize)) {
+        self.after_commit.truncate(commit);
+        let undone = self.after_rollback.drain(rollback..);
+        self.undone.extend(undone);
+    }
+    pub(crate) fn run_committed(&mut self) {
+        let hooks = self.after_commit.drain(..).chain(self.undone.drain(..));
+        hooks.for_each(|hook| hook());
+    }
+    pub(crate) fn run_rolled_back(&mut self) {
+        self.after_commit.clear();
+        let hooks = self.after_rollback.drain(..).chain(self.undone.drain(..));
+        hooks.for_each(|hook| hook());
+    }
+}
+impl std::fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Hooks")
+            .field("after_commit", &self.after_commit.len())
+            .field("after_rollback", &self.after_rollback.len())
+            .field("undone", &self.undone.len())
+            .finish()
+    }
+}
+
+// A context one savepoint deeper for as long as it is held, `depth` telling where its depth is
+// kept. Dropped, it steps back out, so the depth is right again however the savepoint ended,
+// also when its step was dropped midway by a deadline or a cancellation.
+pub(crate) struct Deeper<'c, C> {
+    ctx: &'c mut C,
+    depth: fn(&mut C) -> &mut usize,
+}
+impl<'c, C> Deeper<'c, C> {
+    pub(crate) fn new(ctx: &'c mut C, depth: fn(&mut C) -> &mut usize) -> Self {
+        *depth(ctx) += 1;
+        Self { ctx, depth }
+    }
+}
+impl<C> Deref for Deeper<'_, C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        self.ctx
+    }
+}
+impl<C> DerefMut for Deeper<'_, C> {
+    fn deref_mut(&mut self) -> &mut C {
+        self.ctx
+    }
+}
+impl<C> Drop for Deeper<'_, C> {
+    fn drop(&mut self) {
+        *(self.depth)(self.ctx) -= 1;
+    }
+}
+
+impl<DB: Database, A> EnvCtx for TxCtx<DB, A> {
+    fn env(&self) -> &Env {
+        &self.env
+    }
+    fn env_mut(&mut self) -> &mut Env {
+        &mut self.env
+    }
+}
+
+impl<DB: Database, A> Deref for TxCtx<DB, A> {
+    type Target = DB::Connection;
+
+    fn deref(&self) -> &DB::Connection {
+        &self.transaction
+    }
+}
+impl<DB: Database, A> DerefMut for TxCtx<DB, A> {
+    fn deref_mut(&mut self) -> &mut DB::Connection {
+        self.statements = self.statements.saturating_add(1);
+        &mut self.transaction
+    }
+}
+
+// What a chain may do, tracked in the type of its context: a step which writes is written
+// against `TxCtx<_, WriteTx>` (the default), one which only reads against any `TxCtx<_, A>`.
+// So a chain over `TxCtx<_, ReadTx>` cannot contain writing steps, and the runner begins it
+// `READ ONLY`, which also lets the server reject raw writes slipped into a reading step.
+pub trait TxAccess: Send {
+    const READ_ONLY: bool;
+
+    fn restrict(options: TxOptions) -> TxOptions {
+        if Self::READ_ONLY {
+            options.read_only()
+        } else {
+            options
+        }
+    }
+}
+/// A step written against `TxCtx<_, WriteTx>` does not compose into a `ReadTx` chain:
+///
+/// ```compile_fail
+/// use sqlx::Database;
+/// use tx::prelude::*;
+///
+/// fn write<DB: Database>() -> impl Tx<TxCtx<DB>, Item = (), Err = sqlx::Error, Mode = AsyncMode>
+/// {
+///     with_tx_async(|_: &mut TxCtx<DB>| Box::pin(async { Ok(()) }))
+/// }
+/// fn read<DB: Database, A>(
+/// ) -> impl Tx<TxCtx<DB, A>, Item = (), Err = sqlx::Error, Mode = AsyncMode> {
+///     with_tx_async(|_: &mut TxCtx<DB, A>| Box::pin(async { Ok(()) }))
+/// }
+///
+/// fn report<DB: Database>() -> impl Tx<TxCtx<DB, ReadTx>, Item = (), Err = sqlx::Error> {
+///     read().and_then(|_| write())
+/// }
+/// ```
+#[derive(Debug)]
+pub enum ReadTx {}
+#[derive(Debug)]
+pub enum WriteTx {}
+impl TxAccess for ReadTx {
+    const READ_ONLY: bool = true;
+}
+impl TxAccess for WriteTx {
+    const READ_ONLY: bool = false;
+}
@@ -0,0 +1,208 @@
+use sqlx::PgPool;
+
+use crate::combinator::{AsyncMode, BoxFuture, Tx};
+use crate::runner::{self, PgCtx, Prepared, TxOptions};
+
+// Where the coordinator persists its commit decisions. Once `log_commit` has returned,
+// both sides must eventually be committed, so it has to survive a crash of the process.
+pub trait DecisionLog: Send + Sync {
+    fn log_commit<'a>(&'a self, id: &'a str) -> BoxFuture<'a, Result<(), sqlx::Error>>;
+    fn forget<'a>(&'a self, id: &'a str) -> BoxFuture<'a, Result<(), sqlx::Error>>;
+    fn is_committed<'a>(&'a self, id: &'a str) -> BoxFuture<'a, Result<bool, sqlx::Error>>;
+}
+
+// Decision log kept in a table of a Postgres database, normally the coordinator's own.
+pub struct PgDecisionLog {
+    pool: PgPool,
+}
+impl PgDecisionLog {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+    pub async fn create_table(&self) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"CREATE TABLE IF NOT EXISTS tx_rs_decisions
+               (
+                   id         TEXT PRIMARY KEY,
+                   decided_at TIMESTAMPTZ NOT NULL DEFAULT now()
+               )"#,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+impl DecisionLog for PgDecisionLog {
+    fn log_commit<'a>(&'a self, id: &'a str) -> BoxFuture<'a, Result<(), sqlx::Error>> {
+        Box::pin(async move {
+            sqlx::query("INSERT INTO tx_rs_decisions (id) VALUES ($1)")
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+            Ok(())
+        })
+    }
+    fn forget<'a>(&'a self, id: &'a str) -> BoxFuture<'a, Result<(), sqlx::Error>> {
+        Box::pin(async move {
+            sqlx::query("DELETE FROM tx_rs_decisions WHERE id = $1")
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+            Ok(())
+        })
+    }
+    fn is_committed<'a>(&'a self, id: &'a str) -> BoxFuture<'a, Result<bool, sqlx::Error>> {
+        Box::pin(async move {
+            let (found,): (bool,) =
+                sqlx::query_as("SELECT EXISTS (SELECT 1 FROM tx_rs_decisions WHERE id = $1)")
+                    .bind(id)
+                    .fetch_one(&self.pool)
+                    .await?;
+            Ok(found)
+        })
+    }
+}
+
+// Commits a chain on database A and a chain on database B atomically with two-phase commit:
+// both are prepared, the decision is logged, then both are committed. Anything failing before
+// the decision is logged rolls both back; anything failing after it is finished by `recover`.
+pub struct Coordinator<L> {
+    name: String,
+    pool_a: PgPool,
+    pool_b: PgPool,
+    log: L,
+    options: TxOptions,
+}
+
+// Both sides prepared, no decision made yet. Dropping it leaves the prepared transactions
+// in doubt; `Coordinator::recover` rolls such transactions back (presumed abort).
+#[must_use]
+pub struct InDoubt<'c, L, TA, TB> {
+    coordinator: &'c Coordinator<L>,
+    id: String,
+    a: Prepared<TA>,
+    b: Prepared<TB>,
+}
+
+impl<L: DecisionLog> Coordinator<L> {
+    pub fn new(name: impl Into<String>, pool_a: PgPool, pool_b: PgPool, log: L) -> Self {
+        Self {
+            name: name.into(),
+            pool_a,
+            pool_b,
+            log,
+            options: TxOptions::default(),
+        }
+    }
+    pub fn options(mut self, options: TxOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    fn gid(&self, id: &str, side: &str) -> String {
+        format!("{}:{}:{}", self.name, id, side)
+    }
+
+    pub async fn run<TA, TB, E, XA, XB>(&self, tx_a: XA, tx_b: XB) -> Result<(TA, TB), E>
+    where
+        XA: Tx<PgCtx, Item = TA, Err = E, Mode = AsyncMode>,
+        XB: Tx<PgCtx, Item = TB, Err = E, Mode = AsyncMode>,
+        E: From<sqlx::Error>,
+    {
+        self.prepare(tx_a, tx_b).await?.commit().await
+    }
+
+    // First phase only: prepares both sides under a fresh transaction id.
+    pub async fn prepare<TA, TB, E, XA, XB>(
+        &self,
+        tx_a: XA,
+        tx_b: XB,
+    ) -> Result<InDoubt<'_, L, TA, TB>, E>
+    where
+        XA: Tx<PgCtx, Item = TA, Err = E, Mode = AsyncMode>,
+        XB: Tx<PgCtx, Item = TB, Err = E, Mode = AsyncMode>,
+        E: From<sqlx::Error>,
+    {
+        let id = format!("{:032x}", rand::random::<u128>());
+
+        let a = runner::prepare_tx(&self.pool_a, self.options, self.gid(&id, "a"), tx_a).await?;
+        let b = match runner::prepare_tx(&self.pool_b, self.options, self.gid(&id, "b"), tx_b).await
+        {
+            Ok(b) => b,
+            Err(e) => {
+                let _ = a.rollback(&self.pool_a).await;
+                return Err(e);
+            }
+        };
+
+        Ok(InDoubt {
+            coordinator: self,
+            id,
+            a,
+            b,
+        })
+    }
+
+    // Finishes every prepared transaction this coordinator left behind: committed when its
+    // decision was logged, rolled back otherwise. Returns how many were resolved.
+    pub async fn recover(&self) -> Result<usize, sqlx::Error> {
+        let mut resolved = 0;
+        let mut committed = vec![];
+        for (pool, side) in [(&self.pool_a, "a"), (&self.pool_b, "b")] {
+            for xact in runner::list_prepared(pool).await? {
+                let id = match self.own_id(&xact.gid, side) {
+                    Some(id) => id.to_string(),
+                    None => continue,
+                };
+                if self.log.is_committed(&id).await? {
+                    runner::commit_prepared(pool, &xact.gid).await?;
+                    committed.push(id);
+                } else {
+                    runner::rollback_prepared(pool, &xact.gid).await?;
+                }
+                resolved += 1;
+            }
+        }
+
+        // both sides of these are committed now, so their decisions are no longer needed
+        for id in committed {
+            self.log.forget(&id).await?;
+        }
+        Ok(resolved)
+    }
+
+    fn own_id<'g>(&self, gid: &'g str, side: &str) -> Option<&'g str> {
+        let rest = gid.strip_prefix(self.name.as_str())?.strip_prefix(':')?;
+        let id = rest.strip_suffix(side)?.strip_suffix(':')?;
+        Some(id)
+    }
+}
+
+impl<'c, L: DecisionLog, TA, TB> InDoubt<'c, L, TA, TB> {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    // Second phase: logs the decision, then commits both sides.
+    pub async fn commit<E: From<sqlx::Error>>(self) -> Result<(TA, TB), E> {
+        let coordinator = self.coordinator;
+
+        if let Err(e) = coordinator.log.log_commit(&self.id).await {
+            let _ = self.abort().await;
+            return Err(e.into());
+        }
+
+        let a = self.a.commit(&coordinator.pool_a).await?;
+        let b = self.b.commit(&coordinator.pool_b).await?;
+        coordinator.log.forget(&self.id).await?;
+
+        Ok((a, b))
+    }
+
+    pub async fn abort(self) -> Result<(), sqlx::Error> {
+        let coordinator = self.coordinator;
+        let a = self.a.rollback(&coordinator.pool_a).await;
+        let b = self.b.rollback(&coordinator.pool_b).await;
+        a.and(b)
+    }
+}
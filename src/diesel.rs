@@ -0,0 +1,48 @@
+use ::diesel::result::Error;
+use ::diesel::{Connection, PgConnection};
+
+use crate::combinator::{Description, SyncMode, Tx};
+use crate::runner::Savepoint;
+
+// Chains over diesel, for code bases moving between diesel and sqlx: the context is the
+// `PgConnection` itself, and steps are synchronous, `with_tx` closures running diesel queries,
+//
+//     with_tx(|conn: &mut PgConnection| diesel::delete(todos.filter(done)).execute(conn))
+//
+// composed with the same combinators as the chains of the sqlx runner.
+
+// Runs `tx` in `conn.transaction(..)`: it commits on `Ok` and rolls back on `Err`, and, within a
+// transaction already open on `conn`, becomes a savepoint of it. For other isolation levels or
+// access modes, run it in a transaction of diesel's `build_transaction()` instead:
+//
+//     conn.build_transaction().serializable().run(|conn| chain.run(conn))
+pub fn run_tx<T, E, X>(conn: &mut PgConnection, tx: X) -> Result<T, E>
+where
+    X: Tx<PgConnection, Item = T, Err = E, Mode = SyncMode>,
+    E: From<Error>,
+{
+    conn.transaction(|conn| tx.run(conn))
+}
+
+// As for sqlx: on `Err` only the work of `tx` is rolled back. Diesel's transaction manager
+// names and nests the savepoints.
+impl<X> Tx<PgConnection> for Savepoint<X>
+where
+    X: Tx<PgConnection, Mode = SyncMode>,
+    X::Err: From<Error>,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = SyncMode;
+
+    fn run<'a>(self, conn: &'a mut PgConnection) -> Result<Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        conn.transaction(|conn| self.tx.run(conn))
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("savepoint", vec![self.tx.describe()])
+    }
+}
@@ -0,0 +1,207 @@
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crate::combinator::{AsyncMode, BoxFuture, Description, Output, Tx, Yield};
+
+// Where the chains get the time and fresh ids from, carried in the context so that tests can
+// swap in fixed ones with `with_env` and get the same rows on every run.
+#[derive(Clone)]
+pub struct Env {
+    clock: Arc<dyn Clock>,
+    ids: Arc<dyn IdGen>,
+}
+impl Default for Env {
+    fn default() -> Self {
+        Self {
+            clock: Arc::new(SystemClock),
+            ids: Arc::new(RandomIds),
+        }
+    }
+}
+impl Env {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+    pub fn ids(mut self, ids: impl IdGen + 'static) -> Self {
+        self.ids = Arc::new(ids);
+        self
+    }
+
+    pub fn now(&self) -> SystemTime {
+        self.clock.now()
+    }
+    pub fn next_id(&self) -> i64 {
+        self.ids.next_id()
+    }
+}
+impl std::fmt::Debug for Env {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Env").finish_non_exhaustive()
+    }
+}
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+pub trait IdGen: Send + Sync {
+    fn next_id(&self) -> i64;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+// Stands still until moved on with `advance`; clones share the time.
+#[derive(Debug, Clone)]
+pub struct FixedClock {
+    now: Arc<Mutex<SystemTime>>,
+}
+impl FixedClock {
+    pub fn new(now: SystemTime) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap() += by;
+    }
+}
+impl Clock for FixedClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap()
+    }
+}
+
+// Positive random ids, unlikely to collide.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomIds;
+impl IdGen for RandomIds {
+    fn next_id(&self) -> i64 {
+        (rand::random::<u64>() >> 1) as i64
+    }
+}
+
+// `start`, `start + 1`... ; clones share the sequence.
+#[derive(Debug, Clone)]
+pub struct SequenceIds {
+    next: Arc<AtomicI64>,
+}
+impl SequenceIds {
+    pub fn starting_at(start: i64) -> Self {
+        Self {
+            next: Arc::new(AtomicI64::new(start)),
+        }
+    }
+}
+impl IdGen for SequenceIds {
+    fn next_id(&self) -> i64 {
+        self.next.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+// A context carrying an `Env`: `TxCtx`, `InMemoryCtx` and `MockCtx`.
+pub trait EnvCtx {
+    fn env(&self) -> &Env;
+    fn env_mut(&mut self) -> &mut Env;
+}
+
+// The current time, from the clock of the context.
+pub fn now<E, M>() -> Now<E, M> {
+    Now {
+        mode: PhantomMode::default(),
+    }
+}
+// A fresh id, from the id generator of the context.
+pub fn next_id<E, M>() -> NextId<E, M> {
+    NextId {
+        mode: PhantomMode::default(),
+    }
+}
+
+type PhantomMode<E, M> = std::marker::PhantomData<fn() -> (E, M)>;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Now<E, M> {
+    mode: PhantomMode<E, M>,
+}
+impl<Ctx: EnvCtx, E, M: Yield<SystemTime, E>> Tx<Ctx> for Now<E, M> {
+    type Item = SystemTime;
+    type Err = E;
+    type Mode = M;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        M::ready(Ok(ctx.env().now()))
+    }
+
+    fn describe(&self) -> Description {
+        Description::leaf("now")
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct NextId<E, M> {
+    mode: PhantomMode<E, M>,
+}
+impl<Ctx: EnvCtx, E, M: Yield<i64, E>> Tx<Ctx> for NextId<E, M> {
+    type Item = i64;
+    type Err = E;
+    type Mode = M;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        M::ready(Ok(ctx.env().next_id()))
+    }
+
+    fn describe(&self) -> Description {
+        Description::leaf("next_id")
+    }
+}
+
+// Runs `tx` with `env` in place of the context's own, which is back once `tx` is over.
+pub fn with_env<X>(env: Env, tx: X) -> WithEnv<X> {
+    WithEnv { tx, env }
+}
+#[derive(Debug, Clone)]
+pub struct WithEnv<X> {
+    tx: X,
+    env: Env,
+}
+impl<Ctx, X> Tx<Ctx> for WithEnv<X>
+where
+    Ctx: EnvCtx + Send,
+    X: Tx<Ctx, Mode = AsyncMode> + Send,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> BoxFuture<'a, Result<Self::Item, Self::Err>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            let outer = std::mem::replace(ctx.env_mut(), self.env);
+            let result = self.tx.run(ctx).await;
+            *ctx.env_mut() = outer;
+            result
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("with_env", vec![self.tx.describe()])
+    }
+}
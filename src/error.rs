@@ -0,0 +1,444 @@
+use std::borrow::Cow;
+use std::convert::Infallible;
+use std::time::Duration;
+
+use crate::rt::{Cancelled, TimedOut};
+
+// The failures of the runner's own below are each reported as a `sqlx::Error::Io` wrapping them,
+// so that they reach every chain whatever its error type: of kind `TimedOut` for a deadline,
+// `WouldBlock` for those telling to back off, `Other` for the rest. Their `is` tells them apart
+// in a `sqlx::Error`, and `TxError` has a case for each.
+
+// The deadline of a transaction has passed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeadlineExceeded;
+impl DeadlineExceeded {
+    pub fn is(e: &sqlx::Error) -> bool {
+        match e {
+            sqlx::Error::Io(e) => e.get_ref().is_some_and(|e| e.is::<DeadlineExceeded>()),
+            _ => false,
+        }
+    }
+}
+impl std::fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "deadline exceeded")
+    }
+}
+impl std::error::Error for DeadlineExceeded {}
+impl From<DeadlineExceeded> for sqlx::Error {
+    fn from(e: DeadlineExceeded) -> Self {
+        sqlx::Error::Io(std::io::Error::new(std::io::ErrorKind::TimedOut, e))
+    }
+}
+
+// A chain issued more statements than the budget of `TxOptions::statement_budget` allows, and
+// was rolled back for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatementBudgetExceeded {
+    pub budget: u32,
+    pub statements: u32,
+}
+impl StatementBudgetExceeded {
+    pub fn is(e: &sqlx::Error) -> bool {
+        match e {
+            sqlx::Error::Io(e) => e
+                .get_ref()
+                .is_some_and(|e| e.is::<StatementBudgetExceeded>()),
+            _ => false,
+        }
+    }
+}
+impl std::fmt::Display for StatementBudgetExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} statements issued, over the budget of {}",
+            self.statements, self.budget
+        )
+    }
+}
+impl std::error::Error for StatementBudgetExceeded {}
+impl From<StatementBudgetExceeded> for sqlx::Error {
+    fn from(e: StatementBudgetExceeded) -> Self {
+        sqlx::Error::Io(std::io::Error::other(e))
+    }
+}
+
+// No connection for a transaction: every one of the pool was taken, and the `Backpressure` of
+// its `BoundedRunner` did not let it wait, or not any longer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolExhausted {
+    pub waited: Duration,
+}
+impl PoolExhausted {
+    pub fn is(e: &sqlx::Error) -> bool {
+        match e {
+            sqlx::Error::Io(e) => e.get_ref().is_some_and(|e| e.is::<PoolExhausted>()),
+            _ => false,
+        }
+    }
+}
+impl std::fmt::Display for PoolExhausted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "pool exhausted, gave up after {:?}", self.waited)
+    }
+}
+impl std::error::Error for PoolExhausted {}
+impl From<PoolExhausted> for sqlx::Error {
+    fn from(e: PoolExhausted) -> Self {
+        sqlx::Error::Io(std::io::Error::new(std::io::ErrorKind::WouldBlock, e))
+    }
+}
+
+// A rate limiter had no token for a step within the wait it allows; `retry_after` is how long
+// one would have taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimited {
+    pub retry_after: Duration,
+}
+impl RateLimited {
+    pub fn is(e: &sqlx::Error) -> bool {
+        match e {
+            sqlx::Error::Io(e) => e.get_ref().is_some_and(|e| e.is::<RateLimited>()),
+            _ => false,
+        }
+    }
+}
+impl std::fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "rate limited, retry after {:?}", self.retry_after)
+    }
+}
+impl std::error::Error for RateLimited {}
+impl From<RateLimited> for sqlx::Error {
+    fn from(e: RateLimited) -> Self {
+        sqlx::Error::Io(std::io::Error::new(std::io::ErrorKind::WouldBlock, e))
+    }
+}
+
+// A circuit breaker was open, or half open with its trial step in flight, so the step it guards
+// did not run; `retry_after` is how long until it lets a step through again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitOpen {
+    pub retry_after: Duration,
+}
+impl CircuitOpen {
+    pub fn is(e: &sqlx::Error) -> bool {
+        match e {
+            sqlx::Error::Io(e) => e.get_ref().is_some_and(|e| e.is::<CircuitOpen>()),
+            _ => false,
+        }
+    }
+}
+impl std::fmt::Display for CircuitOpen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "circuit open, retry after {:?}", self.retry_after)
+    }
+}
+impl std::error::Error for CircuitOpen {}
+impl From<CircuitOpen> for sqlx::Error {
+    fn from(e: CircuitOpen) -> Self {
+        sqlx::Error::Io(std::io::Error::new(std::io::ErrorKind::WouldBlock, e))
+    }
+}
+
+// The schema is older than the code expects: the latest migration applied, if any, is older
+// than `required`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SchemaTooOld {
+    pub required: i64,
+    pub applied: Option<i64>,
+}
+impl SchemaTooOld {
+    pub fn is(e: &sqlx::Error) -> bool {
+        match e {
+            sqlx::Error::Io(e) => e.get_ref().is_some_and(|e| e.is::<SchemaTooOld>()),
+            _ => false,
+        }
+    }
+}
+impl std::fmt::Display for SchemaTooOld {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.applied {
+            Some(applied) => write!(
+                f,
+                "schema too old: migration {} required, {} applied",
+                self.required, applied
+            ),
+            None => write!(
+                f,
+                "schema too old: migration {} required, none applied",
+                self.required
+            ),
+        }
+    }
+}
+impl std::error::Error for SchemaTooOld {}
+impl From<SchemaTooOld> for sqlx::Error {
+    fn from(e: SchemaTooOld) -> Self {
+        sqlx::Error::Io(std::io::Error::other(e))
+    }
+}
+
+// Errors that may carry a SQLSTATE, so the runner can tell transient failures apart.
+pub trait SqlState {
+    fn sqlstate(&self) -> Option<Cow<'_, str>>;
+
+    // What the failure means, for branching on it in `or_else`, `try_recover`...
+    fn error_kind(&self) -> ErrorKind {
+        self.sqlstate()
+            .map_or(ErrorKind::Other, |code| ErrorKind::from_sqlstate(&code))
+    }
+    // The constraint a violation is about, when the database names it.
+    fn constraint(&self) -> Option<&str> {
+        None
+    }
+
+    // What a retrying runner returns when it gives up on a serialization failure after
+    // `attempts` attempts: the error as it is, unless the error type has a case for it.
+    fn retries_exhausted(self, attempts: u32) -> Self
+    where
+        Self: Sized,
+    {
+        let _ = attempts;
+        self
+    }
+}
+impl SqlState for sqlx::Error {
+    fn sqlstate(&self) -> Option<Cow<'_, str>> {
+        match self {
+            sqlx::Error::Database(e) => e.code(),
+            _ => None,
+        }
+    }
+    fn error_kind(&self) -> ErrorKind {
+        match self {
+            // sqlx knows the error numbers of each backend for the constraint violations
+            sqlx::Error::Database(e) => match e.kind() {
+                sqlx::error::ErrorKind::UniqueViolation => ErrorKind::UniqueViolation,
+                sqlx::error::ErrorKind::ForeignKeyViolation => ErrorKind::ForeignKeyViolation,
+                sqlx::error::ErrorKind::NotNullViolation => ErrorKind::NotNullViolation,
+                sqlx::error::ErrorKind::CheckViolation => ErrorKind::CheckViolation,
+                _ => e
+                    .code()
+                    .map_or(ErrorKind::Other, |code| ErrorKind::from_sqlstate(&code)),
+            },
+            sqlx::Error::Io(e) if e.get_ref().is_some_and(|e| e.is::<PoolExhausted>()) => {
+                ErrorKind::PoolTimedOut
+            }
+            // the runner's own deadlines, timeouts, cancellations and budgets come as I/O errors
+            sqlx::Error::Io(e)
+                if e.get_ref().is_some_and(|e| {
+                    e.is::<DeadlineExceeded>()
+                        || e.is::<TimedOut>()
+                        || e.is::<Cancelled>()
+                        || e.is::<StatementBudgetExceeded>()
+                        || e.is::<RateLimited>()
+                        || e.is::<CircuitOpen>()
+                        || e.is::<SchemaTooOld>()
+                }) =>
+            {
+                ErrorKind::Other
+            }
+            sqlx::Error::PoolTimedOut => ErrorKind::PoolTimedOut,
+            sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_)
+            | sqlx::Error::Protocol(_)
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::WorkerCrashed => ErrorKind::ConnectionLost,
+            _ => ErrorKind::Other,
+        }
+    }
+    fn constraint(&self) -> Option<&str> {
+        match self {
+            sqlx::Error::Database(e) => e.constraint(),
+            _ => None,
+        }
+    }
+}
+
+// The categories of database failures worth branching on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    UniqueViolation,
+    ForeignKeyViolation,
+    NotNullViolation,
+    CheckViolation,
+    SerializationFailure,
+    Deadlock,
+    // The connection broke or was closed by the server; the transaction is gone with it.
+    ConnectionLost,
+    // No connection of the pool came free within its `acquire_timeout`, or as the `Backpressure`
+    // of a `BoundedRunner` would have it; the transaction never began.
+    PoolTimedOut,
+    Other,
+}
+impl ErrorKind {
+    // By the standard SQLSTATE codes and the Postgres specific ones.
+    pub fn from_sqlstate(code: &str) -> Self {
+        match code {
+            "23505" => ErrorKind::UniqueViolation,
+            "23503" => ErrorKind::ForeignKeyViolation,
+            "23502" => ErrorKind::NotNullViolation,
+            "23514" => ErrorKind::CheckViolation,
+            "40001" => ErrorKind::SerializationFailure,
+            "40P01" => ErrorKind::Deadlock,
+            // admin_shutdown, crash_shutdown, cannot_connect_now
+            "57P01" | "57P02" | "57P03" => ErrorKind::ConnectionLost,
+            _ if code.starts_with("08") => ErrorKind::ConnectionLost,
+            _ => ErrorKind::Other,
+        }
+    }
+
+    // Whether running the transaction again may succeed.
+    pub fn is_transient(self) -> bool {
+        matches!(
+            self,
+            ErrorKind::SerializationFailure
+                | ErrorKind::Deadlock
+                | ErrorKind::ConnectionLost
+                | ErrorKind::PoolTimedOut
+        )
+    }
+}
+
+// The ways a transaction can fail, for callers that match on the category instead of probing
+// a `sqlx::Error`. It works as the error of any chain and runner: the timeouts, cancellations
+// and deadlines the runner reports through `sqlx::Error` land in their own cases, and the
+// chain's own failures go into `Domain`.
+#[derive(Debug, thiserror::Error)]
+pub enum TxError<E = Infallible> {
+    #[error(transparent)]
+    Db(sqlx::Error),
+    #[error("deadline exceeded")]
+    DeadlineExceeded,
+    #[error(transparent)]
+    Timeout(TimedOut),
+    #[error(transparent)]
+    Cancelled(Cancelled),
+    #[error("timed out acquiring a connection from the pool")]
+    PoolTimedOut,
+    #[error(transparent)]
+    PoolExhausted(PoolExhausted),
+    #[error(transparent)]
+    RateLimited(RateLimited),
+    #[error(transparent)]
+    CircuitOpen(CircuitOpen),
+    #[error(transparent)]
+    StatementBudgetExceeded(StatementBudgetExceeded),
+    #[error(transparent)]
+    SchemaTooOld(SchemaTooOld),
+    #[error("serialization failure after {attempts} attempts: {source}")]
+    SerializationRetryExhausted { attempts: u32, source: sqlx::Error },
+    #[error(transparent)]
+    Domain(E),
+}
+impl<E> From<sqlx::Error> for TxError<E> {
+    fn from(e: sqlx::Error) -> Self {
+        if DeadlineExceeded::is(&e) {
+            return TxError::DeadlineExceeded;
+        }
+        if let sqlx::Error::PoolTimedOut = e {
+            return TxError::PoolTimedOut;
+        }
+        if let sqlx::Error::Io(io) = &e {
+            if let Some(inner) = io.get_ref() {
+                if let Some(timed_out) = inner.downcast_ref::<TimedOut>() {
+                    return TxError::Timeout(*timed_out);
+                }
+                if inner.is::<Cancelled>() {
+                    return TxError::Cancelled(Cancelled);
+                }
+                if let Some(exhausted) = inner.downcast_ref::<PoolExhausted>() {
+                    return TxError::PoolExhausted(*exhausted);
+                }
+                if let Some(limited) = inner.downcast_ref::<RateLimited>() {
+                    return TxError::RateLimited(*limited);
+                }
+                if let Some(open) = inner.downcast_ref::<CircuitOpen>() {
+                    return TxError::CircuitOpen(*open);
+                }
+                if let Some(exceeded) = inner.downcast_ref::<StatementBudgetExceeded>() {
+                    return TxError::StatementBudgetExceeded(*exceeded);
+                }
+                if let Some(too_old) = inner.downcast_ref::<SchemaTooOld>() {
+                    return TxError::SchemaTooOld(*too_old);
+                }
+            }
+        }
+        TxError::Db(e)
+    }
+}
+impl<E> From<DeadlineExceeded> for TxError<E> {
+    fn from(_: DeadlineExceeded) -> Self {
+        TxError::DeadlineExceeded
+    }
+}
+impl<E> From<TimedOut> for TxError<E> {
+    fn from(e: TimedOut) -> Self {
+        TxError::Timeout(e)
+    }
+}
+impl<E> From<Cancelled> for TxError<E> {
+    fn from(e: Cancelled) -> Self {
+        TxError::Cancelled(e)
+    }
+}
+impl<E> From<PoolExhausted> for TxError<E> {
+    fn from(e: PoolExhausted) -> Self {
+        TxError::PoolExhausted(e)
+    }
+}
+impl<E> From<RateLimited> for TxError<E> {
+    fn from(e: RateLimited) -> Self {
+        TxError::RateLimited(e)
+    }
+}
+impl<E> From<CircuitOpen> for TxError<E> {
+    fn from(e: CircuitOpen) -> Self {
+        TxError::CircuitOpen(e)
+    }
+}
+impl<E> From<StatementBudgetExceeded> for TxError<E> {
+    fn from(e: StatementBudgetExceeded) -> Self {
+        TxError::StatementBudgetExceeded(e)
+    }
+}
+impl<E> From<SchemaTooOld> for TxError<E> {
+    fn from(e: SchemaTooOld) -> Self {
+        TxError::SchemaTooOld(e)
+    }
+}
+impl<E> SqlState for TxError<E> {
+    fn sqlstate(&self) -> Option<Cow<'_, str>> {
+        match self {
+            TxError::Db(e) | TxError::SerializationRetryExhausted { source: e, .. } => e.sqlstate(),
+            _ => None,
+        }
+    }
+    fn error_kind(&self) -> ErrorKind {
+        match self {
+            TxError::Db(e) | TxError::SerializationRetryExhausted { source: e, .. } => {
+                e.error_kind()
+            }
+            TxError::PoolTimedOut | TxError::PoolExhausted(_) => ErrorKind::PoolTimedOut,
+            _ => ErrorKind::Other,
+        }
+    }
+    fn constraint(&self) -> Option<&str> {
+        match self {
+            TxError::Db(e) | TxError::SerializationRetryExhausted { source: e, .. } => {
+                e.constraint()
+            }
+            _ => None,
+        }
+    }
+    fn retries_exhausted(self, attempts: u32) -> Self {
+        match self {
+            TxError::Db(source) => TxError::SerializationRetryExhausted { attempts, source },
+            e => e,
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value};
+use sqlx::Executor;
+
+use crate::combinator::{AsyncMode, BoxFuture, Description, Tx};
+use crate::runner::PgCtx;
+
+// Test data kept in files and loaded by a step, in the transaction of the test:
+// - `.yaml`: a map from table name to the list of its rows, each a map from column to value;
+// - `.csv`: the rows of the table named like the file, under a header naming the columns,
+//   an empty field being NULL;
+// - `.sql`: statements run as they are.
+// SQL files run first, in the order given. The rows of the other files are then inserted
+// parents first, as the foreign keys between their tables require. Values are converted to
+// the types of the columns by Postgres, as `jsonb_populate_recordset` does; the columns a
+// YAML row leaves out take their defaults.
+
+// Loads the fixture file at `path`.
+pub fn load_fixture(path: impl Into<PathBuf>) -> LoadFixtures {
+    load_fixtures([path])
+}
+// Loads the fixture files at `paths`, ordering the rows of all of them together.
+pub fn load_fixtures<P: Into<PathBuf>>(paths: impl IntoIterator<Item = P>) -> LoadFixtures {
+    LoadFixtures {
+        paths: paths.into_iter().map(Into::into).collect(),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LoadFixtures {
+    paths: Vec<PathBuf>,
+}
+impl Tx<PgCtx> for LoadFixtures {
+    type Item = ();
+    type Err = sqlx::Error;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut PgCtx) -> BoxFuture<'a, Result<(), sqlx::Error>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            let mut scripts = vec![];
+            let mut tables = Tables::new();
+            for path in &self.paths {
+                let text = std::fs::read_to_string(path)?;
+                match extension(path) {
+                    "sql" => scripts.push(text),
+                    "yaml" | "yml" => {
+                        let file: BTreeMap<String, Vec<Map<String, Value>>> =
+                            serde_yaml::from_str(&text).map_err(|e| invalid(path, e))?;
+                        for (table, rows) in file {
+                            tables.entry(table).or_default().extend(rows);
+                        }
+                    }
+                    "csv" => {
+                        let table = table_name(path)?;
+                        let rows = read_csv(&text).map_err(|e| invalid(path, e))?;
+                        tables.entry(table).or_default().extend(rows);
+                    }
+                    _ => return Err(invalid(path, "expected a .yaml, .csv or .sql file")),
+                }
+            }
+
+            for script in scripts {
+                // without arguments, the statements go as one simple query
+                (&mut **ctx).execute(script.as_str()).await?;
+            }
+            let references: Vec<(String, String)> = sqlx::query_as(
+                r#"SELECT conrelid::regclass::text, confrelid::regclass::text
+                   FROM pg_constraint
+                   WHERE contype = 'f' AND conrelid <> confrelid"#,
+            )
+            .fetch_all(&mut **ctx)
+            .await?;
+            for table in insertion_order(&tables, &references)? {
+                // rows with the same columns go together; the columns a row leaves out take
+                // their defaults
+                for rows in tables[&table].chunk_by(|a, b| a.keys().eq(b.keys())) {
+                    if let Some(sql) = insert_sql(&table, rows) {
+                        sqlx::query(&sql)
+                            .bind(sqlx::types::Json(rows))
+                            .execute(&mut **ctx)
+                            .await?;
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::leaf("load_fixtures")
+    }
+}
+
+type Tables = BTreeMap<String, Vec<Map<String, Value>>>;
+
+// The statement inserting `rows`, which have the same columns, into `table`.
+fn insert_sql(table: &str, rows: &[Map<String, Value>]) -> Option<String> {
+    let columns: Vec<&str> = rows.first()?.keys().map(String::as_str).collect();
+    if columns.is_empty() {
+        return None;
+    }
+    let columns = columns.join(", ");
+    Some(format!(
+        "INSERT INTO {} ({}) SELECT {} FROM jsonb_populate_recordset(NULL::{}, $1)",
+        table, columns, columns, table
+    ))
+}
+
+// The tables of `tables`, each after those its foreign keys, `(child, parent)`, point to.
+fn insertion_order(
+    tables: &Tables,
+    references: &[(String, String)],
+) -> Result<Vec<String>, sqlx::Error> {
+    let mut left: Vec<String> = tables.keys().cloned().collect();
+    let mut order = vec![];
+    while !left.is_empty() {
+        let (first, rest): (Vec<String>, Vec<String>) = left.iter().cloned().partition(|table| {
+            !references
+                .iter()
+                .any(|(child, parent)| child == table && left.contains(parent))
+        });
+        if first.is_empty() {
+            let message = format!("foreign keys between {} form a cycle", rest.join(", "));
+            return Err(sqlx::Error::Decode(message.into()));
+        }
+        order.extend(first);
+        left = rest;
+    }
+    Ok(order)
+}
+
+fn read_csv(text: &str) -> Result<Vec<Map<String, Value>>, csv::Error> {
+    let mut reader = csv::Reader::from_reader(text.as_bytes());
+    let headers = reader.headers()?.clone();
+    reader
+        .records()
+        .map(|record| {
+            let record = record?;
+            let row = headers.iter().zip(record.iter()).map(|(column, field)| {
+                let value = match field {
+                    "" => Value::Null,
+                    field => Value::String(field.to_string()),
+                };
+                (column.to_string(), value)
+            });
+            Ok(row.collect())
+        })
+        .collect()
+}
+
+fn extension(path: &Path) -> &str {
+    path.extension().and_then(|e| e.to_str()).unwrap_or("")
+}
+fn table_name(path: &Path) -> Result<String, sqlx::Error> {
+    match path.file_stem().and_then(|stem| stem.to_str()) {
+        Some(stem) => Ok(stem.to_string()),
+        None => Err(invalid(path, "no table name")),
+    }
+}
+fn invalid(path: &Path, e: impl std::fmt::Display) -> sqlx::Error {
+    sqlx::Error::Decode(format!("fixture {}: {}", path.display(), e).into())
+}
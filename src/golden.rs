@@ -0,0 +1,125 @@
+use std::sync::{Arc, Mutex};
+
+use serde_json::{json, Value};
+use tracing::field::{Field, Visit};
+use tracing::instrument::WithSubscriber;
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Event, Metadata, Subscriber};
+
+use crate::postgres_example::{commit_example, explicit_rollback_example, runner_rollback_example};
+
+// `report`: the rollback and commit scenarios of the Postgres example, each as a JSON report
+// of the statements it executed and the rows it left, which `tests/golden.rs` compares with
+// the files under `tests/golden/`. The statements are those sqlx logs, hence the `tracing`
+// feature.
+
+const TEST_ID: i64 = 1;
+
+pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let conn_str =
+        std::env::var("DATABASE_URL").expect("Env var DATABASE_URL is required for this example.");
+    let pool = sqlx::PgPool::connect(&conn_str).await?;
+
+    let mut reports = vec![];
+    for scenario in ["explicit_rollback", "implicit_rollback", "commit"] {
+        sqlx::query("DELETE FROM todos WHERE id = $1")
+            .bind(TEST_ID)
+            .execute(&pool)
+            .await?;
+
+        let log = StatementLog::default();
+        let run = async {
+            match scenario {
+                // the transaction is rolled back by the example itself
+                "explicit_rollback" => explicit_rollback_example(&pool, TEST_ID).await,
+                // the chain fails, and the runner rolls its transaction back
+                "implicit_rollback" => runner_rollback_example(&pool, TEST_ID).await,
+                _ => commit_example(&pool, TEST_ID).await,
+            }
+        };
+        run.with_subscriber(log.clone())
+            .await
+            .map_err(|e| format!("scenario {} failed: {}", scenario, e))?;
+
+        let todos: Vec<(i64, String, bool)> =
+            sqlx::query_as("SELECT id, description, done FROM todos WHERE id = $1 ORDER BY id")
+                .bind(TEST_ID)
+                .fetch_all(&pool)
+                .await?;
+        let todos: Vec<Value> = todos
+            .into_iter()
+            .map(|(id, description, done)| json!({"id": id, "description": description, "done": done}))
+            .collect();
+        reports.push(json!({
+            "scenario": scenario,
+            "statements": log.statements(),
+            "todos": todos,
+        }));
+    }
+    println!("{}", serde_json::to_string_pretty(&reports)?);
+
+    Ok(())
+}
+
+// The statements sqlx logs under `sqlx::query` while it is the subscriber, whitespace
+// collapsed, so that they compare line for line.
+#[derive(Debug, Clone, Default)]
+struct StatementLog {
+    statements: Arc<Mutex<Vec<String>>>,
+}
+impl StatementLog {
+    fn statements(&self) -> Vec<String> {
+        self.statements.lock().unwrap().clone()
+    }
+}
+impl Subscriber for StatementLog {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if metadata.target() == "sqlx::query" {
+            Interest::sometimes()
+        } else {
+            Interest::never()
+        }
+    }
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.target() == "sqlx::query"
+    }
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+    fn event(&self, event: &Event<'_>) {
+        let mut statement = Statement::default();
+        event.record(&mut statement);
+        // the summary is the statement when it is short, and its first words otherwise
+        let sql = match statement.full.trim() {
+            "" => statement.summary,
+            full => full.to_string(),
+        };
+        let sql = sql.split_whitespace().collect::<Vec<_>>().join(" ");
+        self.statements.lock().unwrap().push(sql);
+    }
+    fn enter(&self, _: &Id) {}
+    fn exit(&self, _: &Id) {}
+}
+
+#[derive(Default)]
+struct Statement {
+    summary: String,
+    full: String,
+}
+impl Visit for Statement {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "summary" => self.summary = value.to_string(),
+            "db.statement" => self.full = value.to_string(),
+            _ => {}
+        }
+    }
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "summary" {
+            self.summary = format!("{:?}", value);
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::fmt::Write;
+
+use crate::combinator::Description;
+
+// `describe()` drawn as a graph, for documentation and for reviewing long chains: DOT for
+// Graphviz, or a mermaid flowchart that renders in Markdown on GitHub and GitLab.
+//
+//     println!("{}", chain.describe().to_mermaid());
+//
+// Joins are drawn as diamonds with a numbered edge per branch. `or_else`, `recover` and
+// `try_recover` are hexagons with a dashed edge to the fallback, and `and_then`, `then` and
+// `and_then_into` have one to the step after: those are built by closures when the chain
+// runs, so they are left unnamed. Retries are not in the tree: `run_tx_retry` retries the
+// whole transaction.
+impl Description {
+    pub fn to_dot(&self) -> String {
+        let graph = Graph::of(self);
+        let mut dot = String::from("digraph tx {\n    node [shape=box];\n");
+        for (id, node) in graph.nodes.iter().enumerate() {
+            let label = node.label.replace('\\', "\\\\").replace('"', "\\\"");
+            let attrs = match node.shape {
+                Shape::Step => String::new(),
+                Shape::Join => ", shape=diamond".to_string(),
+                Shape::Fallback => ", shape=hexagon".to_string(),
+                Shape::Later => ", style=dashed".to_string(),
+            };
+            writeln!(dot, "    n{} [label=\"{}\"{}];", id, label, attrs).unwrap();
+        }
+        for edge in &graph.edges {
+            let attrs: Vec<String> = edge
+                .label
+                .iter()
+                .map(|label| format!("label=\"{}\"", label))
+                .chain(edge.dashed.then(|| "style=dashed".to_string()))
+                .collect();
+            let attrs = if attrs.is_empty() {
+                String::new()
+            } else {
+                format!(" [{}]", attrs.join(", "))
+            };
+            writeln!(dot, "    n{} -> n{}{};", edge.from, edge.to, attrs).unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn to_mermaid(&self) -> String {
+        let graph = Graph::of(self);
+        let mut mermaid = String::from("flowchart TD\n");
+        for (id, node) in graph.nodes.iter().enumerate() {
+            let label = node.label.replace('"', "#quot;");
+            let (open, close) = match node.shape {
+                Shape::Step | Shape::Later => ("[\"", "\"]"),
+                Shape::Join => ("{\"", "\"}"),
+                Shape::Fallback => ("{{\"", "\"}}"),
+            };
+            writeln!(mermaid, "    n{}{}{}{}", id, open, label, close).unwrap();
+        }
+        for edge in &graph.edges {
+            let arrow = if edge.dashed { "-.->" } else { "-->" };
+            let label = edge
+                .label
+                .as_ref()
+                .map(|label| format!("|{}|", label))
+                .unwrap_or_default();
+            writeln!(
+                mermaid,
+                "    n{} {}{} n{}",
+                edge.from, arrow, label, edge.to
+            )
+            .unwrap();
+        }
+        mermaid
+    }
+}
+
+// The nodes of the tree in depth-first order, numbered as they come.
+#[derive(Default)]
+struct Graph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+}
+struct Node {
+    label: String,
+    shape: Shape,
+}
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    Step,
+    Join,
+    Fallback,
+    // a step only known when the chain runs
+    Later,
+}
+struct Edge {
+    from: usize,
+    to: usize,
+    label: Option<String>,
+    dashed: bool,
+}
+
+impl Graph {
+    fn of(description: &Description) -> Self {
+        let mut graph = Graph::default();
+        graph.add(description);
+        graph
+    }
+
+    fn add(&mut self, description: &Description) {
+        let (shape, later) = match &*description.name {
+            "join" | "join3" | "join4" => (Shape::Join, None),
+            "or_else" | "recover" | "try_recover" => (Shape::Fallback, Some("on error")),
+            "and_then" | "then" | "and_then_into" => (Shape::Step, Some("then")),
+            _ => (Shape::Step, None),
+        };
+        let id = self.node(description.name.to_string(), shape);
+        let numbered = shape == Shape::Join;
+        for (i, child) in description.children.iter().enumerate() {
+            // the child is the next node, its edge goes before those of its own children
+            let label = numbered.then(|| (i + 1).to_string());
+            self.edge(id, self.nodes.len(), label, false);
+            self.add(child);
+        }
+        if let Some(label) = later {
+            let to = self.node("…".to_string(), Shape::Later);
+            self.edge(id, to, Some(label.to_string()), true);
+        }
+    }
+
+    fn node(&mut self, label: String, shape: Shape) -> usize {
+        self.nodes.push(Node { label, shape });
+        self.nodes.len() - 1
+    }
+    fn edge(&mut self, from: usize, to: usize, label: Option<String>, dashed: bool) {
+        self.edges.push(Edge {
+            from,
+            to,
+            label,
+            dashed,
+        });
+    }
+}
@@ -0,0 +1,88 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sqlx::types::Json;
+use sqlx::PgPool;
+
+use crate::combinator::{AsyncMode, BoxFuture, Description, Tx};
+use crate::runner::PgCtx;
+
+pub async fn create_table(pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"CREATE TABLE IF NOT EXISTS tx_rs_idempotency
+           (
+               key        TEXT PRIMARY KEY,
+               result     JSONB,
+               created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+           )"#,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// Runs `tx` at most once per `key`: the key is recorded together with the serialized result in
+// the transaction of `tx`, and when it is found there the stored result is returned instead.
+// A run under a key which is in flight elsewhere waits for that one to commit or roll back.
+#[derive(Debug, Clone)]
+pub struct Idempotent<X> {
+    tx: X,
+    key: String,
+}
+impl<X> Tx<PgCtx> for Idempotent<X>
+where
+    X: Tx<PgCtx, Mode = AsyncMode> + Send,
+    X::Item: Serialize + DeserializeOwned + Send + Sync,
+    X::Err: From<sqlx::Error> + Send,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut PgCtx) -> BoxFuture<'a, Result<Self::Item, Self::Err>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            // blocks on the row of a concurrent run under the same key until that one is over
+            sqlx::query("INSERT INTO tx_rs_idempotency (key) VALUES ($1) ON CONFLICT DO NOTHING")
+                .bind(&self.key)
+                .execute(&mut **ctx)
+                .await?;
+            let (stored,): (Option<serde_json::Value>,) =
+                sqlx::query_as("SELECT result FROM tx_rs_idempotency WHERE key = $1 FOR UPDATE")
+                    .bind(&self.key)
+                    .fetch_one(&mut **ctx)
+                    .await?;
+            if let Some(stored) = stored {
+                let t =
+                    serde_json::from_value(stored).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+                return Ok(t);
+            }
+
+            let t = self.tx.run(&mut *ctx).await?;
+            sqlx::query("UPDATE tx_rs_idempotency SET result = $2 WHERE key = $1")
+                .bind(&self.key)
+                .bind(Json(&t))
+                .execute(&mut **ctx)
+                .await?;
+            Ok(t)
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("idempotent", vec![self.tx.describe()])
+    }
+}
+
+pub trait IdempotentExt: Tx<PgCtx, Mode = AsyncMode> {
+    fn idempotent(self, key: impl Into<String>) -> Idempotent<Self>
+    where
+        Self: Sized,
+    {
+        Idempotent {
+            tx: self,
+            key: key.into(),
+        }
+    }
+}
+impl<X> IdempotentExt for X where X: Tx<PgCtx, Mode = AsyncMode> {}
@@ -0,0 +1,68 @@
+// So that code generated by `tx-rs-macros` names this crate `::tx` from inside it too.
+extern crate self as tx;
+
+#[cfg(feature = "postgres")]
+pub mod audit;
+pub mod chain;
+pub mod chaos;
+pub mod combinator;
+pub mod context;
+#[cfg(feature = "postgres")]
+pub mod coordinator;
+#[cfg(feature = "diesel")]
+pub mod diesel;
+pub mod env;
+pub mod error;
+#[cfg(feature = "fixtures")]
+pub mod fixtures;
+pub mod graph;
+#[cfg(feature = "postgres")]
+pub mod idempotency;
+pub mod memory;
+#[cfg(feature = "postgres")]
+pub mod migrations;
+pub mod mock;
+#[cfg(feature = "postgres")]
+pub mod notify;
+pub mod observer;
+#[cfg(feature = "postgres")]
+pub mod outbox;
+#[cfg(any(feature = "anyhow", feature = "eyre"))]
+pub mod report;
+pub mod repository;
+pub mod rt;
+pub mod runner;
+#[cfg(feature = "rusqlite")]
+pub mod rusqlite;
+#[cfg(feature = "postgres")]
+pub mod schema;
+#[cfg(feature = "postgres")]
+pub mod seed;
+pub mod sqlcomment;
+pub mod testing;
+pub mod trace;
+pub mod watchdog;
+#[cfg(feature = "postgres")]
+pub mod worker;
+
+pub use combinator::bracket;
+pub use env::{next_id, now};
+
+// What most code using the crate needs: `use tx::prelude::*;`.
+pub mod prelude {
+    pub use crate::combinator::{
+        bracket, ready, with_tx, with_tx_async, AsyncMode, BoxFuture, IntoTx, ResultExt, SyncMode,
+        Tx,
+    };
+    pub use crate::context::{ReadTx, TxAccess, TxCtx, WriteTx};
+    pub use crate::error::{
+        CircuitOpen, DeadlineExceeded, ErrorKind, PoolExhausted, RateLimited, SchemaTooOld,
+        SqlState, StatementBudgetExceeded, TxError,
+    };
+    #[cfg(feature = "postgres")]
+    pub use crate::runner::{as_user, PgCtx, PgReadCtx};
+    pub use crate::runner::{
+        run_tx, run_tx_with, savepoint, Backend, SavepointExt, TimeoutExt, TxOptions,
+    };
+    pub use crate::tx;
+}
@@ -1,653 +1,76 @@
-use sqlx::query;
-
-mod tx_rs {
-    pub trait Tx<Ctx> {
-        type Item;
-        type Err;
-
-        fn run(self, ctx: &mut Ctx) -> Result<Self::Item, Self::Err>;
-
-        fn map<F, T>(self, f: F) -> Map<Self, F>
-        where
-            F: FnOnce(Self::Item) -> T,
-            Self: Sized,
-        {
-            Map { tx1: self, f }
-        }
-        fn and_then<Tx2, F>(self, f: F) -> AndThen<Self, F>
-        where
-            Tx2: Tx<Ctx, Err = Self::Err>,
-            F: FnOnce(Self::Item) -> Tx2,
-            Self: Sized,
-        {
-            AndThen { tx1: self, f }
-        }
-        fn then<Tx2, F>(self, f: F) -> Then<Self, F>
-        where
-            Tx2: Tx<Ctx, Err = Self::Err>,
-            F: FnOnce(Result<Self::Item, Self::Err>) -> Tx2,
-            Self: Sized,
-        {
-            Then { tx1: self, f }
-        }
-        fn or_else<Tx2, F>(self, f: F) -> OrElse<Self, F>
-        where
-            Tx2: Tx<Ctx, Item = Self::Item, Err = Self::Err>,
-            F: FnOnce(Self::Err) -> Tx2,
-            Self: Sized,
-        {
-            OrElse { tx1: self, f }
-        }
-        fn join<Tx2>(self, tx2: Tx2) -> Join<Self, Tx2>
-        where
-            Tx2: Tx<Ctx, Err = Self::Err>,
-            Self: Sized,
-        {
-            Join { tx1: self, tx2 }
-        }
-        fn join3<Tx2, Tx3>(self, tx2: Tx2, tx3: Tx3) -> Join3<Self, Tx2, Tx3>
-        where
-            Tx2: Tx<Ctx, Err = Self::Err>,
-            Tx3: Tx<Ctx, Err = Self::Err>,
-            Self: Sized,
-        {
-            Join3 {
-                tx1: self,
-                tx2,
-                tx3,
-            }
-        }
-        fn join4<Tx2, Tx3, Tx4>(self, tx2: Tx2, tx3: Tx3, tx4: Tx4) -> Join4<Self, Tx2, Tx3, Tx4>
-        where
-            Tx2: Tx<Ctx, Err = Self::Err>,
-            Tx3: Tx<Ctx, Err = Self::Err>,
-            Tx4: Tx<Ctx, Err = Self::Err>,
-            Self: Sized,
-        {
-            Join4 {
-                tx1: self,
-                tx2,
-                tx3,
-                tx4,
+#[cfg(all(feature = "actix-web", feature = "postgres"))]
+mod actix_example;
+#[cfg(feature = "any")]
+mod any_example;
+#[cfg(all(feature = "axum", feature = "postgres"))]
+mod axum_example;
+mod cli;
+#[cfg(all(feature = "postgres", feature = "tracing"))]
+mod golden;
+mod memory_example;
+mod mock_example;
+#[cfg(feature = "mysql")]
+mod mysql_example;
+#[cfg(feature = "postgres")]
+mod postgres_example;
+#[cfg(feature = "postgres")]
+mod seeds;
+#[cfg(feature = "sqlite")]
+mod sqlite_example;
+mod todo_repository;
+#[cfg(all(feature = "tower", feature = "postgres"))]
+mod tower_example;
+
+#[cfg_attr(feature = "runtime-tokio", tokio::main)]
+#[cfg_attr(
+    all(feature = "runtime-async-std", not(feature = "runtime-tokio")),
+    async_std::main
+)]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = <cli::Cli as clap::Parser>::parse();
+    #[cfg(feature = "postgres")]
+    if let Some(command) = cli.command {
+        use cli::{commands, Command};
+        let pool = cli.pool.connect().await?;
+        return match command {
+            Command::Demo { scenario, id } => commands::demo(&pool, scenario, id).await,
+            Command::Migrate => commands::migrate(&pool).await,
+            Command::Seed { set } => seeds::run(&pool, &set).await,
+            Command::Bench { transactions } => commands::bench(&pool, transactions).await,
+            Command::Health { timeout_ms } => {
+                commands::health(&pool, std::time::Duration::from_millis(timeout_ms)).await
             }
-        }
-        fn map_err<F, E>(self, f: F) -> MapErr<Self, F>
-        where
-            F: FnOnce(Self::Err) -> E,
-            Self: Sized,
-        {
-            MapErr { tx1: self, f }
-        }
-        fn try_map<F, T, E>(self, f: F) -> TryMap<Self, F>
-        where
-            F: FnOnce(Self::Item) -> Result<T, E>,
-            Self: Sized,
-        {
-            TryMap { tx1: self, f }
-        }
-        fn recover<F>(self, f: F) -> Recover<Self, F>
-        where
-            F: FnOnce(Self::Err) -> Self::Item,
-            Self: Sized,
-        {
-            Recover { tx1: self, f }
-        }
-        fn try_recover<F>(self, f: F) -> TryRecover<Self, F>
-        where
-            F: FnOnce(Self::Err) -> Result<Self::Item, Self::Err>,
-            Self: Sized,
-        {
-            TryRecover { tx1: self, f }
-        }
-        fn abort<F>(self, f: F) -> Abort<Self, F>
-        where
-            F: FnOnce(Self::Item) -> Self::Err,
-            Self: Sized,
-        {
-            Abort { tx1: self, f }
-        }
-        fn try_abort<F>(self, f: F) -> TryAbort<Self, F>
-        where
-            F: FnOnce(Self::Item) -> Result<Self::Item, Self::Err>,
-            Self: Sized,
-        {
-            TryAbort { tx1: self, f }
-        }
-    }
-
-    impl<Ctx, T, E, F> Tx<Ctx> for F
-    where
-        F: FnOnce(&mut Ctx) -> Result<T, E>,
-    {
-        type Item = T;
-        type Err = E;
-        fn run(self, ctx: &mut Ctx) -> Result<Self::Item, Self::Err> {
-            self(ctx)
-        }
-    }
-
-    fn map<Ctx, Tx1, F, T>(tx1: Tx1, f: F) -> impl FnOnce(&mut Ctx) -> Result<T, Tx1::Err>
-    where
-        Tx1: Tx<Ctx>,
-        F: FnOnce(Tx1::Item) -> T,
-    {
-        move |ctx| match tx1.run(ctx) {
-            Ok(x) => Ok(f(x)),
-            Err(e) => Err(e),
-        }
-    }
-
-    pub struct Map<Tx1, F> {
-        tx1: Tx1,
-        f: F,
-    }
-    impl<Ctx, Tx1, T, F> Tx<Ctx> for Map<Tx1, F>
-    where
-        Tx1: Tx<Ctx>,
-        F: FnOnce(Tx1::Item) -> T,
-    {
-        type Item = T;
-        type Err = Tx1::Err;
-
-        fn run(self, ctx: &mut Ctx) -> Result<Self::Item, Self::Err> {
-            map(self.tx1, self.f)(ctx)
-        }
-    }
-
-    fn and_then<Ctx, Tx1, Tx2, F>(
-        tx1: Tx1,
-        f: F,
-    ) -> impl FnOnce(&mut Ctx) -> Result<Tx2::Item, Tx1::Err>
-    where
-        Tx1: Tx<Ctx>,
-        Tx2: Tx<Ctx, Err = Tx1::Err>,
-        F: FnOnce(Tx1::Item) -> Tx2,
-    {
-        move |ctx| match tx1.run(ctx) {
-            Ok(x) => f(x).run(ctx),
-            Err(e) => Err(e),
-        }
-    }
-
-    pub struct AndThen<Tx1, F> {
-        tx1: Tx1,
-        f: F,
-    }
-    impl<Ctx, Tx1, Tx2, F> Tx<Ctx> for AndThen<Tx1, F>
-    where
-        Tx1: Tx<Ctx>,
-        Tx2: Tx<Ctx, Err = Tx1::Err>,
-        F: FnOnce(Tx1::Item) -> Tx2,
-    {
-        type Item = Tx2::Item;
-        type Err = Tx1::Err;
-
-        fn run(self, ctx: &mut Ctx) -> Result<Self::Item, Self::Err> {
-            and_then(self.tx1, self.f)(ctx)
-        }
-    }
-
-    fn then<Ctx, Tx1, Tx2, F>(
-        tx1: Tx1,
-        f: F,
-    ) -> impl FnOnce(&mut Ctx) -> Result<Tx2::Item, Tx1::Err>
-    where
-        Tx1: Tx<Ctx>,
-        Tx2: Tx<Ctx, Err = Tx1::Err>,
-        F: FnOnce(Result<Tx1::Item, Tx1::Err>) -> Tx2,
-    {
-        move |ctx| f(tx1.run(ctx)).run(ctx)
-    }
-
-    pub struct Then<Tx1, F> {
-        tx1: Tx1,
-        f: F,
-    }
-    impl<Ctx, Tx1, Tx2, F> Tx<Ctx> for Then<Tx1, F>
-    where
-        Tx1: Tx<Ctx>,
-        Tx2: Tx<Ctx, Err = Tx1::Err>,
-        F: FnOnce(Result<Tx1::Item, Tx1::Err>) -> Tx2,
-    {
-        type Item = Tx2::Item;
-        type Err = Tx1::Err;
-
-        fn run(self, ctx: &mut Ctx) -> Result<Self::Item, Self::Err> {
-            then(self.tx1, self.f)(ctx)
-        }
-    }
-
-    fn or_else<Ctx, Tx1, Tx2, F>(
-        tx1: Tx1,
-        f: F,
-    ) -> impl FnOnce(&mut Ctx) -> Result<Tx2::Item, Tx1::Err>
-    where
-        Tx1: Tx<Ctx>,
-        Tx2: Tx<Ctx, Item = Tx1::Item, Err = Tx1::Err>,
-        F: FnOnce(Tx1::Err) -> Tx2,
-    {
-        move |ctx| match tx1.run(ctx) {
-            Ok(t) => Ok(t),
-            Err(e) => f(e).run(ctx),
-        }
-    }
-
-    pub struct OrElse<Tx1, F> {
-        tx1: Tx1,
-        f: F,
-    }
-    impl<Ctx, Tx1, Tx2, F> Tx<Ctx> for OrElse<Tx1, F>
-    where
-        Tx1: Tx<Ctx>,
-        Tx2: Tx<Ctx, Item = Tx1::Item, Err = Tx1::Err>,
-        F: FnOnce(Tx1::Err) -> Tx2,
-    {
-        type Item = Tx1::Item;
-        type Err = Tx1::Err;
-
-        fn run(self, ctx: &mut Ctx) -> Result<Self::Item, Self::Err> {
-            or_else(self.tx1, self.f)(ctx)
-        }
-    }
-
-    fn join<Ctx, Tx1, Tx2>(
-        tx1: Tx1,
-        tx2: Tx2,
-    ) -> impl FnOnce(&mut Ctx) -> Result<(Tx1::Item, Tx2::Item), Tx1::Err>
-    where
-        Tx1: Tx<Ctx>,
-        Tx2: Tx<Ctx, Err = Tx1::Err>,
-    {
-        move |ctx| match (tx1.run(ctx), tx2.run(ctx)) {
-            (Ok(t), Ok(u)) => Ok((t, u)),
-            (Err(e), _) | (_, Err(e)) => Err(e),
-        }
-    }
-
-    pub struct Join<Tx1, Tx2> {
-        tx1: Tx1,
-        tx2: Tx2,
-    }
-    impl<Ctx, Tx1, Tx2> Tx<Ctx> for Join<Tx1, Tx2>
-    where
-        Tx1: Tx<Ctx>,
-        Tx2: Tx<Ctx, Err = Tx1::Err>,
-    {
-        type Item = (Tx1::Item, Tx2::Item);
-        type Err = Tx1::Err;
-
-        fn run(self, ctx: &mut Ctx) -> Result<Self::Item, Self::Err> {
-            join(self.tx1, self.tx2)(ctx)
-        }
-    }
-
-    fn join3<Ctx, Tx1, Tx2, Tx3>(
-        tx1: Tx1,
-        tx2: Tx2,
-        tx3: Tx3,
-    ) -> impl FnOnce(&mut Ctx) -> Result<(Tx1::Item, Tx2::Item, Tx3::Item), Tx1::Err>
-    where
-        Tx1: Tx<Ctx>,
-        Tx2: Tx<Ctx, Err = Tx1::Err>,
-        Tx3: Tx<Ctx, Err = Tx1::Err>,
-    {
-        move |ctx| match (tx1.run(ctx), tx2.run(ctx), tx3.run(ctx)) {
-            (Ok(t), Ok(u), Ok(v)) => Ok((t, u, v)),
-            (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => Err(e),
-        }
-    }
-
-    pub struct Join3<Tx1, Tx2, Tx3> {
-        tx1: Tx1,
-        tx2: Tx2,
-        tx3: Tx3,
-    }
-    impl<Ctx, Tx1, Tx2, Tx3> Tx<Ctx> for Join3<Tx1, Tx2, Tx3>
-    where
-        Tx1: Tx<Ctx>,
-        Tx2: Tx<Ctx, Err = Tx1::Err>,
-        Tx3: Tx<Ctx, Err = Tx1::Err>,
-    {
-        type Item = (Tx1::Item, Tx2::Item, Tx3::Item);
-        type Err = Tx1::Err;
-
-        fn run(self, ctx: &mut Ctx) -> Result<Self::Item, Self::Err> {
-            join3(self.tx1, self.tx2, self.tx3)(ctx)
-        }
-    }
-
-    fn join4<Ctx, Tx1, Tx2, Tx3, Tx4>(
-        tx1: Tx1,
-        tx2: Tx2,
-        tx3: Tx3,
-        tx4: Tx4,
-    ) -> impl FnOnce(&mut Ctx) -> Result<(Tx1::Item, Tx2::Item, Tx3::Item, Tx4::Item), Tx1::Err>
-    where
-        Tx1: Tx<Ctx>,
-        Tx2: Tx<Ctx, Err = Tx1::Err>,
-        Tx3: Tx<Ctx, Err = Tx1::Err>,
-        Tx4: Tx<Ctx, Err = Tx1::Err>,
-    {
-        move |ctx| match (tx1.run(ctx), tx2.run(ctx), tx3.run(ctx), tx4.run(ctx)) {
-            (Ok(t), Ok(u), Ok(v), Ok(w)) => Ok((t, u, v, w)),
-            (Err(e), _, _, _) | (_, Err(e), _, _) | (_, _, Err(e), _) | (_, _, _, Err(e)) => Err(e),
-        }
-    }
-
-    pub struct Join4<Tx1, Tx2, Tx3, Tx4> {
-        tx1: Tx1,
-        tx2: Tx2,
-        tx3: Tx3,
-        tx4: Tx4,
-    }
-    impl<Ctx, Tx1, Tx2, Tx3, Tx4> Tx<Ctx> for Join4<Tx1, Tx2, Tx3, Tx4>
-    where
-        Tx1: Tx<Ctx>,
-        Tx2: Tx<Ctx, Err = Tx1::Err>,
-        Tx3: Tx<Ctx, Err = Tx1::Err>,
-        Tx4: Tx<Ctx, Err = Tx1::Err>,
-    {
-        type Item = (Tx1::Item, Tx2::Item, Tx3::Item, Tx4::Item);
-        type Err = Tx1::Err;
-
-        fn run(self, ctx: &mut Ctx) -> Result<Self::Item, Self::Err> {
-            join4(self.tx1, self.tx2, self.tx3, self.tx4)(ctx)
-        }
-    }
-
-    fn map_err<Ctx, Tx1, F, E>(tx1: Tx1, f: F) -> impl FnOnce(&mut Ctx) -> Result<Tx1::Item, E>
-    where
-        Tx1: Tx<Ctx>,
-        F: FnOnce(Tx1::Err) -> E,
-    {
-        move |ctx| match tx1.run(ctx) {
-            Ok(t) => Ok(t),
-            Err(e) => Err(f(e)),
-        }
-    }
-
-    pub struct MapErr<Tx1, F> {
-        tx1: Tx1,
-        f: F,
-    }
-    impl<Ctx, Tx1, F, E> Tx<Ctx> for MapErr<Tx1, F>
-    where
-        Tx1: Tx<Ctx>,
-        F: FnOnce(Tx1::Err) -> E,
-    {
-        type Item = Tx1::Item;
-        type Err = E;
-
-        fn run(self, ctx: &mut Ctx) -> Result<Self::Item, Self::Err> {
-            map_err(self.tx1, self.f)(ctx)
-        }
-    }
-
-    fn try_map<Ctx, Tx1, F, T>(tx1: Tx1, f: F) -> impl FnOnce(&mut Ctx) -> Result<T, Tx1::Err>
-    where
-        Tx1: Tx<Ctx>,
-        F: FnOnce(Tx1::Item) -> Result<T, Tx1::Err>,
-    {
-        move |ctx| match tx1.run(ctx) {
-            Ok(t) => f(t),
-            Err(e) => Err(e),
-        }
-    }
-
-    pub struct TryMap<Tx1, F> {
-        tx1: Tx1,
-        f: F,
+            // the JSON reports the golden files are compared with, instead of the examples
+            #[cfg(feature = "tracing")]
+            Command::Report => golden::run().await,
+        };
     }
-    impl<Ctx, Tx1, F, T> Tx<Ctx> for TryMap<Tx1, F>
-    where
-        Tx1: Tx<Ctx>,
-        F: FnOnce(Tx1::Item) -> Result<T, Tx1::Err>,
-    {
-        type Item = T;
-        type Err = Tx1::Err;
-
-        fn run(self, ctx: &mut Ctx) -> Result<Self::Item, Self::Err> {
-            try_map(self.tx1, self.f)(ctx)
-        }
-    }
-
-    fn recover<Ctx, Tx1, F>(tx1: Tx1, f: F) -> impl FnOnce(&mut Ctx) -> Result<Tx1::Item, Tx1::Err>
-    where
-        Tx1: Tx<Ctx>,
-        F: FnOnce(Tx1::Err) -> Tx1::Item,
-    {
-        move |ctx| match tx1.run(ctx) {
-            Ok(t) => Ok(t),
-            Err(e) => Ok(f(e)),
-        }
-    }
-
-    pub struct Recover<Tx1, F> {
-        tx1: Tx1,
-        f: F,
-    }
-    impl<Ctx, Tx1, F> Tx<Ctx> for Recover<Tx1, F>
-    where
-        Tx1: Tx<Ctx>,
-        F: FnOnce(Tx1::Err) -> Tx1::Item,
-    {
-        type Item = Tx1::Item;
-        type Err = Tx1::Err;
-
-        fn run(self, ctx: &mut Ctx) -> Result<Self::Item, Self::Err> {
-            recover(self.tx1, self.f)(ctx)
-        }
-    }
-
-    fn try_recover<Ctx, Tx1, F, E>(tx1: Tx1, f: F) -> impl FnOnce(&mut Ctx) -> Result<Tx1::Item, E>
-    where
-        Tx1: Tx<Ctx>,
-        F: FnOnce(Tx1::Err) -> Result<Tx1::Item, E>,
-    {
-        move |ctx| match tx1.run(ctx) {
-            Ok(t) => Ok(t),
-            Err(e) => f(e),
-        }
-    }
-
-    pub struct TryRecover<Tx1, F> {
-        tx1: Tx1,
-        f: F,
-    }
-    impl<Ctx, Tx1, F, E> Tx<Ctx> for TryRecover<Tx1, F>
-    where
-        Tx1: Tx<Ctx>,
-        F: FnOnce(Tx1::Err) -> Result<Tx1::Item, E>,
-    {
-        type Item = Tx1::Item;
-        type Err = E;
-
-        fn run(self, ctx: &mut Ctx) -> Result<Self::Item, Self::Err> {
-            try_recover(self.tx1, self.f)(ctx)
-        }
-    }
-
-    fn abort<Ctx, Tx1, F>(tx1: Tx1, f: F) -> impl FnOnce(&mut Ctx) -> Result<Tx1::Item, Tx1::Err>
-    where
-        Tx1: Tx<Ctx>,
-        F: FnOnce(Tx1::Item) -> Tx1::Err,
-    {
-        move |ctx| match tx1.run(ctx) {
-            Ok(t) => Err(f(t)),
-            Err(e) => Err(e),
-        }
-    }
-
-    pub struct Abort<Tx1, F> {
-        tx1: Tx1,
-        f: F,
-    }
-    impl<Ctx, Tx1, F> Tx<Ctx> for Abort<Tx1, F>
-    where
-        Tx1: Tx<Ctx>,
-        F: FnOnce(Tx1::Item) -> Tx1::Err,
-    {
-        type Item = Tx1::Item;
-        type Err = Tx1::Err;
-
-        fn run(self, ctx: &mut Ctx) -> Result<Self::Item, Self::Err> {
-            abort(self.tx1, self.f)(ctx)
-        }
-    }
-
-    fn try_abort<Ctx, Tx1, F>(
-        tx1: Tx1,
-        f: F,
-    ) -> impl FnOnce(&mut Ctx) -> Result<Tx1::Item, Tx1::Err>
-    where
-        Tx1: Tx<Ctx>,
-        F: FnOnce(Tx1::Item) -> Result<Tx1::Item, Tx1::Err>,
-    {
-        move |ctx| match tx1.run(ctx) {
-            Ok(t) => f(t),
-            Err(e) => Err(e),
-        }
-    }
-
-    pub struct TryAbort<Tx1, F> {
-        tx1: Tx1,
-        f: F,
-    }
-    impl<Ctx, Tx1, F> Tx<Ctx> for TryAbort<Tx1, F>
-    where
-        Tx1: Tx<Ctx>,
-        F: FnOnce(Tx1::Item) -> Result<Tx1::Item, Tx1::Err>,
-    {
-        type Item = Tx1::Item;
-        type Err = Tx1::Err;
-
-        fn run(self, ctx: &mut Ctx) -> Result<Self::Item, Self::Err> {
-            try_abort(self.tx1, self.f)(ctx)
-        }
-    }
-
-    pub fn with_tx<Ctx, F, T, E>(f: F) -> WithTx<F>
-    where
-        F: FnOnce(&mut Ctx) -> Result<T, E>,
-    {
-        WithTx { f }
-    }
-    pub struct WithTx<F> {
-        f: F,
-    }
-    impl<Ctx, F, T, E> Tx<Ctx> for WithTx<F>
-    where
-        F: FnOnce(&mut Ctx) -> Result<T, E>,
-    {
-        type Item = T;
-        type Err = E;
-
-        fn run(self, ctx: &mut Ctx) -> Result<Self::Item, Self::Err> {
-            (self.f)(ctx)
-        }
-    }
-}
-pub use tx_rs::*;
-
-async fn insert_and_verify(
-    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
-    test_id: i64,
-) -> Result<(), Box<dyn std::error::Error>> {
-    query!(
-        r#"INSERT INTO todos (id, description) VALUES ( $1, $2 )"#,
-        test_id,
-        "test todo"
-    )
-    // In 0.7, `Transaction` can no longer implement `Executor` directly,
-    // so it must be dereferenced to the internal connection type.
-    .execute(&mut **transaction)
-    .await?;
-
-    // check that inserted todo can be fetched inside the uncommitted transaction
-    let _ = query!(r#"SELECT FROM todos WHERE id = $1"#, test_id)
-        .fetch_one(&mut **transaction)
-        .await?;
-
-    Ok(())
-}
-
-async fn explicit_rollback_example(
-    pool: &sqlx::PgPool,
-    test_id: i64,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut transaction = pool.begin().await?;
-
-    insert_and_verify(&mut transaction, test_id).await?;
-
-    transaction.rollback().await?;
-
-    Ok(())
-}
-
-async fn implicit_rollback_example(
-    pool: &sqlx::PgPool,
-    test_id: i64,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut transaction = pool.begin().await?;
-
-    insert_and_verify(&mut transaction, test_id).await?;
-
-    // no explicit rollback here but the transaction object is dropped at the end of the scope
-    Ok(())
-}
-
-async fn commit_example(
-    pool: &sqlx::PgPool,
-    test_id: i64,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut transaction = pool.begin().await?;
-
-    insert_and_verify(&mut transaction, test_id).await?;
-
-    transaction.commit().await?;
-
-    Ok(())
-}
-
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let conn_str =
-        std::env::var("DATABASE_URL").expect("Env var DATABASE_URL is required for this example.");
-    let pool = sqlx::PgPool::connect(&conn_str).await?;
-
-    let test_id = 1;
-
-    // remove any old values that might be in the table already with this id from a previous run
-    let _ = query!(r#"DELETE FROM todos WHERE id = $1"#, test_id)
-        .execute(&pool)
-        .await?;
-
-    explicit_rollback_example(&pool, test_id).await?;
+    #[cfg(not(feature = "postgres"))]
+    let _ = cli;
 
-    // check that inserted todo is not visible outside the transaction after explicit rollback
-    let inserted_todo = query!(r#"SELECT FROM todos WHERE id = $1"#, test_id)
-        .fetch_one(&pool)
-        .await;
+    #[cfg(feature = "postgres")]
+    postgres_example::run().await?;
 
-    assert!(inserted_todo.is_err());
+    memory_example::run().await?;
+    mock_example::run().await?;
 
-    implicit_rollback_example(&pool, test_id).await?;
+    #[cfg(feature = "mysql")]
+    mysql_example::run().await?;
 
-    // check that inserted todo is not visible outside the transaction after implicit rollback
-    let inserted_todo = query!(r#"SELECT FROM todos WHERE id = $1"#, test_id)
-        .fetch_one(&pool)
-        .await;
+    #[cfg(feature = "sqlite")]
+    sqlite_example::run().await?;
 
-    assert!(inserted_todo.is_err());
+    #[cfg(feature = "any")]
+    any_example::run().await?;
 
-    commit_example(&pool, test_id).await?;
+    #[cfg(all(feature = "axum", feature = "postgres"))]
+    axum_example::run().await?;
 
-    // check that inserted todo is visible outside the transaction after commit
-    let inserted_todo = query!(r#"SELECT FROM todos WHERE id = $1"#, test_id)
-        .fetch_one(&pool)
-        .await;
+    #[cfg(all(feature = "actix-web", feature = "postgres"))]
+    actix_example::run().await?;
 
-    assert!(inserted_todo.is_ok());
+    #[cfg(all(feature = "tower", feature = "postgres"))]
+    tower_example::run().await?;
 
     Ok(())
 }
@@ -0,0 +1,250 @@
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+
+use crate::combinator::{AsyncMode, BoxFuture, Description, SyncMode, Tx};
+use crate::context::{Deeper, Hooks};
+use crate::env::{Env, EnvCtx};
+use crate::error::SqlState;
+use crate::runner::Savepoint;
+
+// A database kept in memory, for unit tests of chains: a repository implemented over
+// `InMemoryCtx` runs the same services as its SQL twin, without a server. A transaction works
+// on a copy of the tables taken when it begins, which replaces them when it commits; so
+// run one transaction at a time, there is no isolation between concurrent ones.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryDb {
+    tables: Arc<Mutex<Tables>>,
+}
+
+// The context handed to every step, the counterpart of `TxCtx`: the tables as this
+// transaction sees them, and the hooks to run when it ends.
+#[derive(Debug)]
+pub struct InMemoryCtx {
+    tables: Tables,
+    depth: usize,
+    hooks: Hooks,
+    env: Env,
+}
+
+impl InMemoryDb {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Begins a transaction, runs `tx` in it, then commits on `Ok` and rolls back on `Err`.
+    pub async fn run_tx<X>(&self, tx: X) -> Result<X::Item, X::Err>
+    where
+        X: Tx<InMemoryCtx, Mode = AsyncMode>,
+    {
+        let mut ctx = self.begin();
+        let result = tx.run(&mut ctx).await;
+        self.end(ctx, result.is_ok());
+        result
+    }
+    pub fn run_tx_sync<X>(&self, tx: X) -> Result<X::Item, X::Err>
+    where
+        X: Tx<InMemoryCtx, Mode = SyncMode>,
+    {
+        let mut ctx = self.begin();
+        let result = tx.run(&mut ctx);
+        self.end(ctx, result.is_ok());
+        result
+    }
+
+    // The committed rows of `table`, for asserting on outside of a transaction.
+    pub fn rows<K, V>(&self, table: &'static str) -> BTreeMap<K, V>
+    where
+        K: Ord + Clone + Send + 'static,
+        V: Clone + Send + 'static,
+    {
+        let mut tables = self.tables.lock().unwrap();
+        tables.table::<K, V>(table).rows.clone()
+    }
+
+    fn begin(&self) -> InMemoryCtx {
+        InMemoryCtx {
+            tables: self.tables.lock().unwrap().clone(),
+            depth: 0,
+            hooks: Hooks::default(),
+            env: Env::default(),
+        }
+    }
+    fn end(&self, ctx: InMemoryCtx, commit: bool) {
+        let mut hooks = ctx.hooks;
+        if commit {
+            *self.tables.lock().unwrap() = ctx.tables;
+            hooks.run_committed();
+        } else {
+            hooks.run_rolled_back();
+        }
+    }
+}
+
+impl InMemoryCtx {
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    // Table `name` with its rows keyed by `K`, created empty on first use.
+    // Panics when it was used before with other types.
+    pub fn table<K, V>(&mut self, name: &'static str) -> &mut Table<K, V>
+    where
+        K: Ord + Clone + Send + 'static,
+        V: Clone + Send + 'static,
+    {
+        self.tables.table(name)
+    }
+
+    // As `TxCtx::after_commit` and `TxCtx::after_rollback`.
+    pub fn after_commit(&mut self, f: impl FnOnce() + Send + 'static) {
+        self.hooks.after_commit(Box::new(f));
+    }
+    pub fn after_rollback(&mut self, f: impl FnOnce() + Send + 'static) {
+        self.hooks.after_rollback(Box::new(f));
+    }
+}
+
+impl EnvCtx for InMemoryCtx {
+    fn env(&self) -> &Env {
+        &self.env
+    }
+    fn env_mut(&mut self) -> &mut Env {
+        &mut self.env
+    }
+}
+
+// The rows of a table by primary key. Reads and updates go through the map; `insert` is
+// the one which refuses to overwrite a row, as an `INSERT` would.
+#[derive(Debug, Clone)]
+pub struct Table<K, V> {
+    name: &'static str,
+    rows: BTreeMap<K, V>,
+}
+impl<K: Ord, V> Table<K, V> {
+    pub fn insert(&mut self, key: K, row: V) -> Result<(), DuplicateKey> {
+        if self.rows.contains_key(&key) {
+            return Err(DuplicateKey {
+                constraint: format!("{}_pkey", self.name),
+            });
+        }
+        self.rows.insert(key, row);
+        Ok(())
+    }
+}
+impl<K, V> Deref for Table<K, V> {
+    type Target = BTreeMap<K, V>;
+
+    fn deref(&self) -> &BTreeMap<K, V> {
+        &self.rows
+    }
+}
+impl<K, V> DerefMut for Table<K, V> {
+    fn deref_mut(&mut self) -> &mut BTreeMap<K, V> {
+        &mut self.rows
+    }
+}
+
+// The error of `Table::insert`, told apart like the database's own unique violation.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("duplicate key value violates unique constraint \"{constraint}\"")]
+pub struct DuplicateKey {
+    pub constraint: String,
+}
+impl SqlState for DuplicateKey {
+    fn sqlstate(&self) -> Option<std::borrow::Cow<'_, str>> {
+        Some("23505".into())
+    }
+    fn constraint(&self) -> Option<&str> {
+        Some(&self.constraint)
+    }
+}
+
+// A savepoint is a copy of the tables and hooks to go back to on `Err`.
+impl<X> Tx<InMemoryCtx> for Savepoint<X>
+where
+    X: Tx<InMemoryCtx, Mode = AsyncMode> + Send,
+    X::Item: Send,
+    X::Err: Send,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut InMemoryCtx) -> BoxFuture<'a, Result<Self::Item, Self::Err>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            let tables = ctx.tables.clone();
+            let mark = ctx.hooks.mark();
+            let mut deeper = Deeper::new(ctx, |ctx| &mut ctx.depth);
+            let result = self.tx.run(&mut deeper).await;
+            drop(deeper);
+            if result.is_err() {
+                ctx.tables = tables;
+                ctx.hooks.undo_to(mark);
+            }
+            result
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("savepoint", vec![self.tx.describe()])
+    }
+}
+
+#[derive(Debug, Default)]
+struct Tables(HashMap<&'static str, Box<dyn AnyTable>>);
+impl Tables {
+    fn table<K, V>(&mut self, name: &'static str) -> &mut Table<K, V>
+    where
+        K: Ord + Clone + Send + 'static,
+        V: Clone + Send + 'static,
+    {
+        let table = self.0.entry(name).or_insert_with(|| {
+            Box::new(Table::<K, V> {
+                name,
+                rows: BTreeMap::new(),
+            })
+        });
+        match table.as_any_mut().downcast_mut() {
+            Some(table) => table,
+            None => panic!("table {} used with other types", name),
+        }
+    }
+}
+impl Clone for Tables {
+    fn clone(&self) -> Self {
+        Tables(
+            self.0
+                .iter()
+                .map(|(name, table)| (*name, table.clone_box()))
+                .collect(),
+        )
+    }
+}
+
+// A `Table` of whatever types, which can still be copied.
+trait AnyTable: Send {
+    fn clone_box(&self) -> Box<dyn AnyTable>;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+impl<K, V> AnyTable for Table<K, V>
+where
+    K: Clone + Send + 'static,
+    V: Clone + Send + 'static,
+{
+    fn clone_box(&self) -> Box<dyn AnyTable> {
+        Box::new(self.clone())
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+impl std::fmt::Debug for dyn AnyTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Table { .. }")
+    }
+}
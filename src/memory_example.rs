@@ -0,0 +1,133 @@
+use std::convert::Infallible;
+use std::time::{Duration, SystemTime};
+
+use tx::env::{with_env, Env, FixedClock, SequenceIds};
+use tx::memory::InMemoryDb;
+use tx::prelude::*;
+use tx::runner;
+use tx::trace::{Outcome, TraceExt, TxTrace};
+
+use crate::todo_repository::{complete_tx, InMemoryTodoRepository, Todo, TodoRepository};
+
+// The repository flows of the Postgres example, run against tables in memory: the service
+// is the same code, only the repository differs.
+
+const TODOS: InMemoryTodoRepository = InMemoryTodoRepository;
+
+pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let db = InMemoryDb::new();
+    let test_id = 1;
+
+    let todo = db
+        .run_tx(
+            TODOS
+                .insert(test_id, "repository todo")
+                .and_then(|()| complete_tx(&TODOS, test_id)),
+        )
+        .await?;
+    assert_eq!(
+        todo,
+        Some(Todo {
+            id: test_id,
+            description: "repository todo".to_string(),
+            done: true,
+        })
+    );
+
+    // nothing to complete, so the service finds nothing either
+    let todo = db.run_tx(complete_tx(&TODOS, test_id + 1)).await?;
+    assert_eq!(todo, None);
+
+    // inserting it again violates the primary key, as in the database
+    let e = db
+        .run_tx(TODOS.insert(test_id, "repository todo"))
+        .await
+        .unwrap_err();
+
+    assert_eq!(e.error_kind(), ErrorKind::UniqueViolation);
+    assert_eq!(e.constraint(), Some("todos_pkey"));
+
+    // a savepoint rolls back its own work only
+    let inserted = db
+        .run_tx(TODOS.insert(test_id + 1, "second todo").and_then(|()| {
+            runner::savepoint(TODOS.insert(test_id, "repository todo").map(|()| true)).try_recover(
+                |e| match e.error_kind() {
+                    ErrorKind::UniqueViolation => Ok(false),
+                    _ => Err(e),
+                },
+            )
+        }))
+        .await?;
+    assert!(!inserted);
+
+    // and a failing transaction leaves nothing behind
+    let result = db
+        .run_tx(
+            TODOS
+                .delete(test_id)
+                .and_then(|_| TODOS.insert(test_id + 1, "second todo")),
+        )
+        .await;
+    assert!(result.is_err());
+
+    let todos = db.rows::<i64, Todo>("todos");
+    assert_eq!(
+        todos.keys().copied().collect::<Vec<_>>(),
+        [test_id, test_id + 1]
+    );
+    assert_eq!(db.run_tx(TODOS.count()).await?, 2);
+
+    // a trace tells which steps ran, in which order, and how they ended
+    let trace = TxTrace::new();
+    db.run_tx(
+        TODOS
+            .find(test_id)
+            .named("find")
+            .traced(&trace)
+            .and_then(|_| {
+                complete_tx(&TODOS, test_id)
+                    .named("complete")
+                    .traced(&trace)
+            })
+            .or_else(|_| TODOS.find(test_id).named("fallback").traced(&trace)),
+    )
+    .await?;
+    assert!(trace.ran_before("find", "complete"));
+    assert_eq!(trace.outcome("complete"), Some(Outcome::Ok));
+    assert!(!trace.ran("fallback"));
+
+    // ids and time come from the context, so a test can make them predictable
+    let clock = FixedClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+    let env = Env::new()
+        .clock(clock.clone())
+        .ids(SequenceIds::starting_at(10));
+    let add_todo = |description: &'static str| {
+        tx::next_id().and_then(move |id| TODOS.insert(id, description).map(move |()| id))
+    };
+    let (first, second, at) = db
+        .run_tx(with_env(
+            env,
+            add_todo("first").join3(add_todo("second"), tx::now()),
+        ))
+        .await?;
+    assert_eq!((first, second), (10, 11));
+    assert_eq!(
+        at,
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+    );
+
+    // and the clock only moves when told to
+    clock.advance(Duration::from_secs(60));
+    let at = db
+        .run_tx(with_env(
+            Env::new().clock(clock),
+            tx::now::<Infallible, _>(),
+        ))
+        .await?;
+    assert_eq!(
+        at,
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_060)
+    );
+
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use sqlx::migrate::{Migrate as _, MigrateError, Migrator};
+use sqlx::{PgConnection, PgPool, Postgres};
+
+use crate::combinator::{AsyncMode, BoxFuture, Description, Tx};
+use crate::error::SchemaTooOld;
+use crate::runner::{self, PgCtx, TxAccess, TxCtx};
+use crate::schema::current_schema_version;
+
+// The migrations of `migrations/`, embedded at build time, which create the tables of the
+// examples.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+// Applies `MIGRATOR` to the database of `pool` in a transaction of its own, e.g. at start-up
+// to bootstrap a fresh database. Applied migrations are skipped.
+pub async fn run(pool: &PgPool) -> Result<(), sqlx::Error> {
+    runner::run_tx(pool, migrate(&MIGRATOR)).await
+}
+
+// A step applying the migrations of `migrator` not applied yet, each in a savepoint of the
+// transaction it runs in: they are committed together with the rest of the chain, or not at
+// all. Concurrent runs wait for one another on an advisory lock held until the transaction
+// ends. As with `sqlx migrate run`, it fails on an applied migration changed since or missing
+// from `migrator`, and on one which failed halfway.
+pub fn migrate(migrator: &'static Migrator) -> Migrate {
+    Migrate { migrator }
+}
+#[derive(Debug, Clone, Copy)]
+pub struct Migrate {
+    migrator: &'static Migrator,
+}
+impl Tx<PgCtx> for Migrate {
+    type Item = ();
+    type Err = sqlx::Error;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut PgCtx) -> BoxFuture<'a, Result<(), sqlx::Error>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            sqlx::query("SELECT pg_advisory_xact_lock($1)")
+                .bind(MIGRATION_LOCK)
+                .execute(&mut **ctx)
+                .await?;
+            apply_pending(ctx, self.migrator).await?;
+            Ok(())
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::leaf("migrate")
+    }
+}
+
+// The key of the advisory lock taken by `migrate`.
+const MIGRATION_LOCK: i64 = 0x7478_5f6d_6967;
+
+async fn apply_pending(conn: &mut PgConnection, migrator: &Migrator) -> Result<(), MigrateError> {
+    conn.ensure_migrations_table().await?;
+    if let Some(version) = conn.dirty_version().await? {
+        return Err(MigrateError::Dirty(version));
+    }
+    let applied: HashMap<i64, _> = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|applied| (applied.version, applied.checksum))
+        .collect();
+    if let Some(version) = applied
+        .keys()
+        .find(|version| !migrator.iter().any(|m| m.version == **version))
+    {
+        return Err(MigrateError::VersionMissing(*version));
+    }
+    for migration in migrator.iter() {
+        if migration.migration_type.is_down_migration() {
+            continue;
+        }
+        match applied.get(&migration.version) {
+            Some(checksum) if *checksum != migration.checksum => {
+                return Err(MigrateError::VersionMismatch(migration.version));
+            }
+            Some(_) => {}
+            None => {
+                conn.apply(migration).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// A guard failing with `SchemaTooOld` unless migration `version`, or a later one, has been
+// applied, so code running against a database not migrated yet fails fast instead of on
+// the first missing column. Put it first in the chain.
+pub fn require_version(version: i64) -> RequireVersion {
+    RequireVersion { version }
+}
+#[derive(Debug, Clone, Copy)]
+pub struct RequireVersion {
+    version: i64,
+}
+impl<A: TxAccess> Tx<TxCtx<Postgres, A>> for RequireVersion {
+    type Item = ();
+    type Err = sqlx::Error;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut TxCtx<Postgres, A>) -> BoxFuture<'a, Result<(), sqlx::Error>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            let applied = current_schema_version::<A>().run(ctx).await?;
+            if applied.is_some_and(|applied| applied >= self.version) {
+                Ok(())
+            } else {
+                Err(SchemaTooOld {
+                    required: self.version,
+                    applied,
+                }
+                .into())
+            }
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::leaf("require_version")
+    }
+}
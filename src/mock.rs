@@ -0,0 +1,198 @@
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use crate::combinator::{AsyncMode, BoxFuture, Description, SyncMode, Tx};
+use crate::env::{Env, EnvCtx};
+use crate::runner::Savepoint;
+
+// A context which runs nothing: steps written against it name each operation they would
+// issue with `MockCtx::call`, which records it and hands back the response the test scripted.
+// So a test can assert which statements a path issued, in which order, with which parameters.
+#[derive(Debug, Clone, Default)]
+pub struct Mock {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug)]
+pub struct MockCtx {
+    state: Arc<Mutex<State>>,
+    depth: usize,
+    env: Env,
+}
+
+// One recorded operation: a statement with its parameters as `Debug` would print them, or
+// one of `BEGIN`, `COMMIT`, `ROLLBACK` and the savepoint statements, without parameters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Call {
+    pub op: String,
+    pub params: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    calls: Vec<Call>,
+    responses: HashMap<String, VecDeque<Box<dyn Any + Send>>>,
+}
+
+impl Mock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Queues `response` for the next call of `op`; calls of `op` take their responses in the
+    // order they were queued. A `Result` scripts the failure of a call as well as its success.
+    pub fn respond<R: Send + 'static>(&self, op: &str, response: R) -> &Self {
+        let mut state = self.state.lock().unwrap();
+        let responses = state.responses.entry(op.to_string()).or_default();
+        responses.push_back(Box::new(response));
+        self
+    }
+
+    // Everything issued so far, transactions included.
+    pub fn calls(&self) -> Vec<Call> {
+        self.state.lock().unwrap().calls.clone()
+    }
+    pub fn ops(&self) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        state.calls.iter().map(|call| call.op.clone()).collect()
+    }
+    pub fn issued(&self, op: &str) -> bool {
+        let state = self.state.lock().unwrap();
+        state.calls.iter().any(|call| call.op == op)
+    }
+
+    // Runs `tx` in a transaction, recording `COMMIT` on `Ok` and `ROLLBACK` on `Err`.
+    pub async fn run_tx<X>(&self, tx: X) -> Result<X::Item, X::Err>
+    where
+        X: Tx<MockCtx, Mode = AsyncMode>,
+    {
+        let mut ctx = self.begin();
+        let result = tx.run(&mut ctx).await;
+        ctx.record(if result.is_ok() { "COMMIT" } else { "ROLLBACK" }, vec![]);
+        result
+    }
+    pub fn run_tx_sync<X>(&self, tx: X) -> Result<X::Item, X::Err>
+    where
+        X: Tx<MockCtx, Mode = SyncMode>,
+    {
+        let mut ctx = self.begin();
+        let result = tx.run(&mut ctx);
+        ctx.record(if result.is_ok() { "COMMIT" } else { "ROLLBACK" }, vec![]);
+        result
+    }
+
+    fn begin(&self) -> MockCtx {
+        let mut ctx = MockCtx {
+            state: self.state.clone(),
+            depth: 0,
+            env: Env::default(),
+        };
+        ctx.record("BEGIN", vec![]);
+        ctx
+    }
+}
+
+impl MockCtx {
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    // Records `op` with `params` and returns the response scripted for it.
+    // Panics when none is left, or when it is not an `R`.
+    pub fn call<R: 'static>(&mut self, op: &str, params: &[&dyn Debug]) -> R {
+        let params = params.iter().map(|param| format!("{:?}", param)).collect();
+        self.record(op, params);
+
+        let mut state = self.state.lock().unwrap();
+        let response = state
+            .responses
+            .get_mut(op)
+            .and_then(VecDeque::pop_front)
+            .unwrap_or_else(|| panic!("no response scripted for {}", op));
+        match response.downcast() {
+            Ok(response) => *response,
+            Err(_) => panic!(
+                "the response scripted for {} is not a {}",
+                op,
+                std::any::type_name::<R>()
+            ),
+        }
+    }
+
+    fn record(&mut self, op: &str, params: Vec<String>) {
+        let call = Call {
+            op: op.to_string(),
+            params,
+        };
+        self.state.lock().unwrap().calls.push(call);
+    }
+}
+
+impl EnvCtx for MockCtx {
+    fn env(&self) -> &Env {
+        &self.env
+    }
+    fn env_mut(&mut self) -> &mut Env {
+        &mut self.env
+    }
+}
+
+impl<X> Tx<MockCtx> for Savepoint<X>
+where
+    X: Tx<MockCtx, Mode = AsyncMode> + Send,
+    X::Item: Send,
+    X::Err: Send,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut MockCtx) -> BoxFuture<'a, Result<Self::Item, Self::Err>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            let mut savepoint = MockSavepoint::open(ctx);
+            let result = self.tx.run(&mut *savepoint.ctx).await;
+            savepoint.released = result.is_ok();
+            result
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("savepoint", vec![self.tx.describe()])
+    }
+}
+
+// A savepoint recorded as open, until dropped: then recorded as released, or rolled back,
+// also when its step was dropped midway by a deadline or a cancellation.
+struct MockSavepoint<'c> {
+    ctx: &'c mut MockCtx,
+    name: String,
+    released: bool,
+}
+impl<'c> MockSavepoint<'c> {
+    fn open(ctx: &'c mut MockCtx) -> Self {
+        ctx.depth += 1;
+        let name = format!("tx_rs_savepoint_{}", ctx.depth);
+        ctx.record(&format!("SAVEPOINT {}", name), vec![]);
+        Self {
+            ctx,
+            name,
+            released: false,
+        }
+    }
+}
+impl Drop for MockSavepoint<'_> {
+    fn drop(&mut self) {
+        let op = if self.released {
+            "RELEASE SAVEPOINT"
+        } else {
+            "ROLLBACK TO SAVEPOINT"
+        };
+        self.ctx.record(&format!("{} {}", op, self.name), vec![]);
+        self.ctx.depth -= 1;
+    }
+}
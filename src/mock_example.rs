@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use tx::mock::{Call, Mock, MockCtx};
+use tx::prelude::*;
+use tx::runner;
+
+use crate::todo_repository::{
+    complete_tx, MockTodoRepository, Todo, TodoRepository, FIND_TODO, INSERT_TODO, SET_DONE,
+};
+
+// The repository service against a mock: no database, the test scripts what each statement
+// returns and asserts on what was issued.
+
+const TODOS: MockTodoRepository = MockTodoRepository;
+
+pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let mock = Mock::new();
+    let todo = Todo {
+        id: 1,
+        description: "mocked todo".to_string(),
+        done: true,
+    };
+    mock.respond(SET_DONE, Ok::<_, sqlx::Error>(true))
+        .respond(FIND_TODO, Ok::<_, sqlx::Error>(Some(todo.clone())));
+
+    let found = mock.run_tx(complete_tx(&TODOS, 1)).await?;
+    assert_eq!(found, Some(todo));
+    assert_eq!(
+        mock.calls(),
+        [
+            call("BEGIN", &[]),
+            call(SET_DONE, &["true", "1"]),
+            call(FIND_TODO, &["1"]),
+            call("COMMIT", &[]),
+        ]
+    );
+
+    // a failing insert rolls back before the update is ever issued
+    let mock = Mock::new();
+    mock.respond(INSERT_TODO, Err::<(), _>(sqlx::Error::RowNotFound));
+
+    let result = mock
+        .run_tx(
+            TODOS
+                .insert(2, "never inserted")
+                .and_then(|()| TODOS.set_done(2, true)),
+        )
+        .await;
+    assert!(matches!(result, Err(sqlx::Error::RowNotFound)));
+    assert_eq!(mock.ops(), ["BEGIN", INSERT_TODO, "ROLLBACK"]);
+    assert!(!mock.issued(SET_DONE));
+
+    // a savepoint shows in the log with the way it ended
+    let mock = Mock::new();
+    mock.respond(INSERT_TODO, Err::<(), _>(sqlx::Error::RowNotFound));
+
+    let inserted = mock
+        .run_tx(runner::savepoint(TODOS.insert(3, "maybe").map(|()| true)).recover(|_| false))
+        .await?;
+    assert!(!inserted);
+    assert_eq!(
+        mock.ops(),
+        [
+            "BEGIN",
+            "SAVEPOINT tx_rs_savepoint_1",
+            INSERT_TODO,
+            "ROLLBACK TO SAVEPOINT tx_rs_savepoint_1",
+            "COMMIT",
+        ]
+    );
+
+    // so does one whose step was dropped midway, here by a timeout, and the chain carries on
+    // at the depth it was at
+    let mock = Mock::new();
+    let never =
+        with_tx_async(
+            |_: &mut MockCtx| Box::pin(std::future::pending::<Result<(), sqlx::Error>>()),
+        );
+    let depth = with_tx_async(|ctx: &mut MockCtx| {
+        let depth = ctx.depth();
+        Box::pin(async move { Ok(depth) })
+    });
+    let chain = runner::savepoint(never)
+        .timeout(Duration::from_millis(10))
+        .recover(|_| ())
+        .and_then(move |()| depth);
+    assert_eq!(mock.run_tx(chain).await?, 0);
+    assert_eq!(
+        mock.ops(),
+        [
+            "BEGIN",
+            "SAVEPOINT tx_rs_savepoint_1",
+            "ROLLBACK TO SAVEPOINT tx_rs_savepoint_1",
+            "COMMIT",
+        ]
+    );
+
+    Ok(())
+}
+
+fn call(op: &str, params: &[&str]) -> Call {
+    Call {
+        op: op.to_string(),
+        params: params.iter().map(|param| param.to_string()).collect(),
+    }
+}
@@ -0,0 +1,147 @@
+use sqlx::MySql;
+
+use tx::prelude::*;
+use tx::runner::{self, MySqlCtx};
+
+// The same flows as the Postgres example, against MySQL. The compile-time checked `query!`
+// macros are bound to the Postgres `DATABASE_URL`, so plain `sqlx::query` is used here.
+
+async fn insert_and_verify(
+    transaction: &mut sqlx::Transaction<'_, MySql>,
+    test_id: i64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    sqlx::query(r#"INSERT INTO todos (id, description) VALUES ( ?, ? )"#)
+        .bind(test_id)
+        .bind("test todo")
+        .execute(&mut **transaction)
+        .await?;
+
+    // check that inserted todo can be fetched inside the uncommitted transaction
+    let _ = sqlx::query(r#"SELECT id FROM todos WHERE id = ?"#)
+        .bind(test_id)
+        .fetch_one(&mut **transaction)
+        .await?;
+
+    Ok(())
+}
+
+fn insert_and_verify_tx(
+    test_id: i64,
+) -> impl Tx<MySqlCtx, Item = (), Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(move |transaction: &mut MySqlCtx| {
+        Box::pin(async move {
+            sqlx::query(r#"INSERT INTO todos (id, description) VALUES ( ?, ? )"#)
+                .bind(test_id)
+                .bind("test todo")
+                .execute(&mut **transaction)
+                .await?;
+
+            // check that inserted todo can be fetched inside the uncommitted transaction
+            let _ = sqlx::query(r#"SELECT id FROM todos WHERE id = ?"#)
+                .bind(test_id)
+                .fetch_one(&mut **transaction)
+                .await?;
+
+            Ok(())
+        })
+    })
+}
+
+async fn explicit_rollback_example(
+    pool: &sqlx::MySqlPool,
+    test_id: i64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut transaction = pool.begin().await?;
+
+    insert_and_verify(&mut transaction, test_id).await?;
+
+    transaction.rollback().await?;
+
+    Ok(())
+}
+
+async fn implicit_rollback_example(
+    pool: &sqlx::MySqlPool,
+    test_id: i64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut transaction = pool.begin().await?;
+
+    insert_and_verify(&mut transaction, test_id).await?;
+
+    // no explicit rollback here but the transaction object is dropped at the end of the scope
+    Ok(())
+}
+
+async fn commit_example(
+    pool: &sqlx::MySqlPool,
+    test_id: i64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut transaction = pool.begin().await?;
+
+    insert_and_verify(&mut transaction, test_id).await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+async fn exists(pool: &sqlx::MySqlPool, test_id: i64) -> Result<bool, sqlx::Error> {
+    let todo = sqlx::query(r#"SELECT id FROM todos WHERE id = ?"#)
+        .bind(test_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(todo.is_some())
+}
+
+pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let conn_str = std::env::var("MYSQL_DATABASE_URL")
+        .expect("Env var MYSQL_DATABASE_URL is required for the MySQL example.");
+    let pool = sqlx::MySqlPool::connect(&conn_str).await?;
+
+    let test_id = 1;
+
+    // remove any old values that might be in the table already with this id from a previous run
+    sqlx::query(r#"DELETE FROM todos WHERE id = ?"#)
+        .bind(test_id)
+        .execute(&pool)
+        .await?;
+
+    explicit_rollback_example(&pool, test_id).await?;
+
+    // check that inserted todo is not visible outside the transaction after explicit rollback
+    assert!(!exists(&pool, test_id).await?);
+
+    implicit_rollback_example(&pool, test_id).await?;
+
+    // check that inserted todo is not visible outside the transaction after implicit rollback
+    assert!(!exists(&pool, test_id).await?);
+
+    commit_example(&pool, test_id).await?;
+
+    // check that inserted todo is visible outside the transaction after commit
+    assert!(exists(&pool, test_id).await?);
+
+    let test_id = 2;
+
+    sqlx::query(r#"DELETE FROM todos WHERE id = ?"#)
+        .bind(test_id)
+        .execute(&pool)
+        .await?;
+
+    // the chain fails after the insert, so `run_tx` rolls the whole transaction back
+    let result = runner::run_tx(
+        &pool,
+        insert_and_verify_tx(test_id).abort(|_| sqlx::Error::RowNotFound),
+    )
+    .await;
+
+    assert!(result.is_err());
+    assert!(!exists(&pool, test_id).await?);
+
+    let options = runner::TxOptions::new().isolation_level(runner::IsolationLevel::RepeatableRead);
+    runner::run_tx_with(&pool, options, insert_and_verify_tx(test_id)).await?;
+
+    assert!(exists(&pool, test_id).await?);
+
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use std::time::Duration;
+
+use sqlx::postgres::PgListener;
+use sqlx::PgPool;
+
+use crate::combinator::{AsyncMode, BoxFuture, Description, Tx};
+use crate::rt;
+use crate::runner::PgCtx;
+
+// Queues `pg_notify(channel, payload)` once `tx` succeeded, to be sent right before the commit.
+// Postgres delivers notifications on commit only, so listeners never hear of work rolled back,
+// and a savepoint rolled back takes its notifications along.
+#[derive(Debug, Clone)]
+pub struct Notify<X> {
+    tx: X,
+    channel: String,
+    payload: String,
+}
+impl<X> Tx<PgCtx> for Notify<X>
+where
+    X: Tx<PgCtx, Mode = AsyncMode> + Send,
+    X::Item: Send,
+    X::Err: Send,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut PgCtx) -> BoxFuture<'a, Result<Self::Item, Self::Err>>
+    where
+        Self: 'a,
+    {
+        let Notify {
+            tx,
+            channel,
+            payload,
+        } = self;
+        Box::pin(async move {
+            let t = tx.run(&mut *ctx).await?;
+            ctx.before_commit(move |conn| {
+                Box::pin(async move {
+                    sqlx::query("SELECT pg_notify($1, $2)")
+                        .bind(channel)
+                        .bind(payload)
+                        .execute(conn)
+                        .await?;
+                    Ok(())
+                })
+            });
+            Ok(t)
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("notify", vec![self.tx.describe()])
+    }
+}
+
+pub trait NotifyExt: Tx<PgCtx, Mode = AsyncMode> {
+    fn notify_on_commit(
+        self,
+        channel: impl Into<String>,
+        payload: impl Into<String>,
+    ) -> Notify<Self>
+    where
+        Self: Sized,
+    {
+        Notify {
+            tx: self,
+            channel: channel.into(),
+            payload: payload.into(),
+        }
+    }
+}
+impl<X> NotifyExt for X where X: Tx<PgCtx, Mode = AsyncMode> {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub channel: String,
+    pub payload: String,
+}
+
+// Receives the notifications sent on some channels. It holds a connection of its own, which
+// reconnects and listens again by itself; notifications sent meanwhile are lost.
+pub struct Subscriber {
+    listener: PgListener,
+}
+impl Subscriber {
+    pub async fn connect(pool: &PgPool, channels: &[&str]) -> Result<Self, sqlx::Error> {
+        let mut listener = PgListener::connect_with(pool).await?;
+        listener.listen_all(channels.iter().copied()).await?;
+        Ok(Self { listener })
+    }
+
+    pub async fn recv(&mut self) -> Result<Notification, sqlx::Error> {
+        let notification = self.listener.recv().await?;
+        Ok(Notification {
+            channel: notification.channel().to_string(),
+            payload: notification.payload().to_string(),
+        })
+    }
+
+    // `None` when nothing arrived within `timeout`.
+    pub async fn recv_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<Notification>, sqlx::Error> {
+        match rt::timeout(timeout, self.recv()).await {
+            Ok(notification) => notification.map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+}
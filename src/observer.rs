@@ -0,0 +1,265 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::future::{poll_fn, Future};
+use std::pin::pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::combinator::BoxFuture;
+
+// Structured events of the transactions run by `run_tx`, `run_tx_with`, `run_tx_retry`,
+// `run_tx_cancellable`, `run_tx_pending` and `RoutingRunner::run`, handed to every observer added
+// with `add_observer`, so that logging or alerting can be plugged in without touching the runner.
+// Each attempt of a transaction gets a `TxId` of its own, which its events carry:
+//
+//     Begin, then StepStarted and StepFinished for each step labelled with `named`,
+//     then Commit or Rollback
+//
+// with an `Explained` for each statement of the `explained` steps in between, and a
+// `StatementBudgetExceeded` before the end of a chain over its budget. A step dropped
+// unfinished, when the transaction is cancelled or times out, has no `StepFinished`. Observers
+// are called inline, on the task running the transaction: keep them quick, and hand anything
+// slow over to a task of their own.
+pub trait TxObserver: Send + Sync {
+    fn on_event(&self, tx: TxId, event: &TxEvent<'_>);
+}
+
+impl<O: TxObserver + ?Sized> TxObserver for Arc<O> {
+    fn on_event(&self, tx: TxId, event: &TxEvent<'_>) {
+        (**self).on_event(tx, event)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TxId(u64);
+impl fmt::Display for TxId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "tx#{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum TxEvent<'e> {
+    // `name` is the name the chain describes itself with
+    Begin {
+        name: &'e str,
+        attempt: u32,
+    },
+    StepStarted {
+        step: &'static str,
+    },
+    StepFinished {
+        step: &'static str,
+        elapsed: Duration,
+        ok: bool,
+    },
+    Commit {
+        elapsed: Duration,
+    },
+    Rollback {
+        elapsed: Duration,
+        reason: RollbackReason<'e>,
+    },
+    // the plan of a statement run in an `explained` step
+    Explained {
+        sql: &'e str,
+        plan: &'e str,
+    },
+    // the chain issued more statements than its `TxOptions::statement_budget`, whether it is
+    // committed all the same or rolled back for it
+    StatementBudgetExceeded {
+        budget: u32,
+        statements: u32,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum RollbackReason<'e> {
+    // the chain failed, or ran past its deadline
+    Failed,
+    // no transaction could be begun, so nothing was rolled back
+    BeginFailed(&'e sqlx::Error),
+    // the commit, or a `before_commit` hook, failed
+    CommitFailed(&'e sqlx::Error),
+    // the caller rolled a `Pending` back
+    Requested,
+    // a `Pending` was dropped without `commit` or `rollback`
+    Dropped,
+}
+
+static OBSERVERS: RwLock<Vec<Arc<dyn TxObserver>>> = RwLock::new(Vec::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+// For the rest of the process.
+pub fn add_observer(observer: impl TxObserver + 'static) {
+    OBSERVERS.write().unwrap().push(Arc::new(observer));
+}
+
+fn notify(scope: Scope, event: TxEvent<'_>) {
+    let scoped = SCOPED.with(|scoped| scoped.borrow().clone());
+    notify_with(scoped.as_ref(), scope, event);
+}
+fn notify_with(scoped: Option<&Observers>, scope: Scope, event: TxEvent<'_>) {
+    for observer in OBSERVERS.read().unwrap().iter() {
+        observer.on_event(scope.id, &event);
+    }
+    for observer in scoped.into_iter().flat_map(|observers| observers.iter()) {
+        observer.on_event(scope.id, &event);
+    }
+}
+
+// The observers of some of the transactions only, e.g. those of a `TxRuntime`.
+pub(crate) type Observers = Arc<[Arc<dyn TxObserver>]>;
+
+// Runs `future` with `observers` handed the events of the transactions it runs, besides those
+// added with `add_observer`: they are set on the thread for as long as it is polled, as the
+// transaction being polled is.
+pub(crate) async fn scoped<F: Future>(observers: &Observers, future: F) -> F::Output {
+    if observers.is_empty() {
+        return future.await;
+    }
+    let mut future = pin!(future);
+    poll_fn(|cx| {
+        let outer = SCOPED.with(|scoped| scoped.replace(Some(observers.clone())));
+        let result = future.as_mut().poll(cx);
+        SCOPED.with(|scoped| scoped.replace(outer));
+        result
+    })
+    .await
+}
+
+// The transaction being polled on this thread.
+#[derive(Debug, Clone, Copy)]
+struct Scope {
+    id: TxId,
+    started: Instant,
+}
+
+thread_local! {
+    static CURRENT: Cell<Option<Scope>> = const { Cell::new(None) };
+    static SCOPED: RefCell<Option<Observers>> = const { RefCell::new(None) };
+}
+
+pub(crate) fn is_observed() -> bool {
+    !OBSERVERS.read().unwrap().is_empty() || SCOPED.with(|scoped| scoped.borrow().is_some())
+}
+
+// Runs `future`, the attempt of the transaction `name`, with what it does reported to the
+// observers; as it is for `None`, when there are none.
+pub(crate) async fn observed<F: Future>(name: Option<&str>, attempt: u32, future: F) -> F::Output {
+    let Some(name) = name else {
+        return future.await;
+    };
+    let scope = Scope {
+        id: TxId(NEXT_ID.fetch_add(1, Ordering::Relaxed)),
+        started: Instant::now(),
+    };
+    notify(scope, TxEvent::Begin { name, attempt });
+    let mut future = pin!(future);
+    poll_fn(|cx| {
+        let outer = CURRENT.with(|current| current.replace(Some(scope)));
+        let result = future.as_mut().poll(cx);
+        CURRENT.with(|current| current.set(outer));
+        result
+    })
+    .await
+}
+
+pub(crate) fn committed() {
+    if let Some(scope) = CURRENT.with(Cell::get) {
+        let elapsed = scope.started.elapsed();
+        notify(scope, TxEvent::Commit { elapsed });
+    }
+}
+
+pub(crate) fn rolled_back(reason: RollbackReason<'_>) {
+    if let Some(scope) = CURRENT.with(Cell::get) {
+        let elapsed = scope.started.elapsed();
+        notify(scope, TxEvent::Rollback { elapsed, reason });
+    }
+}
+
+// The transaction being polled on this thread, to report how it ends once it is no longer
+// polled, as a `Pending` does; with the observers it is scoped with.
+pub(crate) struct Detached {
+    scope: Scope,
+    scoped: Option<Observers>,
+}
+impl Detached {
+    pub(crate) fn committed(self) {
+        let elapsed = self.scope.started.elapsed();
+        let event = TxEvent::Commit { elapsed };
+        notify_with(self.scoped.as_ref(), self.scope, event);
+    }
+    pub(crate) fn rolled_back(self, reason: RollbackReason<'_>) {
+        let elapsed = self.scope.started.elapsed();
+        let event = TxEvent::Rollback { elapsed, reason };
+        notify_with(self.scoped.as_ref(), self.scope, event);
+    }
+}
+
+pub(crate) fn detach() -> Option<Detached> {
+    let scope = CURRENT.with(Cell::get)?;
+    let scoped = SCOPED.with(|scoped| scoped.borrow().clone());
+    Some(Detached { scope, scoped })
+}
+
+pub(crate) fn explained(sql: &str, plan: &str) {
+    if let Some(scope) = CURRENT.with(Cell::get) {
+        notify(scope, TxEvent::Explained { sql, plan });
+    }
+}
+
+pub(crate) fn statement_budget_exceeded(budget: u32, statements: u32) {
+    if let Some(scope) = CURRENT.with(Cell::get) {
+        notify(
+            scope,
+            TxEvent::StatementBudgetExceeded { budget, statements },
+        );
+    }
+}
+
+// The step `name` of `SyncMode`, reported as it starts and ends.
+pub(crate) fn sync_step<T, E>(
+    name: &'static str,
+    run: impl FnOnce() -> Result<T, E>,
+) -> Result<T, E> {
+    match CURRENT.with(Cell::get) {
+        Some(scope) => {
+            let started = step_started(scope, name);
+            let result = run();
+            step_finished(scope, name, started, result.is_ok());
+            result
+        }
+        None => run(),
+    }
+}
+
+// The step `name` of `AsyncMode`, reported as it starts and as its future completes.
+pub(crate) fn async_step<'a, T: 'a, E: 'a>(
+    name: &'static str,
+    run: impl FnOnce() -> BoxFuture<'a, Result<T, E>>,
+) -> BoxFuture<'a, Result<T, E>> {
+    match CURRENT.with(Cell::get) {
+        Some(scope) => {
+            let started = step_started(scope, name);
+            let future = run();
+            Box::pin(async move {
+                let result = future.await;
+                step_finished(scope, name, started, result.is_ok());
+                result
+            })
+        }
+        None => run(),
+    }
+}
+
+fn step_started(scope: Scope, step: &'static str) -> Instant {
+    notify(scope, TxEvent::StepStarted { step });
+    Instant::now()
+}
+fn step_finished(scope: Scope, step: &'static str, started: Instant, ok: bool) {
+    let elapsed = started.elapsed();
+    notify(scope, TxEvent::StepFinished { step, elapsed, ok });
+}
@@ -0,0 +1,208 @@
+use std::fmt;
+use std::time::Duration;
+
+use serde::Serialize;
+use sqlx::types::Json;
+use sqlx::PgPool;
+
+use crate::combinator::{AsyncMode, BoxFuture, Description, Tx};
+use crate::rt::{self, CancellationToken};
+use crate::runner::PgCtx;
+
+// An event to be published once the transaction recording it has committed.
+#[derive(Debug, Clone)]
+pub struct Event<P> {
+    topic: String,
+    payload: P,
+}
+impl<P: Serialize> Event<P> {
+    pub fn new(topic: impl Into<String>, payload: P) -> Self {
+        Self {
+            topic: topic.into(),
+            payload,
+        }
+    }
+}
+
+// An event as read back from the outbox table by the relay.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct OutboxRecord {
+    pub id: i64,
+    pub topic: String,
+    pub payload: serde_json::Value,
+}
+
+pub async fn create_table(pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"CREATE TABLE IF NOT EXISTS tx_rs_outbox
+           (
+               id           BIGSERIAL PRIMARY KEY,
+               topic        TEXT        NOT NULL,
+               payload      JSONB       NOT NULL,
+               created_at   TIMESTAMPTZ NOT NULL DEFAULT now(),
+               published_at TIMESTAMPTZ
+           )"#,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// Records `event` in the outbox after `tx` succeeded, in the same transaction: the event
+// exists exactly when the work it announces has been committed.
+#[derive(Debug, Clone)]
+pub struct WithOutbox<X, P> {
+    tx: X,
+    event: Event<P>,
+}
+impl<X, P> Tx<PgCtx> for WithOutbox<X, P>
+where
+    X: Tx<PgCtx, Mode = AsyncMode> + Send,
+    X::Item: Send,
+    X::Err: From<sqlx::Error> + Send,
+    P: Serialize + Send + Sync,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut PgCtx) -> BoxFuture<'a, Result<Self::Item, Self::Err>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            let t = self.tx.run(&mut *ctx).await?;
+            sqlx::query("INSERT INTO tx_rs_outbox (topic, payload) VALUES ($1, $2)")
+                .bind(&self.event.topic)
+                .bind(Json(&self.event.payload))
+                .execute(&mut **ctx)
+                .await?;
+            Ok(t)
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("with_outbox_event", vec![self.tx.describe()])
+    }
+}
+
+pub trait OutboxExt: Tx<PgCtx, Mode = AsyncMode> {
+    fn with_outbox_event<P>(self, event: Event<P>) -> WithOutbox<Self, P>
+    where
+        Self: Sized,
+    {
+        WithOutbox { tx: self, event }
+    }
+}
+impl<X> OutboxExt for X where X: Tx<PgCtx, Mode = AsyncMode> {}
+
+// Where the relay hands the events to, e.g. a message broker. Events are delivered at least
+// once and in the order they were recorded: one that failed is offered again by the next relay.
+pub trait Publisher: Send + Sync {
+    fn publish<'a>(&'a self, event: &'a OutboxRecord) -> BoxFuture<'a, Result<(), PublishError>>;
+}
+
+pub type PublishError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug)]
+pub enum RelayError {
+    Db(sqlx::Error),
+    Publish { id: i64, error: PublishError },
+}
+impl fmt::Display for RelayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelayError::Db(e) => write!(f, "{}", e),
+            RelayError::Publish { id, error } => {
+                write!(f, "failed to publish outbox event {}: {}", id, error)
+            }
+        }
+    }
+}
+impl std::error::Error for RelayError {}
+impl From<sqlx::Error> for RelayError {
+    fn from(e: sqlx::Error) -> Self {
+        RelayError::Db(e)
+    }
+}
+
+// Moves committed events from the outbox to a `Publisher`. Events are claimed with
+// `FOR UPDATE SKIP LOCKED`, so several relays can share the table.
+pub struct Relay<P> {
+    pool: PgPool,
+    publisher: P,
+    batch_size: i64,
+}
+impl<P: Publisher> Relay<P> {
+    pub fn new(pool: PgPool, publisher: P) -> Self {
+        Self {
+            pool,
+            publisher,
+            batch_size: 100,
+        }
+    }
+    pub fn batch_size(mut self, batch_size: i64) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+    pub fn publisher(&self) -> &P {
+        &self.publisher
+    }
+
+    // Publishes the next batch of events, returning how many were published. When publishing
+    // fails, the events published before it stay marked and the rest of the batch is left for
+    // the next call.
+    pub async fn relay_once(&self) -> Result<usize, RelayError> {
+        let mut transaction = self.pool.begin().await?;
+        let events: Vec<OutboxRecord> = sqlx::query_as(
+            r#"SELECT id, topic, payload FROM tx_rs_outbox
+               WHERE published_at IS NULL
+               ORDER BY id
+               LIMIT $1
+               FOR UPDATE SKIP LOCKED"#,
+        )
+        .bind(self.batch_size)
+        .fetch_all(&mut *transaction)
+        .await?;
+
+        let mut published = 0;
+        let mut failed = None;
+        for event in &events {
+            if let Err(error) = self.publisher.publish(event).await {
+                failed = Some(RelayError::Publish {
+                    id: event.id,
+                    error,
+                });
+                break;
+            }
+            sqlx::query("UPDATE tx_rs_outbox SET published_at = now() WHERE id = $1")
+                .bind(event.id)
+                .execute(&mut *transaction)
+                .await?;
+            published += 1;
+        }
+        transaction.commit().await?;
+
+        match failed {
+            Some(e) => Err(e),
+            None => Ok(published),
+        }
+    }
+
+    // Relays events until `token` is cancelled, waiting `idle` whenever there are none. Failures
+    // to publish are retried after `idle` as well.
+    pub async fn run(&self, token: &CancellationToken, idle: Duration) -> Result<(), sqlx::Error> {
+        while !token.is_cancelled() {
+            let published = match rt::until_cancelled(token, self.relay_once()).await {
+                Ok(Ok(published)) => published,
+                Ok(Err(RelayError::Db(e))) => return Err(e),
+                Ok(Err(RelayError::Publish { .. })) => 0,
+                Err(_) => break,
+            };
+            if published == 0 {
+                let _ = rt::until_cancelled(token, rt::sleep(idle)).await;
+            }
+        }
+        Ok(())
+    }
+}
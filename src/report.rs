@@ -0,0 +1,146 @@
+use std::borrow::Cow;
+use std::fmt::Display;
+
+use crate::combinator::{Chain, Description, Output, Tx};
+use crate::error::{SqlState, TxError};
+
+// Interop with the error reporting crates services use at their edges: steps attach
+// human-readable context to their errors as these bubble up the chain, and the reports stay
+// `SqlState`, so the retrying runners and `error_kind` still see the database error inside.
+
+// The SQLSTATE of the first database error in a chain of causes.
+fn chain_sqlstate<'e>(
+    mut chain: impl Iterator<Item = &'e (dyn std::error::Error + 'static)>,
+) -> Option<Cow<'e, str>> {
+    chain.find_map(|e| {
+        if let Some(e) = e.downcast_ref::<sqlx::Error>() {
+            e.sqlstate()
+        } else {
+            e.downcast_ref::<TxError>().and_then(SqlState::sqlstate)
+        }
+    })
+}
+
+#[cfg(feature = "anyhow")]
+pub use self::anyhow_context::*;
+#[cfg(feature = "anyhow")]
+mod anyhow_context {
+    use super::*;
+    use anyhow::Context as _;
+
+    impl SqlState for anyhow::Error {
+        fn sqlstate(&self) -> Option<Cow<'_, str>> {
+            chain_sqlstate(self.chain())
+        }
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    pub struct WithContext<X, C> {
+        tx: X,
+        context: C,
+    }
+    impl<Ctx, X, C> Tx<Ctx> for WithContext<X, C>
+    where
+        X: Tx<Ctx>,
+        X::Mode: Chain<Ctx, X, C>,
+        Result<X::Item, X::Err>: anyhow::Context<X::Item, X::Err>,
+        C: Display + Send + Sync + 'static,
+    {
+        type Item = X::Item;
+        type Err = anyhow::Error;
+        type Mode = X::Mode;
+
+        fn run<'a>(self, ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+        where
+            Self: 'a,
+        {
+            X::Mode::map(self.tx, ctx, self.context, |context, r| r.context(context))
+        }
+
+        fn describe(&self) -> Description {
+            Description::new(
+                format!("context: {}", self.context),
+                vec![self.tx.describe()],
+            )
+        }
+    }
+
+    pub trait AnyhowExt<Ctx>: Tx<Ctx> {
+        // Fails with an `anyhow::Error` carrying `context` on top of the step's own error.
+        fn context<C>(self, context: C) -> WithContext<Self, C>
+        where
+            C: Display + Send + Sync + 'static,
+            Self: Sized,
+        {
+            WithContext { tx: self, context }
+        }
+    }
+    impl<Ctx, X> AnyhowExt<Ctx> for X
+    where
+        X: Tx<Ctx>,
+        Result<X::Item, X::Err>: anyhow::Context<X::Item, X::Err>,
+    {
+    }
+}
+
+#[cfg(feature = "eyre")]
+pub use self::eyre_context::*;
+#[cfg(feature = "eyre")]
+mod eyre_context {
+    use super::*;
+    use eyre::WrapErr as _;
+
+    impl SqlState for eyre::Report {
+        fn sqlstate(&self) -> Option<Cow<'_, str>> {
+            chain_sqlstate(self.chain())
+        }
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    pub struct WrapErr<X, C> {
+        tx: X,
+        context: C,
+    }
+    impl<Ctx, X, C> Tx<Ctx> for WrapErr<X, C>
+    where
+        X: Tx<Ctx>,
+        X::Mode: Chain<Ctx, X, C>,
+        Result<X::Item, X::Err>: eyre::WrapErr<X::Item, X::Err>,
+        C: Display + Send + Sync + 'static,
+    {
+        type Item = X::Item;
+        type Err = eyre::Report;
+        type Mode = X::Mode;
+
+        fn run<'a>(self, ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+        where
+            Self: 'a,
+        {
+            X::Mode::map(self.tx, ctx, self.context, |context, r| r.wrap_err(context))
+        }
+
+        fn describe(&self) -> Description {
+            Description::new(
+                format!("wrap_err: {}", self.context),
+                vec![self.tx.describe()],
+            )
+        }
+    }
+
+    pub trait EyreExt<Ctx>: Tx<Ctx> {
+        // Fails with an `eyre::Report` carrying `context` on top of the step's own error.
+        fn wrap_err<C>(self, context: C) -> WrapErr<Self, C>
+        where
+            C: Display + Send + Sync + 'static,
+            Self: Sized,
+        {
+            WrapErr { tx: self, context }
+        }
+    }
+    impl<Ctx, X> EyreExt<Ctx> for X
+    where
+        X: Tx<Ctx>,
+        Result<X::Item, X::Err>: eyre::WrapErr<X::Item, X::Err>,
+    {
+    }
+}
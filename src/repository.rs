@@ -0,0 +1,22 @@
+use crate::combinator::{AsyncMode, Tx};
+
+// A repository hands out steps instead of running statements, so a service can chain the calls
+// it makes on several repositories and run them as one transaction. Its methods return
+// `impl Step<Self, T>`: a step over the context of the repository yielding a `T`.
+pub trait Repository: Sync {
+    type Ctx;
+    type Err;
+}
+
+// `Tx` with the context and error of repository `R` filled in. Never implemented by hand:
+// every such step is one.
+pub trait Step<R: Repository + ?Sized, T>:
+    Tx<R::Ctx, Item = T, Err = R::Err, Mode = AsyncMode> + Send
+{
+}
+impl<R, T, X> Step<R, T> for X
+where
+    R: Repository + ?Sized,
+    X: Tx<R::Ctx, Item = T, Err = R::Err, Mode = AsyncMode> + Send,
+{
+}
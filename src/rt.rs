@@ -0,0 +1,188 @@
+use std::future::{poll_fn, Future};
+use std::pin::pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
+use std::time::Duration;
+
+// The async runtime the runner waits on, chosen with the `runtime-*` features.
+
+#[cfg(not(any(feature = "runtime-tokio", feature = "runtime-async-std")))]
+compile_error!("one of the `runtime-tokio` or `runtime-async-std` features must be enabled");
+
+// A future which did not finish within the time it was given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimedOut {
+    pub after: Duration,
+}
+impl std::fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "timed out after {:?}", self.after)
+    }
+}
+impl std::error::Error for TimedOut {}
+impl From<TimedOut> for sqlx::Error {
+    fn from(e: TimedOut) -> Self {
+        sqlx::Error::Io(std::io::Error::new(std::io::ErrorKind::TimedOut, e))
+    }
+}
+
+// Work given up on because its `CancellationToken` was cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cancelled")
+    }
+}
+impl std::error::Error for Cancelled {}
+impl From<Cancelled> for sqlx::Error {
+    fn from(e: Cancelled) -> Self {
+        sqlx::Error::Io(std::io::Error::new(std::io::ErrorKind::Interrupted, e))
+    }
+}
+
+// Runtime independent stand-in for tokio-util's token of the same name. Clones share the state,
+// so one clone is kept by whoever may cancel (e.g. a shutdown handler) and others handed out.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<CancellationState>,
+}
+#[derive(Debug, Default)]
+struct CancellationState {
+    cancelled: AtomicBool,
+    wakers: Mutex<Vec<Waker>>,
+}
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        for waker in self.inner.wakers.lock().unwrap().drain(..) {
+            waker.wake();
+        }
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+    // Resolves once `cancel` has been called on any clone.
+    pub async fn cancelled(&self) {
+        poll_fn(|cx| {
+            // checked under the lock, so a concurrent `cancel` either is seen here or wakes us
+            let mut wakers = self.inner.wakers.lock().unwrap();
+            if self.is_cancelled() {
+                return Poll::Ready(());
+            }
+            if !wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                wakers.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+// Drops `future` unfinished as soon as `token` is cancelled.
+pub async fn until_cancelled<F: Future>(
+    token: &CancellationToken,
+    future: F,
+) -> Result<F::Output, Cancelled> {
+    let mut future = pin!(future);
+    let mut cancelled = pin!(token.cancelled());
+    poll_fn(|cx| {
+        if cancelled.as_mut().poll(cx).is_ready() {
+            return Poll::Ready(Err(Cancelled));
+        }
+        future.as_mut().poll(cx).map(Ok)
+    })
+    .await
+}
+
+// Polls both futures until both are `Ok`, or the first `Err`, dropping the other unfinished then.
+pub(crate) async fn try_join<T1, T2, E, F1, F2>(f1: F1, f2: F2) -> Result<(T1, T2), E>
+where
+    F1: Future<Output = Result<T1, E>>,
+    F2: Future<Output = Result<T2, E>>,
+{
+    let (mut f1, mut f2) = (pin!(f1), pin!(f2));
+    let (mut r1, mut r2) = (None, None);
+    poll_fn(|cx| {
+        if r1.is_none() {
+            if let Poll::Ready(r) = f1.as_mut().poll(cx) {
+                r1 = Some(r?);
+            }
+        }
+        if r2.is_none() {
+            if let Poll::Ready(r) = f2.as_mut().poll(cx) {
+                r2 = Some(r?);
+            }
+        }
+        match (r1.take(), r2.take()) {
+            (Some(t1), Some(t2)) => Poll::Ready(Ok((t1, t2))),
+            (s1, s2) => {
+                (r1, r2) = (s1, s2);
+                Poll::Pending
+            }
+        }
+    })
+    .await
+}
+
+pub async fn sleep(duration: Duration) {
+    #[cfg(feature = "runtime-tokio")]
+    tokio::time::sleep(duration).await;
+
+    #[cfg(all(feature = "runtime-async-std", not(feature = "runtime-tokio")))]
+    async_std::task::sleep(duration).await;
+
+    // no runtime to sleep on: the `compile_error!` above is all there is to report
+    #[cfg(not(any(feature = "runtime-tokio", feature = "runtime-async-std")))]
+    let _ = duration;
+}
+
+// Drops `future` unfinished once `duration` has passed.
+pub async fn timeout<F: Future>(duration: Duration, future: F) -> Result<F::Output, TimedOut> {
+    #[cfg(feature = "runtime-tokio")]
+    let result = tokio::time::timeout(duration, future).await.ok();
+
+    #[cfg(all(feature = "runtime-async-std", not(feature = "runtime-tokio")))]
+    let result = async_std::future::timeout(duration, future).await.ok();
+
+    result.ok_or(TimedOut { after: duration })
+}
+
+// Drives `future` to completion from synchronous code, without panicking when called on a
+// runtime thread: a multi-threaded tokio worker is handed over to the blocking pool meanwhile,
+// while a current-thread runtime cannot be blocked at all and is reported as an error.
+// Outside of any runtime a shared background one is used, so that pools created through
+// `block_on` stay usable by later calls.
+#[cfg(feature = "runtime-tokio")]
+pub fn block_on<F: Future>(future: F) -> Result<F::Output, sqlx::Error> {
+    use std::sync::OnceLock;
+    use tokio::runtime::{Builder, Handle, Runtime, RuntimeFlavor};
+
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::CurrentThread => Err(
+            sqlx::Error::Configuration("cannot block on a current-thread tokio runtime".into()),
+        ),
+        Ok(handle) => Ok(tokio::task::block_in_place(|| handle.block_on(future))),
+        Err(_) => {
+            let runtime = RUNTIME.get_or_init(|| {
+                Builder::new_multi_thread()
+                    .worker_threads(1)
+                    .enable_all()
+                    .build()
+                    .expect("failed to start the background tokio runtime")
+            });
+            Ok(runtime.block_on(future))
+        }
+    }
+}
+
+#[cfg(all(feature = "runtime-async-std", not(feature = "runtime-tokio")))]
+pub fn block_on<F: Future>(future: F) -> Result<F::Output, sqlx::Error> {
+    Ok(async_std::task::block_on(future))
+}
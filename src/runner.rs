@@ -0,0 +1,1194 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use sqlx::database::{HasArguments, HasStatementCache};
+use sqlx::{Database, Pool, Transaction};
+
+use crate::chaos::{FaultExt, FaultPolicy};
+use crate::combinator::{AsyncMode, BoxFuture, Description, OrElse, Tx};
+use crate::context::{Deeper, Hooks};
+use crate::env::Env;
+use crate::observer::{self, RollbackReason};
+use crate::rt::{self, CancellationToken, Cancelled, TimedOut};
+
+#[cfg(feature = "actix-web")]
+mod actix;
+#[cfg(feature = "any")]
+mod any;
+#[cfg(feature = "axum")]
+mod axum;
+mod backpressure;
+mod batch;
+mod bulk;
+mod chunked;
+mod circuit_breaker;
+mod config;
+mod dry_run;
+mod explain;
+mod health;
+#[cfg(feature = "postgres")]
+mod keyset;
+#[cfg(feature = "postgres")]
+mod locking;
+#[cfg(feature = "mysql")]
+mod mysql;
+mod parallel;
+#[cfg(feature = "postgres")]
+mod postgres;
+mod rate_limit;
+mod registry;
+#[cfg(feature = "postgres")]
+mod rls;
+mod routing;
+mod saga;
+mod sql;
+#[cfg(feature = "postgres")]
+mod sql_ctx;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod statement_cache;
+#[cfg(feature = "metrics")]
+mod stats;
+mod stream;
+#[cfg(feature = "postgres")]
+mod tenant;
+#[cfg(feature = "tower")]
+mod tower;
+#[cfg(feature = "postgres")]
+mod unit_of_work;
+mod value;
+
+#[cfg(feature = "actix-web")]
+pub use self::actix::*;
+#[cfg(feature = "any")]
+pub use self::any::*;
+#[cfg(feature = "axum")]
+pub use self::axum::*;
+pub use self::backpressure::*;
+pub use self::batch::*;
+pub use self::bulk::*;
+pub use self::chunked::*;
+pub use self::circuit_breaker::*;
+pub use self::config::*;
+pub use self::dry_run::*;
+pub use self::explain::*;
+pub use self::health::*;
+#[cfg(feature = "postgres")]
+pub use self::keyset::*;
+#[cfg(feature = "postgres")]
+pub use self::locking::*;
+#[cfg(feature = "mysql")]
+pub use self::mysql::*;
+pub use self::parallel::*;
+#[cfg(feature = "postgres")]
+pub use self::postgres::*;
+pub use self::rate_limit::*;
+pub use self::registry::*;
+#[cfg(feature = "postgres")]
+pub use self::rls::*;
+pub use self::routing::*;
+pub use self::saga::*;
+pub use self::sql::*;
+#[cfg(feature = "postgres")]
+pub use self::sql_ctx::*;
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::*;
+pub use self::statement_cache::*;
+pub use self::stream::*;
+#[cfg(feature = "postgres")]
+pub use self::tenant::*;
+#[cfg(feature = "tower")]
+pub use self::tower::*;
+#[cfg(feature = "postgres")]
+pub use self::unit_of_work::*;
+pub use self::value::*;
+pub use crate::context::{ReadTx, TxAccess, TxCtx, WriteTx};
+pub use crate::error::{
+    CircuitOpen, DeadlineExceeded, ErrorKind, PoolExhausted, RateLimited, SchemaTooOld, SqlState,
+    StatementBudgetExceeded, TxError,
+};
+
+// What the runner needs to know about a database beyond `sqlx::Database`.
+pub trait Backend: Database + HasStatementCache {
+    // Opens a transaction on a connection of `pool` with the characteristics in `options`.
+    fn begin(
+        pool: &Pool<Self>,
+        options: TxOptions,
+    ) -> BoxFuture<'_, Result<Transaction<'static, Self>, sqlx::Error>>;
+
+    fn execute<'c>(
+        conn: &'c mut Self::Connection,
+        sql: &'c str,
+    ) -> BoxFuture<'c, Result<(), sqlx::Error>>;
+
+    // What `bulk_insert` does on this backend; the rows all have one value per column.
+    fn bulk_insert<'c>(
+        conn: &'c mut Self::Connection,
+        table: &'c str,
+        columns: &'c [String],
+        rows: Vec<Vec<Value>>,
+    ) -> BoxFuture<'c, Result<u64, sqlx::Error>>;
+
+    fn rows_affected(result: &Self::QueryResult) -> u64;
+
+    // One statement doing the work of the independent `statements`, taking the values bound to
+    // them one statement after the other, `binds[i]` of them for the `i`th, and yielding a row
+    // of how many rows each affected, for `batch`; none where it cannot, and the statements
+    // run one by one.
+    fn combine(statements: &[&str], binds: &[usize]) -> Option<String> {
+        let _ = (statements, binds);
+        None
+    }
+
+    // The plan of `sql` as the backend ran it, leaving nothing of its run behind, for
+    // `explained`; none where it cannot tell.
+    fn explain<'c>(
+        conn: &'c mut Self::Connection,
+        sql: &'c str,
+        args: <Self as HasArguments<'c>>::Arguments,
+    ) -> BoxFuture<'c, Result<Option<String>, sqlx::Error>> {
+        let _ = (conn, sql, args);
+        Box::pin(async { Ok(None) })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsolationLevel {
+    ReadCommitted,
+    RepeatableRead,
+    Serializable,
+}
+impl IsolationLevel {
+    #[cfg(any(feature = "postgres", feature = "mysql", feature = "any"))]
+    fn as_sql(self) -> &'static str {
+        match self {
+            IsolationLevel::ReadCommitted => "ISOLATION LEVEL READ COMMITTED",
+            IsolationLevel::RepeatableRead => "ISOLATION LEVEL REPEATABLE READ",
+            IsolationLevel::Serializable => "ISOLATION LEVEL SERIALIZABLE",
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    fn name(self) -> &'static str {
+        match self {
+            IsolationLevel::ReadCommitted => "read committed",
+            IsolationLevel::RepeatableRead => "repeatable read",
+            IsolationLevel::Serializable => "serializable",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessMode {
+    ReadOnly,
+    ReadWrite,
+}
+impl AccessMode {
+    #[cfg(any(feature = "postgres", feature = "mysql", feature = "any"))]
+    fn as_sql(self) -> &'static str {
+        match self {
+            AccessMode::ReadOnly => "READ ONLY",
+            AccessMode::ReadWrite => "READ WRITE",
+        }
+    }
+}
+
+// What becomes of a chain issuing more statements than its `TxOptions::statement_budget`.
+// Either way the observers are handed a `TxEvent::StatementBudgetExceeded`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverBudget {
+    // committed all the same, and logged as a warning with the `tracing` feature
+    Warn,
+    // rolled back with `StatementBudgetExceeded`
+    Fail,
+}
+
+// Characteristics applied with `SET TRANSACTION`, and timeouts which hold for the transaction
+// only. How they are applied is up to the `Backend`, which may also reject what it cannot do.
+// Anything left unset falls back to the server defaults. The deadline is kept by the runner.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TxOptions {
+    isolation_level: Option<IsolationLevel>,
+    access_mode: Option<AccessMode>,
+    statement_timeout: Option<Duration>,
+    lock_timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    sql_comments: bool,
+    statement_budget: Option<(u32, OverBudget)>,
+    persistent_statements: Option<bool>,
+    priority: Priority,
+}
+impl TxOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn isolation_level(mut self, level: IsolationLevel) -> Self {
+        self.isolation_level = Some(level);
+        self
+    }
+    pub fn access_mode(mut self, mode: AccessMode) -> Self {
+        self.access_mode = Some(mode);
+        self
+    }
+    pub fn read_only(self) -> Self {
+        self.access_mode(AccessMode::ReadOnly)
+    }
+    pub fn read_write(self) -> Self {
+        self.access_mode(AccessMode::ReadWrite)
+    }
+    pub fn statement_timeout(mut self, timeout: Duration) -> Self {
+        self.statement_timeout = Some(timeout);
+        self
+    }
+    pub fn lock_timeout(mut self, timeout: Duration) -> Self {
+        self.lock_timeout = Some(timeout);
+        self
+    }
+    // The whole run, retries included, has to be over by `deadline`: no transaction is begun
+    // after it, and a chain still running then is dropped and rolled back.
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    // Appends sqlcommenter comments to the statements of the crate's SQL helpers; see
+    // `sqlcomment`.
+    pub fn sql_comments(mut self, on: bool) -> Self {
+        self.sql_comments = on;
+        self
+    }
+
+    // How many statements the chain may issue through its context, i.e. how many times a step
+    // takes the connection out of it with `&mut **ctx`, to catch N+1 chains such as a
+    // step running a query per item of a list. Checked once the chain is over.
+    pub fn statement_budget(mut self, budget: u32, over: OverBudget) -> Self {
+        self.statement_budget = Some((budget, over));
+        self
+    }
+
+    // Whether the statements of the crate's SQL helpers are kept prepared in the statement
+    // cache of the connection, as sqlx does by default, or prepared for the one run and
+    // dropped. Turned off for the chains which run rarely, the cache is left to the `hot`
+    // steps of those which run all the time; its capacity is set on the connect options of
+    // the pool, with sqlx's `statement_cache_capacity`.
+    pub fn persistent_statements(mut self, on: bool) -> Self {
+        self.persistent_statements = Some(on);
+        self
+    }
+    // The class the chain is run in by a `BoundedRunner`, which queues and budgets its
+    // connections per class; other runners ignore it.
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    fn deadline_passed(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| deadline <= Instant::now())
+    }
+
+    #[cfg(any(feature = "postgres", feature = "mysql", feature = "any"))]
+    fn set_transaction_sql(&self, separator: &str) -> Option<String> {
+        let modes: Vec<&str> = self
+            .isolation_level
+            .map(IsolationLevel::as_sql)
+            .into_iter()
+            .chain(self.access_mode.map(AccessMode::as_sql))
+            .collect();
+        if modes.is_empty() {
+            None
+        } else {
+            Some(format!("SET TRANSACTION {}", modes.join(separator)))
+        }
+    }
+
+    // The timeouts as Postgres `SET LOCAL` statements.
+    #[cfg(any(feature = "postgres", feature = "any"))]
+    fn set_local_sqls(&self) -> Vec<String> {
+        let timeouts = [
+            ("statement_timeout", self.statement_timeout),
+            ("lock_timeout", self.lock_timeout),
+        ];
+        timeouts
+            .iter()
+            .filter_map(|(name, timeout)| {
+                timeout.map(|t| format!("SET LOCAL {} = {}", name, t.as_millis().max(1)))
+            })
+            .collect()
+    }
+}
+
+// Begins a transaction on `pool`, runs `tx` in it, then commits on `Ok` and rolls back on `Err`.
+// A failing rollback is not reported: the error from the chain is the interesting one,
+// and the connection is discarded by sqlx anyway if it is left in a broken state.
+pub async fn run_tx<DB, A, T, E, X>(pool: &Pool<DB>, tx: X) -> Result<T, E>
+where
+    DB: Backend,
+    A: TxAccess,
+    X: Tx<TxCtx<DB, A>, Item = T, Err = E, Mode = AsyncMode>,
+    E: From<sqlx::Error>,
+{
+    run_tx_with(pool, TxOptions::default(), tx).await
+}
+
+pub(crate) async fn begin<DB: Backend, A: TxAccess>(
+    pool: &Pool<DB>,
+    options: TxOptions,
+) -> Result<TxCtx<DB, A>, sqlx::Error> {
+    if options.deadline_passed() {
+        return Err(DeadlineExceeded.into());
+    }
+    #[cfg(feature = "metrics")]
+    let started = Instant::now();
+    let transaction = DB::begin(pool, A::restrict(options)).await;
+    #[cfg(feature = "metrics")]
+    stats::begun(pool, started, transaction.as_ref().err());
+    Ok(TxCtx {
+        transaction: transaction?,
+        depth: 0,
+        deadline: options.deadline,
+        before_commit: vec![],
+        hooks: Hooks::default(),
+        env: Env::default(),
+        sql_comments: options.sql_comments,
+        name: None,
+        statements: 0,
+        statement_budget: options.statement_budget,
+        plan: None,
+        explain: false,
+        persistent: options.persistent_statements.unwrap_or(true),
+        access: PhantomData,
+    })
+}
+
+// A context owning the `TxCtx` of its transaction, such as `TenantCtx` or `UowCtx`: chains over
+// it are run as those over a bare `TxCtx` are, with the same deadline, statement budget,
+// observer events, span and metrics.
+pub(crate) trait OwnsTxCtx: Send {
+    type DB: Backend;
+    type Access: TxAccess;
+
+    fn tx_ctx(&mut self) -> &mut TxCtx<Self::DB, Self::Access>;
+    fn into_tx_ctx(self) -> TxCtx<Self::DB, Self::Access>;
+}
+impl<DB: Backend, A: TxAccess> OwnsTxCtx for TxCtx<DB, A> {
+    type DB = DB;
+    type Access = A;
+
+    fn tx_ctx(&mut self) -> &mut TxCtx<DB, A> {
+        self
+    }
+    fn into_tx_ctx(self) -> TxCtx<DB, A> {
+        self
+    }
+}
+
+// Runs `tx` on `ctx`, giving up once the deadline of `ctx` has passed.
+async fn run_chain<C, X>(ctx: &mut C, tx: X) -> Result<X::Item, X::Err>
+where
+    C: OwnsTxCtx,
+    X: Tx<C, Mode = AsyncMode>,
+    X::Err: From<sqlx::Error>,
+{
+    let inner = ctx.tx_ctx();
+    if inner.sql_comments || inner.statement_budget.is_some() || cfg!(feature = "metrics") {
+        inner.name = Some(tx.describe().name);
+    }
+    let result = match inner.remaining() {
+        Some(remaining) => match rt::timeout(remaining, tx.run(ctx)).await {
+            Ok(result) => result,
+            Err(_) => Err(sqlx::Error::from(DeadlineExceeded).into()),
+        },
+        None => tx.run(ctx).await,
+    };
+    ctx.tx_ctx().check_statement_budget(result)
+}
+
+pub async fn run_tx_with<DB, A, T, E, X>(pool: &Pool<DB>, options: TxOptions, tx: X) -> Result<T, E>
+where
+    DB: Backend,
+    A: TxAccess,
+    X: Tx<TxCtx<DB, A>, Item = T, Err = E, Mode = AsyncMode>,
+    E: From<sqlx::Error>,
+{
+    run_numbered(pool, options, 1, |ctx| ctx, tx).await
+}
+
+// `run_tx_with` for chains over a context owning the `TxCtx` of the transaction, which `wrap`
+// makes of it.
+#[cfg(feature = "postgres")]
+pub(crate) async fn run_tx_as<C, T, E, X>(
+    pool: &Pool<C::DB>,
+    options: TxOptions,
+    wrap: impl FnOnce(TxCtx<C::DB, C::Access>) -> C,
+    tx: X,
+) -> Result<T, E>
+where
+    C: OwnsTxCtx,
+    X: Tx<C, Item = T, Err = E, Mode = AsyncMode>,
+    E: From<sqlx::Error>,
+{
+    run_numbered(pool, options, 1, wrap, tx).await
+}
+
+// `run_tx_with` for the `attempt`th run of a transaction. With the `tracing` feature it runs
+// in a span telling the name the chain describes itself with, the isolation level, the
+// attempt and whether it committed; with the `metrics` feature it counts and times it. The
+// `TxObserver`s are told how it goes.
+async fn run_numbered<C, T, E, X>(
+    pool: &Pool<C::DB>,
+    options: TxOptions,
+    attempt: u32,
+    wrap: impl FnOnce(TxCtx<C::DB, C::Access>) -> C,
+    tx: X,
+) -> Result<T, E>
+where
+    C: OwnsTxCtx,
+    X: Tx<C, Item = T, Err = E, Mode = AsyncMode>,
+    E: From<sqlx::Error>,
+{
+    run_begun(|| begin(pool, options), options, attempt, wrap, tx).await
+}
+
+// `run_numbered` in the transaction `begin` begins, for runners choosing the pool themselves.
+// `begin` is called inside the observed future, so that what it returns is not also kept in
+// the state of this one: `join_parallel` runs its branches on one stack.
+async fn run_begun<C, T, E, X, B>(
+    begin: impl FnOnce() -> B,
+    options: TxOptions,
+    attempt: u32,
+    wrap: impl FnOnce(TxCtx<C::DB, C::Access>) -> C,
+    tx: X,
+) -> Result<T, E>
+where
+    C: OwnsTxCtx,
+    X: Tx<C, Item = T, Err = E, Mode = AsyncMode>,
+    E: From<sqlx::Error>,
+    B: Future<Output = Result<TxCtx<C::DB, C::Access>, sqlx::Error>>,
+{
+    let ending = Ending::start(&tx, options, attempt);
+    let observed = observer::is_observed().then(|| tx.describe().name);
+
+    let result = async {
+        let ctx = begin_observed(begin()).await?;
+        run_in(wrap(ctx), tx).await
+    };
+    let result = observer::observed(observed.as_deref(), attempt, result);
+    #[cfg(feature = "tracing")]
+    let result = tracing::Instrument::instrument(result, ending.span.clone());
+    let result = result.await;
+
+    ending.end(result.is_ok());
+    result
+}
+
+// What `run_numbered` tells of a transaction besides its events: its span with the `tracing`
+// feature, its count and duration with the `metrics` feature.
+struct Ending {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    #[cfg(feature = "metrics")]
+    name: std::borrow::Cow<'static, str>,
+    #[cfg(feature = "metrics")]
+    started: Instant,
+}
+impl Ending {
+    // The `attempt`th run of `tx` is starting.
+    fn start<Ctx, X: Tx<Ctx>>(tx: &X, options: TxOptions, attempt: u32) -> Self {
+        #[cfg(feature = "metrics")]
+        stats::started(tx.describe().name, attempt);
+        #[cfg(not(feature = "tracing"))]
+        let _ = options;
+        #[cfg(not(any(feature = "tracing", feature = "metrics")))]
+        let _ = (tx, attempt);
+        Self {
+            #[cfg(feature = "tracing")]
+            span: tracing::info_span!(
+                "transaction",
+                name = %tx.describe().name,
+                isolation = options.isolation_level.map_or("default", IsolationLevel::name),
+                attempt,
+                outcome = tracing::field::Empty,
+            ),
+            #[cfg(feature = "metrics")]
+            name: tx.describe().name,
+            #[cfg(feature = "metrics")]
+            started: Instant::now(),
+        }
+    }
+
+    fn end(self, committed: bool) {
+        #[cfg(feature = "tracing")]
+        self.span.record(
+            "outcome",
+            if committed {
+                "committed"
+            } else {
+                "rolled back"
+            },
+        );
+        #[cfg(feature = "metrics")]
+        stats::ended(self.name, self.started, committed);
+        #[cfg(not(any(feature = "tracing", feature = "metrics")))]
+        let _ = committed;
+    }
+}
+
+// Awaits `begin`, with the observers told when it fails.
+async fn begin_observed<DB: Backend, A: TxAccess>(
+    begin: impl Future<Output = Result<TxCtx<DB, A>, sqlx::Error>>,
+) -> Result<TxCtx<DB, A>, sqlx::Error> {
+    let result = begin.await;
+    if let Err(e) = &result {
+        observer::rolled_back(RollbackReason::BeginFailed(e));
+    }
+    result
+}
+
+// Runs `tx` in the transaction of `ctx`, then commits on `Ok` and rolls back on `Err`.
+async fn run_in<C, T, E, X>(mut ctx: C, tx: X) -> Result<T, E>
+where
+    C: OwnsTxCtx,
+    X: Tx<C, Item = T, Err = E, Mode = AsyncMode>,
+    E: From<sqlx::Error>,
+{
+    let result = run_chain(&mut ctx, tx).await;
+    let ctx = ctx.into_tx_ctx();
+    match result {
+        Ok(t) => match ctx.commit().await {
+            Ok(()) => {
+                observer::committed();
+                Ok(t)
+            }
+            Err(e) => {
+                observer::rolled_back(RollbackReason::CommitFailed(&e));
+                Err(e.into())
+            }
+        },
+        Err(e) => {
+            let _ = ctx.rollback().await;
+            observer::rolled_back(RollbackReason::Failed);
+            Err(e)
+        }
+    }
+}
+
+// The low-level counterpart of `run_tx_with`: runs `tx` but leaves the transaction open, so the
+// caller decides with the result in hand. On `Err` the transaction is rolled back right away.
+pub async fn run_tx_pending<DB, A, T, E, X>(
+    pool: &Pool<DB>,
+    options: TxOptions,
+    tx: X,
+) -> Result<Pending<DB, T, A>, E>
+where
+    DB: Backend,
+    A: TxAccess,
+    X: Tx<TxCtx<DB, A>, Item = T, Err = E, Mode = AsyncMode>,
+    E: From<sqlx::Error>,
+{
+    let ending = Ending::start(&tx, options, 1);
+    let observed = observer::is_observed().then(|| tx.describe().name);
+
+    let result = async {
+        let mut ctx = begin_observed(begin(pool, options)).await?;
+        match run_chain(&mut ctx, tx).await {
+            Ok(item) => Ok((ctx, item, observer::detach())),
+            Err(e) => {
+                let _ = ctx.rollback().await;
+                observer::rolled_back(RollbackReason::Failed);
+                Err(e)
+            }
+        }
+    };
+    let result = observer::observed(observed.as_deref(), 1, result);
+    #[cfg(feature = "tracing")]
+    let result = tracing::Instrument::instrument(result, ending.span.clone());
+
+    match result.await {
+        Ok((ctx, item, observed)) => Ok(Pending {
+            ctx: Some(ctx),
+            item: Some(item),
+            observed,
+            ending: Some(ending),
+        }),
+        Err(e) => {
+            ending.end(false);
+            Err(e)
+        }
+    }
+}
+
+static LEAKED_PENDING: AtomicU64 = AtomicU64::new(0);
+
+// How many `Pending` were dropped without `commit` or `rollback` so far.
+pub fn leaked_pending() -> u64 {
+    LEAKED_PENDING.load(Ordering::Relaxed)
+}
+
+// A chain which ran to completion in a still open transaction. It has to be consumed with
+// `commit` or `rollback`; dropping it rolls back like a bare sqlx `Transaction` does, but is
+// logged as a warning with the `tracing` feature and counted in `leaked_pending`, as it almost
+// always is a forgotten commit.
+#[must_use = "the transaction is rolled back unless `commit` is called"]
+pub struct Pending<DB: Database, T, A = WriteTx> {
+    ctx: Option<TxCtx<DB, A>>,
+    item: Option<T>,
+    // how the transaction ends is told once it does
+    observed: Option<observer::Detached>,
+    ending: Option<Ending>,
+}
+impl<DB: Database, T, A> Pending<DB, T, A> {
+    pub fn item(&self) -> &T {
+        self.item.as_ref().unwrap()
+    }
+    pub async fn commit(mut self) -> Result<T, sqlx::Error> {
+        let ctx = self.ctx.take().unwrap();
+        let result = ctx.commit().await;
+        match &result {
+            Ok(()) => self.ended(None),
+            Err(e) => self.ended(Some(RollbackReason::CommitFailed(e))),
+        }
+        result?;
+        Ok(self.item.take().unwrap())
+    }
+    pub async fn rollback(mut self) -> Result<T, sqlx::Error> {
+        let ctx = self.ctx.take().unwrap();
+        let result = ctx.rollback().await;
+        self.ended(Some(RollbackReason::Requested));
+        result?;
+        Ok(self.item.take().unwrap())
+    }
+
+    // Tells that the transaction committed, or rolled back for `rolled_back`.
+    fn ended(&mut self, rolled_back: Option<RollbackReason<'_>>) {
+        if let Some(observed) = self.observed.take() {
+            match rolled_back {
+                Some(reason) => observed.rolled_back(reason),
+                None => observed.committed(),
+            }
+        }
+        if let Some(ending) = self.ending.take() {
+            ending.end(rolled_back.is_none());
+        }
+    }
+}
+impl<DB: Database, T, A> Drop for Pending<DB, T, A> {
+    fn drop(&mut self) {
+        if let Some(mut ctx) = self.ctx.take() {
+            LEAKED_PENDING.fetch_add(1, Ordering::Relaxed);
+            #[cfg(feature = "tracing")]
+            tracing::warn!(
+                target: "tx::pending",
+                "pending transaction dropped without commit or rollback, rolling back"
+            );
+            ctx.hooks.run_rolled_back();
+            self.ended(Some(RollbackReason::Dropped));
+        }
+    }
+}
+
+// `run_tx_with` which gives up as soon as `token` is cancelled, e.g. on graceful shutdown: the
+// chain is dropped at the point it is waiting on, the transaction rolled back and `Cancelled`
+// returned. A chain which completes after cancellation is rolled back all the same instead of
+// committed.
+pub async fn run_tx_cancellable<DB, A, T, E, X>(
+    pool: &Pool<DB>,
+    options: TxOptions,
+    token: &CancellationToken,
+    tx: X,
+) -> Result<T, E>
+where
+    DB: Backend,
+    A: TxAccess,
+    X: Tx<TxCtx<DB, A>, Item = T, Err = E, Mode = AsyncMode>,
+    E: From<sqlx::Error> + From<Cancelled>,
+{
+    if token.is_cancelled() {
+        return Err(Cancelled.into());
+    }
+    run_numbered(pool, options, 1, |ctx| ctx, UntilCancelled { tx, token }).await
+}
+
+// Runs `tx` until `token` is cancelled, failing with `Cancelled` from then on even when `tx`
+// completed; it describes itself as `tx` does.
+struct UntilCancelled<'t, X> {
+    tx: X,
+    token: &'t CancellationToken,
+}
+impl<Ctx, X> Tx<Ctx> for UntilCancelled<'_, X>
+where
+    X: Tx<Ctx, Mode = AsyncMode>,
+    X::Err: From<Cancelled>,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> BoxFuture<'a, Result<X::Item, X::Err>>
+    where
+        Self: 'a,
+    {
+        let token = self.token;
+        let run = self.tx.run(ctx);
+        Box::pin(async move {
+            match rt::until_cancelled(token, run).await {
+                Ok(Ok(_)) if token.is_cancelled() => Err(Cancelled.into()),
+                Ok(result) => result,
+                Err(cancelled) => Err(cancelled.into()),
+            }
+        })
+    }
+
+    fn describe(&self) -> Description {
+        self.tx.describe()
+    }
+}
+
+// `run_tx` for synchronous callers such as CLI tools or plain `#[test]`s; see `rt::block_on`
+// for how it behaves when called from within a runtime.
+pub fn run_tx_blocking<DB, A, T, E, X>(pool: &Pool<DB>, tx: X) -> Result<T, E>
+where
+    DB: Backend,
+    A: TxAccess,
+    X: Tx<TxCtx<DB, A>, Item = T, Err = E, Mode = AsyncMode>,
+    E: From<sqlx::Error>,
+{
+    rt::block_on(run_tx(pool, tx))?
+}
+
+pub(crate) const SERIALIZATION_FAILURE: &str = "40001";
+const DEADLOCK_DETECTED: &str = "40P01";
+
+// Exponential backoff: `base_delay * 2^(attempt - 1)`, capped at `max_delay`.
+fn backoff(base_delay: Duration, max_delay: Duration, attempt: u32) -> Duration {
+    let factor = 1u32
+        .checked_shl(attempt.saturating_sub(1))
+        .unwrap_or(u32::MAX);
+    base_delay
+        .checked_mul(factor)
+        .map_or(max_delay, |d| d.min(max_delay))
+}
+
+// Retries on serialization failures (SQLSTATE 40001). Deadlocks are handled by the
+// `DeadlockPolicy`, which defaults to `DeadlockPolicy::default()` when not given.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    deadlock: DeadlockPolicy,
+    metrics: Option<Arc<RetryMetrics>>,
+    faults: Option<FaultPolicy>,
+    replays: u32,
+}
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_secs(1),
+            deadlock: DeadlockPolicy::default(),
+            metrics: None,
+            faults: None,
+            replays: 0,
+        }
+    }
+}
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    // Total number of runs including the first one; `1` disables retrying.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+    pub fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+    pub fn on_deadlock(mut self, deadlock: DeadlockPolicy) -> Self {
+        self.deadlock = deadlock;
+        self
+    }
+    pub fn metrics(mut self, metrics: Arc<RetryMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+    // How many times a read chain, over `TxCtx<_, ReadTx>`, is run again from the beginning on a
+    // new connection when its connection is lost or none could be had in time; `0`, the
+    // default, disables it. Such a chain cannot write, so replaying it is safe as long as its
+    // closures have no side effects of their own. Writing chains are never replayed.
+    pub fn replay_reads(mut self, max_replays: u32) -> Self {
+        self.replays = max_replays;
+        self
+    }
+    // Chaos testing: every attempt runs as `chain.inject_fault(faults)`.
+    pub fn inject_faults(mut self, faults: FaultPolicy) -> Self {
+        self.faults = Some(faults);
+        self
+    }
+
+    fn delay(&self, attempt: u32) -> Duration {
+        backoff(self.base_delay, self.max_delay, attempt)
+    }
+}
+
+// Retries on deadlocks (SQLSTATE 40P01). Both sides of a deadlock usually retry at the same
+// moment, so the backoff is jittered: half of it is fixed, the other half random.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeadlockPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    jitter: bool,
+}
+impl Default for DeadlockPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(20),
+            max_delay: Duration::from_secs(2),
+            jitter: true,
+        }
+    }
+}
+impl DeadlockPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    // Total number of runs including the first one; `1` disables retrying.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+    pub fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    fn delay(&self, attempt: u32) -> Duration {
+        let delay = backoff(self.base_delay, self.max_delay, attempt);
+        if self.jitter {
+            let half = delay / 2;
+            half + half.mul_f64(rand::random::<f64>())
+        } else {
+            delay
+        }
+    }
+}
+
+// Counters shared between runs, telling how many attempts logical transactions needed.
+#[derive(Debug, Default)]
+pub struct RetryMetrics {
+    stats: Mutex<RetryStats>,
+}
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetryStats {
+    pub transactions: u64,
+    pub serialization_retries: u64,
+    pub deadlock_retries: u64,
+    pub replays: u64,
+    // number of transactions keyed by the number of attempts they took
+    pub attempts: BTreeMap<u32, u64>,
+}
+impl RetryMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn snapshot(&self) -> RetryStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    fn record(&self, serialization_retries: u32, deadlock_retries: u32, replays: u32) {
+        let mut stats = self.stats.lock().unwrap();
+        stats.transactions += 1;
+        stats.serialization_retries += u64::from(serialization_retries);
+        stats.deadlock_retries += u64::from(deadlock_retries);
+        stats.replays += u64::from(replays);
+        *stats
+            .attempts
+            .entry(1 + serialization_retries + deadlock_retries + replays)
+            .or_insert(0) += 1;
+    }
+}
+
+// Runs the chain built by `make_tx` in its own transaction, and when it fails with a
+// serialization failure or a deadlock, rolls back, waits and runs a freshly built chain again;
+// a read chain also when its connection is lost, if the policy says so.
+// A `Tx` is consumed by `run`, hence the factory; a chain whose steps and closures are all
+// `Clone` is `Clone` itself and can be composed once and passed as `|| chain.clone()`.
+pub async fn run_tx_retry<DB, A, T, E, X, M>(
+    pool: &Pool<DB>,
+    options: TxOptions,
+    policy: RetryPolicy,
+    mut make_tx: M,
+) -> Result<T, E>
+where
+    DB: Backend,
+    A: TxAccess,
+    M: FnMut() -> X,
+    X: Tx<TxCtx<DB, A>, Item = T, Err = E, Mode = AsyncMode> + Send,
+    E: From<sqlx::Error> + SqlState,
+{
+    let mut retries = Retries::new(&policy, options, A::READ_ONLY);
+    loop {
+        let result = run_attempt(pool, options, &policy, retries.attempt(), make_tx()).await;
+        match retries.next_delay(&result) {
+            Some(delay) => rt::sleep(delay).await,
+            None => return result.map_err(|e| retries.give_up(e)),
+        }
+    }
+}
+
+// One attempt of a retried transaction, with the faults of the policy injected if any.
+async fn run_attempt<DB, A, X>(
+    pool: &Pool<DB>,
+    options: TxOptions,
+    policy: &RetryPolicy,
+    attempt: u32,
+    tx: X,
+) -> Result<X::Item, X::Err>
+where
+    DB: Backend,
+    A: TxAccess,
+    X: Tx<TxCtx<DB, A>, Mode = AsyncMode> + Send,
+    X::Err: From<sqlx::Error>,
+{
+    match &policy.faults {
+        Some(faults) => {
+            let tx = tx.inject_fault(faults.clone());
+            run_numbered(pool, options, attempt, |ctx| ctx, tx).await
+        }
+        None => run_numbered(pool, options, attempt, |ctx| ctx, tx).await,
+    }
+}
+
+// What `run_tx_retry` keeps track of across the attempts of one transaction.
+struct Retries<'p> {
+    policy: &'p RetryPolicy,
+    options: TxOptions,
+    serialization_retries: u32,
+    deadlock_retries: u32,
+    // whether the chain may be replayed on a new connection
+    replay_safe: bool,
+    replays: u32,
+}
+impl<'p> Retries<'p> {
+    fn new(policy: &'p RetryPolicy, options: TxOptions, replay_safe: bool) -> Self {
+        Self {
+            policy,
+            options,
+            serialization_retries: 0,
+            deadlock_retries: 0,
+            replay_safe,
+            replays: 0,
+        }
+    }
+
+    // The number of the attempt about to run, from 1.
+    fn attempt(&self) -> u32 {
+        1 + self.serialization_retries + self.deadlock_retries + self.replays
+    }
+
+    // How long to wait before the next attempt, or `None` when `result` is final; the
+    // metrics are recorded then.
+    fn next_delay<T, E: SqlState>(&mut self, result: &Result<T, E>) -> Option<Duration> {
+        let policy = self.policy;
+        let delay = match result {
+            Err(e) => match e.sqlstate().as_deref() {
+                Some(SERIALIZATION_FAILURE)
+                    if self.serialization_retries + 1 < policy.max_attempts =>
+                {
+                    self.serialization_retries += 1;
+                    Some(policy.delay(self.serialization_retries))
+                }
+                Some(DEADLOCK_DETECTED)
+                    if self.deadlock_retries + 1 < policy.deadlock.max_attempts =>
+                {
+                    self.deadlock_retries += 1;
+                    Some(policy.deadlock.delay(self.deadlock_retries))
+                }
+                _ if self.replay_safe
+                    && self.replays < policy.replays
+                    && matches!(
+                        e.error_kind(),
+                        ErrorKind::ConnectionLost | ErrorKind::PoolTimedOut
+                    ) =>
+                {
+                    self.replays += 1;
+                    Some(policy.delay(self.replays))
+                }
+                _ => None,
+            },
+            Ok(_) => None,
+        };
+
+        // no point in waiting for a retry which would start past the deadline
+        let delay = delay.filter(|delay| {
+            self.options
+                .deadline
+                .is_none_or(|deadline| Instant::now() + *delay < deadline)
+        });
+        if delay.is_none() {
+            if let Some(metrics) = &policy.metrics {
+                metrics.record(
+                    self.serialization_retries,
+                    self.deadlock_retries,
+                    self.replays,
+                );
+            }
+        }
+        delay
+    }
+
+    // The final error of a transaction retried on serialization failures, as returned to
+    // the caller: `SqlState::retries_exhausted` when it is one of those.
+    fn give_up<E: SqlState>(&self, e: E) -> E {
+        if e.sqlstate().as_deref() == Some(SERIALIZATION_FAILURE) {
+            e.retries_exhausted(self.serialization_retries + 1)
+        } else {
+            e
+        }
+    }
+}
+
+// Runs `tx` inside a `SAVEPOINT` of the already open transaction. On `Err` only the work
+// done since the savepoint is rolled back, so the outer chain can carry on (e.g. via `recover`).
+// This is also how nested transactions are emulated on backends without them, like SQLite.
+pub fn savepoint<X>(tx: X) -> Savepoint<X> {
+    Savepoint { tx }
+}
+#[derive(Debug, Clone, Copy)]
+pub struct Savepoint<X> {
+    pub(crate) tx: X,
+}
+impl<DB, A, X> Tx<TxCtx<DB, A>> for Savepoint<X>
+where
+    DB: Backend,
+    A: TxAccess,
+    X: Tx<TxCtx<DB, A>, Mode = AsyncMode> + Send,
+    X::Item: Send,
+    X::Err: From<sqlx::Error> + Send,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut TxCtx<DB, A>) -> BoxFuture<'a, Result<Self::Item, Self::Err>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            let mut ctx = Deeper::new(ctx, |ctx| &mut ctx.depth);
+            let name = format!("tx_rs_savepoint_{}", ctx.depth);
+            run_in_savepoint(&mut *ctx, &name, self.tx).await
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("savepoint", vec![self.tx.describe()])
+    }
+}
+
+async fn run_in_savepoint<C, X>(ctx: &mut C, name: &str, tx: X) -> Result<X::Item, X::Err>
+where
+    C: OwnsTxCtx,
+    X: Tx<C, Mode = AsyncMode>,
+    X::Err: From<sqlx::Error>,
+{
+    C::DB::execute(&mut **ctx.tx_ctx(), &format!("SAVEPOINT {}", name)).await?;
+    let mark = (ctx.tx_ctx().before_commit.len(), ctx.tx_ctx().hooks.mark());
+
+    match tx.run(ctx).await {
+        Ok(t) => {
+            C::DB::execute(&mut **ctx.tx_ctx(), &format!("RELEASE SAVEPOINT {}", name)).await?;
+            Ok(t)
+        }
+        Err(e) => {
+            let inner = ctx.tx_ctx();
+            inner.before_commit.truncate(mark.0);
+            inner.hooks.undo_to(mark.1);
+            C::DB::execute(&mut **inner, &format!("ROLLBACK TO SAVEPOINT {}", name)).await?;
+            Err(e)
+        }
+    }
+}
+
+// Fails `tx` with `TimedOut` if it has not finished within `duration`. The step in flight is
+// dropped, which stops the client from waiting but not the statement on the server: pair it with
+// `TxOptions::statement_timeout` to bound that too. The surrounding runner then rolls back.
+#[derive(Debug, Clone, Copy)]
+pub struct Timeout<X> {
+    tx: X,
+    duration: Duration,
+}
+impl<Ctx, X> Tx<Ctx> for Timeout<X>
+where
+    Ctx: Send,
+    X: Tx<Ctx, Mode = AsyncMode>,
+    X::Item: Send,
+    X::Err: From<TimedOut> + Send,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> BoxFuture<'a, Result<Self::Item, Self::Err>>
+    where
+        Self: 'a,
+    {
+        let duration = self.duration;
+        let run = self.tx.run(ctx);
+        Box::pin(async move { rt::timeout(duration, run).await? })
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("timeout", vec![self.tx.describe()])
+    }
+}
+
+pub trait TimeoutExt<Ctx>: Tx<Ctx, Mode = AsyncMode> {
+    fn timeout(self, duration: Duration) -> Timeout<Self>
+    where
+        Self: Sized,
+    {
+        Timeout { tx: self, duration }
+    }
+}
+impl<Ctx, X> TimeoutExt<Ctx> for X where X: Tx<Ctx, Mode = AsyncMode> {}
+
+pub trait SavepointExt<DB: Backend, A: TxAccess>: Tx<TxCtx<DB, A>, Mode = AsyncMode> {
+    // Like `or_else`, but the primary branch runs in a savepoint which is rolled back
+    // before `f` is called, so the fallback never sees the partial writes of the failed attempt.
+    fn or_else_savepoint<Tx2, F>(self, f: F) -> OrElse<Savepoint<Self>, F>
+    where
+        Tx2: Tx<TxCtx<DB, A>, Item = Self::Item, Err = Self::Err, Mode = AsyncMode>,
+        F: FnOnce(Self::Err) -> Tx2,
+        Self: Sized + Send,
+        Self::Item: Send,
+        Self::Err: From<sqlx::Error> + Send,
+    {
+        savepoint(self).or_else(f)
+    }
+}
+impl<DB: Backend, A: TxAccess, X> SavepointExt<DB, A> for X where
+    X: Tx<TxCtx<DB, A>, Mode = AsyncMode>
+{
+}
@@ -0,0 +1,106 @@
+use std::future::{poll_fn, ready, Future, Ready};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::pin::pin;
+use std::sync::Arc;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::error::{ErrorInternalServerError, ErrorServiceUnavailable};
+use actix_web::middleware::Next;
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest};
+use sqlx::{Database, Pool};
+use tokio::sync::Mutex;
+
+use super::{begin, run_chain, Backend, TxCtx, TxOptions};
+use crate::combinator::{AsyncMode, Tx};
+
+// The actix-web counterpart of `RequestTx`: the transaction of the current request, finished by
+// `actix_transaction_per_request` once the handler is done.
+pub struct ActixRequestTx<DB: Database> {
+    ctx: Arc<Mutex<Option<TxCtx<DB>>>>,
+}
+impl<DB: Database> Clone for ActixRequestTx<DB> {
+    fn clone(&self) -> Self {
+        Self {
+            ctx: self.ctx.clone(),
+        }
+    }
+}
+impl<DB: Backend> ActixRequestTx<DB> {
+    pub async fn run<T, E, X>(&self, tx: X) -> Result<T, E>
+    where
+        X: Tx<TxCtx<DB>, Item = T, Err = E, Mode = AsyncMode>,
+        E: From<sqlx::Error>,
+    {
+        let mut ctx = self.ctx.lock().await;
+        match ctx.as_mut() {
+            Some(ctx) => run_chain(ctx, tx).await,
+            None => Err(sqlx::Error::Protocol(
+                "the request's transaction is already finished".into(),
+            )
+            .into()),
+        }
+    }
+}
+
+impl<DB: Backend> FromRequest for ActixRequestTx<DB> {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(req.extensions().get::<Self>().cloned().ok_or_else(|| {
+            ErrorInternalServerError("`actix_transaction_per_request` middleware is missing")
+        }))
+    }
+}
+
+// Middleware, installed with
+// `.wrap(middleware::from_fn(runner::actix_transaction_per_request::<Postgres, _>))` on an app
+// holding the pool as `web::Data<Pool<DB>>`. Commits on a 2xx status, rolls back otherwise,
+// also when the handler panics: the panic is turned into a 500 after the rollback.
+pub async fn actix_transaction_per_request<DB: Backend, B: MessageBody>(
+    request: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<B>, actix_web::Error> {
+    let pool = request
+        .app_data::<web::Data<Pool<DB>>>()
+        .ok_or_else(|| ErrorInternalServerError("no pool in the app data"))?
+        .clone();
+    let ctx = begin(&pool, TxOptions::default())
+        .await
+        .map_err(ErrorServiceUnavailable)?;
+    let ctx = Arc::new(Mutex::new(Some(ctx)));
+    request
+        .extensions_mut()
+        .insert(ActixRequestTx { ctx: ctx.clone() });
+
+    let result = catch_panic(next.call(request)).await;
+
+    let ctx = ctx.lock().await.take();
+    match (ctx, result) {
+        (Some(ctx), Ok(Ok(response))) if response.status().is_success() => {
+            ctx.commit().await.map_err(ErrorInternalServerError)?;
+            Ok(response)
+        }
+        (ctx, result) => {
+            if let Some(ctx) = ctx {
+                let _ = ctx.rollback().await;
+            }
+            match result {
+                Ok(result) => result,
+                Err(()) => Err(ErrorInternalServerError("handler panicked")),
+            }
+        }
+    }
+}
+
+async fn catch_panic<F: Future>(future: F) -> Result<F::Output, ()> {
+    let mut future = pin!(future);
+    poll_fn(
+        |cx| match catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+            Ok(poll) => poll.map(Ok),
+            Err(_) => std::task::Poll::Ready(Err(())),
+        },
+    )
+    .await
+}
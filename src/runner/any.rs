@@ -0,0 +1,127 @@
+use sqlx::{Any, AnyConnection, Executor, Pool, Transaction};
+
+use super::bulk::insert_rows;
+use super::{Backend, ReadTx, TxCtx, TxOptions, Value};
+use crate::combinator::BoxFuture;
+
+pub type AnyCtx = TxCtx<Any>;
+pub type AnyReadCtx = TxCtx<Any, ReadTx>;
+
+// What the database behind an `Any` connection supports, as far as the runner is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub savepoints: bool,
+    pub isolation_levels: bool,
+    pub read_only: bool,
+    pub timeouts: bool,
+}
+impl Capabilities {
+    // Keyed by `AnyConnection::backend_name`. Unknown backends are assumed to support nothing.
+    pub fn of(backend_name: &str) -> Self {
+        match backend_name {
+            "PostgreSQL" => Self {
+                savepoints: true,
+                isolation_levels: true,
+                read_only: true,
+                timeouts: true,
+            },
+            "MySQL" => Self {
+                savepoints: true,
+                isolation_levels: true,
+                read_only: true,
+                timeouts: false,
+            },
+            // always serializable, so there is no level to choose
+            "SQLite" => Self {
+                savepoints: true,
+                isolation_levels: false,
+                read_only: false,
+                timeouts: false,
+            },
+            _ => Self {
+                savepoints: false,
+                isolation_levels: false,
+                read_only: false,
+                timeouts: false,
+            },
+        }
+    }
+}
+
+impl TxOptions {
+    // Drops whatever the database cannot do, so a chain written against Postgres still runs
+    // elsewhere, just without the extra guarantees.
+    pub fn degrade(mut self, capabilities: Capabilities) -> Self {
+        if !capabilities.isolation_levels {
+            self.isolation_level = None;
+        }
+        if !capabilities.read_only {
+            self.access_mode = None;
+        }
+        if !capabilities.timeouts {
+            self.statement_timeout = None;
+            self.lock_timeout = None;
+        }
+        self
+    }
+}
+
+impl<A> TxCtx<Any, A> {
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities::of(self.backend_name())
+    }
+}
+
+// The backend is only known once a connection is open, so the options are degraded to what it
+// supports and then applied the way its own `Backend` impl would.
+impl Backend for Any {
+    fn begin(
+        pool: &Pool<Self>,
+        options: TxOptions,
+    ) -> BoxFuture<'_, Result<Transaction<'static, Self>, sqlx::Error>> {
+        Box::pin(async move {
+            let mut conn = pool.acquire().await?;
+            let options = options.degrade(Capabilities::of(conn.backend_name()));
+
+            if conn.backend_name() == "MySQL" {
+                if let Some(sql) = options.set_transaction_sql(", ") {
+                    conn.execute(sql.as_str()).await?;
+                }
+                return Transaction::begin(conn).await;
+            }
+
+            let mut transaction = Transaction::begin(conn).await?;
+            if let Some(sql) = options.set_transaction_sql(" ") {
+                transaction.execute(sql.as_str()).await?;
+            }
+            for sql in options.set_local_sqls() {
+                transaction.execute(sql.as_str()).await?;
+            }
+
+            Ok(transaction)
+        })
+    }
+
+    fn execute<'c>(
+        conn: &'c mut AnyConnection,
+        sql: &'c str,
+    ) -> BoxFuture<'c, Result<(), sqlx::Error>> {
+        Box::pin(async move {
+            conn.execute(sql).await?;
+            Ok(())
+        })
+    }
+
+    fn bulk_insert<'c>(
+        conn: &'c mut AnyConnection,
+        table: &'c str,
+        columns: &'c [String],
+        rows: Vec<Vec<Value>>,
+    ) -> BoxFuture<'c, Result<u64, sqlx::Error>> {
+        Box::pin(insert_rows::<Self>(conn, table, columns, rows))
+    }
+
+    fn rows_affected(result: &sqlx::any::AnyQueryResult) -> u64 {
+        result.rows_affected()
+    }
+}
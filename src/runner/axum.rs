@@ -0,0 +1,91 @@
+use std::sync::Arc;
+
+use ::axum::async_trait;
+use ::axum::extract::{FromRequestParts, Request, State};
+use ::axum::http::request::Parts;
+use ::axum::http::StatusCode;
+use ::axum::middleware::Next;
+use ::axum::response::{IntoResponse, Response};
+use sqlx::{Database, Pool};
+use tokio::sync::Mutex;
+
+use super::{begin, run_chain, Backend, TxCtx, TxOptions};
+use crate::combinator::{AsyncMode, Tx};
+
+// The transaction of the current request, handed to handlers as an extractor. Every chain run
+// through it shares the one transaction, which `transaction_per_request` finishes once the
+// handler has responded: committed on a 2xx status, rolled back on anything else.
+pub struct RequestTx<DB: Database> {
+    ctx: Arc<Mutex<Option<TxCtx<DB>>>>,
+}
+impl<DB: Database> Clone for RequestTx<DB> {
+    fn clone(&self) -> Self {
+        Self {
+            ctx: self.ctx.clone(),
+        }
+    }
+}
+impl<DB: Backend> RequestTx<DB> {
+    pub async fn run<T, E, X>(&self, tx: X) -> Result<T, E>
+    where
+        X: Tx<TxCtx<DB>, Item = T, Err = E, Mode = AsyncMode>,
+        E: From<sqlx::Error>,
+    {
+        let mut ctx = self.ctx.lock().await;
+        match ctx.as_mut() {
+            Some(ctx) => run_chain(ctx, tx).await,
+            None => Err(sqlx::Error::Protocol(
+                "the request's transaction is already finished".into(),
+            )
+            .into()),
+        }
+    }
+}
+
+#[async_trait]
+impl<S, DB> FromRequestParts<S> for RequestTx<DB>
+where
+    S: Send + Sync,
+    DB: Backend,
+{
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts.extensions.get::<Self>().cloned().ok_or((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "`transaction_per_request` middleware is missing",
+        ))
+    }
+}
+
+// Middleware, installed with
+// `axum::middleware::from_fn_with_state(pool, runner::transaction_per_request::<Postgres>)`.
+pub async fn transaction_per_request<DB: Backend>(
+    State(pool): State<Pool<DB>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let ctx = match begin(&pool, TxOptions::default()).await {
+        Ok(ctx) => ctx,
+        Err(e) => return (StatusCode::SERVICE_UNAVAILABLE, e.to_string()).into_response(),
+    };
+    let ctx = Arc::new(Mutex::new(Some(ctx)));
+    request
+        .extensions_mut()
+        .insert(RequestTx { ctx: ctx.clone() });
+
+    let response = next.run(request).await;
+
+    let ctx = ctx.lock().await.take();
+    match ctx {
+        Some(ctx) if response.status().is_success() => match ctx.commit().await {
+            Ok(()) => response,
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        },
+        Some(ctx) => {
+            let _ = ctx.rollback().await;
+            response
+        }
+        None => response,
+    }
+}
@@ -0,0 +1,249 @@
+use std::future::poll_fn;
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
+use std::time::{Duration, Instant};
+
+use sqlx::{Database, Pool};
+
+use super::{run_tx_with, Backend, PoolExhausted, TxAccess, TxCtx, TxOptions};
+use crate::combinator::{AsyncMode, Tx};
+use crate::rt;
+
+// What becomes of a transaction of a `BoundedRunner` finding every connection taken: it waits
+// in a queue for one to come back, unless the queue is already `max_queued` long, and for no
+// longer than `max_wait`, failing with `PoolExhausted` otherwise. `Backpressure::shed()` does
+// not queue at all. By default the queue and the wait are unbounded, as with a bare pool.
+// A `budget` caps the connections the transactions of a class hold at once: those over it
+// wait, even with connections free, which are left to the other class.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Backpressure {
+    max_queued: Option<usize>,
+    max_wait: Option<Duration>,
+    budgets: [Option<u32>; 2],
+}
+impl Backpressure {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn max_queued(mut self, max_queued: usize) -> Self {
+        self.max_queued = Some(max_queued);
+        self
+    }
+    pub fn max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = Some(max_wait);
+        self
+    }
+    pub fn budget(mut self, priority: Priority, connections: u32) -> Self {
+        self.budgets[priority as usize] = Some(connections);
+        self
+    }
+    // Fails a transaction at once when there is no connection for it.
+    pub fn shed() -> Self {
+        Self::new().max_queued(0)
+    }
+}
+
+// The class of a transaction, set with `TxOptions::priority`. The queued `Interactive` ones get
+// the next connection before the `Batch` ones, the earliest first within a class.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Batch,
+    #[default]
+    Interactive,
+}
+// Runs transactions on `pool`, as many at once as it has connections and their classes' budgets
+// allow, the others queued as its `Backpressure` says. Only the transactions run through the
+// runner are counted: give it a pool of its own.
+pub struct BoundedRunner<DB: Database> {
+    pool: Pool<DB>,
+    policy: Backpressure,
+    gate: Arc<Gate>,
+}
+impl<DB: Backend> BoundedRunner<DB> {
+    pub fn new(pool: Pool<DB>, policy: Backpressure) -> Self {
+        let connections = pool.options().get_max_connections();
+        Self {
+            pool,
+            policy,
+            gate: Arc::new(Gate {
+                state: Mutex::new(GateState {
+                    free: connections,
+                    held: [0; 2],
+                    budgets: policy.budgets,
+                    queue: vec![],
+                    next: 0,
+                }),
+            }),
+        }
+    }
+    pub fn pool(&self) -> &Pool<DB> {
+        &self.pool
+    }
+    // How many transactions wait for a connection.
+    pub fn queued(&self) -> usize {
+        self.gate.state.lock().unwrap().queue.len()
+    }
+    // How many connections the transactions of `priority` hold.
+    pub fn held(&self, priority: Priority) -> u32 {
+        self.gate.state.lock().unwrap().held[priority as usize]
+    }
+
+    pub async fn run<A, T, E, X>(&self, options: TxOptions, tx: X) -> Result<T, E>
+    where
+        A: TxAccess,
+        X: Tx<TxCtx<DB, A>, Item = T, Err = E, Mode = AsyncMode>,
+        E: From<sqlx::Error>,
+    {
+        let _permit = self
+            .admit(options.priority)
+            .await
+            .map_err(sqlx::Error::from)?;
+        run_tx_with(&self.pool, options, tx).await
+    }
+
+    async fn admit(&self, priority: Priority) -> Result<Permit, PoolExhausted> {
+        let started = Instant::now();
+        let queued = {
+            let mut state = self.gate.state.lock().unwrap();
+            // the waiters of the class, if any, are over its budget too
+            if state.admits(priority) {
+                state.take(priority);
+                return Ok(Permit {
+                    gate: self.gate.clone(),
+                    priority,
+                });
+            }
+            if self
+                .policy
+                .max_queued
+                .is_some_and(|max| state.queue.len() >= max)
+            {
+                return Err(PoolExhausted {
+                    waited: Duration::ZERO,
+                });
+            }
+            let id = state.next;
+            state.next += 1;
+            state.queue.push(Waiter {
+                id,
+                priority,
+                admitted: false,
+                waker: None,
+            });
+            Queued {
+                gate: self.gate.clone(),
+                id,
+                priority,
+            }
+        };
+        let admitted = match self.policy.max_wait {
+            Some(max_wait) => rt::timeout(max_wait, queued.admitted()).await.ok(),
+            None => Some(queued.admitted().await),
+        };
+        admitted.ok_or(PoolExhausted {
+            waited: started.elapsed(),
+        })
+    }
+}
+
+struct Gate {
+    state: Mutex<GateState>,
+}
+struct GateState {
+    // connections no transaction of the runner holds
+    free: u32,
+    // connections held per class, admitted waiters included
+    held: [u32; 2],
+    budgets: [Option<u32>; 2],
+    queue: Vec<Waiter>,
+    next: u64,
+}
+struct Waiter {
+    id: u64,
+    priority: Priority,
+    // handed the connection of a transaction which ended
+    admitted: bool,
+    waker: Option<Waker>,
+}
+impl GateState {
+    fn admits(&self, priority: Priority) -> bool {
+        let class = priority as usize;
+        self.free > 0 && self.budgets[class].is_none_or(|budget| self.held[class] < budget)
+    }
+
+    fn take(&mut self, priority: Priority) {
+        self.free -= 1;
+        self.held[priority as usize] += 1;
+    }
+
+    // Hands a connection of `priority` back, to the first waiter of the highest class within
+    // its budget if any.
+    fn release(&mut self, priority: Priority) {
+        self.free += 1;
+        self.held[priority as usize] -= 1;
+        let next = self
+            .queue
+            .iter()
+            .enumerate()
+            .filter(|(_, waiter)| !waiter.admitted && self.admits(waiter.priority))
+            .max_by_key(|(_, waiter)| (waiter.priority, std::cmp::Reverse(waiter.id)))
+            .map(|(at, _)| at);
+        if let Some(at) = next {
+            let priority = self.queue[at].priority;
+            self.take(priority);
+            let waiter = &mut self.queue[at];
+            waiter.admitted = true;
+            if let Some(waker) = waiter.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+// A connection of the pool, for one transaction of the runner.
+struct Permit {
+    gate: Arc<Gate>,
+    priority: Priority,
+}
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.gate.state.lock().unwrap().release(self.priority);
+    }
+}
+
+// A place in the queue, given up when dropped before being admitted, e.g. on timeout.
+struct Queued {
+    gate: Arc<Gate>,
+    id: u64,
+    priority: Priority,
+}
+impl Queued {
+    async fn admitted(&self) -> Permit {
+        poll_fn(|cx| {
+            let mut state = self.gate.state.lock().unwrap();
+            let at = state.queue.iter().position(|waiter| waiter.id == self.id);
+            let waiter = &mut state.queue[at.expect("queued")];
+            if waiter.admitted {
+                state.queue.remove(at.unwrap());
+                return Poll::Ready(Permit {
+                    gate: self.gate.clone(),
+                    priority: self.priority,
+                });
+            }
+            waiter.waker = Some(cx.waker().clone());
+            Poll::Pending
+        })
+        .await
+    }
+}
+impl Drop for Queued {
+    fn drop(&mut self) {
+        let mut state = self.gate.state.lock().unwrap();
+        if let Some(at) = state.queue.iter().position(|waiter| waiter.id == self.id) {
+            // admitted meanwhile: the connection goes to the next one
+            if state.queue.remove(at).admitted {
+                state.release(self.priority);
+            }
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::fmt;
+
+use sqlx::database::HasArguments;
+use sqlx::{ColumnIndex, Decode, Executor, IntoArguments, Row, Type};
+
+use super::{Backend, Sql, TxCtx, WriteTx};
+use crate::combinator::{AsyncMode, BoxFuture, Description, Tx};
+
+// Runs `statements`, writes independent of one another, in as few round trips as the backend
+// allows, and yields how many rows each affected. On Postgres, `INSERT`, `UPDATE` and
+// `DELETE` statements without a `RETURNING` of their own go out as one statement, each in a
+// data-modifying `WITH` of its own: they all see the rows as they were before the batch, so
+// none may depend on what another writes, nor write the same rows. Elsewhere, and for
+// statements which cannot be combined, in a dry run or an `explained` step, they run one by one.
+pub fn batch<DB: Backend>(statements: impl IntoIterator<Item = Sql<DB>>) -> Batch<DB> {
+    Batch {
+        statements: statements.into_iter().collect(),
+    }
+}
+
+pub struct Batch<DB: Backend> {
+    statements: Vec<Sql<DB>>,
+}
+impl<DB: Backend> fmt::Debug for Batch<DB> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Batch")
+            .field("statements", &self.statements)
+            .finish()
+    }
+}
+impl<DB: Backend> Batch<DB> {
+    fn combined(&self) -> Option<String> {
+        if self.statements.len() < 2 {
+            return None;
+        }
+        let sqls: Vec<&str> = self.statements.iter().map(Sql::text).collect();
+        let binds: Vec<usize> = self.statements.iter().map(Sql::binds).collect();
+        DB::combine(&sqls, &binds)
+    }
+
+    fn arguments<'q>(&self) -> <DB as HasArguments<'q>>::Arguments {
+        let mut args = <DB as HasArguments<'q>>::Arguments::default();
+        for statement in &self.statements {
+            statement.bind_to(&mut args);
+        }
+        args
+    }
+}
+impl<DB> Tx<TxCtx<DB, WriteTx>> for Batch<DB>
+where
+    DB: Backend,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'r> i64: Decode<'r, DB> + Type<DB>,
+    usize: ColumnIndex<DB::Row>,
+{
+    type Item = Vec<u64>;
+    type Err = sqlx::Error;
+    type Mode = AsyncMode;
+
+    fn run<'a>(
+        self,
+        ctx: &'a mut TxCtx<DB, WriteTx>,
+    ) -> BoxFuture<'a, Result<Vec<u64>, sqlx::Error>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            let combined = self.combined();
+            let sql = match combined {
+                Some(sql) if ctx.plan.is_none() && !ctx.explain => sql,
+                _ => {
+                    let mut rows = Vec::with_capacity(self.statements.len());
+                    for statement in self.statements {
+                        rows.push(statement.run(ctx).await?);
+                    }
+                    return Ok(rows);
+                }
+            };
+            let sql = ctx.commented(&sql);
+            let args = self.arguments();
+            let row = sqlx::query_with(&sql, args)
+                .persistent(ctx.persistent)
+                .fetch_one(&mut **ctx)
+                .await?;
+            let counts = (0..self.statements.len()).map(|i| row.try_get::<i64, _>(i));
+            let rows = counts
+                .map(|count| count.map(|count| count as u64))
+                .collect::<Result<Vec<u64>, _>>()?;
+            #[cfg(feature = "metrics")]
+            for &affected in &rows {
+                super::stats::rows_affected(ctx.name.as_ref(), affected);
+            }
+            Ok(rows)
+        })
+    }
+    fn describe(&self) -> Description {
+        let statements = self.statements.iter().map(|_| Description::leaf("sql"));
+        Description::new("batch", statements.collect())
+    }
+}
@@ -0,0 +1,151 @@
+use super::{Backend, TxCtx, Value};
+use crate::combinator::{with_tx_async, AsyncMode, Tx};
+
+// Inserts `rows` into `columns` of `table` in as few round trips as the backend allows: with
+// `COPY ... FROM STDIN` on Postgres, with multi-row `INSERT`s elsewhere. Returns how many rows
+// were inserted. Table and columns are spliced into the SQL as they are.
+pub fn bulk_insert<DB: Backend>(
+    table: impl Into<String>,
+    columns: &[&str],
+    rows: Vec<Vec<Value>>,
+) -> impl Tx<TxCtx<DB>, Item = u64, Err = sqlx::Error, Mode = AsyncMode> {
+    let table = table.into();
+    let columns: Vec<String> = columns.iter().map(|column| column.to_string()).collect();
+    with_tx_async(move |ctx: &mut TxCtx<DB>| {
+        Box::pin(async move {
+            if let Some(row) = rows.iter().find(|row| row.len() != columns.len()) {
+                return Err(sqlx::Error::Protocol(format!(
+                    "bulk insert into {}: row of {} values for {} columns",
+                    table,
+                    row.len(),
+                    columns.len()
+                )));
+            }
+            let planned = || {
+                let rows = rows.iter().map(|row| {
+                    let values: Vec<String> = row.iter().map(Value::to_param).collect();
+                    format!("({})", values.join(", "))
+                });
+                rows.collect()
+            };
+            let sql = format!("bulk insert into {} ({})", table, columns.join(", "));
+            if ctx.rehearse(&sql, planned) {
+                return Ok(0);
+            }
+            let inserted = DB::bulk_insert(&mut **ctx, &table, &columns, rows).await?;
+            #[cfg(feature = "metrics")]
+            super::stats::rows_affected(ctx.name.as_ref(), inserted);
+            Ok(inserted)
+        })
+    })
+}
+
+#[cfg(feature = "postgres")]
+pub(super) async fn copy_rows(
+    conn: &mut sqlx::PgConnection,
+    table: &str,
+    columns: &[String],
+    rows: Vec<Vec<Value>>,
+) -> Result<u64, sqlx::Error> {
+    // flushed to the server whenever this much is buffered
+    const CHUNK: usize = 64 * 1024;
+
+    let sql = format!(
+        "COPY {} ({}) FROM STDIN WITH (FORMAT csv)",
+        table,
+        columns.join(", ")
+    );
+    let mut copy = conn.copy_in_raw(&sql).await?;
+    let mut buf = String::new();
+    for row in rows {
+        let fields: Vec<String> = row.into_iter().map(csv_field).collect();
+        buf.push_str(&fields.join(","));
+        buf.push('\n');
+        if buf.len() >= CHUNK {
+            if let Err(e) = copy.send(buf.as_bytes()).await {
+                let _ = copy.abort(e.to_string()).await;
+                return Err(e);
+            }
+            buf.clear();
+        }
+    }
+    if !buf.is_empty() {
+        if let Err(e) = copy.send(buf.as_bytes()).await {
+            let _ = copy.abort(e.to_string()).await;
+            return Err(e);
+        }
+    }
+    copy.finish().await
+}
+
+#[cfg(feature = "postgres")]
+fn csv_field(value: Value) -> String {
+    let text = match value {
+        Value::Bool(v) => return v.to_string(),
+        Value::Int(v) => return v.to_string(),
+        Value::Float(v) => return v.to_string(),
+        Value::Text(v) => v,
+        Value::Json(v) => v.to_string(),
+    };
+    // quoted, so an empty string is not read as NULL
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+// The fallback for backends without `COPY`: one `INSERT` per as many rows as fit in the
+// parameters of a statement.
+#[cfg(any(feature = "mysql", feature = "sqlite", feature = "any"))]
+pub(super) async fn insert_rows<DB>(
+    conn: &mut DB::Connection,
+    table: &str,
+    columns: &[String],
+    rows: Vec<Vec<Value>>,
+) -> Result<u64, sqlx::Error>
+where
+    DB: sqlx::Database,
+    for<'c> &'c mut DB::Connection: sqlx::Executor<'c, Database = DB>,
+    for<'q> <DB as sqlx::database::HasArguments<'q>>::Arguments: sqlx::IntoArguments<'q, DB>,
+    for<'q> bool: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    for<'q> i64: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    for<'q> f64: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    for<'q> String: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+{
+    use sqlx::Arguments;
+
+    // the lowest limit among the backends: SQLite's
+    const MAX_BINDS: usize = 32766;
+
+    let mut inserted = 0;
+    let mut rows = rows.into_iter().peekable();
+    while rows.peek().is_some() {
+        let chunk: Vec<Vec<Value>> = rows
+            .by_ref()
+            .take(MAX_BINDS / columns.len().max(1))
+            .collect();
+        let len = chunk.len() as u64;
+
+        let mut sql = format!("INSERT INTO {} ({}) VALUES ", table, columns.join(", "));
+        let mut args = <DB as sqlx::database::HasArguments<'_>>::Arguments::default();
+        for (i, row) in chunk.into_iter().enumerate() {
+            sql.push_str(if i == 0 { "(" } else { ", (" });
+            for (j, value) in row.into_iter().enumerate() {
+                if j > 0 {
+                    sql.push_str(", ");
+                }
+                match value {
+                    Value::Bool(v) => args.add(v),
+                    Value::Int(v) => args.add(v),
+                    Value::Float(v) => args.add(v),
+                    Value::Text(v) => args.add(v),
+                    // as text, which every backend takes into its JSON columns
+                    Value::Json(v) => args.add(v.to_string()),
+                }
+                args.format_placeholder(&mut sql)
+                    .map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+            }
+            sql.push(')');
+        }
+        sqlx::query_with(&sql, args).execute(&mut *conn).await?;
+        inserted += len;
+    }
+    Ok(inserted)
+}
@@ -0,0 +1,202 @@
+use std::fmt;
+
+use sqlx::{Database, Pool};
+
+use super::saga::{boxed, StepTx};
+use super::{run_tx_with, Backend, TxCtx, TxOptions};
+use crate::combinator::{with_tx_async, AsyncMode, Tx};
+
+// What `Chunked` does when the transaction of a chunk fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnChunkFailure {
+    // give up, keeping the chunks committed so far
+    Stop,
+    // carry on with the next chunk, reporting the failed one
+    Skip,
+    // give up, and undo the chunks committed so far with the compensation, newest first
+    Compensate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkProgress {
+    // chunks done so far, failed ones included
+    pub chunks_done: usize,
+    pub chunks: usize,
+    pub items_committed: usize,
+    pub items: usize,
+}
+
+#[derive(Debug)]
+pub struct ChunkReport<E> {
+    pub chunks_committed: usize,
+    pub items_committed: usize,
+    // index and error of every chunk skipped
+    pub failed: Vec<(usize, E)>,
+}
+
+#[derive(Debug)]
+pub struct ChunkedError<E> {
+    pub chunk: usize,
+    pub error: E,
+    // chunks which stay committed: compensated ones are not counted
+    pub chunks_committed: usize,
+    pub compensation_errors: Vec<(usize, E)>,
+}
+impl<E: fmt::Display> fmt::Display for ChunkedError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "chunk {} failed: {}", self.chunk, self.error)?;
+        for (chunk, error) in &self.compensation_errors {
+            write!(f, "; compensation of chunk {} failed: {}", chunk, error)?;
+        }
+        Ok(())
+    }
+}
+impl<E: fmt::Debug + fmt::Display> std::error::Error for ChunkedError<E> {}
+
+type Compensation<DB, I, E> = Box<dyn FnMut(Vec<I>) -> StepTx<DB, E> + Send>;
+
+// Processes a collection too large for one transaction in chunks of `chunk_size`, each chunk in
+// a transaction of its own with the chain `make_tx` builds for it.
+pub fn chunked<DB, I, F, X, E>(
+    pool: &Pool<DB>,
+    items: Vec<I>,
+    chunk_size: usize,
+    make_tx: F,
+) -> Chunked<'_, DB, I, F, E>
+where
+    DB: Backend,
+    F: FnMut(Vec<I>) -> X,
+    X: Tx<TxCtx<DB>, Err = E, Mode = AsyncMode>,
+{
+    Chunked {
+        pool,
+        options: TxOptions::default(),
+        items,
+        chunk_size: chunk_size.max(1),
+        make_tx,
+        on_failure: OnChunkFailure::Stop,
+        compensation: None,
+        progress: None,
+    }
+}
+
+pub struct Chunked<'p, DB: Database, I, F, E> {
+    pool: &'p Pool<DB>,
+    options: TxOptions,
+    items: Vec<I>,
+    chunk_size: usize,
+    make_tx: F,
+    on_failure: OnChunkFailure,
+    compensation: Option<Compensation<DB, I, E>>,
+    progress: Option<Box<dyn FnMut(ChunkProgress) + Send + 'p>>,
+}
+impl<'p, DB, I, F, X, E> Chunked<'p, DB, I, F, E>
+where
+    DB: Backend,
+    I: Clone,
+    F: FnMut(Vec<I>) -> X,
+    X: Tx<TxCtx<DB>, Err = E, Mode = AsyncMode>,
+    E: From<sqlx::Error> + Send + 'static,
+{
+    pub fn options(mut self, options: TxOptions) -> Self {
+        self.options = options;
+        self
+    }
+    pub fn skip_failed(mut self) -> Self {
+        self.on_failure = OnChunkFailure::Skip;
+        self
+    }
+    // `compensate` builds the chain undoing a committed chunk from its items.
+    pub fn compensate<G, X2>(mut self, mut compensate: G) -> Self
+    where
+        G: FnMut(Vec<I>) -> X2 + Send + 'static,
+        X2: Tx<TxCtx<DB>, Err = E, Mode = AsyncMode> + Send + 'static,
+    {
+        self.on_failure = OnChunkFailure::Compensate;
+        self.compensation = Some(Box::new(move |items| boxed(compensate(items))));
+        self
+    }
+    // Called after every chunk, whether it committed or not.
+    pub fn on_progress(mut self, progress: impl FnMut(ChunkProgress) + Send + 'p) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    pub async fn run(mut self) -> Result<ChunkReport<E>, ChunkedError<E>> {
+        let items = self.items.len();
+        let chunks = items.div_ceil(self.chunk_size);
+        let mut remaining = std::mem::take(&mut self.items).into_iter();
+        let mut report = ChunkReport {
+            chunks_committed: 0,
+            items_committed: 0,
+            failed: vec![],
+        };
+        // the items of the committed chunks, for the compensation
+        let mut committed = vec![];
+
+        for chunk in 0..chunks {
+            let chunk_items: Vec<I> = remaining.by_ref().take(self.chunk_size).collect();
+            let len = chunk_items.len();
+            if self.compensation.is_some() {
+                committed.push(chunk_items.clone());
+            }
+            let tx = (self.make_tx)(chunk_items);
+            match run_tx_with(self.pool, self.options, tx).await {
+                Ok(_) => {
+                    report.chunks_committed += 1;
+                    report.items_committed += len;
+                }
+                Err(error) => {
+                    committed.truncate(report.chunks_committed);
+                    match self.on_failure {
+                        OnChunkFailure::Skip => report.failed.push((chunk, error)),
+                        OnChunkFailure::Stop => {
+                            return Err(ChunkedError {
+                                chunk,
+                                error,
+                                chunks_committed: report.chunks_committed,
+                                compensation_errors: vec![],
+                            })
+                        }
+                        OnChunkFailure::Compensate => {
+                            return Err(self.compensate_committed(chunk, error, committed).await)
+                        }
+                    }
+                }
+            }
+            if let Some(progress) = self.progress.as_mut() {
+                progress(ChunkProgress {
+                    chunks_done: chunk + 1,
+                    chunks,
+                    items_committed: report.items_committed,
+                    items,
+                });
+            }
+        }
+        Ok(report)
+    }
+
+    async fn compensate_committed(
+        &mut self,
+        chunk: usize,
+        error: E,
+        committed: Vec<Vec<I>>,
+    ) -> ChunkedError<E> {
+        let mut chunks_committed = 0;
+        let mut compensation_errors = vec![];
+        let compensation = self.compensation.as_mut().unwrap();
+        for (i, items) in committed.into_iter().enumerate().rev() {
+            let tx = with_tx_async(compensation(items));
+            if let Err(e) = run_tx_with(self.pool, self.options, tx).await {
+                chunks_committed += 1;
+                compensation_errors.push((i, e));
+            }
+        }
+        ChunkedError {
+            chunk,
+            error,
+            chunks_committed,
+            compensation_errors,
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::CircuitOpen;
+use crate::combinator::{AsyncMode, BoxFuture, Description, Tx};
+
+// Guards the steps calling a resource which periodically falls over, e.g. a foreign table or
+// a `dblink` call. Closed, it lets them run and keeps the outcomes of the last `window` of them;
+// once at least `failure_rate` of a full window failed, it opens. Open, it fails them at once
+// with `CircuitOpen` for `open_for`, then half opens: the first step then runs as a trial, the
+// others still failing, and closes it on success or opens it again on failure. Any error of
+// a step counts as a failure: guard the calls to the resource, not the whole chain. Clones
+// share the circuit.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    failure_rate: f64,
+    window: usize,
+    open_for: Duration,
+    circuit: Arc<Mutex<Circuit>>,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+#[derive(Debug)]
+enum Circuit {
+    // the outcomes of the last steps, `true` for the failures
+    Closed(VecDeque<bool>),
+    Open(Instant),
+    // whether the trial step is in flight
+    HalfOpen(bool),
+}
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self {
+            failure_rate: 0.5,
+            window: 10,
+            open_for: Duration::from_secs(30),
+            circuit: Arc::new(Mutex::new(Circuit::Closed(VecDeque::new()))),
+        }
+    }
+}
+impl CircuitBreaker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    // The share of failures, 0.0 to 1.0, opening the circuit.
+    pub fn failure_rate(mut self, rate: f64) -> Self {
+        self.failure_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+    // How many of the last steps the failure rate is computed over, at least 1.
+    pub fn window(mut self, steps: usize) -> Self {
+        self.window = steps.max(1);
+        self
+    }
+    pub fn open_for(mut self, duration: Duration) -> Self {
+        self.open_for = duration;
+        self
+    }
+
+    pub fn state(&self) -> BreakerState {
+        match *self.circuit.lock().unwrap() {
+            Circuit::Closed(_) => BreakerState::Closed,
+            Circuit::Open(since) if since.elapsed() < self.open_for => BreakerState::Open,
+            Circuit::Open(_) | Circuit::HalfOpen(_) => BreakerState::HalfOpen,
+        }
+    }
+
+    // Lets a step through, or tells how long until one may go.
+    fn admit(&self) -> Result<Trial<'_>, CircuitOpen> {
+        let mut circuit = self.circuit.lock().unwrap();
+        if let Circuit::Open(since) = *circuit {
+            let elapsed = since.elapsed();
+            if elapsed < self.open_for {
+                return Err(CircuitOpen {
+                    retry_after: self.open_for - elapsed,
+                });
+            }
+            *circuit = Circuit::HalfOpen(false);
+        }
+        match &mut *circuit {
+            Circuit::HalfOpen(true) => Err(CircuitOpen {
+                retry_after: Duration::ZERO,
+            }),
+            Circuit::HalfOpen(in_flight) => {
+                *in_flight = true;
+                Ok(Trial {
+                    breaker: self,
+                    decisive: true,
+                })
+            }
+            _ => Ok(Trial {
+                breaker: self,
+                decisive: false,
+            }),
+        }
+    }
+}
+
+// A step let through, to be recorded once over. The trial of a half open circuit dropped before
+// it is over leaves the circuit half open for another one.
+struct Trial<'b> {
+    breaker: &'b CircuitBreaker,
+    decisive: bool,
+}
+impl Trial<'_> {
+    fn record(mut self, failed: bool) {
+        let breaker = self.breaker;
+        let mut circuit = breaker.circuit.lock().unwrap();
+        match &mut *circuit {
+            Circuit::HalfOpen(_) if self.decisive => {
+                *circuit = if failed {
+                    Circuit::Open(Instant::now())
+                } else {
+                    Circuit::Closed(VecDeque::new())
+                };
+            }
+            Circuit::Closed(outcomes) => {
+                outcomes.push_back(failed);
+                if outcomes.len() > breaker.window {
+                    outcomes.pop_front();
+                }
+                let failures = outcomes.iter().filter(|failed| **failed).count();
+                if outcomes.len() == breaker.window
+                    && failures as f64 >= breaker.failure_rate * breaker.window as f64
+                {
+                    *circuit = Circuit::Open(Instant::now());
+                }
+            }
+            // a step let through before the circuit opened or half opened
+            _ => {}
+        }
+        self.decisive = false;
+    }
+}
+impl Drop for Trial<'_> {
+    fn drop(&mut self) {
+        if self.decisive {
+            if let Circuit::HalfOpen(in_flight) = &mut *self.breaker.circuit.lock().unwrap() {
+                *in_flight = false;
+            }
+        }
+    }
+}
+
+// Runs `tx` when `breaker` lets it through.
+#[derive(Debug, Clone)]
+pub struct Breaker<X> {
+    tx: X,
+    breaker: CircuitBreaker,
+}
+impl<Ctx, X> Tx<Ctx> for Breaker<X>
+where
+    Ctx: Send,
+    X: Tx<Ctx, Mode = AsyncMode> + Send,
+    X::Err: From<CircuitOpen>,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> BoxFuture<'a, Result<Self::Item, Self::Err>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            let trial = self.breaker.admit()?;
+            let result = self.tx.run(ctx).await;
+            trial.record(result.is_err());
+            result
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("circuit_breaker", vec![self.tx.describe()])
+    }
+}
+
+pub trait CircuitBreakerExt<Ctx>: Tx<Ctx, Mode = AsyncMode> {
+    fn circuit_breaker(self, breaker: CircuitBreaker) -> Breaker<Self>
+    where
+        Self: Sized,
+    {
+        Breaker { tx: self, breaker }
+    }
+}
+impl<Ctx, X> CircuitBreakerExt<Ctx> for X where X: Tx<Ctx, Mode = AsyncMode> {}
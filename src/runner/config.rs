@@ -0,0 +1,262 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use sqlx::pool::PoolOptions;
+use sqlx::{Database, Pool};
+
+use super::{
+    run_tx_retry, run_tx_with, Backend, IsolationLevel, RetryPolicy, TxAccess, TxCtx, TxOptions,
+};
+use crate::combinator::{AsyncMode, Tx};
+use crate::error::SqlState;
+use crate::observer::{self, Observers, TxObserver};
+
+// The settings of the transactions of an application in one place: the pool, the options
+// every transaction starts from, the retry policy and the observers. Built in code, or read
+// from the environment and then adjusted in code:
+//
+//     let config = TxRuntimeConfig::from_env()?.retry(RetryPolicy::new().max_attempts(3));
+//     let runtime = TxRuntime::<Postgres>::connect(config).await?;
+//
+// `from_env` reads, when set:
+//
+//     DATABASE_URL
+//     DATABASE_MAX_CONNECTIONS, DATABASE_MIN_CONNECTIONS
+//     DATABASE_ACQUIRE_TIMEOUT_MS
+//     DATABASE_ISOLATION             read-committed, repeatable-read or serializable
+//     DATABASE_STATEMENT_TIMEOUT_MS
+//     DATABASE_RETRY_ATTEMPTS        the `max_attempts` of the retry policy
+#[derive(Clone)]
+pub struct TxRuntimeConfig {
+    url: Option<String>,
+    max_connections: u32,
+    min_connections: u32,
+    acquire_timeout: Duration,
+    options: TxOptions,
+    retry: RetryPolicy,
+    observers: Vec<Arc<dyn TxObserver>>,
+}
+impl Default for TxRuntimeConfig {
+    // the defaults of sqlx for the pool, of the server for the transactions
+    fn default() -> Self {
+        Self {
+            url: None,
+            max_connections: 10,
+            min_connections: 0,
+            acquire_timeout: Duration::from_secs(30),
+            options: TxOptions::default(),
+            retry: RetryPolicy::default(),
+            observers: vec![],
+        }
+    }
+}
+impl fmt::Debug for TxRuntimeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TxRuntimeConfig")
+            .field("url", &self.url.as_ref().map(|_| ".."))
+            .field("max_connections", &self.max_connections)
+            .field("min_connections", &self.min_connections)
+            .field("acquire_timeout", &self.acquire_timeout)
+            .field("options", &self.options)
+            .field("retry", &self.retry)
+            .field("observers", &self.observers.len())
+            .finish()
+    }
+}
+impl TxRuntimeConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    // The defaults, overridden by the variables of the environment which are set. Fails on a
+    // value which does not parse.
+    pub fn from_env() -> Result<Self, sqlx::Error> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, sqlx::Error> {
+        let number = |name: &str| -> Result<Option<u64>, sqlx::Error> {
+            var(name)
+                .map(|value| value.trim().parse().map_err(|_| invalid(name, &value)))
+                .transpose()
+        };
+        let millis = |name: &str| number(name).map(|ms| ms.map(Duration::from_millis));
+        let count = |name: &str| {
+            number(name)?
+                .map(|n| u32::try_from(n).map_err(|_| invalid(name, &n.to_string())))
+                .transpose()
+        };
+
+        let mut config = Self::new();
+        config.url = var("DATABASE_URL");
+        if let Some(n) = count("DATABASE_MAX_CONNECTIONS")? {
+            config = config.max_connections(n);
+        }
+        if let Some(n) = count("DATABASE_MIN_CONNECTIONS")? {
+            config = config.min_connections(n);
+        }
+        if let Some(timeout) = millis("DATABASE_ACQUIRE_TIMEOUT_MS")? {
+            config = config.acquire_timeout(timeout);
+        }
+        if let Some(value) = var("DATABASE_ISOLATION") {
+            let level =
+                parse_isolation(&value).ok_or_else(|| invalid("DATABASE_ISOLATION", &value))?;
+            config = config.isolation_level(level);
+        }
+        if let Some(timeout) = millis("DATABASE_STATEMENT_TIMEOUT_MS")? {
+            config = config.statement_timeout(timeout);
+        }
+        if let Some(n) = count("DATABASE_RETRY_ATTEMPTS")? {
+            config.retry = config.retry.max_attempts(n);
+        }
+        Ok(config)
+    }
+
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.url = Some(url.into());
+        self
+    }
+    pub fn max_connections(mut self, n: u32) -> Self {
+        self.max_connections = n;
+        self
+    }
+    pub fn min_connections(mut self, n: u32) -> Self {
+        self.min_connections = n;
+        self
+    }
+    // How long a transaction waits for a connection before failing with `PoolTimedOut`.
+    pub fn acquire_timeout(mut self, timeout: Duration) -> Self {
+        self.acquire_timeout = timeout;
+        self
+    }
+    pub fn isolation_level(mut self, level: IsolationLevel) -> Self {
+        self.options = self.options.isolation_level(level);
+        self
+    }
+    pub fn statement_timeout(mut self, timeout: Duration) -> Self {
+        self.options = self.options.statement_timeout(timeout);
+        self
+    }
+    // The options every transaction starts from, replacing the isolation level and statement
+    // timeout set so far.
+    pub fn options(mut self, options: TxOptions) -> Self {
+        self.options = options;
+        self
+    }
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+    // Handed the events of the transactions run through a `TxRuntime` made of the config, and of
+    // those only, unlike the observers of `add_observer`.
+    pub fn observer(mut self, observer: impl TxObserver + 'static) -> Self {
+        self.observers.push(Arc::new(observer));
+        self
+    }
+
+    pub fn tx_options(&self) -> TxOptions {
+        self.options
+    }
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry
+    }
+}
+
+fn invalid(name: &str, value: &str) -> sqlx::Error {
+    let message = format!("{}: invalid value {:?}", name, value);
+    sqlx::Error::Configuration(message.into())
+}
+
+fn parse_isolation(value: &str) -> Option<IsolationLevel> {
+    let value = value.trim().to_ascii_lowercase().replace(['_', ' '], "-");
+    match value.as_str() {
+        "read-committed" => Some(IsolationLevel::ReadCommitted),
+        "repeatable-read" => Some(IsolationLevel::RepeatableRead),
+        "serializable" => Some(IsolationLevel::Serializable),
+        _ => None,
+    }
+}
+
+// The pool and the config of an application, shared by everything running its transactions:
+// clones share both. `run` and `run_retry` start from the options of the config; `run_with`
+// takes the options as given, e.g. `runtime.options().read_only()`.
+pub struct TxRuntime<DB: Database> {
+    pool: Pool<DB>,
+    config: Arc<TxRuntimeConfig>,
+    observers: Observers,
+}
+impl<DB: Database> Clone for TxRuntime<DB> {
+    fn clone(&self) -> Self {
+        Self {
+            pool: self.pool.clone(),
+            config: self.config.clone(),
+            observers: self.observers.clone(),
+        }
+    }
+}
+impl<DB: Backend> TxRuntime<DB> {
+    // Opens a pool on the URL of the config, sized as it says.
+    pub async fn connect(config: TxRuntimeConfig) -> Result<Self, sqlx::Error> {
+        let Some(url) = config.url.clone() else {
+            let message = "no database URL: set DATABASE_URL or TxRuntimeConfig::url";
+            return Err(sqlx::Error::Configuration(message.into()));
+        };
+        let pool = PoolOptions::<DB>::new()
+            .max_connections(config.max_connections)
+            .min_connections(config.min_connections)
+            .acquire_timeout(config.acquire_timeout)
+            .connect(&url)
+            .await?;
+        Ok(Self::new(pool, config))
+    }
+    // Runs the transactions on `pool`, whatever the pool settings of the config say.
+    pub fn new(pool: Pool<DB>, config: TxRuntimeConfig) -> Self {
+        Self {
+            pool,
+            observers: config.observers.iter().cloned().collect(),
+            config: Arc::new(config),
+        }
+    }
+
+    pub fn pool(&self) -> &Pool<DB> {
+        &self.pool
+    }
+    pub fn config(&self) -> &TxRuntimeConfig {
+        &self.config
+    }
+    pub fn options(&self) -> TxOptions {
+        self.config.options
+    }
+
+    pub async fn run<A, T, E, X>(&self, tx: X) -> Result<T, E>
+    where
+        A: TxAccess,
+        X: Tx<TxCtx<DB, A>, Item = T, Err = E, Mode = AsyncMode>,
+        E: From<sqlx::Error>,
+    {
+        let run = run_tx_with(&self.pool, self.options(), tx);
+        observer::scoped(&self.observers, run).await
+    }
+
+    pub async fn run_with<A, T, E, X>(&self, options: TxOptions, tx: X) -> Result<T, E>
+    where
+        A: TxAccess,
+        X: Tx<TxCtx<DB, A>, Item = T, Err = E, Mode = AsyncMode>,
+        E: From<sqlx::Error>,
+    {
+        observer::scoped(&self.observers, run_tx_with(&self.pool, options, tx)).await
+    }
+
+    // `run_tx_retry` with the retry policy of the config.
+    pub async fn run_retry<A, T, E, X, M>(&self, make_tx: M) -> Result<T, E>
+    where
+        A: TxAccess,
+        M: FnMut() -> X,
+        X: Tx<TxCtx<DB, A>, Item = T, Err = E, Mode = AsyncMode> + Send,
+        E: From<sqlx::Error> + SqlState,
+    {
+        let policy = self.config.retry.clone();
+        let run = run_tx_retry(&self.pool, self.options(), policy, make_tx);
+        observer::scoped(&self.observers, run).await
+    }
+}
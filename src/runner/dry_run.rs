@@ -0,0 +1,70 @@
+use std::fmt;
+
+use sqlx::Pool;
+
+use super::{begin, run_chain, Backend, TxAccess, TxCtx, TxOptions};
+use crate::combinator::{AsyncMode, Tx};
+
+// What a dry run would have written, in order, for reviewing a data fix before running it
+// for real. Displays as a numbered list of statements with their parameters.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Plan {
+    pub statements: Vec<PlannedStatement>,
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedStatement {
+    pub sql: String,
+    // as `Debug` renders them
+    pub params: Vec<String>,
+}
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, statement) in self.statements.iter().enumerate() {
+            writeln!(f, "{}. {}", i + 1, statement.sql)?;
+            if !statement.params.is_empty() {
+                writeln!(f, "   with {}", statement.params.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<DB: sqlx::Database, A> TxCtx<DB, A> {
+    // Puts the write `sql` in the plan of a dry run, telling whether it is one, in which case
+    // the caller skips it.
+    pub(crate) fn rehearse(&mut self, sql: &str, params: impl FnOnce() -> Vec<String>) -> bool {
+        match &mut self.plan {
+            Some(plan) => {
+                plan.statements.push(PlannedStatement {
+                    sql: sql.to_string(),
+                    params: params(),
+                });
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// `run_tx_with` rehearsing `tx`: the writes of the SQL helpers, `sql(...)` run as a step and
+// `bulk_insert`, go into the returned plan instead of being executed, as if they affected no
+// rows, and the transaction is rolled back whatever happens. Reads run as usual, and so do
+// the statements steps issue themselves, which are rolled back with the rest.
+pub async fn run_tx_dry<DB, A, T, E, X>(
+    pool: &Pool<DB>,
+    options: TxOptions,
+    tx: X,
+) -> Result<(T, Plan), E>
+where
+    DB: Backend,
+    A: TxAccess,
+    X: Tx<TxCtx<DB, A>, Item = T, Err = E, Mode = AsyncMode>,
+    E: From<sqlx::Error>,
+{
+    let mut ctx = begin(pool, options).await?;
+    ctx.plan = Some(Plan::default());
+    let result = run_chain(&mut ctx, tx).await;
+    let plan = ctx.plan.take().unwrap_or_default();
+    let _ = ctx.rollback().await;
+    result.map(|t| (t, plan))
+}
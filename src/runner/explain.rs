@@ -0,0 +1,57 @@
+use super::{Backend, TxAccess, TxCtx};
+use crate::combinator::{AsyncMode, BoxFuture, Description, Tx};
+use crate::observer;
+
+// Has the statements of the SQL helpers in a step explained, for looking into why it is slow
+// without leaving the chain: on the backends that can, Postgres for now, each one is run
+// first under `EXPLAIN (ANALYZE, BUFFERS)` in a savepoint rolled back right after, then for
+// real. The plans go to the observers as `TxEvent::Explained` and, with the `tracing`
+// feature, into the trace as events of the `tx::explain` target. Analyzing runs the
+// statement twice, and what a rollback does not undo, such as sequences, is done twice.
+pub trait ExplainExt<DB: Backend, A>: Tx<TxCtx<DB, A>> {
+    fn explained(self) -> Explained<Self>
+    where
+        Self: Sized,
+    {
+        Explained { tx: self }
+    }
+}
+impl<DB: Backend, A, X: Tx<TxCtx<DB, A>>> ExplainExt<DB, A> for X {}
+
+#[derive(Debug, Clone)]
+pub struct Explained<X> {
+    tx: X,
+}
+impl<DB, A, X> Tx<TxCtx<DB, A>> for Explained<X>
+where
+    DB: Backend,
+    A: TxAccess,
+    X: Tx<TxCtx<DB, A>, Mode = AsyncMode> + Send,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut TxCtx<DB, A>) -> BoxFuture<'a, Result<Self::Item, Self::Err>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            let outer = std::mem::replace(&mut ctx.explain, true);
+            let result = self.tx.run(ctx).await;
+            ctx.explain = outer;
+            result
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("explained", vec![self.tx.describe()])
+    }
+}
+
+// Hands the plan of `sql` over to whoever is listening.
+pub(crate) fn report(sql: &str, plan: &str) {
+    #[cfg(feature = "tracing")]
+    tracing::info!(target: "tx::explain", sql, plan);
+    observer::explained(sql, plan);
+}
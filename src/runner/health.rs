@@ -0,0 +1,33 @@
+use std::time::Duration;
+
+use sqlx::Pool;
+
+use super::{Backend, TimeoutExt, TxAccess, TxCtx};
+use crate::combinator::{with_tx_async, AsyncMode, Tx};
+
+// Opens connections on `pool` until it holds `n` of them, or as many as it may, so that the
+// first requests after a start do not pay for connecting; e.g. before reporting ready.
+pub async fn warmup<DB: Backend>(pool: &Pool<DB>, n: u32) -> Result<(), sqlx::Error> {
+    let n = n.min(pool.options().get_max_connections());
+    let mut held = Vec::with_capacity(n as usize);
+    // held until the end, so that each acquire has to open a connection of its own
+    while held.len() < n as usize {
+        held.push(pool.acquire().await?);
+    }
+    Ok(())
+}
+
+// A `SELECT 1` failing with a `TimedOut` when the server has not answered within `timeout`,
+// for readiness probes: `run_tx_with::<_, ReadTx, ...>(pool, options, health_check(timeout))`.
+// Getting the connection is bounded by the `acquire_timeout` of the pool instead, which fails
+// with `sqlx::Error::PoolTimedOut`, i.e. `ErrorKind::PoolTimedOut`.
+pub fn health_check<DB, A>(
+    timeout: Duration,
+) -> impl Tx<TxCtx<DB, A>, Item = (), Err = sqlx::Error, Mode = AsyncMode>
+where
+    DB: Backend,
+    A: TxAccess,
+{
+    let select = with_tx_async(|ctx: &mut TxCtx<DB, A>| DB::execute(&mut **ctx, "SELECT 1"));
+    select.timeout(timeout).named("health_check")
+}
@@ -0,0 +1,182 @@
+use sqlx::postgres::PgRow;
+use sqlx::{Decode, Encode, FromRow, PgPool, Postgres, Row, Type};
+
+use super::{run_tx_with, ReadTx, TxAccess, TxCtx, TxOptions};
+use crate::combinator::{with_tx_async, AsyncMode, Tx};
+
+// Walks the rows of `query` in pages of `page_size` ordered by `key_column`, each page starting
+// after the key of the last row of the previous one. Unlike `OFFSET`, a page costs the same
+// wherever it is in the table, and rows inserted behind the walk do not shift it.
+// The key must be unique, and `query` must not order or limit on its own.
+pub fn paginate_keyset<K>(
+    query: impl Into<String>,
+    key_column: impl Into<String>,
+    page_size: usize,
+) -> Keyset<K> {
+    Keyset {
+        query: query.into(),
+        key_column: key_column.into(),
+        page_size: page_size.max(1),
+        key: std::marker::PhantomData,
+    }
+}
+
+#[derive(Debug)]
+pub struct Keyset<K> {
+    query: String,
+    key_column: String,
+    page_size: usize,
+    key: std::marker::PhantomData<fn() -> K>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page<R, K> {
+    pub rows: Vec<R>,
+    // where the next page starts, `None` when this one is the last
+    pub next: Option<K>,
+}
+
+impl<K> Keyset<K>
+where
+    K: for<'q> Encode<'q, Postgres> + for<'r> Decode<'r, Postgres> + Type<Postgres>,
+    K: Clone + Send + Sync + 'static,
+{
+    // The page after `after`, or the first page for `None`.
+    pub fn page<R, A: TxAccess>(
+        &self,
+        after: Option<K>,
+    ) -> impl Tx<TxCtx<Postgres, A>, Item = Page<R, K>, Err = sqlx::Error, Mode = AsyncMode>
+    where
+        R: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        let sql = self.sql(after.is_some());
+        let key_column = self.key_column.clone();
+        let page_size = self.page_size;
+        with_tx_async(move |ctx: &mut TxCtx<Postgres, A>| {
+            Box::pin(async move {
+                let sql = ctx.commented(&sql);
+                let mut query = sqlx::query(&sql);
+                if let Some(after) = after {
+                    query = query.bind(after);
+                }
+                let rows = query.fetch_all(&mut **ctx).await?;
+
+                let next = match rows.last() {
+                    Some(last) if rows.len() == page_size => {
+                        Some(last.try_get(key_column.as_str())?)
+                    }
+                    _ => None,
+                };
+                let rows = rows.iter().map(R::from_row).collect::<Result<_, _>>()?;
+                Ok(Page { rows, next })
+            })
+        })
+    }
+
+    // Every page, in one transaction, folded into `init` with `f`.
+    pub fn fold<R, A: TxAccess, T, F>(
+        &self,
+        init: T,
+        mut f: F,
+    ) -> impl Tx<TxCtx<Postgres, A>, Item = T, Err = sqlx::Error, Mode = AsyncMode>
+    where
+        R: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+        T: Send + 'static,
+        F: FnMut(T, Vec<R>) -> T + Send + 'static,
+    {
+        let keyset = self.clone();
+        with_tx_async(move |ctx: &mut TxCtx<Postgres, A>| {
+            Box::pin(async move {
+                let mut acc = init;
+                let mut after = None;
+                loop {
+                    let page = keyset.page(after).run(ctx).await?;
+                    acc = f(acc, page.rows);
+                    match page.next {
+                        Some(next) => after = Some(next),
+                        None => return Ok(acc),
+                    }
+                }
+            })
+        })
+    }
+
+    // A cursor over the pages which runs each page in a read-only transaction of its own, so
+    // a long walk holds no snapshot open in between.
+    pub fn pages<'p>(&self, pool: &'p PgPool) -> Pager<'p, K> {
+        Pager {
+            keyset: self.clone(),
+            pool,
+            options: TxOptions::default(),
+            after: None,
+            done: false,
+        }
+    }
+
+    fn sql(&self, after: bool) -> String {
+        let filter = if after {
+            format!("WHERE {} > $1 ", self.key_column)
+        } else {
+            String::new()
+        };
+        format!(
+            "SELECT * FROM ({}) AS tx_rs_page {}ORDER BY {} LIMIT {}",
+            self.query, filter, self.key_column, self.page_size
+        )
+    }
+}
+impl<K> Clone for Keyset<K> {
+    fn clone(&self) -> Self {
+        Self {
+            query: self.query.clone(),
+            key_column: self.key_column.clone(),
+            page_size: self.page_size,
+            key: std::marker::PhantomData,
+        }
+    }
+}
+
+pub struct Pager<'p, K> {
+    keyset: Keyset<K>,
+    pool: &'p PgPool,
+    options: TxOptions,
+    after: Option<K>,
+    done: bool,
+}
+impl<'p, K> Pager<'p, K>
+where
+    K: for<'q> Encode<'q, Postgres> + for<'r> Decode<'r, Postgres> + Type<Postgres>,
+    K: Clone + Send + Sync + 'static,
+{
+    pub fn options(mut self, options: TxOptions) -> Self {
+        self.options = options;
+        self
+    }
+    // Resumes a walk after the key a previous one stopped at.
+    pub fn after(mut self, key: K) -> Self {
+        self.after = Some(key);
+        self
+    }
+
+    // The rows of the next page, `None` once the walk is over.
+    pub async fn next<R>(&mut self) -> Result<Option<Vec<R>>, sqlx::Error>
+    where
+        R: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        if self.done {
+            return Ok(None);
+        }
+        let page = run_tx_with(
+            self.pool,
+            self.options,
+            self.keyset.page::<R, ReadTx>(self.after.clone()),
+        )
+        .await?;
+        self.done = page.next.is_none();
+        self.after = page.next;
+        if page.rows.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(page.rows))
+    }
+}
@@ -0,0 +1,166 @@
+use std::fmt;
+
+use sqlx::postgres::{PgHasArrayType, PgRow};
+use sqlx::{Encode, FromRow, Postgres, Type};
+
+use super::{savepoint, PgCtx, SqlState};
+use crate::combinator::{with_tx_async, AsyncMode, Tx};
+
+const LOCK_NOT_AVAILABLE: &str = "55P03";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockStrength {
+    Update,
+    NoKeyUpdate,
+    Share,
+    KeyShare,
+}
+
+// What to do about rows locked by another transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockWait {
+    Wait,
+    // fail with `LockError::WouldBlock`
+    NoWait,
+    // leave them out of the result
+    SkipLocked,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RowLock {
+    strength: LockStrength,
+    wait: LockWait,
+}
+impl RowLock {
+    pub fn update() -> Self {
+        Self::new(LockStrength::Update)
+    }
+    pub fn share() -> Self {
+        Self::new(LockStrength::Share)
+    }
+    pub fn new(strength: LockStrength) -> Self {
+        Self {
+            strength,
+            wait: LockWait::Wait,
+        }
+    }
+    pub fn nowait(mut self) -> Self {
+        self.wait = LockWait::NoWait;
+        self
+    }
+    pub fn skip_locked(mut self) -> Self {
+        self.wait = LockWait::SkipLocked;
+        self
+    }
+
+    fn as_sql(&self) -> String {
+        let strength = match self.strength {
+            LockStrength::Update => "FOR UPDATE",
+            LockStrength::NoKeyUpdate => "FOR NO KEY UPDATE",
+            LockStrength::Share => "FOR SHARE",
+            LockStrength::KeyShare => "FOR KEY SHARE",
+        };
+        match self.wait {
+            LockWait::Wait => strength.to_string(),
+            LockWait::NoWait => format!("{} NOWAIT", strength),
+            LockWait::SkipLocked => format!("{} SKIP LOCKED", strength),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LockError {
+    // a row was locked by another transaction and the lock was taken with `nowait`
+    WouldBlock,
+    Db(sqlx::Error),
+}
+impl fmt::Display for LockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockError::WouldBlock => write!(f, "row is locked by another transaction"),
+            LockError::Db(e) => write!(f, "{}", e),
+        }
+    }
+}
+impl std::error::Error for LockError {}
+impl From<sqlx::Error> for LockError {
+    fn from(e: sqlx::Error) -> Self {
+        if e.sqlstate().as_deref() == Some(LOCK_NOT_AVAILABLE) {
+            LockError::WouldBlock
+        } else {
+            LockError::Db(e)
+        }
+    }
+}
+// For chains failing with `sqlx::Error`: `WouldBlock` becomes an `Io` error of that kind.
+impl From<LockError> for sqlx::Error {
+    fn from(e: LockError) -> Self {
+        match e {
+            LockError::WouldBlock => {
+                sqlx::Error::Io(std::io::Error::new(std::io::ErrorKind::WouldBlock, e))
+            }
+            LockError::Db(e) => e,
+        }
+    }
+}
+
+// Selects the row of `table` whose `key_column` equals `key` and locks it. `None` when there is
+// no such row, or it is locked and skipped. It runs in a savepoint, so a `WouldBlock` leaves the
+// transaction usable for a fallback.
+pub fn lock_row<R, K>(
+    table: &'static str,
+    key_column: &'static str,
+    key: K,
+    lock: RowLock,
+) -> impl Tx<PgCtx, Item = Option<R>, Err = LockError, Mode = AsyncMode>
+where
+    R: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    K: for<'q> Encode<'q, Postgres> + Type<Postgres> + Send + 'static,
+{
+    let sql = format!(
+        "SELECT * FROM {} WHERE {} = $1 {}",
+        table,
+        key_column,
+        lock.as_sql()
+    );
+    savepoint(with_tx_async(move |ctx: &mut PgCtx| {
+        Box::pin(async move {
+            let sql = ctx.commented(&sql);
+            let row = sqlx::query_as(&sql)
+                .bind(key)
+                .fetch_optional(&mut **ctx)
+                .await?;
+            Ok(row)
+        })
+    }))
+}
+
+// `lock_row` for several keys. The rows are locked in key order, so two transactions locking
+// overlapping sets cannot deadlock each other.
+pub fn lock_rows<R, K>(
+    table: &'static str,
+    key_column: &'static str,
+    keys: Vec<K>,
+    lock: RowLock,
+) -> impl Tx<PgCtx, Item = Vec<R>, Err = LockError, Mode = AsyncMode>
+where
+    R: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    K: for<'q> Encode<'q, Postgres> + Type<Postgres> + PgHasArrayType + Send + 'static,
+{
+    let sql = format!(
+        "SELECT * FROM {0} WHERE {1} = ANY($1) ORDER BY {1} {2}",
+        table,
+        key_column,
+        lock.as_sql()
+    );
+    savepoint(with_tx_async(move |ctx: &mut PgCtx| {
+        Box::pin(async move {
+            let sql = ctx.commented(&sql);
+            let rows = sqlx::query_as(&sql)
+                .bind(keys)
+                .fetch_all(&mut **ctx)
+                .await?;
+            Ok(rows)
+        })
+    }))
+}
@@ -0,0 +1,56 @@
+use sqlx::{Executor, MySql, MySqlConnection, Pool, Transaction};
+
+use super::bulk::insert_rows;
+use super::{Backend, ReadTx, TxCtx, TxOptions, Value};
+use crate::combinator::BoxFuture;
+
+pub type MySqlCtx = TxCtx<MySql>;
+pub type MySqlReadCtx = TxCtx<MySql, ReadTx>;
+
+// MySQL takes the characteristics with `SET TRANSACTION` *before* `START TRANSACTION`, where
+// they apply to the next transaction only. It has no transaction-scoped timeouts, so those
+// are rejected rather than leaking into the session.
+impl Backend for MySql {
+    fn begin(
+        pool: &Pool<Self>,
+        options: TxOptions,
+    ) -> BoxFuture<'_, Result<Transaction<'static, Self>, sqlx::Error>> {
+        Box::pin(async move {
+            if options.statement_timeout.is_some() || options.lock_timeout.is_some() {
+                return Err(sqlx::Error::Configuration(
+                    "MySQL does not support transaction-scoped statement or lock timeouts".into(),
+                ));
+            }
+
+            let mut conn = pool.acquire().await?;
+            if let Some(sql) = options.set_transaction_sql(", ") {
+                conn.execute(sql.as_str()).await?;
+            }
+
+            Transaction::begin(conn).await
+        })
+    }
+
+    fn execute<'c>(
+        conn: &'c mut MySqlConnection,
+        sql: &'c str,
+    ) -> BoxFuture<'c, Result<(), sqlx::Error>> {
+        Box::pin(async move {
+            conn.execute(sql).await?;
+            Ok(())
+        })
+    }
+
+    fn bulk_insert<'c>(
+        conn: &'c mut MySqlConnection,
+        table: &'c str,
+        columns: &'c [String],
+        rows: Vec<Vec<Value>>,
+    ) -> BoxFuture<'c, Result<u64, sqlx::Error>> {
+        Box::pin(insert_rows::<Self>(conn, table, columns, rows))
+    }
+
+    fn rows_affected(result: &sqlx::mysql::MySqlQueryResult) -> u64 {
+        result.rows_affected()
+    }
+}
@@ -0,0 +1,54 @@
+use sqlx::Pool;
+
+use super::{run_tx_with, Backend, ReadTx, TxCtx, TxOptions};
+use crate::combinator::{AsyncMode, Tx};
+use crate::rt;
+
+// Runs the chains `tx1` and `tx2` side by side, each in a transaction of its own on a connection
+// of its own from `pool`, e.g. the independent queries of a dashboard, and hands back both
+// results. The chains are over `TxCtx<_, ReadTx>`, so neither can contain a writing step, and
+// both are begun READ ONLY. They do not share a snapshot: each sees what was committed when it
+// began. The first to fail fails the whole, the other being dropped and rolled back.
+// It takes as many connections as branches at once: with fewer left in the pool, the branches
+// wait for one another and run one after the other.
+pub async fn join_parallel<DB, T1, T2, E, X1, X2>(
+    pool: &Pool<DB>,
+    options: TxOptions,
+    tx1: X1,
+    tx2: X2,
+) -> Result<(T1, T2), E>
+where
+    DB: Backend,
+    X1: Tx<TxCtx<DB, ReadTx>, Item = T1, Err = E, Mode = AsyncMode>,
+    X2: Tx<TxCtx<DB, ReadTx>, Item = T2, Err = E, Mode = AsyncMode>,
+    E: From<sqlx::Error>,
+{
+    rt::try_join(
+        run_tx_with(pool, options, tx1),
+        run_tx_with(pool, options, tx2),
+    )
+    .await
+}
+
+// `join_parallel` for three chains.
+pub async fn join_parallel3<DB, T1, T2, T3, E, X1, X2, X3>(
+    pool: &Pool<DB>,
+    options: TxOptions,
+    tx1: X1,
+    tx2: X2,
+    tx3: X3,
+) -> Result<(T1, T2, T3), E>
+where
+    DB: Backend,
+    X1: Tx<TxCtx<DB, ReadTx>, Item = T1, Err = E, Mode = AsyncMode>,
+    X2: Tx<TxCtx<DB, ReadTx>, Item = T2, Err = E, Mode = AsyncMode>,
+    X3: Tx<TxCtx<DB, ReadTx>, Item = T3, Err = E, Mode = AsyncMode>,
+    E: From<sqlx::Error>,
+{
+    let (t1, (t2, t3)) = rt::try_join(
+        run_tx_with(pool, options, tx1),
+        join_parallel(pool, options, tx2, tx3),
+    )
+    .await?;
+    Ok((t1, t2, t3))
+}
@@ -0,0 +1,362 @@
+use std::time::Duration;
+
+use sqlx::postgres::PgArguments;
+use sqlx::{Executor, PgConnection, PgPool, Pool, Postgres, Transaction};
+
+use super::bulk::copy_rows;
+use super::{begin, run_chain, Backend, Hooks, ReadTx, TxCtx, TxOptions, Value};
+use crate::combinator::{AsyncMode, BoxFuture, Description, Tx};
+
+pub type PgCtx = TxCtx<Postgres>;
+pub type PgReadCtx = TxCtx<Postgres, ReadTx>;
+
+// Postgres takes the characteristics with `SET TRANSACTION` right after `BEGIN`, and the
+// timeouts with `SET LOCAL`, so they are reset by the server as soon as the transaction ends.
+impl Backend for Postgres {
+    fn begin(
+        pool: &Pool<Self>,
+        options: TxOptions,
+    ) -> BoxFuture<'_, Result<Transaction<'static, Self>, sqlx::Error>> {
+        Box::pin(async move {
+            let mut transaction = pool.begin().await?;
+
+            if let Some(sql) = options.set_transaction_sql(" ") {
+                transaction.execute(sql.as_str()).await?;
+            }
+            for sql in options.set_local_sqls() {
+                transaction.execute(sql.as_str()).await?;
+            }
+
+            Ok(transaction)
+        })
+    }
+
+    fn execute<'c>(
+        conn: &'c mut PgConnection,
+        sql: &'c str,
+    ) -> BoxFuture<'c, Result<(), sqlx::Error>> {
+        Box::pin(async move {
+            conn.execute(sql).await?;
+            Ok(())
+        })
+    }
+
+    fn bulk_insert<'c>(
+        conn: &'c mut PgConnection,
+        table: &'c str,
+        columns: &'c [String],
+        rows: Vec<Vec<Value>>,
+    ) -> BoxFuture<'c, Result<u64, sqlx::Error>> {
+        Box::pin(copy_rows(conn, table, columns, rows))
+    }
+
+    fn rows_affected(result: &sqlx::postgres::PgQueryResult) -> u64 {
+        result.rows_affected()
+    }
+
+    fn combine(statements: &[&str], binds: &[usize]) -> Option<String> {
+        combine_writes(statements, binds)
+    }
+
+    // Analyzed in a savepoint, rolled back whether it worked or not; when it did not, the
+    // statement run for real tells why.
+    fn explain<'c>(
+        conn: &'c mut PgConnection,
+        sql: &'c str,
+        args: PgArguments,
+    ) -> BoxFuture<'c, Result<Option<String>, sqlx::Error>> {
+        Box::pin(async move {
+            conn.execute("SAVEPOINT tx_rs_explain").await?;
+            let sql = format!("EXPLAIN (ANALYZE, BUFFERS) {}", sql);
+            let plan = sqlx::query_scalar_with::<_, String, _>(&sql, args)
+                .fetch_all(&mut *conn)
+                .await;
+            conn.execute("ROLLBACK TO SAVEPOINT tx_rs_explain; RELEASE SAVEPOINT tx_rs_explain")
+                .await?;
+            Ok(plan.ok().map(|lines| lines.join("\n")))
+        })
+    }
+}
+
+// `WITH b0 AS (<statement 0> RETURNING 1), ... SELECT (SELECT count(*) FROM b0), ...`, the
+// placeholders of each statement shifted past those of the statements before it. Only plain
+// writes are taken, and none whose placeholders cannot be told apart for sure.
+fn combine_writes(statements: &[&str], binds: &[usize]) -> Option<String> {
+    let mut writes = Vec::with_capacity(statements.len());
+    let mut counts = Vec::with_capacity(statements.len());
+    let mut offset = 0;
+    for (i, (sql, &binds)) in statements.iter().zip(binds).enumerate() {
+        let sql = sql.trim().trim_end_matches(';').trim_end();
+        let verb = sql.split_whitespace().next().unwrap_or("");
+        let plain = ["INSERT", "UPDATE", "DELETE"]
+            .iter()
+            .any(|write| verb.eq_ignore_ascii_case(write));
+        if !plain || sql.to_ascii_uppercase().contains("RETURNING") {
+            return None;
+        }
+        let sql = shift_placeholders(sql, offset, binds)?;
+        // on a line of its own, in case the statement ends with a comment
+        writes.push(format!("b{} AS ({}\nRETURNING 1)", i, sql));
+        counts.push(format!("(SELECT count(*) FROM b{})", i));
+        offset += binds;
+    }
+    Some(format!(
+        "WITH {} SELECT {}",
+        writes.join(", "),
+        counts.join(", ")
+    ))
+}
+
+// `sql` with `$n` turned into `$(n + offset)`, leaving alone what is in strings, quoted
+// identifiers and comments. None for placeholders past the `binds` of the statement, and for
+// what it does not follow: backslash escapes, dollar quoting.
+fn shift_placeholders(sql: &str, offset: usize, binds: usize) -> Option<String> {
+    let mut shifted = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+    let mut previous = ' ';
+    while let Some(c) = chars.next() {
+        shifted.push(c);
+        match c {
+            '\\' => return None,
+            '\'' | '"' => loop {
+                let next = chars.next()?;
+                if next == '\\' {
+                    return None;
+                }
+                shifted.push(next);
+                if next == c {
+                    break;
+                }
+            },
+            '-' if chars.peek() == Some(&'-') => {
+                for next in chars.by_ref() {
+                    shifted.push(next);
+                    if next == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => loop {
+                let next = chars.next()?;
+                shifted.push(next);
+                if next == '*' && chars.peek() == Some(&'/') {
+                    shifted.push(chars.next()?);
+                    break;
+                }
+            },
+            // part of an identifier
+            '$' if previous.is_alphanumeric() || previous == '_' || previous == '$' => {}
+            '$' => {
+                let mut digits = String::new();
+                while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                    digits.push(digit);
+                }
+                let n: usize = digits.parse().ok()?;
+                if n == 0 || n > binds {
+                    return None;
+                }
+                shifted.push_str(&(n + offset).to_string());
+            }
+            _ => {}
+        }
+        previous = c;
+    }
+    Some(shifted)
+}
+
+// A transaction left behind by `prepare_tx`: its work survives disconnects and server
+// restarts until `COMMIT PREPARED` or `ROLLBACK PREPARED` is issued for its `gid`.
+// The server must run with `max_prepared_transactions` > 0.
+#[must_use]
+#[derive(Debug)]
+pub struct Prepared<T> {
+    gid: String,
+    item: T,
+    hooks: Hooks,
+}
+impl<T> Prepared<T> {
+    pub fn gid(&self) -> &str {
+        &self.gid
+    }
+    pub fn item(&self) -> &T {
+        &self.item
+    }
+    // The hooks registered by the chain run here, once its fate is known.
+    pub async fn commit(mut self, pool: &PgPool) -> Result<T, sqlx::Error> {
+        commit_prepared(pool, &self.gid).await?;
+        self.hooks.run_committed();
+        Ok(self.item)
+    }
+    pub async fn rollback(mut self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        rollback_prepared(pool, &self.gid).await?;
+        self.hooks.run_rolled_back();
+        Ok(())
+    }
+}
+
+fn quote_literal(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+// Like `run_tx_with`, but on `Ok` the transaction is only prepared under `gid` (first phase
+// of a two-phase commit); a coordinator finishes it later with `commit_prepared`/`rollback_prepared`.
+pub async fn prepare_tx<T, E, X>(
+    pool: &PgPool,
+    options: TxOptions,
+    gid: impl Into<String>,
+    tx: X,
+) -> Result<Prepared<T>, E>
+where
+    X: Tx<PgCtx, Item = T, Err = E, Mode = AsyncMode>,
+    E: From<sqlx::Error>,
+{
+    let gid = gid.into();
+    let mut ctx = begin(pool, options).await?;
+
+    match run_chain(&mut ctx, tx).await {
+        Ok(item) => {
+            if let Err(e) = ctx.run_before_commit().await {
+                let _ = ctx.rollback().await;
+                return Err(e.into());
+            }
+            let prepare = format!("PREPARE TRANSACTION {}", quote_literal(&gid));
+            if let Err(e) = sqlx::query(&prepare).execute(&mut *ctx).await {
+                let _ = ctx.rollback().await;
+                return Err(e.into());
+            }
+            // the session is out of its transaction now; this just settles sqlx's bookkeeping
+            let TxCtx {
+                transaction, hooks, ..
+            } = ctx;
+            transaction.commit().await?;
+            Ok(Prepared { gid, item, hooks })
+        }
+        Err(e) => {
+            let _ = ctx.rollback().await;
+            Err(e)
+        }
+    }
+}
+
+pub async fn commit_prepared(pool: &PgPool, gid: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(&format!("COMMIT PREPARED {}", quote_literal(gid)))
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn rollback_prepared(pool: &PgPool, gid: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(&format!("ROLLBACK PREPARED {}", quote_literal(gid)))
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreparedXact {
+    pub gid: String,
+    pub owner: String,
+    pub age: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Commit,
+    Rollback,
+}
+
+// In-doubt transactions prepared in the current database, oldest first.
+pub async fn list_prepared(pool: &PgPool) -> Result<Vec<PreparedXact>, sqlx::Error> {
+    let rows: Vec<(String, String, f64)> = sqlx::query_as(
+        r#"SELECT gid, owner, extract(epoch FROM now() - prepared)::float8
+           FROM pg_prepared_xacts
+           WHERE database = current_database()
+           ORDER BY prepared"#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(gid, owner, age)| PreparedXact {
+            gid,
+            owner,
+            age: Duration::from_secs_f64(age.max(0.0)),
+        })
+        .collect())
+}
+
+// Recovery helper: asks `decide` about every in-doubt transaction and commits or rolls back
+// the ones it has an answer for. Returns what was done.
+pub async fn resolve_prepared<F>(
+    pool: &PgPool,
+    mut decide: F,
+) -> Result<Vec<(String, Resolution)>, sqlx::Error>
+where
+    F: FnMut(&PreparedXact) -> Option<Resolution>,
+{
+    let mut resolved = vec![];
+    for xact in list_prepared(pool).await? {
+        let resolution = match decide(&xact) {
+            Some(resolution) => resolution,
+            None => continue,
+        };
+        match resolution {
+            Resolution::Commit => commit_prepared(pool, &xact.gid).await?,
+            Resolution::Rollback => rollback_prepared(pool, &xact.gid).await?,
+        }
+        resolved.push((xact.gid, resolution));
+    }
+    Ok(resolved)
+}
+
+// Runs `tx` after setting each `(name, value)` with `set_config(name, value, true)`, the
+// function form of `SET LOCAL`: the values hold until the end of the surrounding transaction.
+pub fn with_local_settings<I, K, V, X>(settings: I, tx: X) -> WithLocalSettings<X>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: Into<String>,
+    V: ToString,
+{
+    WithLocalSettings {
+        settings: settings
+            .into_iter()
+            .map(|(k, v)| (k.into(), v.to_string()))
+            .collect(),
+        tx,
+    }
+}
+#[derive(Debug, Clone)]
+pub struct WithLocalSettings<X> {
+    settings: Vec<(String, String)>,
+    tx: X,
+}
+impl<X> Tx<PgCtx> for WithLocalSettings<X>
+where
+    X: Tx<PgCtx, Mode = AsyncMode> + Send,
+    X::Err: From<sqlx::Error>,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut PgCtx) -> BoxFuture<'a, Result<Self::Item, Self::Err>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            for (name, value) in self.settings {
+                sqlx::query("SELECT set_config($1, $2, true)")
+                    .bind(name)
+                    .bind(value)
+                    .execute(&mut **ctx)
+                    .await?;
+            }
+            self.tx.run(ctx).await
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("with_local_settings", vec![self.tx.describe()])
+    }
+}
@@ -0,0 +1,126 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::RateLimited;
+use crate::combinator::{AsyncMode, BoxFuture, Description, Tx};
+use crate::rt;
+
+// A token bucket shared by the chains it limits, e.g. the transactions of the batch jobs
+// against one database: `per_second` tokens come in every second, up to `burst` of them
+// kept for later, and a `rate_limited` step takes one before it runs. A step finding none
+// waits for the next, unless that would take longer than `max_wait`: it then fails with
+// `RateLimited` without running. By default it waits as long as it takes; `reject()` never
+// waits. Clones share the bucket.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    bucket: Arc<Mutex<Bucket>>,
+    max_wait: Option<Duration>,
+}
+#[derive(Debug)]
+struct Bucket {
+    per_second: f64,
+    burst: f64,
+    // below zero when steps wait for tokens yet to come
+    tokens: f64,
+    refilled: Instant,
+}
+impl RateLimiter {
+    pub fn new(per_second: f64) -> Self {
+        let per_second = per_second.max(f64::MIN_POSITIVE);
+        let burst = per_second.max(1.0);
+        Self {
+            bucket: Arc::new(Mutex::new(Bucket {
+                per_second,
+                burst,
+                tokens: burst,
+                refilled: Instant::now(),
+            })),
+            max_wait: None,
+        }
+    }
+    // How many tokens the bucket keeps, at least 1, and starts with; `per_second` by default.
+    pub fn burst(self, burst: u32) -> Self {
+        {
+            let mut bucket = self.bucket.lock().unwrap();
+            bucket.burst = f64::from(burst.max(1));
+            bucket.tokens = bucket.burst;
+        }
+        self
+    }
+    pub fn max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = Some(max_wait);
+        self
+    }
+    pub fn reject(self) -> Self {
+        self.max_wait(Duration::ZERO)
+    }
+
+    // Takes a token, waiting for it if need be.
+    pub async fn acquire(&self) -> Result<(), RateLimited> {
+        let wait = self.reserve()?;
+        if !wait.is_zero() {
+            rt::sleep(wait).await;
+        }
+        Ok(())
+    }
+
+    // Takes the next token, and tells how long until it is there.
+    fn reserve(&self) -> Result<Duration, RateLimited> {
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.refilled).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * bucket.per_second).min(bucket.burst);
+        bucket.refilled = now;
+        let wait = if bucket.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - bucket.tokens) / bucket.per_second)
+        };
+        if self.max_wait.is_some_and(|max_wait| wait > max_wait) {
+            return Err(RateLimited { retry_after: wait });
+        }
+        bucket.tokens -= 1.0;
+        Ok(wait)
+    }
+}
+
+// Runs `tx` once `limiter` has a token for it.
+#[derive(Debug, Clone)]
+pub struct RateLimit<X> {
+    tx: X,
+    limiter: RateLimiter,
+}
+impl<Ctx, X> Tx<Ctx> for RateLimit<X>
+where
+    Ctx: Send,
+    X: Tx<Ctx, Mode = AsyncMode> + Send,
+    X::Err: From<RateLimited>,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> BoxFuture<'a, Result<Self::Item, Self::Err>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            self.limiter.acquire().await?;
+            self.tx.run(ctx).await
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("rate_limited", vec![self.tx.describe()])
+    }
+}
+
+pub trait RateLimitExt<Ctx>: Tx<Ctx, Mode = AsyncMode> {
+    fn rate_limited(self, limiter: RateLimiter) -> RateLimit<Self>
+    where
+        Self: Sized,
+    {
+        RateLimit { tx: self, limiter }
+    }
+}
+impl<Ctx, X> RateLimitExt<Ctx> for X where X: Tx<Ctx, Mode = AsyncMode> {}
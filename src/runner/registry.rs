@@ -0,0 +1,195 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use sqlx::pool::PoolOptions;
+use sqlx::{Database, Pool};
+
+use super::{run_tx_with, Backend, TxAccess, TxCtx, TxOptions};
+use crate::combinator::{AsyncMode, Tx};
+
+// The pools of a database-per-tenant deployment, keyed by tenant id. The pool of a tenant is
+// made on its first transaction, from the URL `url` gives for it and the `pool_options`, and
+// opens its connections as they are needed. A tenant with no transaction running nor run for
+// `evict_after` is idle: `evict_idle`, called now and then, e.g. from a task of its own, closes
+// its pool, to be made again on its next transaction.
+pub struct PoolRegistry<DB: Database> {
+    url: Box<dyn Fn(&str) -> String + Send + Sync>,
+    options: PoolOptions<DB>,
+    evict_after: Duration,
+    pools: Mutex<HashMap<String, TenantPool<DB>>>,
+}
+struct TenantPool<DB: Database> {
+    pool: Pool<DB>,
+    in_flight: u32,
+    transactions: u64,
+    last_used: Instant,
+}
+
+// How a tenant uses its pool, as `PoolRegistry::usage` tells it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TenantUsage {
+    // the open connections, and the idle ones among them
+    pub connections: u32,
+    pub idle_connections: usize,
+    // the transactions running, and run since the pool was made
+    pub in_flight: u32,
+    pub transactions: u64,
+    pub idle_for: Duration,
+}
+
+impl<DB: Backend> PoolRegistry<DB> {
+    pub fn new(url: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+        Self {
+            url: Box::new(url),
+            options: PoolOptions::new(),
+            evict_after: Duration::from_secs(600),
+            pools: Mutex::new(HashMap::new()),
+        }
+    }
+    // The options of the pool of every tenant; keep `max_connections` low, as there are many.
+    pub fn pool_options(mut self, options: PoolOptions<DB>) -> Self {
+        self.options = options;
+        self
+    }
+    pub fn evict_after(mut self, idle: Duration) -> Self {
+        self.evict_after = idle;
+        self
+    }
+
+    // The pool of `tenant`, made if it has none. What runs on it other than through the registry
+    // is not counted: an eviction closes the pool under it.
+    pub fn pool(&self, tenant: &str) -> Result<Pool<DB>, sqlx::Error> {
+        let mut pools = self.pools.lock().unwrap();
+        self.entry(&mut pools, tenant)
+            .map(|entry| entry.pool.clone())
+    }
+
+    fn entry<'p>(
+        &self,
+        pools: &'p mut HashMap<String, TenantPool<DB>>,
+        tenant: &str,
+    ) -> Result<&'p mut TenantPool<DB>, sqlx::Error> {
+        if !pools.contains_key(tenant) {
+            let pool = self.options.clone().connect_lazy(&(self.url)(tenant))?;
+            let entry = TenantPool {
+                pool,
+                in_flight: 0,
+                transactions: 0,
+                last_used: Instant::now(),
+            };
+            pools.insert(tenant.to_string(), entry);
+            #[cfg(feature = "metrics")]
+            super::stats::tenant_pools(pools.len());
+        }
+        Ok(pools.get_mut(tenant).unwrap())
+    }
+
+    pub async fn run_for_tenant<A, T, E, X>(&self, tenant: &str, tx: X) -> Result<T, E>
+    where
+        A: TxAccess,
+        X: Tx<TxCtx<DB, A>, Item = T, Err = E, Mode = AsyncMode>,
+        E: From<sqlx::Error>,
+    {
+        self.run_for_tenant_with(tenant, TxOptions::default(), tx)
+            .await
+    }
+
+    pub async fn run_for_tenant_with<A, T, E, X>(
+        &self,
+        tenant: &str,
+        options: TxOptions,
+        tx: X,
+    ) -> Result<T, E>
+    where
+        A: TxAccess,
+        X: Tx<TxCtx<DB, A>, Item = T, Err = E, Mode = AsyncMode>,
+        E: From<sqlx::Error>,
+    {
+        let pool = {
+            let mut pools = self.pools.lock().unwrap();
+            let entry = self.entry(&mut pools, tenant)?;
+            entry.in_flight += 1;
+            entry.transactions += 1;
+            entry.pool.clone()
+        };
+        let _running = Running {
+            registry: self,
+            tenant,
+        };
+        let result = run_tx_with(&pool, options, tx).await;
+        #[cfg(feature = "metrics")]
+        super::stats::tenant_ran(tenant, &pool);
+        result
+    }
+
+    // Closes the pools of the idle tenants, and tells which they were.
+    pub async fn evict_idle(&self) -> Vec<String> {
+        let evicted: Vec<(String, Pool<DB>)> = {
+            let mut pools = self.pools.lock().unwrap();
+            let idle: Vec<String> = pools
+                .iter()
+                .filter(|(_, entry)| {
+                    entry.in_flight == 0 && entry.last_used.elapsed() >= self.evict_after
+                })
+                .map(|(tenant, _)| tenant.clone())
+                .collect();
+            let evicted = idle
+                .into_iter()
+                .map(|tenant| {
+                    let entry = pools.remove(&tenant).unwrap();
+                    (tenant, entry.pool)
+                })
+                .collect();
+            #[cfg(feature = "metrics")]
+            super::stats::tenant_pools(pools.len());
+            evicted
+        };
+        let mut tenants = Vec::with_capacity(evicted.len());
+        for (tenant, pool) in evicted {
+            pool.close().await;
+            #[cfg(feature = "metrics")]
+            super::stats::tenant_evicted(&tenant);
+            tenants.push(tenant);
+        }
+        tenants.sort();
+        tenants
+    }
+
+    // The usage of the pool of every tenant which has one.
+    pub fn usage(&self) -> BTreeMap<String, TenantUsage> {
+        let pools = self.pools.lock().unwrap();
+        pools
+            .iter()
+            .map(|(tenant, entry)| {
+                let usage = TenantUsage {
+                    connections: entry.pool.size(),
+                    idle_connections: entry.pool.num_idle(),
+                    in_flight: entry.in_flight,
+                    transactions: entry.transactions,
+                    idle_for: if entry.in_flight == 0 {
+                        entry.last_used.elapsed()
+                    } else {
+                        Duration::ZERO
+                    },
+                };
+                (tenant.clone(), usage)
+            })
+            .collect()
+    }
+}
+
+// A transaction of a tenant, counted until it is over, cancelled included.
+struct Running<'r, DB: Database> {
+    registry: &'r PoolRegistry<DB>,
+    tenant: &'r str,
+}
+impl<DB: Database> Drop for Running<'_, DB> {
+    fn drop(&mut self) {
+        let mut pools = self.registry.pools.lock().unwrap();
+        if let Some(entry) = pools.get_mut(self.tenant) {
+            entry.in_flight -= 1;
+            entry.last_used = Instant::now();
+        }
+    }
+}
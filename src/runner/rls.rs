@@ -0,0 +1,113 @@
+use std::ops::DerefMut;
+
+use sqlx::PgConnection;
+
+use crate::combinator::{AsyncMode, BoxFuture, Description, Tx};
+
+// The setting `as_user` puts the id of the user in, for row level security policies:
+//
+//     CREATE POLICY own_notes ON notes USING (owner = current_setting('app.user_id', true));
+pub const USER_SETTING: &str = "app.user_id";
+
+// Runs `tx` on behalf of the user `user_id`: `app.user_id`, and the other settings and the role
+// given, are set with `set_config(.., true)`, i.e. `SET LOCAL`, and set back to what they were
+// once `tx` is over, whether it succeeded or not. So they hold for `tx` only, and can outlive
+// neither the transaction, whose end resets them anyway, nor a savepoint rolled back around
+// them. A step failing on the server leaves the transaction aborted, where nothing can be set
+// back: the rollback, of the transaction or of a savepoint around `as_user`, does it.
+//
+// Policies do not apply to the owner of a table without `FORCE ROW LEVEL SECURITY`, nor to
+// superusers: when the pool connects as either, give a `role` of the application to switch to.
+//
+//     let notes = as_user(user_id, list_notes()).role("app_user");
+pub fn as_user<X>(user_id: impl ToString, tx: X) -> AsUser<X> {
+    AsUser {
+        tx,
+        settings: vec![(USER_SETTING.to_string(), user_id.to_string())],
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AsUser<X> {
+    tx: X,
+    settings: Vec<(String, String)>,
+}
+impl<X> AsUser<X> {
+    // Another setting for the policies, e.g. `app.tenant_id`.
+    pub fn setting(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.settings.push((name.into(), value.into()));
+        self
+    }
+    // Switches to `role`, as `SET LOCAL ROLE` does, for `tx`.
+    pub fn role(self, role: impl Into<String>) -> Self {
+        self.setting("role", role)
+    }
+}
+
+// Sets each setting to its value, yielding what they were; `None` for the ones never set.
+async fn set_all(
+    conn: &mut PgConnection,
+    settings: &[(String, String)],
+) -> Result<Vec<Option<String>>, sqlx::Error> {
+    let (names, values): (Vec<&str>, Vec<&str>) = settings
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .unzip();
+    let previous = sqlx::query_scalar(
+        "SELECT current_setting(name, true) FROM unnest($1::text[]) WITH ORDINALITY AS s(name, n) \
+         ORDER BY n",
+    )
+    .bind(&names)
+    .fetch_all(&mut *conn)
+    .await?;
+    sqlx::query("SELECT set_config(name, value, true) FROM unnest($1::text[], $2::text[]) AS s(name, value)")
+        .bind(&names)
+        .bind(&values)
+        .execute(conn)
+        .await?;
+    Ok(previous)
+}
+
+impl<Ctx, X> Tx<Ctx> for AsUser<X>
+where
+    Ctx: DerefMut<Target = PgConnection> + Send,
+    X: Tx<Ctx, Mode = AsyncMode> + Send,
+    X::Item: Send,
+    X::Err: From<sqlx::Error> + Send,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> BoxFuture<'a, Result<Self::Item, Self::Err>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            let previous = set_all(ctx, &self.settings).await?;
+            let result = self.tx.run(ctx).await;
+            let restored: Vec<(String, String)> = self
+                .settings
+                .into_iter()
+                .zip(previous)
+                .map(|((name, _), value)| (name, value.unwrap_or_default()))
+                .collect();
+            match result {
+                Ok(item) => {
+                    set_all(ctx, &restored).await?;
+                    Ok(item)
+                }
+                // set back for the steps recovering from it; this fails when the transaction
+                // is aborted, where the rollback sets them back
+                Err(e) => {
+                    let _ = set_all(ctx, &restored).await;
+                    Err(e)
+                }
+            }
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("as_user", vec![self.tx.describe()])
+    }
+}
@@ -0,0 +1,77 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use sqlx::{Database, Pool};
+
+use super::{begin, run_begun, AccessMode, Backend, DeadlineExceeded, TxAccess, TxCtx, TxOptions};
+use crate::combinator::{AsyncMode, Tx};
+
+// Runs read-only transactions (`ReadTx` chains, or `TxOptions::read_only`) on the replicas,
+// round robin, and everything else on the primary. A replica which cannot begin a transaction
+// (it is down, or its pool is exhausted) is skipped, and when none can, the primary takes the
+// read as well. Give the replica pools a short `acquire_timeout`, or a dead replica holds up
+// every read for the default 30 seconds.
+pub struct RoutingRunner<DB: Database> {
+    primary: Pool<DB>,
+    replicas: Vec<Pool<DB>>,
+    next: AtomicUsize,
+}
+impl<DB: Backend> RoutingRunner<DB> {
+    pub fn new(primary: Pool<DB>) -> Self {
+        Self {
+            primary,
+            replicas: vec![],
+            next: AtomicUsize::new(0),
+        }
+    }
+    pub fn replica(mut self, pool: Pool<DB>) -> Self {
+        self.replicas.push(pool);
+        self
+    }
+    pub fn primary(&self) -> &Pool<DB> {
+        &self.primary
+    }
+
+    pub async fn run<A, T, E, X>(&self, options: TxOptions, tx: X) -> Result<T, E>
+    where
+        A: TxAccess,
+        X: Tx<TxCtx<DB, A>, Item = T, Err = E, Mode = AsyncMode>,
+        E: From<sqlx::Error>,
+    {
+        let read = A::restrict(options).access_mode == Some(AccessMode::ReadOnly);
+        let begin = || async move {
+            if read {
+                self.begin_read(options).await
+            } else {
+                begin(&self.primary, options).await
+            }
+        };
+        run_begun(begin, options, 1, |ctx| ctx, tx).await
+    }
+
+    async fn begin_read<A: TxAccess>(
+        &self,
+        options: TxOptions,
+    ) -> Result<TxCtx<DB, A>, sqlx::Error> {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        for i in 0..self.replicas.len() {
+            let replica = &self.replicas[(start + i) % self.replicas.len()];
+            match begin(replica, options).await {
+                Ok(ctx) => return Ok(ctx),
+                Err(e) if is_unavailable(&e) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        begin(&self.primary, options).await
+    }
+}
+
+fn is_unavailable(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::Io(_) => !DeadlineExceeded::is(e),
+        sqlx::Error::Tls(_)
+        | sqlx::Error::PoolTimedOut
+        | sqlx::Error::PoolClosed
+        | sqlx::Error::WorkerCrashed => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,116 @@
+use std::fmt;
+
+use sqlx::{Database, Pool};
+
+use super::{run_tx_with, Backend, TxCtx, TxOptions};
+use crate::combinator::{with_tx_async, AsyncMode, BoxFuture, Tx};
+
+pub(super) type StepTx<DB, E> =
+    Box<dyn for<'c> FnOnce(&'c mut TxCtx<DB>) -> BoxFuture<'c, Result<(), E>> + Send>;
+
+// A chain with its types erased, so chains of different types fit in one collection.
+pub(super) fn boxed<DB, E, X>(tx: X) -> StepTx<DB, E>
+where
+    DB: Backend,
+    X: Tx<TxCtx<DB>, Err = E, Mode = AsyncMode> + Send + 'static,
+    E: 'static,
+{
+    Box::new(move |ctx| {
+        let run = tx.run(ctx);
+        Box::pin(async move { run.await.map(|_| ()) })
+    })
+}
+
+struct Step<DB: Database, E> {
+    name: String,
+    forward: StepTx<DB, E>,
+    compensation: StepTx<DB, E>,
+}
+
+// A workflow too wide for one ACID transaction: every step commits its forward chain in a
+// transaction of its own. When a step fails, the compensations of the steps completed before it
+// run in reverse order, each in its own transaction too, undoing what was committed.
+pub struct Saga<DB: Database, E> {
+    pool: Pool<DB>,
+    options: TxOptions,
+    steps: Vec<Step<DB, E>>,
+}
+
+// Which step failed, and which compensations failed in turn. Compensations are attempted for
+// every completed step regardless; those listed here are left for an operator to sort out.
+#[derive(Debug)]
+pub struct SagaError<E> {
+    pub step: String,
+    pub error: E,
+    pub compensation_errors: Vec<(String, E)>,
+}
+impl<E> SagaError<E> {
+    pub fn is_compensated(&self) -> bool {
+        self.compensation_errors.is_empty()
+    }
+}
+impl<E: fmt::Display> fmt::Display for SagaError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "saga step {} failed: {}", self.step, self.error)?;
+        for (step, error) in &self.compensation_errors {
+            write!(f, "; compensation of {} failed: {}", step, error)?;
+        }
+        Ok(())
+    }
+}
+impl<E: fmt::Debug + fmt::Display> std::error::Error for SagaError<E> {}
+
+impl<DB, E> Saga<DB, E>
+where
+    DB: Backend,
+    E: From<sqlx::Error> + Send + 'static,
+{
+    pub fn new(pool: Pool<DB>) -> Self {
+        Self {
+            pool,
+            options: TxOptions::default(),
+            steps: vec![],
+        }
+    }
+    // Used for the transaction of every step and compensation.
+    pub fn options(mut self, options: TxOptions) -> Self {
+        self.options = options;
+        self
+    }
+    pub fn step<X1, X2>(mut self, name: impl Into<String>, forward: X1, compensation: X2) -> Self
+    where
+        X1: Tx<TxCtx<DB>, Err = E, Mode = AsyncMode> + Send + 'static,
+        X2: Tx<TxCtx<DB>, Err = E, Mode = AsyncMode> + Send + 'static,
+    {
+        self.steps.push(Step {
+            name: name.into(),
+            forward: boxed(forward),
+            compensation: boxed(compensation),
+        });
+        self
+    }
+
+    pub async fn run(self) -> Result<(), SagaError<E>> {
+        let mut completed = vec![];
+        for step in self.steps {
+            match run_tx_with(&self.pool, self.options, with_tx_async(step.forward)).await {
+                Ok(()) => completed.push((step.name, step.compensation)),
+                Err(error) => {
+                    let mut compensation_errors = vec![];
+                    for (name, compensation) in completed.into_iter().rev() {
+                        let compensation = with_tx_async(compensation);
+                        if let Err(e) = run_tx_with(&self.pool, self.options, compensation).await {
+                            compensation_errors.push((name, e));
+                        }
+                    }
+                    return Err(SagaError {
+                        step: step.name,
+                        error,
+                        compensation_errors,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
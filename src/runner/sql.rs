@@ -0,0 +1,183 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use sqlx::database::HasArguments;
+use sqlx::{Arguments, Encode, Executor, FromRow, IntoArguments, Type};
+
+use super::{explain, Backend, TxCtx, WriteTx};
+use crate::combinator::{with_tx_async, AsyncMode, BoxFuture, Description, Tx};
+
+// Binds a value, cloned each time, so that an explained statement can be run twice.
+type Bind<DB> = Box<dyn for<'q> Fn(&mut <DB as HasArguments<'q>>::Arguments) + Send + Sync>;
+
+// A statement written at run time, for what `query!` cannot express: columns picked
+// dynamically, ad-hoc admin statements. As a step it executes and yields the rows affected;
+// `fetch_*` turn it into a step yielding rows instead.
+pub fn sql<DB: Backend>(sql: impl Into<String>) -> Sql<DB> {
+    Sql {
+        sql: sql.into(),
+        binds: vec![],
+        params: vec![],
+        db: PhantomData,
+    }
+}
+
+pub struct Sql<DB: Backend> {
+    sql: String,
+    binds: Vec<Bind<DB>>,
+    // the values bound, for the plan of a dry run
+    params: Vec<String>,
+    db: PhantomData<fn() -> DB>,
+}
+impl<DB: Backend> fmt::Debug for Sql<DB> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sql")
+            .field("sql", &self.sql)
+            .field("binds", &self.binds.len())
+            .finish()
+    }
+}
+impl<DB: Backend> Sql<DB> {
+    pub(super) fn text(&self) -> &str {
+        &self.sql
+    }
+    pub(super) fn binds(&self) -> usize {
+        self.binds.len()
+    }
+    // Adds the values bound to the statement to `args`, after those already there.
+    pub(super) fn bind_to(&self, args: &mut <DB as HasArguments<'_>>::Arguments) {
+        for bind in &self.binds {
+            bind(args);
+        }
+    }
+}
+impl<DB> Sql<DB>
+where
+    DB: Backend,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+{
+    // Binds the next placeholder, in the syntax of the backend: `$1`, `?`... Text goes in as
+    // `String` on SQLite, whose `&str` encoding borrows for as long as the arguments live.
+    pub fn bind<T>(mut self, value: T) -> Self
+    where
+        T: for<'q> Encode<'q, DB> + Type<DB> + Clone + fmt::Debug + Send + Sync + 'static,
+    {
+        self.params.push(format!("{:?}", value));
+        self.binds
+            .push(Box::new(move |args| args.add(value.clone())));
+        self
+    }
+
+    pub fn fetch_all<R, A>(
+        self,
+    ) -> impl Tx<TxCtx<DB, A>, Item = Vec<R>, Err = sqlx::Error, Mode = AsyncMode>
+    where
+        R: for<'r> FromRow<'r, DB::Row> + Send + Unpin,
+        A: Send,
+    {
+        with_tx_async(move |ctx: &mut TxCtx<DB, A>| {
+            Box::pin(async move {
+                self.explain(ctx).await?;
+                let sql = ctx.commented(&self.sql);
+                let args = self.arguments();
+                sqlx::query_as_with(&sql, args)
+                    .persistent(ctx.persistent)
+                    .fetch_all(&mut **ctx)
+                    .await
+            })
+        })
+    }
+    pub fn fetch_one<R, A>(
+        self,
+    ) -> impl Tx<TxCtx<DB, A>, Item = R, Err = sqlx::Error, Mode = AsyncMode>
+    where
+        R: for<'r> FromRow<'r, DB::Row> + Send + Unpin,
+        A: Send,
+    {
+        with_tx_async(move |ctx: &mut TxCtx<DB, A>| {
+            Box::pin(async move {
+                self.explain(ctx).await?;
+                let sql = ctx.commented(&self.sql);
+                let args = self.arguments();
+                sqlx::query_as_with(&sql, args)
+                    .persistent(ctx.persistent)
+                    .fetch_one(&mut **ctx)
+                    .await
+            })
+        })
+    }
+    pub fn fetch_optional<R, A>(
+        self,
+    ) -> impl Tx<TxCtx<DB, A>, Item = Option<R>, Err = sqlx::Error, Mode = AsyncMode>
+    where
+        R: for<'r> FromRow<'r, DB::Row> + Send + Unpin,
+        A: Send,
+    {
+        with_tx_async(move |ctx: &mut TxCtx<DB, A>| {
+            Box::pin(async move {
+                self.explain(ctx).await?;
+                let sql = ctx.commented(&self.sql);
+                let args = self.arguments();
+                sqlx::query_as_with(&sql, args)
+                    .persistent(ctx.persistent)
+                    .fetch_optional(&mut **ctx)
+                    .await
+            })
+        })
+    }
+
+    fn arguments<'q>(&self) -> <DB as HasArguments<'q>>::Arguments {
+        let mut args = <DB as HasArguments<'q>>::Arguments::default();
+        self.bind_to(&mut args);
+        args
+    }
+
+    // Runs the statement once to explain it first, when in an `explained` step.
+    async fn explain<A>(&self, ctx: &mut TxCtx<DB, A>) -> Result<(), sqlx::Error> {
+        if ctx.explain {
+            // not through the context, to leave the statement count alone
+            let conn = &mut *ctx.transaction;
+            if let Some(plan) = DB::explain(conn, &self.sql, self.arguments()).await? {
+                explain::report(&self.sql, &plan);
+            }
+        }
+        Ok(())
+    }
+}
+impl<DB> Tx<TxCtx<DB, WriteTx>> for Sql<DB>
+where
+    DB: Backend,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+{
+    type Item = u64;
+    type Err = sqlx::Error;
+    type Mode = AsyncMode;
+
+    fn run<'a>(mut self, ctx: &'a mut TxCtx<DB, WriteTx>) -> BoxFuture<'a, Result<u64, sqlx::Error>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            let params = &mut self.params;
+            if ctx.rehearse(&self.sql, || std::mem::take(params)) {
+                return Ok(0);
+            }
+            self.explain(ctx).await?;
+            let sql = ctx.commented(&self.sql);
+            let args = self.arguments();
+            let result = sqlx::query_with(&sql, args)
+                .persistent(ctx.persistent)
+                .execute(&mut **ctx)
+                .await?;
+            let rows = DB::rows_affected(&result);
+            #[cfg(feature = "metrics")]
+            super::stats::rows_affected(ctx.name.as_ref(), rows);
+            Ok(rows)
+        })
+    }
+    fn describe(&self) -> Description {
+        Description::leaf("sql")
+    }
+}
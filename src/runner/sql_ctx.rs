@@ -0,0 +1,103 @@
+use futures_core::future::BoxFuture;
+use futures_core::stream::BoxStream;
+use sqlx::postgres::{PgQueryResult, PgRow, PgStatement, PgTypeInfo};
+use sqlx::{Describe, Either, Execute, Executor, PgConnection, PgPool, Postgres, Transaction};
+
+use super::PgCtx;
+
+// A context for chains which do not care whether they run in a transaction: the same chain
+// can be run against a pool (every statement on its own, e.g. in tests or for read-only
+// reports) or inside a transaction. Steps use `&mut *ctx` wherever sqlx wants an executor.
+#[derive(Debug)]
+pub enum SqlCtx<'e> {
+    Pool(&'e PgPool),
+    Connection(&'e mut PgConnection),
+    // the connection of an open transaction
+    Transaction(&'e mut PgConnection),
+}
+impl<'e> SqlCtx<'e> {
+    pub fn in_transaction(&self) -> bool {
+        matches!(self, SqlCtx::Transaction(_))
+    }
+}
+impl<'e> From<&'e PgPool> for SqlCtx<'e> {
+    fn from(pool: &'e PgPool) -> Self {
+        SqlCtx::Pool(pool)
+    }
+}
+impl<'e> From<&'e mut PgConnection> for SqlCtx<'e> {
+    fn from(conn: &'e mut PgConnection) -> Self {
+        SqlCtx::Connection(conn)
+    }
+}
+impl<'e, 't> From<&'e mut Transaction<'t, Postgres>> for SqlCtx<'e> {
+    fn from(transaction: &'e mut Transaction<'t, Postgres>) -> Self {
+        SqlCtx::Transaction(transaction)
+    }
+}
+impl<'e> From<&'e mut PgCtx> for SqlCtx<'e> {
+    fn from(ctx: &'e mut PgCtx) -> Self {
+        SqlCtx::Transaction(ctx)
+    }
+}
+
+impl<'c, 'e> Executor<'c> for &'c mut SqlCtx<'e> {
+    type Database = Postgres;
+
+    fn fetch_many<'r, 'q: 'r, E>(
+        self,
+        query: E,
+    ) -> BoxStream<'r, Result<Either<PgQueryResult, PgRow>, sqlx::Error>>
+    where
+        'c: 'r,
+        E: 'q + Execute<'q, Postgres>,
+    {
+        match self {
+            SqlCtx::Pool(pool) => pool.fetch_many(query),
+            SqlCtx::Connection(conn) | SqlCtx::Transaction(conn) => conn.fetch_many(query),
+        }
+    }
+
+    fn fetch_optional<'r, 'q: 'r, E>(
+        self,
+        query: E,
+    ) -> BoxFuture<'r, Result<Option<PgRow>, sqlx::Error>>
+    where
+        'c: 'r,
+        E: 'q + Execute<'q, Postgres>,
+    {
+        match self {
+            SqlCtx::Pool(pool) => pool.fetch_optional(query),
+            SqlCtx::Connection(conn) | SqlCtx::Transaction(conn) => conn.fetch_optional(query),
+        }
+    }
+
+    fn prepare_with<'r, 'q: 'r>(
+        self,
+        sql: &'q str,
+        parameters: &'r [PgTypeInfo],
+    ) -> BoxFuture<'r, Result<PgStatement<'q>, sqlx::Error>>
+    where
+        'c: 'r,
+    {
+        match self {
+            SqlCtx::Pool(pool) => pool.prepare_with(sql, parameters),
+            SqlCtx::Connection(conn) | SqlCtx::Transaction(conn) => {
+                conn.prepare_with(sql, parameters)
+            }
+        }
+    }
+
+    fn describe<'r, 'q: 'r>(
+        self,
+        sql: &'q str,
+    ) -> BoxFuture<'r, Result<Describe<Postgres>, sqlx::Error>>
+    where
+        'c: 'r,
+    {
+        match self {
+            SqlCtx::Pool(pool) => pool.describe(sql),
+            SqlCtx::Connection(conn) | SqlCtx::Transaction(conn) => conn.describe(sql),
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::{Executor, Pool, Sqlite, SqliteConnection, SqlitePool, Transaction};
+
+use super::bulk::insert_rows;
+use super::{AccessMode, Backend, ReadTx, TxCtx, TxOptions, Value};
+use crate::combinator::BoxFuture;
+
+pub type SqliteCtx = TxCtx<Sqlite>;
+pub type SqliteReadCtx = TxCtx<Sqlite, ReadTx>;
+
+// SQLite runs every transaction serializably, which satisfies any requested isolation level.
+// It has no read-only transactions nor transaction-scoped timeouts, so those are rejected.
+// Nesting needs no support from here: `savepoint` works on SQLite as it does elsewhere.
+impl Backend for Sqlite {
+    fn begin(
+        pool: &Pool<Self>,
+        options: TxOptions,
+    ) -> BoxFuture<'_, Result<Transaction<'static, Self>, sqlx::Error>> {
+        Box::pin(async move {
+            if options.access_mode == Some(AccessMode::ReadOnly) {
+                return Err(sqlx::Error::Configuration(
+                    "SQLite does not support read-only transactions".into(),
+                ));
+            }
+            if options.statement_timeout.is_some() || options.lock_timeout.is_some() {
+                return Err(sqlx::Error::Configuration(
+                    "SQLite does not support transaction-scoped statement or lock timeouts".into(),
+                ));
+            }
+
+            pool.begin().await
+        })
+    }
+
+    fn execute<'c>(
+        conn: &'c mut SqliteConnection,
+        sql: &'c str,
+    ) -> BoxFuture<'c, Result<(), sqlx::Error>> {
+        Box::pin(async move {
+            conn.execute(sql).await?;
+            Ok(())
+        })
+    }
+
+    fn bulk_insert<'c>(
+        conn: &'c mut SqliteConnection,
+        table: &'c str,
+        columns: &'c [String],
+        rows: Vec<Vec<Value>>,
+    ) -> BoxFuture<'c, Result<u64, sqlx::Error>> {
+        Box::pin(insert_rows::<Self>(conn, table, columns, rows))
+    }
+
+    fn rows_affected(result: &sqlx::sqlite::SqliteQueryResult) -> u64 {
+        result.rows_affected()
+    }
+}
+
+// A pool over a private in-memory database. Every connection to `sqlite::memory:` opens a
+// database of its own, so the pool is kept to a single connection which is never closed.
+pub async fn memory_pool() -> Result<SqlitePool, sqlx::Error> {
+    SqlitePoolOptions::new()
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect("sqlite::memory:")
+        .await
+}
@@ -0,0 +1,56 @@
+use sqlx::Connection;
+
+use super::{Backend, TxAccess, TxCtx};
+use crate::combinator::{AsyncMode, BoxFuture, Description, Tx};
+
+impl<DB: Backend, A> TxCtx<DB, A> {
+    // How many prepared statements the connection keeps in its statement cache.
+    pub fn cached_statements(&self) -> usize {
+        self.transaction.cached_statements_size()
+    }
+}
+
+// Keeps the statements of the SQL helpers in a step prepared in the statement cache of the
+// connection, even in a run with `TxOptions::persistent_statements(false)`: mark the steps of
+// the chains running all the time as hot, run the others without persistent statements, and
+// the cache holds the hot statements instead of being thrashed by the rest.
+pub trait StatementCacheExt<DB: Backend, A>: Tx<TxCtx<DB, A>> {
+    fn hot(self) -> Hot<Self>
+    where
+        Self: Sized,
+    {
+        Hot { tx: self }
+    }
+}
+impl<DB: Backend, A, X: Tx<TxCtx<DB, A>>> StatementCacheExt<DB, A> for X {}
+
+#[derive(Debug, Clone)]
+pub struct Hot<X> {
+    tx: X,
+}
+impl<DB, A, X> Tx<TxCtx<DB, A>> for Hot<X>
+where
+    DB: Backend,
+    A: TxAccess,
+    X: Tx<TxCtx<DB, A>, Mode = AsyncMode> + Send,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut TxCtx<DB, A>) -> BoxFuture<'a, Result<Self::Item, Self::Err>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            let outer = std::mem::replace(&mut ctx.persistent, true);
+            let result = self.tx.run(ctx).await;
+            ctx.persistent = outer;
+            result
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("hot", vec![self.tx.describe()])
+    }
+}
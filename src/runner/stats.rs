@@ -0,0 +1,85 @@
+use std::borrow::Cow;
+use std::time::Instant;
+
+use sqlx::{Database, Pool};
+
+// What the `metrics` feature reports through the `metrics` facade, to whichever recorder the
+// application installed, labelled with the name the chain describes itself with:
+//
+//     tx_transactions_started_total      counter, every attempt
+//     tx_transactions_committed_total    counter
+//     tx_transactions_rolled_back_total  counter
+//     tx_transaction_retries_total       counter, the attempts after the first
+//     tx_transaction_duration_seconds    histogram, also labelled `outcome`
+//     tx_rows_affected                   histogram, per statement of `sql` and `bulk_insert`
+//
+// and, unlabelled, of the pools the transactions begin on:
+//
+//     tx_connection_wait_seconds         histogram, from asking for a connection to `BEGIN` done
+//     tx_pool_timeouts_total             counter, no connection came free in time
+//     tx_pool_connections                gauge, open connections, as of the last `BEGIN`
+//     tx_pool_idle_connections           gauge, idle ones among them
+//
+// and of the pools of a `PoolRegistry`, labelled with the tenant:
+//
+//     tx_tenant_transactions_total       counter
+//     tx_tenant_pool_connections         gauge, open connections, as of the last transaction
+//     tx_tenant_pool_idle_connections    gauge, idle ones among them
+//     tx_tenant_pool_evictions_total     counter
+//     tx_tenant_pools                    gauge, unlabelled, the tenants with a pool
+
+pub(crate) fn started(name: Cow<'static, str>, attempt: u32) {
+    metrics::counter!("tx_transactions_started_total", "name" => name.clone()).increment(1);
+    if attempt > 1 {
+        metrics::counter!("tx_transaction_retries_total", "name" => name).increment(1);
+    }
+}
+
+pub(crate) fn ended(name: Cow<'static, str>, started: Instant, committed: bool) {
+    let (counter, outcome) = if committed {
+        ("tx_transactions_committed_total", "committed")
+    } else {
+        ("tx_transactions_rolled_back_total", "rolled back")
+    };
+    metrics::counter!(counter, "name" => name.clone()).increment(1);
+    metrics::histogram!(
+        "tx_transaction_duration_seconds",
+        "name" => name,
+        "outcome" => outcome
+    )
+    .record(started.elapsed().as_secs_f64());
+}
+
+pub(crate) fn rows_affected(name: Option<&Cow<'static, str>>, rows: u64) {
+    let name = name.cloned().unwrap_or(Cow::Borrowed(""));
+    metrics::histogram!("tx_rows_affected", "name" => name).record(rows as f64);
+}
+
+pub(crate) fn begun<DB: Database>(pool: &Pool<DB>, started: Instant, error: Option<&sqlx::Error>) {
+    metrics::histogram!("tx_connection_wait_seconds").record(started.elapsed().as_secs_f64());
+    if let Some(sqlx::Error::PoolTimedOut) = error {
+        metrics::counter!("tx_pool_timeouts_total").increment(1);
+    }
+    metrics::gauge!("tx_pool_connections").set(pool.size() as f64);
+    metrics::gauge!("tx_pool_idle_connections").set(pool.num_idle() as f64);
+}
+
+pub(crate) fn tenant_ran<DB: Database>(tenant: &str, pool: &Pool<DB>) {
+    let tenant = tenant.to_string();
+    metrics::counter!("tx_tenant_transactions_total", "tenant" => tenant.clone()).increment(1);
+    metrics::gauge!("tx_tenant_pool_connections", "tenant" => tenant.clone())
+        .set(pool.size() as f64);
+    metrics::gauge!("tx_tenant_pool_idle_connections", "tenant" => tenant)
+        .set(pool.num_idle() as f64);
+}
+
+pub(crate) fn tenant_evicted(tenant: &str) {
+    let tenant = tenant.to_string();
+    metrics::counter!("tx_tenant_pool_evictions_total", "tenant" => tenant.clone()).increment(1);
+    metrics::gauge!("tx_tenant_pool_connections", "tenant" => tenant.clone()).set(0.0);
+    metrics::gauge!("tx_tenant_pool_idle_connections", "tenant" => tenant).set(0.0);
+}
+
+pub(crate) fn tenant_pools(pools: usize) {
+    metrics::gauge!("tx_tenant_pools").set(pools as f64);
+}
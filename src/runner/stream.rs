@@ -0,0 +1,97 @@
+use std::future::poll_fn;
+use std::pin::Pin;
+
+use futures_core::Stream;
+use sqlx::database::HasArguments;
+use sqlx::{Database, Executor, FromRow, IntoArguments};
+
+#[cfg(feature = "postgres")]
+use sqlx::postgres::PgRow;
+
+#[cfg(feature = "postgres")]
+use super::PgCtx;
+use super::TxCtx;
+#[cfg(feature = "postgres")]
+use crate::combinator::BoxFuture;
+use crate::combinator::{with_tx_async, AsyncMode, Tx};
+
+// Folds the rows of `sql` into `init` with `f` as they arrive, so a chain can go through more
+// rows than fit in memory. The connection is busy until the last row, so `f` cannot query;
+// on Postgres, `for_each_batch` can.
+pub fn fold_rows<DB, A, R, T, F>(
+    sql: impl Into<String>,
+    init: T,
+    mut f: F,
+) -> impl Tx<TxCtx<DB, A>, Item = T, Err = sqlx::Error, Mode = AsyncMode>
+where
+    DB: Database,
+    A: Send,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    R: for<'r> FromRow<'r, DB::Row> + Send + Unpin,
+    T: Send + 'static,
+    F: FnMut(T, R) -> Result<T, sqlx::Error> + Send + 'static,
+{
+    let sql = sql.into();
+    with_tx_async(move |ctx: &mut TxCtx<DB, A>| {
+        Box::pin(async move {
+            let sql = ctx.commented(&sql).into_owned();
+            let mut rows = sqlx::query_as::<DB, R>(&sql).fetch(&mut **ctx);
+            let mut acc = init;
+            while let Some(row) = poll_fn(|cx| Pin::new(&mut rows).poll_next(cx)).await {
+                acc = f(acc, row?)?;
+            }
+            Ok(acc)
+        })
+    })
+}
+
+// Walks the rows of `sql` through a server-side cursor, fetching `batch_size` rows per round
+// trip and handing each batch to `f`. Between fetches the connection is free, so `f` may run
+// statements of its own in the transaction. Returns how many rows there were.
+#[cfg(feature = "postgres")]
+pub fn for_each_batch<R, E, F>(
+    sql: impl Into<String>,
+    batch_size: usize,
+    mut f: F,
+) -> impl Tx<PgCtx, Item = u64, Err = E, Mode = AsyncMode>
+where
+    R: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    E: From<sqlx::Error> + Send,
+    F: for<'c> FnMut(&'c mut PgCtx, Vec<R>) -> BoxFuture<'c, Result<(), E>> + Send + 'static,
+{
+    let sql = sql.into();
+    with_tx_async(move |ctx: &mut PgCtx| {
+        Box::pin(async move {
+            let cursor = format!("tx_rs_cursor_{:016x}", rand::random::<u64>());
+            let declare = format!("DECLARE {} NO SCROLL CURSOR FOR {}", cursor, sql);
+            sqlx::query(&ctx.commented(&declare))
+                .execute(&mut **ctx)
+                .await?;
+
+            let fetch = format!("FETCH FORWARD {} FROM {}", batch_size.max(1), cursor);
+            let mut seen = 0;
+            let result = loop {
+                let rows: Vec<R> = match sqlx::query_as(&fetch).fetch_all(&mut **ctx).await {
+                    Ok(rows) => rows,
+                    Err(e) => break Err(e.into()),
+                };
+                if rows.is_empty() {
+                    break Ok(seen);
+                }
+                seen += rows.len() as u64;
+                if let Err(e) = f(ctx, rows).await {
+                    break Err(e);
+                }
+            };
+
+            // after a failure this fails too, when the transaction is aborted
+            let closed = sqlx::query(&format!("CLOSE {}", cursor))
+                .execute(&mut **ctx)
+                .await;
+            let seen = result?;
+            closed?;
+            Ok(seen)
+        })
+    })
+}
@@ -0,0 +1,261 @@
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+use sqlx::{PgConnection, PgPool, Postgres};
+
+use super::{
+    run_in_savepoint, run_tx_as, OwnsTxCtx, Savepoint, TxAccess, TxCtx, TxOptions, WriteTx,
+};
+use crate::combinator::{AsyncMode, BoxFuture, Description, Tx};
+use crate::context::Deeper;
+
+// The setting `run_tenant_tx` puts the id of the tenant in, for queries and row level security
+// policies to read with `current_setting('app.tenant_id')`.
+pub const TENANT_SETTING: &str = "app.tenant_id";
+
+// The tenant a transaction runs for, resolved per request, e.g. from the host name or a claim
+// of the token. A tenant of its own schema has the `search_path` of its transactions set to
+// that schema, then the `shared` ones: unqualified names resolve to its tables. A tenant of a
+// shared schema only has its id set, for the queries and policies to filter the rows on.
+// `T` is the brand of the tenant, `Unbranded` until `branded` gives it one.
+pub struct Tenant<T = Unbranded> {
+    id: String,
+    search_path: Option<Vec<String>>,
+    brand: PhantomData<fn() -> T>,
+}
+// The brand of the tenants not given one.
+#[derive(Debug)]
+pub enum Unbranded {}
+impl Tenant {
+    // A tenant whose tables are in the schema `schema`, also its id.
+    pub fn schema(schema: impl Into<String>) -> Self {
+        let schema = schema.into();
+        Self {
+            search_path: Some(vec![schema.clone()]),
+            id: schema,
+            brand: PhantomData,
+        }
+    }
+    pub fn id(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            search_path: None,
+            brand: PhantomData,
+        }
+    }
+}
+impl<T> Tenant<T> {
+    // Searched after the schema of the tenant, e.g. `public` for tables shared by all of them.
+    pub fn shared(mut self, schema: impl Into<String>) -> Self {
+        if let Some(search_path) = &mut self.search_path {
+            search_path.push(schema.into());
+        }
+        self
+    }
+    // The tenant with the brand `B`, e.g. a marker type of each tenant the application knows of,
+    // given where it is resolved. Its chains are over `TenantCtx<B>`, so steps written for
+    // another brand do not fit in them.
+    pub fn branded<B>(self) -> Tenant<B> {
+        Tenant {
+            id: self.id,
+            search_path: self.search_path,
+            brand: PhantomData,
+        }
+    }
+    pub fn tenant_id(&self) -> &str {
+        &self.id
+    }
+
+    fn search_path(&self) -> Option<String> {
+        let schemas = self.search_path.as_ref()?.iter();
+        let quoted: Vec<String> = schemas
+            .map(|schema| format!("\"{}\"", schema.replace('"', "\"\"")))
+            .collect();
+        Some(quoted.join(", "))
+    }
+}
+// By hand, for brands to need none of these.
+impl<T> Clone for Tenant<T> {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id.clone(),
+            search_path: self.search_path.clone(),
+            brand: PhantomData,
+        }
+    }
+}
+impl<T> PartialEq for Tenant<T> {
+    fn eq(&self, other: &Self) -> bool {
+        (&self.id, &self.search_path) == (&other.id, &other.search_path)
+    }
+}
+impl<T> Eq for Tenant<T> {}
+impl<T> std::fmt::Debug for Tenant<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Tenant")
+            .field("id", &self.id)
+            .field("search_path", &self.search_path)
+            .finish()
+    }
+}
+
+// The context of chains run by `run_tenant_tx`: a transaction set up for one tenant, which it
+// has no way to switch to another. Being a type apart from `TxCtx`, it keeps steps written for
+// a bare `TxCtx`, e.g. admin steps working across tenants, out of chains for tenants, and
+// those chains from being run without a tenant. The brand `T` of the tenant keeps the steps of
+// one brand out of the chains of another; steps generic over it fit in any. Nothing checks
+// what the SQL of a step does, though. It derefs to the connection, as `TxCtx` does.
+///
+/// ```compile_fail
+/// use tx::prelude::*;
+/// use tx::runner::TenantCtx;
+///
+/// enum Acme {}
+/// enum Globex {}
+///
+/// fn acme() -> impl Tx<TenantCtx<Acme>, Item = (), Err = sqlx::Error, Mode = AsyncMode> {
+///     with_tx_async(|_: &mut TenantCtx<Acme>| Box::pin(async { Ok(()) }))
+/// }
+/// fn globex() -> impl Tx<TenantCtx<Globex>, Item = (), Err = sqlx::Error, Mode = AsyncMode> {
+///     with_tx_async(|_: &mut TenantCtx<Globex>| Box::pin(async { Ok(()) }))
+/// }
+///
+/// fn both() -> impl Tx<TenantCtx<Acme>, Item = (), Err = sqlx::Error> {
+///     acme().and_then(|_| globex())
+/// }
+/// ```
+pub struct TenantCtx<T = Unbranded, A = WriteTx> {
+    ctx: TxCtx<Postgres, A>,
+    tenant: Tenant<T>,
+}
+impl<T, A> TenantCtx<T, A> {
+    pub fn tenant(&self) -> &Tenant<T> {
+        &self.tenant
+    }
+    pub fn depth(&self) -> usize {
+        self.ctx.depth()
+    }
+    // The hooks of `TxCtx`, registered on the transaction of the tenant.
+    pub fn after_commit(&mut self, f: impl FnOnce() + Send + 'static) {
+        self.ctx.after_commit(f);
+    }
+    pub fn after_rollback(&mut self, f: impl FnOnce() + Send + 'static) {
+        self.ctx.after_rollback(f);
+    }
+    pub fn before_commit<F>(&mut self, f: F)
+    where
+        F: for<'c> FnOnce(&'c mut PgConnection) -> BoxFuture<'c, Result<(), sqlx::Error>>
+            + Send
+            + 'static,
+    {
+        self.ctx.before_commit(f);
+    }
+}
+impl<T, A> Deref for TenantCtx<T, A> {
+    type Target = PgConnection;
+
+    fn deref(&self) -> &PgConnection {
+        &self.ctx
+    }
+}
+impl<T, A> DerefMut for TenantCtx<T, A> {
+    fn deref_mut(&mut self) -> &mut PgConnection {
+        &mut self.ctx
+    }
+}
+
+impl<T, A: TxAccess> OwnsTxCtx for TenantCtx<T, A> {
+    type DB = Postgres;
+    type Access = A;
+
+    fn tx_ctx(&mut self) -> &mut TxCtx<Postgres, A> {
+        &mut self.ctx
+    }
+    fn into_tx_ctx(self) -> TxCtx<Postgres, A> {
+        self.ctx
+    }
+}
+
+// `savepoint` in the transaction of the tenant.
+impl<T, A, X> Tx<TenantCtx<T, A>> for Savepoint<X>
+where
+    A: TxAccess,
+    X: Tx<TenantCtx<T, A>, Mode = AsyncMode> + Send,
+    X::Item: Send,
+    X::Err: From<sqlx::Error> + Send,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut TenantCtx<T, A>) -> BoxFuture<'a, Result<X::Item, X::Err>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            let mut ctx = Deeper::new(ctx, |ctx| &mut ctx.ctx.depth);
+            let name = format!("tx_rs_savepoint_{}", ctx.depth());
+            run_in_savepoint(&mut *ctx, &name, self.tx).await
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("savepoint", vec![self.tx.describe()])
+    }
+}
+
+// `run_tx_with` for chains over a `TenantCtx`: the `search_path` and the tenant id are set for
+// the transaction only, with `SET LOCAL` semantics, so the connection goes back to the pool
+// as it came whatever the chain ends with.
+pub async fn run_tenant_tx<B, A, T, E, X>(
+    pool: &PgPool,
+    options: TxOptions,
+    tenant: Tenant<B>,
+    tx: X,
+) -> Result<T, E>
+where
+    A: TxAccess,
+    X: Tx<TenantCtx<B, A>, Item = T, Err = E, Mode = AsyncMode> + Send,
+    E: From<sqlx::Error>,
+{
+    let wrap = |ctx| TenantCtx { ctx, tenant };
+    run_tx_as(pool, options, wrap, SetTenant { tx }).await
+}
+
+// Sets the tenant up, then runs `tx`; it describes itself as `tx` does.
+struct SetTenant<X> {
+    tx: X,
+}
+impl<B, A, X> Tx<TenantCtx<B, A>> for SetTenant<X>
+where
+    A: TxAccess,
+    X: Tx<TenantCtx<B, A>, Mode = AsyncMode> + Send,
+    X::Err: From<sqlx::Error>,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut TenantCtx<B, A>) -> BoxFuture<'a, Result<X::Item, X::Err>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            // a tenant of a shared schema keeps the search path of the connection; not a
+            // statement of the chain, against its budget
+            let sql = "SELECT set_config($1, $2, true), \
+                       set_config('search_path', coalesce($3, current_setting('search_path')), true)";
+            sqlx::query(sql)
+                .bind(TENANT_SETTING)
+                .bind(&ctx.tenant.id)
+                .bind(ctx.tenant.search_path())
+                .execute(&mut *ctx.ctx.transaction)
+                .await?;
+            self.tx.run(ctx).await
+        })
+    }
+
+    fn describe(&self) -> Description {
+        self.tx.describe()
+    }
+}
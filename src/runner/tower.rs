@@ -0,0 +1,113 @@
+use std::future::poll_fn;
+use std::task::{Context, Poll};
+
+use ::tower::{Layer, Service};
+use sqlx::{Database, Pool};
+
+use super::{
+    rt, run_attempt, Backend, DeadlockPolicy, Retries, RetryPolicy, SqlState, TxCtx, TxOptions,
+};
+use crate::combinator::{AsyncMode, BoxFuture, Tx};
+
+// Turns a service which builds a chain from its request into one which runs that chain in a
+// transaction of its own and answers with the chain's item. On a serialization failure or a
+// deadlock the inner service is called again with a clone of the request for a fresh chain,
+// so anything it does outside the database must be safe to repeat. Retries are off by default.
+pub struct TxLayer<DB: Database> {
+    pool: Pool<DB>,
+    options: TxOptions,
+    policy: RetryPolicy,
+}
+impl<DB: Database> TxLayer<DB> {
+    pub fn new(pool: Pool<DB>) -> Self {
+        Self {
+            pool,
+            options: TxOptions::default(),
+            policy: RetryPolicy::new()
+                .max_attempts(1)
+                .on_deadlock(DeadlockPolicy::new().max_attempts(1)),
+        }
+    }
+    pub fn options(mut self, options: TxOptions) -> Self {
+        self.options = options;
+        self
+    }
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
+impl<DB: Database> Clone for TxLayer<DB> {
+    fn clone(&self) -> Self {
+        Self {
+            pool: self.pool.clone(),
+            options: self.options,
+            policy: self.policy.clone(),
+        }
+    }
+}
+impl<DB: Database, S> Layer<S> for TxLayer<DB> {
+    type Service = TxService<DB, S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TxService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+pub struct TxService<DB: Database, S> {
+    inner: S,
+    layer: TxLayer<DB>,
+}
+impl<DB: Database, S: Clone> Clone for TxService<DB, S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            layer: self.layer.clone(),
+        }
+    }
+}
+impl<DB, S, Req, X> Service<Req> for TxService<DB, S>
+where
+    DB: Backend,
+    S: Service<Req, Response = X> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: From<sqlx::Error> + SqlState + Send,
+    Req: Clone + Send + 'static,
+    X: Tx<TxCtx<DB>, Err = S::Error, Mode = AsyncMode> + Send,
+    X::Item: Send,
+{
+    type Response = X::Item;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<X::Item, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Req) -> Self::Future {
+        // the service which was polled ready goes into the future, a fresh clone stays behind
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let layer = self.layer.clone();
+
+        Box::pin(async move {
+            // the chains run in write transactions, never replayed on a new connection
+            let mut retries = Retries::new(&layer.policy, layer.options, false);
+            let mut tx = inner.call(request.clone()).await?;
+            loop {
+                let attempt = retries.attempt();
+                let result = run_attempt(&layer.pool, layer.options, &layer.policy, attempt, tx);
+                let result = result.await;
+                match retries.next_delay(&result) {
+                    Some(delay) => rt::sleep(delay).await,
+                    None => return result.map_err(|e| retries.give_up(e)),
+                }
+                poll_fn(|cx| inner.poll_ready(cx)).await?;
+                tx = inner.call(request.clone()).await?;
+            }
+        })
+    }
+}
@@ -0,0 +1,293 @@
+use std::collections::HashSet;
+use std::ops::{Deref, DerefMut};
+
+use sqlx::query_builder::Separated;
+use sqlx::{PgConnection, PgPool, Postgres, QueryBuilder};
+
+use super::{run_tx_as, OwnsTxCtx, PgCtx, TxOptions, Value, WriteTx};
+use crate::combinator::{AsyncMode, BoxFuture, Description, Tx};
+
+// Postgres binds at most this many parameters in one statement.
+const MAX_BINDS: usize = u16::MAX as usize;
+
+fn push_value(values: &mut Separated<'_, 'static, Postgres, &str>, value: Value) {
+    match value {
+        Value::Bool(v) => values.push_bind(v),
+        Value::Int(v) => values.push_bind(v),
+        Value::Float(v) => values.push_bind(v),
+        Value::Text(v) => values.push_bind(v),
+        Value::Json(v) => values.push_bind(v),
+    };
+}
+
+// Column names and values of a row; tables and columns are spliced into the SQL as they are.
+pub type Row = Vec<(&'static str, Value)>;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Change {
+    Insert {
+        table: &'static str,
+        row: Row,
+    },
+    Update {
+        table: &'static str,
+        key: (&'static str, Value),
+        set: Row,
+    },
+    Delete {
+        table: &'static str,
+        key: (&'static str, Value),
+    },
+}
+impl Change {
+    // Changes with the same shape go into one statement.
+    fn shape(&self) -> (u8, &'static str, Vec<&'static str>) {
+        let columns = |row: &Row| row.iter().map(|(column, _)| *column).collect();
+        match self {
+            Change::Insert { table, row } => (0, table, columns(row)),
+            Change::Update { table, key, set } => {
+                let mut shape = vec![key.0];
+                shape.extend(set.iter().map(|(column, _)| *column));
+                (1, table, shape)
+            }
+            Change::Delete { table, key } => (2, table, vec![key.0]),
+        }
+    }
+    // The key an update is for: `UPDATE .. FROM` applies only one of the rows joined to the
+    // same key, which one being undefined, so a batch updates each key once.
+    fn updated_key(&self) -> Option<String> {
+        match self {
+            Change::Update { key, .. } => Some(key.1.to_param()),
+            _ => None,
+        }
+    }
+}
+
+// The context of chains run by `run_unit_of_work`. Changes registered with `insert`, `update`
+// and `delete` are only kept in memory until `flush`, which sends every run of changes of the
+// same shape as a single statement. It derefs to the connection for reads, which do not see
+// changes not flushed yet.
+pub struct UowCtx {
+    ctx: PgCtx,
+    changes: Vec<Change>,
+}
+impl UowCtx {
+    pub fn insert(&mut self, table: &'static str, row: Row) {
+        self.changes.push(Change::Insert { table, row });
+    }
+    // An update setting no column changes nothing, and is left out.
+    pub fn update(&mut self, table: &'static str, key: (&'static str, impl Into<Value>), set: Row) {
+        if set.is_empty() {
+            return;
+        }
+        let key = (key.0, key.1.into());
+        self.changes.push(Change::Update { table, key, set });
+    }
+    pub fn delete(&mut self, table: &'static str, key: (&'static str, impl Into<Value>)) {
+        let key = (key.0, key.1.into());
+        self.changes.push(Change::Delete { table, key });
+    }
+    pub fn pending(&self) -> usize {
+        self.changes.len()
+    }
+
+    // Writes the registered changes in the order they were registered, returning how many
+    // statements that took.
+    pub async fn flush(&mut self) -> Result<usize, sqlx::Error> {
+        let mut changes = std::mem::take(&mut self.changes).into_iter().peekable();
+        let mut statements = 0;
+        while let Some(first) = changes.next() {
+            let shape = first.shape();
+            let max_rows = MAX_BINDS / shape.2.len().max(1);
+            let mut keys: HashSet<String> = first.updated_key().into_iter().collect();
+            let mut batch = vec![first];
+            while batch.len() < max_rows {
+                let next = changes.next_if(|change| {
+                    change.shape() == shape
+                        && change.updated_key().is_none_or(|key| !keys.contains(&key))
+                });
+                match next {
+                    Some(change) => {
+                        keys.extend(change.updated_key());
+                        batch.push(change);
+                    }
+                    None => break,
+                }
+            }
+            statement(batch).build().execute(&mut *self.ctx).await?;
+            statements += 1;
+        }
+        Ok(statements)
+    }
+}
+impl Deref for UowCtx {
+    type Target = PgConnection;
+
+    fn deref(&self) -> &PgConnection {
+        &self.ctx
+    }
+}
+impl DerefMut for UowCtx {
+    fn deref_mut(&mut self) -> &mut PgConnection {
+        &mut self.ctx
+    }
+}
+
+// One statement for a batch of changes of the same shape.
+fn statement(batch: Vec<Change>) -> QueryBuilder<'static, Postgres> {
+    let mut builder = QueryBuilder::new("");
+    let (kind, table, columns) = batch[0].shape();
+    match kind {
+        0 => {
+            builder.push(format!("INSERT INTO {} ({}) ", table, columns.join(", ")));
+            builder.push_values(batch, |mut b, change| {
+                if let Change::Insert { row, .. } = change {
+                    for (_, value) in row {
+                        push_value(&mut b, value);
+                    }
+                }
+            });
+        }
+        1 => {
+            let (key, set) = columns.split_first().unwrap();
+            let assignments: Vec<String> = set
+                .iter()
+                .map(|column| format!("{0} = tx_rs_v.{0}", column))
+                .collect();
+            builder.push(format!(
+                "UPDATE {} SET {} FROM (",
+                table,
+                assignments.join(", ")
+            ));
+            builder.push_values(batch, |mut b, change| {
+                if let Change::Update { key, set, .. } = change {
+                    push_value(&mut b, key.1);
+                    for (_, value) in set {
+                        push_value(&mut b, value);
+                    }
+                }
+            });
+            builder.push(format!(
+                ") AS tx_rs_v ({}) WHERE {}.{2} = tx_rs_v.{2}",
+                columns.join(", "),
+                table,
+                key
+            ));
+        }
+        _ => {
+            builder.push(format!("DELETE FROM {} WHERE {} IN (", table, columns[0]));
+            let mut keys = builder.separated(", ");
+            for change in batch {
+                if let Change::Delete { key, .. } = change {
+                    push_value(&mut keys, key.1);
+                }
+            }
+            builder.push(")");
+        }
+    }
+    builder
+}
+
+impl OwnsTxCtx for UowCtx {
+    type DB = Postgres;
+    type Access = WriteTx;
+
+    fn tx_ctx(&mut self) -> &mut PgCtx {
+        &mut self.ctx
+    }
+    fn into_tx_ctx(self) -> PgCtx {
+        self.ctx
+    }
+}
+
+// `run_tx_with` for chains over a `UowCtx`: what is still pending once `tx` succeeded is flushed
+// before the commit.
+pub async fn run_unit_of_work<T, E, X>(pool: &PgPool, options: TxOptions, tx: X) -> Result<T, E>
+where
+    X: Tx<UowCtx, Item = T, Err = E, Mode = AsyncMode> + Send,
+    X::Item: Send,
+    E: From<sqlx::Error>,
+{
+    let wrap = |ctx| UowCtx {
+        ctx,
+        changes: vec![],
+    };
+    run_tx_as(pool, options, wrap, FlushAfter { tx }).await
+}
+
+// Runs `tx`, then flushes; it describes itself as `tx` does.
+struct FlushAfter<X> {
+    tx: X,
+}
+impl<X> Tx<UowCtx> for FlushAfter<X>
+where
+    X: Tx<UowCtx, Mode = AsyncMode> + Send,
+    X::Item: Send,
+    X::Err: From<sqlx::Error>,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut UowCtx) -> BoxFuture<'a, Result<X::Item, X::Err>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            let t = self.tx.run(ctx).await?;
+            ctx.flush().await?;
+            Ok(t)
+        })
+    }
+
+    fn describe(&self) -> Description {
+        self.tx.describe()
+    }
+}
+
+// A step flushing the changes registered so far, e.g. before reading them back.
+pub fn flush() -> Flush {
+    Flush
+}
+pub struct Flush;
+impl Tx<UowCtx> for Flush {
+    type Item = usize;
+    type Err = sqlx::Error;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut UowCtx) -> BoxFuture<'a, Result<usize, sqlx::Error>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ctx.flush())
+    }
+}
+
+// Runs a chain written for a plain transaction inside a unit of work. It does not see the
+// changes still pending, unless they are flushed first.
+pub fn lift<X>(tx: X) -> Lift<X> {
+    Lift { tx }
+}
+#[derive(Debug, Clone, Copy)]
+pub struct Lift<X> {
+    tx: X,
+}
+impl<X> Tx<UowCtx> for Lift<X>
+where
+    X: Tx<PgCtx, Mode = AsyncMode>,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut UowCtx) -> BoxFuture<'a, Result<X::Item, X::Err>>
+    where
+        Self: 'a,
+    {
+        self.tx.run(&mut ctx.ctx)
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("lift", vec![self.tx.describe()])
+    }
+}
@@ -0,0 +1,56 @@
+// A value of a column, for the helpers which write rows they know nothing about.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Json(serde_json::Value),
+}
+impl Value {
+    // As the plan of a dry run shows it.
+    pub(crate) fn to_param(&self) -> String {
+        match self {
+            Value::Bool(v) => format!("{:?}", v),
+            Value::Int(v) => format!("{:?}", v),
+            Value::Float(v) => format!("{:?}", v),
+            Value::Text(v) => format!("{:?}", v),
+            Value::Json(v) => v.to_string(),
+        }
+    }
+}
+impl From<bool> for Value {
+    fn from(v: bool) -> Self {
+        Value::Bool(v)
+    }
+}
+impl From<i32> for Value {
+    fn from(v: i32) -> Self {
+        Value::Int(v.into())
+    }
+}
+impl From<i64> for Value {
+    fn from(v: i64) -> Self {
+        Value::Int(v)
+    }
+}
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Value::Float(v)
+    }
+}
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Value::Text(v.to_string())
+    }
+}
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Value::Text(v)
+    }
+}
+impl From<serde_json::Value> for Value {
+    fn from(v: serde_json::Value) -> Self {
+        Value::Json(v)
+    }
+}
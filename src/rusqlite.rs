@@ -0,0 +1,198 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use ::rusqlite::types::Value;
+use ::rusqlite::{params_from_iter, Connection, OptionalExtension, Row, TransactionBehavior};
+
+use crate::combinator::{with_tx, Description, SyncMode, Tx};
+use crate::runner::Savepoint;
+
+// Chains over rusqlite, for embedded and desktop apps: the context is the `Connection` itself,
+// on which the transaction is open, and steps are synchronous, `with_tx` closures such as
+//
+//     with_tx(|conn: &mut Connection| conn.execute("DELETE FROM todos WHERE done", []))
+//
+// so the domain transactions composed for sqlx carry over, their steps written once per
+// backend as a `Repository` does.
+
+// Begins a deferred transaction on `conn`, runs `tx` in it, then commits on `Ok` and rolls back
+// on `Err`, or when `tx` panics.
+pub fn run_tx<T, E, X>(conn: &mut Connection, tx: X) -> Result<T, E>
+where
+    X: Tx<Connection, Item = T, Err = E, Mode = SyncMode>,
+    E: From<::rusqlite::Error>,
+{
+    run_tx_with(conn, TransactionBehavior::Deferred, tx)
+}
+
+// As `run_tx`, taking the write lock as `behavior` says: `Immediate` for a transaction which
+// writes, so that it waits for the lock up front rather than failing with `SQLITE_BUSY` halfway.
+pub fn run_tx_with<T, E, X>(
+    conn: &mut Connection,
+    behavior: TransactionBehavior,
+    tx: X,
+) -> Result<T, E>
+where
+    X: Tx<Connection, Item = T, Err = E, Mode = SyncMode>,
+    E: From<::rusqlite::Error>,
+{
+    let begin = match behavior {
+        TransactionBehavior::Immediate => "BEGIN IMMEDIATE",
+        TransactionBehavior::Exclusive => "BEGIN EXCLUSIVE",
+        _ => "BEGIN DEFERRED",
+    };
+    conn.execute_batch(begin)?;
+    let mut open = Open(conn);
+    let item = tx.run(&mut open)?;
+    open.execute_batch("COMMIT")?;
+    Ok(item)
+}
+
+// The connection with a transaction open on it, rolled back when dropped before it commits.
+struct Open<'c>(&'c mut Connection);
+impl Deref for Open<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.0
+    }
+}
+impl DerefMut for Open<'_> {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.0
+    }
+}
+impl Drop for Open<'_> {
+    fn drop(&mut self) {
+        if !self.0.is_autocommit() {
+            let _ = self.0.execute_batch("ROLLBACK");
+        }
+    }
+}
+
+// As for sqlx: on `Err` only the work of `tx` is rolled back. SQLite nests savepoints of the
+// same name, so all of them share one.
+impl<X> Tx<Connection> for Savepoint<X>
+where
+    X: Tx<Connection, Mode = SyncMode>,
+    X::Err: From<::rusqlite::Error>,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = SyncMode;
+
+    fn run<'a>(self, conn: &'a mut Connection) -> Result<Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        conn.execute_batch("SAVEPOINT tx_rs_savepoint")?;
+        match self.tx.run(conn) {
+            Ok(t) => {
+                conn.execute_batch("RELEASE SAVEPOINT tx_rs_savepoint")?;
+                Ok(t)
+            }
+            Err(e) => {
+                conn.execute_batch(
+                    "ROLLBACK TO SAVEPOINT tx_rs_savepoint; RELEASE SAVEPOINT tx_rs_savepoint",
+                )?;
+                Err(e)
+            }
+        }
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("savepoint", vec![self.tx.describe()])
+    }
+}
+
+// The twin of `runner::sql`: a statement written at run time which, as a step, executes and
+// yields the rows affected; `fetch_*` turn it into a step yielding the rows `row` maps instead.
+// Statements are prepared through the cache of the connection.
+pub fn sql(sql: impl Into<String>) -> Sql {
+    Sql {
+        sql: sql.into(),
+        params: vec![],
+    }
+}
+
+#[derive(Clone)]
+pub struct Sql {
+    sql: String,
+    params: Vec<Value>,
+}
+impl fmt::Debug for Sql {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sql")
+            .field("sql", &self.sql)
+            .field("binds", &self.params.len())
+            .finish()
+    }
+}
+impl Sql {
+    // Binds the next `?`.
+    pub fn bind(mut self, value: impl Into<Value>) -> Self {
+        self.params.push(value.into());
+        self
+    }
+
+    pub fn fetch_all<R, F>(
+        self,
+        row: F,
+    ) -> impl Tx<Connection, Item = Vec<R>, Err = ::rusqlite::Error, Mode = SyncMode>
+    where
+        F: FnMut(&Row<'_>) -> Result<R, ::rusqlite::Error>,
+    {
+        with_tx(move |conn: &mut Connection| {
+            let mut statement = conn.prepare_cached(&self.sql)?;
+            let rows = statement.query_map(params_from_iter(&self.params), row)?;
+            rows.collect()
+        })
+    }
+
+    // Fails with `QueryReturnedNoRows` when there is none.
+    pub fn fetch_one<R, F>(
+        self,
+        row: F,
+    ) -> impl Tx<Connection, Item = R, Err = ::rusqlite::Error, Mode = SyncMode>
+    where
+        F: FnOnce(&Row<'_>) -> Result<R, ::rusqlite::Error>,
+    {
+        with_tx(move |conn: &mut Connection| {
+            let mut statement = conn.prepare_cached(&self.sql)?;
+            statement.query_row(params_from_iter(&self.params), row)
+        })
+    }
+
+    pub fn fetch_optional<R, F>(
+        self,
+        row: F,
+    ) -> impl Tx<Connection, Item = Option<R>, Err = ::rusqlite::Error, Mode = SyncMode>
+    where
+        F: FnOnce(&Row<'_>) -> Result<R, ::rusqlite::Error>,
+    {
+        with_tx(move |conn: &mut Connection| {
+            let mut statement = conn.prepare_cached(&self.sql)?;
+            statement
+                .query_row(params_from_iter(&self.params), row)
+                .optional()
+        })
+    }
+}
+
+impl Tx<Connection> for Sql {
+    type Item = usize;
+    type Err = ::rusqlite::Error;
+    type Mode = SyncMode;
+
+    fn run<'a>(self, conn: &'a mut Connection) -> Result<usize, ::rusqlite::Error>
+    where
+        Self: 'a,
+    {
+        let mut statement = conn.prepare_cached(&self.sql)?;
+        statement.execute(params_from_iter(&self.params))
+    }
+
+    fn describe(&self) -> Description {
+        Description::leaf("sql")
+    }
+}
@@ -0,0 +1,70 @@
+use sqlx::Postgres;
+
+use crate::combinator::{with_tx_async, AsyncMode, Tx};
+use crate::context::{TxAccess, TxCtx};
+
+// Steps telling what the schema of the database looks like, for chains and tests to check
+// what they count on before doing anything destructive, e.g. with `try_abort`. They only
+// read, so they run in any transaction; table names are resolved along the `search_path`,
+// and may be schema-qualified.
+
+// Whether `table`, or a view of that name, exists.
+pub fn table_exists<A: TxAccess>(
+    table: impl Into<String>,
+) -> impl Tx<TxCtx<Postgres, A>, Item = bool, Err = sqlx::Error, Mode = AsyncMode> {
+    let table = table.into();
+    with_tx_async(move |ctx: &mut TxCtx<Postgres, A>| {
+        Box::pin(async move {
+            sqlx::query_scalar("SELECT to_regclass($1) IS NOT NULL")
+                .bind(table)
+                .fetch_one(&mut **ctx)
+                .await
+        })
+    })
+    .named("table_exists")
+}
+
+// The type of `column` of `table` as Postgres writes it, e.g. `text` or
+// `character varying(80)`; `None` when there is no such column.
+pub fn column_type<A: TxAccess>(
+    table: impl Into<String>,
+    column: impl Into<String>,
+) -> impl Tx<TxCtx<Postgres, A>, Item = Option<String>, Err = sqlx::Error, Mode = AsyncMode> {
+    let (table, column) = (table.into(), column.into());
+    with_tx_async(move |ctx: &mut TxCtx<Postgres, A>| {
+        Box::pin(async move {
+            sqlx::query_scalar(
+                r#"SELECT format_type(atttypid, atttypmod)
+                   FROM pg_attribute
+                   WHERE attrelid = to_regclass($1) AND attname = $2
+                     AND attnum > 0 AND NOT attisdropped"#,
+            )
+            .bind(table)
+            .bind(column)
+            .fetch_optional(&mut **ctx)
+            .await
+        })
+    })
+    .named("column_type")
+}
+
+// The version of the latest migration applied by `migrations`, or sqlx, successfully; `None`
+// when none was.
+pub fn current_schema_version<A: TxAccess>(
+) -> impl Tx<TxCtx<Postgres, A>, Item = Option<i64>, Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(|ctx: &mut TxCtx<Postgres, A>| {
+        Box::pin(async move {
+            let migrated: bool =
+                sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+                    .fetch_one(&mut **ctx)
+                    .await?;
+            if !migrated {
+                return Ok(None);
+            }
+            sqlx::query_scalar("SELECT max(version) FROM _sqlx_migrations WHERE success")
+                .fetch_one(&mut **ctx)
+                .await
+        })
+    })
+    .named("current_schema_version")
+}
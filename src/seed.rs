@@ -0,0 +1,218 @@
+use serde_json::{Map, Value};
+
+use crate::combinator::{AsyncMode, BoxFuture, Description, Tx};
+use crate::runner::PgCtx;
+
+// Seed data, e.g. the rows a dev, demo or test database starts with, declared as sets of
+// named seeds. A seed upserts rows into tables: a row whose key is there already is updated
+// to the values given, so a set can be run again, e.g. after being edited, and leaves the
+// same rows. A seed may depend on others of its set, which then run first. Sets and seeds are
+// steps like any other: run a set in a transaction of its own, or compose it with the
+// migrations.
+//
+//     let todos = Seed::new("todos").upsert("todos", &["id"], [json!({"id": 1, "description": "one"})]);
+//     let tags = Seed::new("tags").after("todos").upsert("todo_tags", &["todo_id", "tag"], tag_rows);
+//     run_tx(&pool, SeedSet::new("demo").seed(todos).seed(tags)).await?;
+
+#[derive(Debug, Clone)]
+pub struct Seed {
+    name: &'static str,
+    after: Vec<&'static str>,
+    upserts: Vec<Upsert>,
+}
+#[derive(Debug, Clone)]
+struct Upsert {
+    table: String,
+    key: Vec<String>,
+    rows: Vec<Value>,
+}
+impl Seed {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            after: vec![],
+            upserts: vec![],
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+    // Runs the seed after the seed `name` of its set.
+    pub fn after(mut self, name: &'static str) -> Self {
+        self.after.push(name);
+        self
+    }
+    // Upserts `rows`, JSON objects from column to value, into `table`, on the conflict of the
+    // unique `key` columns. The values are converted to the types of the columns by Postgres,
+    // as `jsonb_populate_recordset` does; the columns a row leaves out take their defaults
+    // when it is inserted, and are left as they are when it is updated. A row which is not an
+    // object fails the seed.
+    pub fn upsert(
+        mut self,
+        table: impl Into<String>,
+        key: &[&str],
+        rows: impl IntoIterator<Item = Value>,
+    ) -> Self {
+        self.upserts.push(Upsert {
+            table: table.into(),
+            key: key.iter().map(|column| column.to_string()).collect(),
+            rows: rows.into_iter().collect(),
+        });
+        self
+    }
+}
+impl Tx<PgCtx> for Seed {
+    type Item = ();
+    type Err = sqlx::Error;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut PgCtx) -> BoxFuture<'a, Result<(), sqlx::Error>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            for upsert in &self.upserts {
+                let rows = upsert
+                    .rows
+                    .iter()
+                    .map(|row| match row {
+                        Value::Object(row) => Ok(row),
+                        row => {
+                            let message =
+                                format!("seed {}: row {} is not an object", self.name, row);
+                            Err(sqlx::Error::Configuration(message.into()))
+                        }
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                // rows with the same columns go together
+                for rows in rows.chunk_by(|a, b| a.keys().eq(b.keys())) {
+                    if let Some(sql) = upsert_sql(&upsert.table, &upsert.key, rows) {
+                        sqlx::query(&sql)
+                            .bind(sqlx::types::Json(rows))
+                            .execute(&mut **ctx)
+                            .await?;
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::leaf(format!("seed {}", self.name))
+    }
+}
+
+// The seeds of a data set, run in the order their dependencies require.
+#[derive(Debug, Clone)]
+pub struct SeedSet {
+    name: &'static str,
+    seeds: Vec<Seed>,
+}
+impl SeedSet {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            seeds: vec![],
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+    pub fn seed(mut self, seed: Seed) -> Self {
+        self.seeds.push(seed);
+        self
+    }
+
+    // The seeds, each after those it depends on, otherwise in the order they were added.
+    // Fails on a dependency which is not in the set, and on a cycle.
+    pub fn ordered(&self) -> Result<Vec<&Seed>, sqlx::Error> {
+        let invalid = |message: String| {
+            let message = format!("seed set {}: {}", self.name, message);
+            sqlx::Error::Configuration(message.into())
+        };
+        for seed in &self.seeds {
+            if let Some(missing) = seed
+                .after
+                .iter()
+                .find(|name| !self.seeds.iter().any(|other| other.name == **name))
+            {
+                return Err(invalid(format!(
+                    "{} depends on unknown {}",
+                    seed.name, missing
+                )));
+            }
+        }
+        let mut left: Vec<&Seed> = self.seeds.iter().collect();
+        let mut order = vec![];
+        while !left.is_empty() {
+            let (ready, rest): (Vec<&Seed>, Vec<&Seed>) = left.iter().partition(|seed| {
+                !seed
+                    .after
+                    .iter()
+                    .any(|name| left.iter().any(|other| other.name == *name))
+            });
+            if ready.is_empty() {
+                let names: Vec<&str> = rest.iter().map(|seed| seed.name).collect();
+                return Err(invalid(format!(
+                    "{} depend on each other",
+                    names.join(", ")
+                )));
+            }
+            order.extend(ready);
+            left = rest;
+        }
+        Ok(order)
+    }
+}
+impl Tx<PgCtx> for SeedSet {
+    type Item = ();
+    type Err = sqlx::Error;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut PgCtx) -> BoxFuture<'a, Result<(), sqlx::Error>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            let seeds: Vec<Seed> = self.ordered()?.into_iter().cloned().collect();
+            for seed in seeds {
+                seed.run(ctx).await?;
+            }
+            Ok(())
+        })
+    }
+
+    fn describe(&self) -> Description {
+        let seeds = self.seeds.iter().map(Seed::describe);
+        Description::new(format!("seed_set {}", self.name), seeds.collect())
+    }
+}
+
+// The statement upserting `rows`, which have the same columns, into `table`.
+fn upsert_sql(table: &str, key: &[String], rows: &[&Map<String, Value>]) -> Option<String> {
+    let columns: Vec<&str> = rows.first()?.keys().map(String::as_str).collect();
+    if columns.is_empty() {
+        return None;
+    }
+    let updates: Vec<String> = columns
+        .iter()
+        .filter(|column| !key.iter().any(|k| k == *column))
+        .map(|column| format!("{} = EXCLUDED.{}", column, column))
+        .collect();
+    let on_conflict = if updates.is_empty() {
+        "DO NOTHING".to_string()
+    } else {
+        format!("DO UPDATE SET {}", updates.join(", "))
+    };
+    let columns = columns.join(", ");
+    Some(format!(
+        "INSERT INTO {} ({}) SELECT {} FROM jsonb_populate_recordset(NULL::{}, $1) ON CONFLICT ({}) {}",
+        table,
+        columns,
+        columns,
+        table,
+        key.join(", "),
+        on_conflict
+    ))
+}
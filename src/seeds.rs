@@ -0,0 +1,54 @@
+use serde_json::json;
+
+use tx::migrations;
+use tx::prelude::*;
+use tx::seed::{Seed, SeedSet};
+
+// `seed <set>`: migrates the database of `DATABASE_URL` and seeds it with one of the data
+// sets below. Seeding again updates the rows back to what the set says.
+
+// Their ids are far above those the examples use.
+fn todos() -> Seed {
+    Seed::new("todos").upsert(
+        "todos",
+        &["id"],
+        [
+            json!({"id": 1001, "description": "read the README"}),
+            json!({"id": 1002, "description": "run the examples"}),
+        ],
+    )
+}
+
+fn sets() -> Vec<SeedSet> {
+    let backlog = Seed::new("backlog").after("todos").upsert(
+        "todos",
+        &["id"],
+        [
+            json!({"id": 1003, "description": "compose a chain", "done": false}),
+            json!({"id": 1004, "description": "retry it", "done": false}),
+            json!({"id": 1005, "description": "ship it", "done": true}),
+        ],
+    );
+    let fixture = Seed::new("fixture").upsert(
+        "todos",
+        &["id"],
+        [json!({"id": 1001, "description": "test todo", "done": false})],
+    );
+    vec![
+        SeedSet::new("dev").seed(todos()),
+        SeedSet::new("demo").seed(backlog).seed(todos()),
+        SeedSet::new("test").seed(fixture),
+    ]
+}
+
+pub async fn run(pool: &sqlx::PgPool, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(set) = sets().into_iter().find(|set| set.name() == name) else {
+        let names: Vec<&str> = sets().iter().map(SeedSet::name).collect();
+        let names = names.join(", ");
+        return Err(format!("unknown seed set {}, expected one of {}", name, names).into());
+    };
+    migrations::run(pool).await?;
+    run_tx(pool, set).await?;
+    println!("seeded {}", name);
+    Ok(())
+}
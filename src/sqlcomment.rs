@@ -0,0 +1,78 @@
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+// sqlcommenter-style comments, appended to the statements of the crate's SQL helpers (`sql`,
+// `fold_rows`, `for_each_batch`, `Keyset`, `lock_row`...) in transactions run with
+// `TxOptions::sql_comments`, so that the server logs and pg_stat_statements can be matched
+// with the traces of the application:
+//
+//     SELECT * FROM todos /*traceparent='00-4bf9...-00f0...-01',tx='create_todo'*/
+//
+// `tx` is the name the chain describes itself with. The trace comes from the source set with
+// `set_trace_source`, typically reading the current OpenTelemetry context; without one, or
+// outside of a trace, there is only `tx`.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceContext {
+    pub trace_id: u128,
+    pub span_id: u64,
+    pub sampled: bool,
+}
+impl TraceContext {
+    // As in the W3C `traceparent` header.
+    pub fn traceparent(&self) -> String {
+        format!(
+            "00-{:032x}-{:016x}-{:02x}",
+            self.trace_id, self.span_id, self.sampled as u8
+        )
+    }
+}
+
+type TraceSource = Box<dyn Fn() -> Option<TraceContext> + Send + Sync>;
+
+static TRACE_SOURCE: OnceLock<TraceSource> = OnceLock::new();
+
+// Where the trace of the comments comes from, asked for each statement. Set once for the
+// process: later calls change nothing and return `false`.
+pub fn set_trace_source(source: impl Fn() -> Option<TraceContext> + Send + Sync + 'static) -> bool {
+    TRACE_SOURCE.set(Box::new(source)).is_ok()
+}
+
+// `sql` with the comment of the transaction named `tx`.
+pub(crate) fn apply<'s>(sql: &'s str, tx: Option<&str>) -> Cow<'s, str> {
+    // as sqlcommenter does, statements with a comment of their own are left alone
+    if sql.contains("/*") {
+        return Cow::Borrowed(sql);
+    }
+    let trace = TRACE_SOURCE.get().and_then(|source| source());
+    // in key order
+    let tags: Vec<String> = trace
+        .map(|trace| ("traceparent", trace.traceparent()))
+        .into_iter()
+        .chain(tx.map(|tx| ("tx", tx.to_string())))
+        .map(|(key, value)| format!("{}='{}'", key, encode(&value)))
+        .collect();
+    if tags.is_empty() {
+        return Cow::Borrowed(sql);
+    }
+    let sql = sql.trim_end();
+    let (sql, semicolon) = match sql.strip_suffix(';') {
+        Some(sql) => (sql, ";"),
+        None => (sql, ""),
+    };
+    Cow::Owned(format!("{} /*{}*/{}", sql, tags.join(","), semicolon))
+}
+
+// Percent-encoded but for the unreserved characters, which also takes care of quotes.
+fn encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
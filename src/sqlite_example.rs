@@ -0,0 +1,242 @@
+use sqlx::Sqlite;
+
+use tx::prelude::*;
+use tx::runner::{self, SavepointExt, SqliteCtx};
+
+// The same flows as the Postgres example, against a private in-memory SQLite database,
+// so they run without any server.
+
+async fn create_table(pool: &sqlx::SqlitePool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"CREATE TABLE IF NOT EXISTS todos
+           (
+               id          INTEGER PRIMARY KEY,
+               description TEXT    NOT NULL,
+               done        BOOLEAN NOT NULL DEFAULT FALSE
+           )"#,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+async fn insert_and_verify(
+    transaction: &mut sqlx::Transaction<'_, Sqlite>,
+    test_id: i64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    sqlx::query(r#"INSERT INTO todos (id, description) VALUES ( ?, ? )"#)
+        .bind(test_id)
+        .bind("test todo")
+        .execute(&mut **transaction)
+        .await?;
+
+    // check that inserted todo can be fetched inside the uncommitted transaction
+    let _ = sqlx::query(r#"SELECT id FROM todos WHERE id = ?"#)
+        .bind(test_id)
+        .fetch_one(&mut **transaction)
+        .await?;
+
+    Ok(())
+}
+
+fn insert_and_verify_tx(
+    test_id: i64,
+) -> impl Tx<SqliteCtx, Item = (), Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(move |transaction: &mut SqliteCtx| {
+        Box::pin(async move {
+            sqlx::query(r#"INSERT INTO todos (id, description) VALUES ( ?, ? )"#)
+                .bind(test_id)
+                .bind("test todo")
+                .execute(&mut **transaction)
+                .await?;
+
+            // check that inserted todo can be fetched inside the uncommitted transaction
+            let _ = sqlx::query(r#"SELECT id FROM todos WHERE id = ?"#)
+                .bind(test_id)
+                .fetch_one(&mut **transaction)
+                .await?;
+
+            Ok(())
+        })
+    })
+}
+
+async fn explicit_rollback_example(
+    pool: &sqlx::SqlitePool,
+    test_id: i64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut transaction = pool.begin().await?;
+
+    insert_and_verify(&mut transaction, test_id).await?;
+
+    transaction.rollback().await?;
+
+    Ok(())
+}
+
+async fn implicit_rollback_example(
+    pool: &sqlx::SqlitePool,
+    test_id: i64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut transaction = pool.begin().await?;
+
+    insert_and_verify(&mut transaction, test_id).await?;
+
+    // no explicit rollback here but the transaction object is dropped at the end of the scope
+    Ok(())
+}
+
+async fn commit_example(
+    pool: &sqlx::SqlitePool,
+    test_id: i64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut transaction = pool.begin().await?;
+
+    insert_and_verify(&mut transaction, test_id).await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+async fn runner_savepoint_example(
+    pool: &sqlx::SqlitePool,
+    test_id: i64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // SQLite cannot nest transactions, but the nested chain runs in a savepoint: its duplicate
+    // key only rolls the savepoint back and the outer chain can still insert and commit
+    let chain = insert_and_verify_tx(test_id)
+        .and_then(move |()| runner::savepoint(insert_and_verify_tx(test_id)).recover(|_| ()))
+        .and_then(move |()| insert_and_verify_tx(test_id + 1));
+
+    runner::run_tx(pool, chain).await?;
+
+    Ok(())
+}
+
+async fn runner_or_else_savepoint_example(
+    pool: &sqlx::SqlitePool,
+    test_id: i64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // the primary branch inserts and then fails; its insert is rolled back with the savepoint,
+    // so the fallback can insert the very same id without hitting a duplicate key
+    let chain = insert_and_verify_tx(test_id)
+        .abort(|_| sqlx::Error::RowNotFound)
+        .or_else_savepoint(move |_| insert_and_verify_tx(test_id));
+
+    runner::run_tx(pool, chain).await?;
+
+    Ok(())
+}
+
+fn todo_rows(ids: std::ops::Range<i64>) -> Vec<Vec<runner::Value>> {
+    ids.map(|id| vec![id.into(), format!("bulk todo {}", id).into()])
+        .collect()
+}
+
+async fn exists(pool: &sqlx::SqlitePool, test_id: i64) -> Result<bool, sqlx::Error> {
+    let todo = sqlx::query(r#"SELECT id FROM todos WHERE id = ?"#)
+        .bind(test_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(todo.is_some())
+}
+
+pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = runner::memory_pool().await?;
+    create_table(&pool).await?;
+
+    let test_id = 1;
+
+    explicit_rollback_example(&pool, test_id).await?;
+
+    // check that inserted todo is not visible outside the transaction after explicit rollback
+    assert!(!exists(&pool, test_id).await?);
+
+    implicit_rollback_example(&pool, test_id).await?;
+
+    // check that inserted todo is not visible outside the transaction after implicit rollback
+    assert!(!exists(&pool, test_id).await?);
+
+    commit_example(&pool, test_id).await?;
+
+    // check that inserted todo is visible outside the transaction after commit
+    assert!(exists(&pool, test_id).await?);
+
+    // inserting it again is a unique violation, which sqlx recognizes from SQLite's own codes
+    let result = runner::run_tx(&pool, insert_and_verify_tx(test_id)).await;
+    assert_eq!(result.unwrap_err().error_kind(), ErrorKind::UniqueViolation);
+
+    let test_id = 2;
+
+    // read-only transactions are not available on SQLite
+    let options = runner::TxOptions::new().read_only();
+    let result = runner::run_tx_with(&pool, options, insert_and_verify_tx(test_id)).await;
+    assert!(matches!(result, Err(sqlx::Error::Configuration(_))));
+
+    // the chain fails after the insert, so `run_tx` rolls the whole transaction back
+    let result = runner::run_tx(
+        &pool,
+        insert_and_verify_tx(test_id).abort(|_| sqlx::Error::RowNotFound),
+    )
+    .await;
+    assert!(result.is_err());
+    assert!(!exists(&pool, test_id).await?);
+
+    let test_id = 3;
+
+    runner_savepoint_example(&pool, test_id).await?;
+
+    // the outer inserts are committed, the failed nested one did not break the transaction
+    assert!(exists(&pool, test_id).await?);
+    assert!(exists(&pool, test_id + 1).await?);
+
+    let test_id = 5;
+
+    runner_or_else_savepoint_example(&pool, test_id).await?;
+
+    // only the fallback's insert is committed
+    assert!(exists(&pool, test_id).await?);
+
+    let test_id = 1000;
+
+    // no COPY here: the rows go in multi-row INSERTs, as many per statement as SQLite binds
+    let inserted = runner::run_tx(
+        &pool,
+        runner::bulk_insert(
+            "todos",
+            &["id", "description"],
+            todo_rows(test_id..test_id + 20_000),
+        ),
+    )
+    .await?;
+    assert_eq!(inserted, 20_000);
+    assert!(exists(&pool, test_id + 19_999).await?);
+
+    let test_id = 7;
+
+    // a column picked at run time, which `query!` cannot take
+    let column = "description";
+    let (updated, description) = runner::run_tx(
+        &pool,
+        runner::sql(format!("INSERT INTO todos (id, {}) VALUES (?, ?)", column))
+            .bind(test_id)
+            .bind("raw todo".to_string())
+            .and_then(|_| {
+                runner::sql(format!("UPDATE todos SET {} = ? WHERE id = ?", column))
+                    .bind("updated raw todo".to_string())
+                    .bind(test_id)
+            })
+            .and_then(|updated| {
+                runner::sql(format!("SELECT {} FROM todos WHERE id = ?", column))
+                    .bind(test_id)
+                    .fetch_one()
+                    .map(move |(description,): (String,)| (updated, description))
+            }),
+    )
+    .await?;
+    assert_eq!(updated, 1);
+    assert_eq!(description, "updated raw todo");
+
+    Ok(())
+}
@@ -0,0 +1,94 @@
+use sqlx::{Database, Pool};
+
+use crate::combinator::BoxFuture;
+use crate::context::{TxAccess, TxCtx};
+use crate::runner::{self, Backend, TxOptions};
+
+pub use tx_rs_macros::tx_test;
+
+pub mod isolation;
+
+// Runs a test body in a transaction which is always rolled back, whatever the body returns,
+// so tests sharing a database leave no rows behind and need no cleanup before they start.
+// Chains run in it with `chain.run(ctx)`; their `after_commit` hooks never run.
+// Panics when the transaction cannot begin.
+pub async fn tx_test<DB, A, R, F>(pool: &Pool<DB>, f: F) -> R
+where
+    DB: Backend,
+    A: TxAccess,
+    F: for<'c> FnOnce(&'c mut TxCtx<DB, A>) -> BoxFuture<'c, R>,
+{
+    let mut ctx = match runner::begin(pool, TxOptions::default()).await {
+        Ok(ctx) => ctx,
+        Err(e) => panic!("tx_test: cannot begin a transaction: {}", e),
+    };
+    let result = f(&mut ctx).await;
+    let _ = ctx.rollback().await;
+    result
+}
+
+// The database of a context, for `#[tx_test]` to ask sqlx for a pool of it.
+pub trait TestCtx {
+    type Database: Database;
+}
+impl<DB: Database, A> TestCtx for TxCtx<DB, A> {
+    type Database = DB;
+}
+
+#[cfg(feature = "postgres")]
+pub use self::snapshot::*;
+#[cfg(feature = "postgres")]
+mod snapshot;
+
+#[cfg(feature = "testing")]
+pub use self::container::*;
+#[cfg(feature = "testing")]
+mod container {
+    use sqlx::migrate::Migrator;
+    use sqlx::PgPool;
+    use testcontainers_modules::postgres::Postgres;
+    use testcontainers_modules::testcontainers::runners::AsyncRunner;
+    use testcontainers_modules::testcontainers::{ContainerAsync, ImageExt};
+
+    // A throwaway Postgres in a container, migrated, for tests which cannot count on a
+    // provisioned `DATABASE_URL`. The container is removed when this is dropped, so keep it
+    // alive as long as the pool is used.
+    pub struct TestDb {
+        pool: PgPool,
+        _container: ContainerAsync<Postgres>,
+    }
+    impl TestDb {
+        // Starts the same server as `docker-compose.yml` and runs `migrator` on it.
+        // Panics when docker is not available or the migrations fail, failing the test.
+        pub async fn start(migrator: &Migrator) -> Self {
+            let container = Postgres::default()
+                .with_tag("16.4-alpine")
+                .with_cmd(["postgres", "-c", "max_prepared_transactions=10"])
+                .start()
+                .await
+                .expect("testing: cannot start a Postgres container");
+            let host = container.get_host().await.expect("testing: no host");
+            let port = container
+                .get_host_port_ipv4(5432)
+                .await
+                .expect("testing: port 5432 not mapped");
+            let url = format!("postgres://postgres:postgres@{}:{}/postgres", host, port);
+
+            let pool = PgPool::connect(&url)
+                .await
+                .expect("testing: cannot connect to the container");
+            migrator
+                .run(&pool)
+                .await
+                .expect("testing: migrations failed");
+            TestDb {
+                pool,
+                _container: container,
+            }
+        }
+
+        pub fn pool(&self) -> &PgPool {
+            &self.pool
+        }
+    }
+}
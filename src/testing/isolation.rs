@@ -0,0 +1,216 @@
+use std::future::{poll_fn, Future};
+use std::pin::pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
+
+use sqlx::Pool;
+
+use crate::combinator::{AsyncMode, BoxFuture, Description, Tx};
+use crate::context::{TxAccess, TxCtx};
+use crate::runner::{self, Backend, TxOptions};
+
+// Two transactions run side by side on connections of their own, with their steps
+// interleaved in a given order, to show the anomalies each isolation level lets through:
+// non-repeatable reads, lost updates, write skew. A step wrapped by `Schedule::at` waits for
+// its turn, and the turn after it comes once it is done.
+//
+// A turn no step waits at, such as `B("end")`, passes when its transaction has ended,
+// committed or rolled back; so does every turn of a transaction which failed before
+// reaching it. A step blocked on a lock of the other transaction keeps its turn until that
+// one ends: order the turns so that it can.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Turn {
+    A(&'static str),
+    B(&'static str),
+}
+impl Turn {
+    fn side(self) -> Side {
+        match self {
+            Turn::A(_) => Side::A,
+            Turn::B(_) => Side::B,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    A,
+    B,
+}
+
+// The order of the turns. Clones share it, so each transaction holds one.
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    inner: Arc<ScheduleState>,
+}
+#[derive(Debug)]
+struct ScheduleState {
+    turns: Vec<Turn>,
+    passed: Mutex<Vec<bool>>,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl Schedule {
+    pub fn new(turns: impl IntoIterator<Item = Turn>) -> Self {
+        let turns: Vec<Turn> = turns.into_iter().collect();
+        Self {
+            inner: Arc::new(ScheduleState {
+                passed: Mutex::new(vec![false; turns.len()]),
+                turns,
+                wakers: Mutex::new(vec![]),
+            }),
+        }
+    }
+
+    // Runs `tx` at `turn`. Panics when the schedule has no such turn left.
+    pub fn at<X>(&self, turn: Turn, tx: X) -> At<X> {
+        At {
+            tx,
+            turn,
+            schedule: self.clone(),
+        }
+    }
+
+    // Resolves once every turn before the first `turn` not passed yet has passed.
+    async fn wait_for(&self, turn: Turn) {
+        poll_fn(|cx| {
+            // checked under the lock, so a concurrent `pass` either is seen here or wakes us
+            let mut wakers = self.inner.wakers.lock().unwrap();
+            let passed = self.inner.passed.lock().unwrap();
+            let next = self
+                .inner
+                .turns
+                .iter()
+                .zip(passed.iter())
+                .position(|(_, p)| !p);
+            match next {
+                Some(next) if self.inner.turns[next] == turn => return Poll::Ready(()),
+                Some(_) if self.position(&passed, turn).is_some() => {}
+                _ => panic!("isolation: {:?} is not in the schedule", turn),
+            }
+            if !wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                wakers.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+        .await
+    }
+
+    fn pass(&self, turn: Turn) {
+        self.mark(|passed| {
+            if let Some(i) = self.position(passed, turn) {
+                passed[i] = true;
+            }
+        });
+    }
+    // The turns of `side` left over once its transaction has ended.
+    fn end(&self, side: Side) {
+        self.mark(|passed| {
+            for (i, turn) in self.inner.turns.iter().enumerate() {
+                if turn.side() == side {
+                    passed[i] = true;
+                }
+            }
+        });
+    }
+
+    fn mark(&self, f: impl FnOnce(&mut Vec<bool>)) {
+        let mut wakers = self.inner.wakers.lock().unwrap();
+        f(&mut self.inner.passed.lock().unwrap());
+        for waker in wakers.drain(..) {
+            waker.wake();
+        }
+    }
+    fn position(&self, passed: &[bool], turn: Turn) -> Option<usize> {
+        let mut turns = self.inner.turns.iter().zip(passed);
+        turns.position(|(t, p)| *t == turn && !p)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct At<X> {
+    tx: X,
+    turn: Turn,
+    schedule: Schedule,
+}
+impl<Ctx, X> Tx<Ctx> for At<X>
+where
+    Ctx: Send,
+    X: Tx<Ctx, Mode = AsyncMode> + Send,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = AsyncMode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> BoxFuture<'a, Result<Self::Item, Self::Err>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            self.schedule.wait_for(self.turn).await;
+            let result = self.tx.run(ctx).await;
+            self.schedule.pass(self.turn);
+            result
+        })
+    }
+
+    fn describe(&self) -> Description {
+        Description::new("at", vec![self.tx.describe()])
+    }
+}
+
+// Runs `a` and `b` at once, each in a transaction of its own as `run_tx_with` does, with
+// their steps interleaved as `schedule` says.
+pub async fn run_pair<DB, A1, A2, X1, X2>(
+    pool: &Pool<DB>,
+    schedule: &Schedule,
+    a: (TxOptions, X1),
+    b: (TxOptions, X2),
+) -> (Result<X1::Item, X1::Err>, Result<X2::Item, X2::Err>)
+where
+    DB: Backend,
+    A1: TxAccess,
+    A2: TxAccess,
+    X1: Tx<TxCtx<DB, A1>, Mode = AsyncMode>,
+    X1::Err: From<sqlx::Error>,
+    X2: Tx<TxCtx<DB, A2>, Mode = AsyncMode>,
+    X2::Err: From<sqlx::Error>,
+{
+    let a = async {
+        let result = runner::run_tx_with(pool, a.0, a.1).await;
+        schedule.end(Side::A);
+        result
+    };
+    let b = async {
+        let result = runner::run_tx_with(pool, b.0, b.1).await;
+        schedule.end(Side::B);
+        result
+    };
+    join(a, b).await
+}
+
+async fn join<F1: Future, F2: Future>(f1: F1, f2: F2) -> (F1::Output, F2::Output) {
+    let (mut f1, mut f2) = (pin!(f1), pin!(f2));
+    let (mut r1, mut r2) = (None, None);
+    poll_fn(|cx| {
+        if r1.is_none() {
+            if let Poll::Ready(r) = f1.as_mut().poll(cx) {
+                r1 = Some(r);
+            }
+        }
+        if r2.is_none() {
+            if let Poll::Ready(r) = f2.as_mut().poll(cx) {
+                r2 = Some(r);
+            }
+        }
+        match (r1.take(), r2.take()) {
+            (Some(r1), Some(r2)) => Poll::Ready((r1, r2)),
+            (s1, s2) => {
+                (r1, r2) = (s1, s2);
+                Poll::Pending
+            }
+        }
+    })
+    .await
+}
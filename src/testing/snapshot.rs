@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde_json::{Map, Value};
+use sqlx::types::Json;
+use sqlx::{PgConnection, PgPool};
+
+use crate::combinator::{AsyncMode, Tx};
+use crate::runner::{self, PgCtx};
+
+// The state of some tables at one point, to tell by `diff` against a later one which rows
+// a chain inserted, updated and deleted. `rows` keeps every row, keyed by primary key, or by
+// the whole row in a table without one; `checksums` keeps a count and a hash per table, for
+// tables too big to keep, which tells whether a table changed but not which rows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    tables: BTreeMap<String, TableState>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TableState {
+    Rows(BTreeMap<String, Value>),
+    Checksum { count: i64, md5: String },
+}
+
+impl Snapshot {
+    pub async fn rows(conn: &mut PgConnection, tables: &[&str]) -> Result<Self, sqlx::Error> {
+        let mut snapshot = BTreeMap::new();
+        for table in tables {
+            let key: Vec<String> = sqlx::query_scalar(
+                r#"SELECT a.attname::text
+                   FROM pg_index i
+                   JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = ANY(i.indkey)
+                   WHERE i.indrelid = $1::regclass AND i.indisprimary
+                   ORDER BY array_position(i.indkey::int2[], a.attnum)"#,
+            )
+            .bind(table)
+            .fetch_all(&mut *conn)
+            .await?;
+            let sql = format!("SELECT to_jsonb(t) FROM {} t", table);
+            let rows: Vec<Json<Value>> = sqlx::query_scalar(&sql).fetch_all(&mut *conn).await?;
+
+            let rows = rows
+                .into_iter()
+                .map(|Json(row)| (row_key(&key, &row), row))
+                .collect();
+            snapshot.insert(table.to_string(), TableState::Rows(rows));
+        }
+        Ok(Snapshot { tables: snapshot })
+    }
+
+    pub async fn checksums(conn: &mut PgConnection, tables: &[&str]) -> Result<Self, sqlx::Error> {
+        let mut snapshot = BTreeMap::new();
+        for table in tables {
+            let sql = format!(
+                "SELECT count(*), coalesce(md5(string_agg(t::text, ',' ORDER BY t::text)), '') FROM {} t",
+                table
+            );
+            let (count, md5) = sqlx::query_as(&sql).fetch_one(&mut *conn).await?;
+            snapshot.insert(table.to_string(), TableState::Checksum { count, md5 });
+        }
+        Ok(Snapshot { tables: snapshot })
+    }
+
+    // What changed from this snapshot to `after`, in the tables of this one.
+    // Panics when a table was taken as rows in one and as checksums in the other.
+    pub fn diff(&self, after: &Snapshot) -> Diff {
+        let mut tables = BTreeMap::new();
+        for (table, before) in &self.tables {
+            let diff = match (before, after.tables.get(table)) {
+                (TableState::Rows(before), Some(TableState::Rows(after))) => {
+                    diff_rows(before, after)
+                }
+                (TableState::Rows(before), None) => diff_rows(before, &BTreeMap::new()),
+                (
+                    TableState::Checksum { count, md5 },
+                    Some(TableState::Checksum {
+                        count: count_after,
+                        md5: md5_after,
+                    }),
+                ) if md5 != md5_after => Some(TableDiff::Checksum {
+                    before: *count,
+                    after: *count_after,
+                }),
+                (TableState::Checksum { .. }, Some(TableState::Checksum { .. })) => None,
+                _ => panic!("snapshot: {} taken as rows and as checksums", table),
+            };
+            if let Some(diff) = diff {
+                tables.insert(table.clone(), diff);
+            }
+        }
+        Diff { tables }
+    }
+}
+
+// Runs `tx` in a transaction of its own, as `run_tx` does, between two snapshots of the rows
+// of `tables`: the diff is what it left in the database, nothing when it rolled back.
+// Panics when a snapshot cannot be taken.
+pub async fn diff_tx<X>(pool: &PgPool, tables: &[&str], tx: X) -> (Result<X::Item, X::Err>, Diff)
+where
+    X: Tx<PgCtx, Mode = AsyncMode>,
+    X::Err: From<sqlx::Error>,
+{
+    let before = snapshot(pool, tables).await;
+    let result = runner::run_tx(pool, tx).await;
+    let after = snapshot(pool, tables).await;
+    (result, before.diff(&after))
+}
+
+async fn snapshot(pool: &PgPool, tables: &[&str]) -> Snapshot {
+    let taken = match pool.acquire().await {
+        Ok(mut conn) => Snapshot::rows(&mut conn, tables).await,
+        Err(e) => Err(e),
+    };
+    taken.unwrap_or_else(|e| panic!("snapshot: cannot read {}: {}", tables.join(", "), e))
+}
+
+// The changes to each table which changed.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Diff {
+    tables: BTreeMap<String, TableDiff>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableDiff {
+    Rows {
+        inserted: Vec<Value>,
+        // each row as it was, then as it is
+        updated: Vec<(Value, Value)>,
+        deleted: Vec<Value>,
+    },
+    // the row counts of a table snapshotted by checksum
+    Checksum {
+        before: i64,
+        after: i64,
+    },
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+    pub fn tables(&self) -> impl Iterator<Item = &str> {
+        self.tables.keys().map(String::as_str)
+    }
+    pub fn table(&self, table: &str) -> Option<&TableDiff> {
+        self.tables.get(table)
+    }
+
+    // The rows of `table` inserted, updated and deleted; none when it did not change or was
+    // snapshotted by checksum.
+    pub fn inserted(&self, table: &str) -> &[Value] {
+        match self.tables.get(table) {
+            Some(TableDiff::Rows { inserted, .. }) => inserted,
+            _ => &[],
+        }
+    }
+    pub fn updated(&self, table: &str) -> &[(Value, Value)] {
+        match self.tables.get(table) {
+            Some(TableDiff::Rows { updated, .. }) => updated,
+            _ => &[],
+        }
+    }
+    pub fn deleted(&self, table: &str) -> &[Value] {
+        match self.tables.get(table) {
+            Some(TableDiff::Rows { deleted, .. }) => deleted,
+            _ => &[],
+        }
+    }
+}
+
+// One table a line, then one row a line, for assertion messages:
+//   todos
+//     + {"id":1,...}
+//     ~ {"id":2,...} -> {"id":2,...}
+//     - {"id":3,...}
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "no changes");
+        }
+        for (table, diff) in &self.tables {
+            match diff {
+                TableDiff::Rows {
+                    inserted,
+                    updated,
+                    deleted,
+                } => {
+                    writeln!(f, "{}", table)?;
+                    for row in inserted {
+                        writeln!(f, "  + {}", row)?;
+                    }
+                    for (before, after) in updated {
+                        writeln!(f, "  ~ {} -> {}", before, after)?;
+                    }
+                    for row in deleted {
+                        writeln!(f, "  - {}", row)?;
+                    }
+                }
+                TableDiff::Checksum { before, after } => {
+                    writeln!(f, "{} changed: {} rows -> {} rows", table, before, after)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn diff_rows(
+    before: &BTreeMap<String, Value>,
+    after: &BTreeMap<String, Value>,
+) -> Option<TableDiff> {
+    let inserted: Vec<Value> = after
+        .iter()
+        .filter(|(key, _)| !before.contains_key(*key))
+        .map(|(_, row)| row.clone())
+        .collect();
+    let deleted: Vec<Value> = before
+        .iter()
+        .filter(|(key, _)| !after.contains_key(*key))
+        .map(|(_, row)| row.clone())
+        .collect();
+    let updated: Vec<(Value, Value)> = before
+        .iter()
+        .filter_map(|(key, row)| match after.get(key) {
+            Some(changed) if changed != row => Some((row.clone(), changed.clone())),
+            _ => None,
+        })
+        .collect();
+
+    if inserted.is_empty() && updated.is_empty() && deleted.is_empty() {
+        return None;
+    }
+    Some(TableDiff::Rows {
+        inserted,
+        updated,
+        deleted,
+    })
+}
+
+// The primary key of `row` as text, or the whole row when there is no primary key.
+fn row_key(key: &[String], row: &Value) -> String {
+    match row {
+        Value::Object(columns) if !key.is_empty() => {
+            let key: Map<String, Value> = key
+                .iter()
+                .map(|column| {
+                    (
+                        column.clone(),
+                        columns.get(column).cloned().unwrap_or(Value::Null),
+                    )
+                })
+                .collect();
+            Value::Object(key).to_string()
+        }
+        row => row.to_string(),
+    }
+}
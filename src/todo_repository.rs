@@ -0,0 +1,205 @@
+use tx::combinator::{with_tx_async, AsyncMode, Tx};
+use tx::memory::{DuplicateKey, InMemoryCtx, Table};
+use tx::mock::MockCtx;
+use tx::repository::{Repository, Step};
+#[cfg(feature = "postgres")]
+use tx::runner::PgCtx;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Todo {
+    pub id: i64,
+    pub description: String,
+    pub done: bool,
+}
+
+pub trait TodoRepository: Repository {
+    fn insert(&self, id: i64, description: &str) -> impl Step<Self, ()>;
+    fn find(&self, id: i64) -> impl Step<Self, Option<Todo>>;
+    fn count(&self) -> impl Step<Self, i64>;
+    // Whether there was such a todo.
+    fn set_done(&self, id: i64, done: bool) -> impl Step<Self, bool>;
+    fn delete(&self, id: i64) -> impl Step<Self, bool>;
+}
+
+// A service: calls on the repository composed into one transaction.
+pub fn complete_tx<R: TodoRepository>(
+    todos: &R,
+    id: i64,
+) -> impl Tx<R::Ctx, Item = Option<Todo>, Err = R::Err, Mode = AsyncMode> + '_
+where
+    R::Ctx: Send,
+{
+    todos.set_done(id, true).and_then(move |_| todos.find(id))
+}
+
+#[cfg(feature = "postgres")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PgTodoRepository;
+#[cfg(feature = "postgres")]
+impl Repository for PgTodoRepository {
+    type Ctx = PgCtx;
+    type Err = sqlx::Error;
+}
+#[cfg(feature = "postgres")]
+impl TodoRepository for PgTodoRepository {
+    fn insert(&self, id: i64, description: &str) -> impl Step<Self, ()> {
+        let description = description.to_string();
+        with_tx_async(move |ctx: &mut PgCtx| {
+            Box::pin(async move {
+                sqlx::query!(
+                    r#"INSERT INTO todos (id, description) VALUES ( $1, $2 )"#,
+                    id,
+                    description
+                )
+                .execute(&mut **ctx)
+                .await?;
+                Ok(())
+            })
+        })
+    }
+
+    fn find(&self, id: i64) -> impl Step<Self, Option<Todo>> {
+        with_tx_async(move |ctx: &mut PgCtx| {
+            Box::pin(async move {
+                sqlx::query_as!(
+                    Todo,
+                    r#"SELECT id, description, done FROM todos WHERE id = $1"#,
+                    id
+                )
+                .fetch_optional(&mut **ctx)
+                .await
+            })
+        })
+    }
+
+    fn count(&self) -> impl Step<Self, i64> {
+        with_tx_async(|ctx: &mut PgCtx| {
+            Box::pin(async move {
+                let count = sqlx::query_scalar!(r#"SELECT count(*) AS "count!" FROM todos"#)
+                    .fetch_one(&mut **ctx)
+                    .await?;
+                Ok(count)
+            })
+        })
+    }
+
+    fn set_done(&self, id: i64, done: bool) -> impl Step<Self, bool> {
+        with_tx_async(move |ctx: &mut PgCtx| {
+            Box::pin(async move {
+                let result = sqlx::query!(r#"UPDATE todos SET done = $1 WHERE id = $2"#, done, id)
+                    .execute(&mut **ctx)
+                    .await?;
+                Ok(result.rows_affected() == 1)
+            })
+        })
+    }
+
+    fn delete(&self, id: i64) -> impl Step<Self, bool> {
+        with_tx_async(move |ctx: &mut PgCtx| {
+            Box::pin(async move {
+                let result = sqlx::query!(r#"DELETE FROM todos WHERE id = $1"#, id)
+                    .execute(&mut **ctx)
+                    .await?;
+                Ok(result.rows_affected() == 1)
+            })
+        })
+    }
+}
+
+// The same repository over tables in memory, for running the services without a database.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InMemoryTodoRepository;
+impl Repository for InMemoryTodoRepository {
+    type Ctx = InMemoryCtx;
+    type Err = DuplicateKey;
+}
+fn todos(ctx: &mut InMemoryCtx) -> &mut Table<i64, Todo> {
+    ctx.table("todos")
+}
+impl TodoRepository for InMemoryTodoRepository {
+    fn insert(&self, id: i64, description: &str) -> impl Step<Self, ()> {
+        let todo = Todo {
+            id,
+            description: description.to_string(),
+            done: false,
+        };
+        with_tx_async(move |ctx: &mut InMemoryCtx| {
+            Box::pin(async move { todos(ctx).insert(id, todo) })
+        })
+    }
+
+    fn find(&self, id: i64) -> impl Step<Self, Option<Todo>> {
+        with_tx_async(move |ctx: &mut InMemoryCtx| {
+            Box::pin(async move { Ok(todos(ctx).get(&id).cloned()) })
+        })
+    }
+
+    fn count(&self) -> impl Step<Self, i64> {
+        with_tx_async(|ctx: &mut InMemoryCtx| Box::pin(async move { Ok(todos(ctx).len() as i64) }))
+    }
+
+    fn set_done(&self, id: i64, done: bool) -> impl Step<Self, bool> {
+        with_tx_async(move |ctx: &mut InMemoryCtx| {
+            Box::pin(async move {
+                let todo = todos(ctx).get_mut(&id);
+                Ok(todo.map(|todo| todo.done = done).is_some())
+            })
+        })
+    }
+
+    fn delete(&self, id: i64) -> impl Step<Self, bool> {
+        with_tx_async(move |ctx: &mut InMemoryCtx| {
+            Box::pin(async move { Ok(todos(ctx).remove(&id).is_some()) })
+        })
+    }
+}
+
+// The same repository over a mock, which records each statement and answers it as scripted.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MockTodoRepository;
+impl Repository for MockTodoRepository {
+    type Ctx = MockCtx;
+    type Err = sqlx::Error;
+}
+pub const INSERT_TODO: &str = "INSERT INTO todos (id, description) VALUES ( $1, $2 )";
+pub const FIND_TODO: &str = "SELECT id, description, done FROM todos WHERE id = $1";
+pub const COUNT_TODOS: &str = "SELECT count(*) FROM todos";
+pub const SET_DONE: &str = "UPDATE todos SET done = $1 WHERE id = $2";
+pub const DELETE_TODO: &str = "DELETE FROM todos WHERE id = $1";
+impl TodoRepository for MockTodoRepository {
+    fn insert(&self, id: i64, description: &str) -> impl Step<Self, ()> {
+        let description = description.to_string();
+        with_tx_async(move |ctx: &mut MockCtx| {
+            let result = ctx.call(INSERT_TODO, &[&id, &description]);
+            Box::pin(async move { result })
+        })
+    }
+
+    fn find(&self, id: i64) -> impl Step<Self, Option<Todo>> {
+        with_tx_async(move |ctx: &mut MockCtx| {
+            let result = ctx.call(FIND_TODO, &[&id]);
+            Box::pin(async move { result })
+        })
+    }
+
+    fn count(&self) -> impl Step<Self, i64> {
+        with_tx_async(|ctx: &mut MockCtx| {
+            let result = ctx.call(COUNT_TODOS, &[]);
+            Box::pin(async move { result })
+        })
+    }
+
+    fn set_done(&self, id: i64, done: bool) -> impl Step<Self, bool> {
+        with_tx_async(move |ctx: &mut MockCtx| {
+            let result = ctx.call(SET_DONE, &[&done, &id]);
+            Box::pin(async move { result })
+        })
+    }
+
+    fn delete(&self, id: i64) -> impl Step<Self, bool> {
+        with_tx_async(move |ctx: &mut MockCtx| {
+            let result = ctx.call(DELETE_TODO, &[&id]);
+            Box::pin(async move { result })
+        })
+    }
+}
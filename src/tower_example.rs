@@ -0,0 +1,103 @@
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+use sqlx::query;
+use tower::{service_fn, ServiceBuilder, ServiceExt};
+
+use tx::prelude::*;
+use tx::runner::{self, PgCtx, SqlState};
+
+#[derive(Debug)]
+enum Error {
+    Db(sqlx::Error),
+    // stands in for a serialization failure reported by the database
+    Conflict,
+}
+impl From<sqlx::Error> for Error {
+    fn from(e: sqlx::Error) -> Self {
+        Error::Db(e)
+    }
+}
+impl SqlState for Error {
+    fn sqlstate(&self) -> Option<Cow<'_, str>> {
+        match self {
+            Error::Db(e) => e.sqlstate(),
+            Error::Conflict => Some(Cow::Borrowed("40001")),
+        }
+    }
+}
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Db(e) => write!(f, "{}", e),
+            Error::Conflict => write!(f, "conflict"),
+        }
+    }
+}
+impl std::error::Error for Error {}
+
+fn insert_todo_tx(
+    id: i64,
+    conflict: bool,
+) -> impl Tx<PgCtx, Item = i64, Err = Error, Mode = AsyncMode> {
+    with_tx_async(move |_: &mut PgCtx| {
+        Box::pin(async move {
+            if conflict {
+                return Err(Error::Conflict);
+            }
+            Ok(())
+        })
+    })
+    // the insert fails with `sqlx::Error` only, which `Error` converts from
+    .and_then_into(move |()| {
+        with_tx_async(move |transaction: &mut PgCtx| {
+            Box::pin(async move {
+                query!(
+                    r#"INSERT INTO todos (id, description) VALUES ( $1, $2 )"#,
+                    id,
+                    "todo from a service"
+                )
+                .execute(&mut **transaction)
+                .await?;
+                Ok::<_, sqlx::Error>(id)
+            })
+        })
+    })
+}
+
+pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let conn_str =
+        std::env::var("DATABASE_URL").expect("Env var DATABASE_URL is required for this example.");
+    let pool = sqlx::PgPool::connect(&conn_str).await?;
+
+    let test_id = 24;
+
+    query!(r#"DELETE FROM todos WHERE id = $1"#, test_id)
+        .execute(&pool)
+        .await?;
+
+    // the handler only builds the chain; the layer runs it, and here the first one conflicts
+    let calls = Arc::new(AtomicU32::new(0));
+    let handler_calls = calls.clone();
+    let service = ServiceBuilder::new()
+        .layer(runner::TxLayer::new(pool.clone()).retry(runner::RetryPolicy::new()))
+        .service(service_fn(move |id: i64| {
+            let first_call = handler_calls.fetch_add(1, Ordering::SeqCst) == 0;
+            async move { Ok::<_, Error>(insert_todo_tx(id, first_call)) }
+        }));
+
+    let id = service.oneshot(test_id).await?;
+
+    assert_eq!(id, test_id);
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    // the insert of the conflicting attempt was rolled back, so there is no duplicate key
+    let inserted_todos = query!(r#"SELECT id FROM todos WHERE id = $1"#, test_id)
+        .fetch_all(&pool)
+        .await?;
+
+    assert_eq!(inserted_todos.len(), 1);
+
+    Ok(())
+}
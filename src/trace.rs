@@ -0,0 +1,133 @@
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::combinator::{Chain, Description, Output, Tx};
+
+// A record of the steps a chain ran, for tests to assert on the path it took: the steps
+// wrapped by `traced(&trace)` add themselves to it, in the order they start, with how long
+// they took and how they ended. Clones share the record, so keep one to read it after `run`.
+#[derive(Debug, Clone, Default)]
+pub struct TxTrace {
+    steps: Arc<Mutex<Vec<TraceStep>>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceStep {
+    // the name of the step in `describe`
+    pub name: Cow<'static, str>,
+    pub started: Instant,
+    // `None` while it runs, or when it never ended because its future was dropped
+    pub duration: Option<Duration>,
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Ok,
+    Err,
+    Unfinished,
+}
+
+impl TxTrace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn steps(&self) -> Vec<TraceStep> {
+        self.steps.lock().unwrap().clone()
+    }
+    pub fn names(&self) -> Vec<Cow<'static, str>> {
+        let steps = self.steps.lock().unwrap();
+        steps.iter().map(|step| step.name.clone()).collect()
+    }
+
+    pub fn ran(&self, name: &str) -> bool {
+        self.position(name).is_some()
+    }
+    // Whether `first` started before `second` did, both having run.
+    pub fn ran_before(&self, first: &str, second: &str) -> bool {
+        match (self.position(first), self.position(second)) {
+            (Some(first), Some(second)) => first < second,
+            _ => false,
+        }
+    }
+    // How the first run of `name` ended, if it ran.
+    pub fn outcome(&self, name: &str) -> Option<Outcome> {
+        let steps = self.steps.lock().unwrap();
+        let step = steps.iter().find(|step| step.name == name);
+        step.map(|step| step.outcome)
+    }
+
+    pub fn clear(&self) {
+        self.steps.lock().unwrap().clear();
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        let steps = self.steps.lock().unwrap();
+        steps.iter().position(|step| step.name == name)
+    }
+
+    fn start(&self, name: Cow<'static, str>) -> usize {
+        let mut steps = self.steps.lock().unwrap();
+        steps.push(TraceStep {
+            name,
+            started: Instant::now(),
+            duration: None,
+            outcome: Outcome::Unfinished,
+        });
+        steps.len() - 1
+    }
+    fn finish(&self, index: usize, ok: bool) {
+        let mut steps = self.steps.lock().unwrap();
+        let step = &mut steps[index];
+        step.duration = Some(step.started.elapsed());
+        step.outcome = if ok { Outcome::Ok } else { Outcome::Err };
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Traced<X> {
+    tx: X,
+    trace: TxTrace,
+}
+impl<Ctx, X> Tx<Ctx> for Traced<X>
+where
+    X: Tx<Ctx>,
+    X::Mode: Chain<Ctx, X, (TxTrace, usize)>,
+{
+    type Item = X::Item;
+    type Err = X::Err;
+    type Mode = X::Mode;
+
+    fn run<'a>(self, ctx: &'a mut Ctx) -> Output<'a, Self::Mode, Self::Item, Self::Err>
+    where
+        Self: 'a,
+    {
+        let trace = self.trace;
+        let index = trace.start(self.tx.describe().name);
+        X::Mode::map(self.tx, ctx, (trace, index), |(trace, index), r| {
+            trace.finish(index, r.is_ok());
+            r
+        })
+    }
+
+    fn describe(&self) -> Description {
+        self.tx.describe()
+    }
+}
+
+pub trait TraceExt<Ctx>: Tx<Ctx> {
+    // Records the step in `trace` each time it runs, under the name it describes itself
+    // with; `named` gives it one.
+    fn traced(self, trace: &TxTrace) -> Traced<Self>
+    where
+        Self: Sized,
+    {
+        Traced {
+            tx: self,
+            trace: trace.clone(),
+        }
+    }
+}
+impl<Ctx, X: Tx<Ctx>> TraceExt<Ctx> for X {}
@@ -0,0 +1,200 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fmt;
+use std::future::{pending, poll_fn, Future};
+use std::pin::pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use sqlx::Pool;
+
+use crate::combinator::{AsyncMode, BoxFuture, Tx};
+use crate::context::{TxAccess, TxCtx};
+use crate::rt::{self, CancellationToken, Cancelled};
+use crate::runner::{self, Backend, TxOptions};
+
+// Keeps an eye on transactions left open for long, the usual cause of bloat and lock queues:
+// one run with `run_tx_watched` is reported once it has been running for `slow_after`, and
+// again every `slow_after` after that, with the step labelled with `named` it is in. With
+// `cancel_after`, it is cancelled at that point as `run_tx_cancellable` would be: the
+// statement in flight still runs to its end before the rollback, so keep a `statement_timeout`
+// for those.
+#[derive(Clone)]
+pub struct Watchdog {
+    slow_after: Duration,
+    cancel_after: Option<Duration>,
+    on_slow: Arc<dyn Fn(&SlowTx) + Send + Sync>,
+}
+impl Watchdog {
+    // Logs a warning with the `tracing` feature until told otherwise with `on_slow`.
+    pub fn new(slow_after: Duration) -> Self {
+        Self {
+            slow_after: slow_after.max(Duration::from_millis(1)),
+            cancel_after: None,
+            on_slow: Arc::new(warn_slow),
+        }
+    }
+    pub fn on_slow(mut self, f: impl Fn(&SlowTx) + Send + Sync + 'static) -> Self {
+        self.on_slow = Arc::new(f);
+        self
+    }
+    pub fn cancel_after(mut self, limit: Duration) -> Self {
+        self.cancel_after = Some(limit);
+        self
+    }
+
+    // Reports `name` each time it gets past another `slow_after`, until the cancel limit.
+    async fn watch(&self, name: Cow<'static, str>, steps: &Steps, token: &CancellationToken) {
+        let started = Instant::now();
+        let mut next = self.slow_after;
+        loop {
+            let cancelling = self.cancel_after.is_some_and(|limit| limit <= next);
+            let at = self.cancel_after.filter(|_| cancelling).unwrap_or(next);
+            rt::sleep(at.saturating_sub(started.elapsed())).await;
+            (self.on_slow)(&SlowTx {
+                name: name.clone(),
+                step: steps.current(),
+                elapsed: started.elapsed(),
+                cancelled: cancelling,
+            });
+            if cancelling {
+                token.cancel();
+                return pending().await;
+            }
+            next += self.slow_after;
+        }
+    }
+}
+impl fmt::Debug for Watchdog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watchdog")
+            .field("slow_after", &self.slow_after)
+            .field("cancel_after", &self.cancel_after)
+            .finish_non_exhaustive()
+    }
+}
+
+fn warn_slow(slow: &SlowTx) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(target: "tx::watchdog", "{}", slow);
+    #[cfg(not(feature = "tracing"))]
+    let _ = slow;
+}
+
+// What `Watchdog::on_slow` is told about a transaction running for too long.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlowTx {
+    // the name the chain describes itself with
+    pub name: Cow<'static, str>,
+    // the innermost step labelled with `named` running, if any
+    pub step: Option<&'static str>,
+    pub elapsed: Duration,
+    // whether it is being cancelled for it
+    pub cancelled: bool,
+}
+impl fmt::Display for SlowTx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "transaction {} running for {:?}",
+            self.name, self.elapsed
+        )?;
+        if let Some(step) = self.step {
+            write!(f, ", in step {}", step)?;
+        }
+        if self.cancelled {
+            write!(f, ", cancelling it")?;
+        }
+        Ok(())
+    }
+}
+
+// `run_tx_cancellable` under the eye of `watchdog`.
+pub async fn run_tx_watched<DB, A, T, E, X>(
+    pool: &Pool<DB>,
+    options: TxOptions,
+    watchdog: &Watchdog,
+    tx: X,
+) -> Result<T, E>
+where
+    DB: Backend,
+    A: TxAccess,
+    X: Tx<TxCtx<DB, A>, Item = T, Err = E, Mode = AsyncMode>,
+    E: From<sqlx::Error> + From<Cancelled>,
+{
+    let name = tx.describe().name;
+    let steps = Steps::default();
+    let token = CancellationToken::new();
+
+    let mut run = pin!(runner::run_tx_cancellable(pool, options, &token, tx));
+    let mut watch = pin!(watchdog.watch(name, &steps, &token));
+    poll_fn(|cx| {
+        // the steps started while the chain is polled register with `steps`
+        let outer = WATCHED.with(|watched| watched.replace(Some(steps.clone())));
+        let result = run.as_mut().poll(cx);
+        WATCHED.with(|watched| *watched.borrow_mut() = outer);
+        if result.is_pending() {
+            let _ = watch.as_mut().poll(cx);
+        }
+        result
+    })
+    .await
+}
+
+thread_local! {
+    // the steps of the watched transaction being polled on this thread, if any
+    static WATCHED: RefCell<Option<Steps>> = const { RefCell::new(None) };
+}
+
+// The steps running in a watched transaction, innermost last.
+#[derive(Debug, Clone, Default)]
+struct Steps(Arc<Mutex<Vec<&'static str>>>);
+impl Steps {
+    fn current(&self) -> Option<&'static str> {
+        self.0.lock().unwrap().last().copied()
+    }
+    fn enter(self, name: &'static str) -> StepGuard {
+        self.0.lock().unwrap().push(name);
+        StepGuard { steps: self, name }
+    }
+}
+
+struct StepGuard {
+    steps: Steps,
+    name: &'static str,
+}
+impl Drop for StepGuard {
+    fn drop(&mut self) {
+        let mut steps = self.steps.0.lock().unwrap();
+        if let Some(i) = steps.iter().rposition(|step| *step == self.name) {
+            steps.remove(i);
+        }
+    }
+}
+
+fn enter(name: &'static str) -> Option<StepGuard> {
+    WATCHED.with(|watched| watched.borrow().clone().map(|steps| steps.enter(name)))
+}
+
+// The step `name` of `SyncMode`, registered while it runs.
+pub(crate) fn sync_step<R>(name: &'static str, run: impl FnOnce() -> R) -> R {
+    let _guard = enter(name);
+    run()
+}
+
+// The step `name` of `AsyncMode`, registered until its future is over or dropped.
+pub(crate) fn async_step<'a, T: 'a>(
+    name: &'static str,
+    run: impl FnOnce() -> BoxFuture<'a, T>,
+) -> BoxFuture<'a, T> {
+    match enter(name) {
+        Some(guard) => {
+            let future = run();
+            Box::pin(async move {
+                let _guard = guard;
+                future.await
+            })
+        }
+        None => run(),
+    }
+}
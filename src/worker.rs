@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sqlx::types::Json;
+use sqlx::{PgConnection, PgPool};
+
+use crate::combinator::{with_tx_async, AsyncMode, BoxFuture, Tx};
+use crate::rt::{self, CancellationToken};
+use crate::runner::{self, PgCtx};
+
+// A job's chain with its types erased, so chains of all handlers fit in one registry.
+type JobTx = Box<dyn for<'c> FnOnce(&'c mut PgCtx) -> BoxFuture<'c, Result<(), String>> + Send>;
+type Handler = Box<dyn Fn(serde_json::Value) -> Result<JobTx, String> + Send + Sync>;
+
+// Which chain to build for each kind of job.
+#[derive(Default)]
+pub struct Registry {
+    handlers: HashMap<String, Handler>,
+}
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    // `handler` builds the chain of a job of `kind` from its deserialized payload.
+    pub fn register<P, X, E, F>(mut self, kind: impl Into<String>, handler: F) -> Self
+    where
+        P: DeserializeOwned,
+        F: Fn(P) -> X + Send + Sync + 'static,
+        X: Tx<PgCtx, Item = (), Err = E, Mode = AsyncMode> + Send + 'static,
+        E: Display + 'static,
+    {
+        let handler: Handler = Box::new(move |payload| {
+            let payload = serde_json::from_value(payload).map_err(|e| e.to_string())?;
+            let tx = handler(payload);
+            let job: JobTx = Box::new(move |ctx| {
+                let run = tx.run(ctx);
+                Box::pin(async move { run.await.map_err(|e| e.to_string()) })
+            });
+            Ok(job)
+        });
+        self.handlers.insert(kind.into(), handler);
+        self
+    }
+}
+
+// Queues a job. Pass the connection of a transaction (`&mut **ctx` in a step) to have the job
+// queued only if that transaction commits.
+pub async fn enqueue<P: Serialize + Sync>(
+    conn: &mut PgConnection,
+    kind: &str,
+    payload: &P,
+) -> Result<i64, sqlx::Error> {
+    let (id,): (i64,) =
+        sqlx::query_as("INSERT INTO tx_rs_jobs (kind, payload) VALUES ($1, $2) RETURNING id")
+            .bind(kind)
+            .bind(Json(payload))
+            .fetch_one(conn)
+            .await?;
+    Ok(id)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobOutcome {
+    Done { id: i64 },
+    // failed, and queued again for later
+    Retrying { id: i64, attempts: i32 },
+    // failed for good: out of attempts, or no handler or payload it could use
+    Failed { id: i64, attempts: i32 },
+}
+
+// Takes queued jobs one at a time with `FOR UPDATE SKIP LOCKED`, so any number of workers can
+// share the table. The job's chain runs in a savepoint of the transaction holding the row lock,
+// so a failing job leaves nothing behind but its bookkeeping: the attempt is counted and the job
+// either queued again with exponential backoff or, out of attempts, marked failed.
+pub struct Worker {
+    pool: PgPool,
+    registry: Arc<Registry>,
+    max_attempts: i32,
+    base_delay: Duration,
+}
+impl Worker {
+    pub fn new(pool: PgPool, registry: Registry) -> Self {
+        Self {
+            pool,
+            registry: Arc::new(registry),
+            max_attempts: 5,
+            base_delay: Duration::from_secs(1),
+        }
+    }
+    pub fn max_attempts(mut self, max_attempts: i32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+    // Delay before the first retry; it doubles with every further attempt.
+    pub fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    pub async fn create_table(&self) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"CREATE TABLE IF NOT EXISTS tx_rs_jobs
+               (
+                   id          BIGSERIAL PRIMARY KEY,
+                   kind        TEXT        NOT NULL,
+                   payload     JSONB       NOT NULL,
+                   status      TEXT        NOT NULL DEFAULT 'queued',
+                   attempts    INT         NOT NULL DEFAULT 0,
+                   last_error  TEXT,
+                   run_at      TIMESTAMPTZ NOT NULL DEFAULT now(),
+                   finished_at TIMESTAMPTZ
+               )"#,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // Runs the next job which is due, if any.
+    pub async fn run_once(&self) -> Result<Option<JobOutcome>, sqlx::Error> {
+        let registry = self.registry.clone();
+        let (max_attempts, base_delay) = (self.max_attempts, self.base_delay);
+        runner::run_tx(
+            &self.pool,
+            with_tx_async(move |ctx: &mut PgCtx| {
+                Box::pin(async move { run_next(&registry, max_attempts, base_delay, ctx).await })
+            }),
+        )
+        .await
+    }
+
+    // Runs jobs until `token` is cancelled, waiting `idle` whenever none is due.
+    pub async fn run(&self, token: &CancellationToken, idle: Duration) -> Result<(), sqlx::Error> {
+        while !token.is_cancelled() {
+            let ran = match rt::until_cancelled(token, self.run_once()).await {
+                Ok(outcome) => outcome?.is_some(),
+                Err(_) => break,
+            };
+            if !ran {
+                let _ = rt::until_cancelled(token, rt::sleep(idle)).await;
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn run_next(
+    registry: &Registry,
+    max_attempts: i32,
+    base_delay: Duration,
+    ctx: &mut PgCtx,
+) -> Result<Option<JobOutcome>, sqlx::Error> {
+    let job: Option<(i64, String, serde_json::Value, i32)> = sqlx::query_as(
+        r#"SELECT id, kind, payload, attempts FROM tx_rs_jobs
+           WHERE status = 'queued' AND run_at <= now()
+           ORDER BY run_at, id
+           LIMIT 1
+           FOR UPDATE SKIP LOCKED"#,
+    )
+    .fetch_optional(&mut **ctx)
+    .await?;
+    let (id, kind, payload, attempts) = match job {
+        Some(job) => job,
+        None => return Ok(None),
+    };
+    let attempts = attempts + 1;
+
+    let job = match registry.handlers.get(&kind) {
+        Some(handler) => handler(payload),
+        None => Err(format!("no handler for jobs of kind {:?}", kind)),
+    };
+    let result = match job {
+        Ok(job) => {
+            runner::savepoint(with_tx_async(move |ctx: &mut PgCtx| {
+                Box::pin(async move { job(ctx).await.map_err(JobError::Job) })
+            }))
+            .run(ctx)
+            .await
+        }
+        // nothing to retry: the same payload will not deserialize any better next time
+        Err(e) => Err(JobError::Unusable(e)),
+    };
+
+    let outcome = match result {
+        Ok(()) => {
+            sqlx::query(
+                "UPDATE tx_rs_jobs SET status = 'done', attempts = $2, finished_at = now() WHERE id = $1",
+            )
+            .bind(id)
+            .bind(attempts)
+            .execute(&mut **ctx)
+            .await?;
+            JobOutcome::Done { id }
+        }
+        Err(JobError::Db(e)) => return Err(e),
+        Err(JobError::Job(e)) if attempts < max_attempts => {
+            let delay = base_delay * 2u32.saturating_pow(attempts as u32 - 1);
+            sqlx::query(
+                r#"UPDATE tx_rs_jobs
+                   SET attempts = $2, last_error = $3, run_at = now() + $4 * interval '1 millisecond'
+                   WHERE id = $1"#,
+            )
+            .bind(id)
+            .bind(attempts)
+            .bind(e)
+            .bind(delay.as_millis() as f64)
+            .execute(&mut **ctx)
+            .await?;
+            JobOutcome::Retrying { id, attempts }
+        }
+        Err(JobError::Job(e)) | Err(JobError::Unusable(e)) => {
+            sqlx::query(
+                r#"UPDATE tx_rs_jobs
+                   SET status = 'failed', attempts = $2, last_error = $3, finished_at = now()
+                   WHERE id = $1"#,
+            )
+            .bind(id)
+            .bind(attempts)
+            .bind(e)
+            .execute(&mut **ctx)
+            .await?;
+            JobOutcome::Failed { id, attempts }
+        }
+    };
+    Ok(Some(outcome))
+}
+
+enum JobError {
+    // the savepoint itself failed; the transaction is of no use any more
+    Db(sqlx::Error),
+    Job(String),
+    Unusable(String),
+}
+impl From<sqlx::Error> for JobError {
+    fn from(e: sqlx::Error) -> Self {
+        JobError::Db(e)
+    }
+}
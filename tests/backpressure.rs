@@ -0,0 +1,178 @@
+#![cfg(feature = "postgres")]
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::Postgres;
+
+use tx::prelude::*;
+use tx::runner::{Backpressure, BoundedRunner, Priority};
+
+// A transaction holding its connection until `release` is set.
+fn hold(release: Arc<AtomicBool>) -> impl Tx<PgCtx, Item = (), Err = TxError, Mode = AsyncMode> {
+    with_tx_async(move |_: &mut PgCtx| {
+        Box::pin(async move {
+            while !release.load(Ordering::SeqCst) {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+            Ok(())
+        })
+    })
+}
+
+fn record(
+    ran: Arc<Mutex<Vec<&'static str>>>,
+    name: &'static str,
+) -> impl Tx<PgCtx, Item = (), Err = TxError, Mode = AsyncMode> {
+    with_tx_async(move |_: &mut PgCtx| {
+        ran.lock().unwrap().push(name);
+        Box::pin(async { Ok(()) })
+    })
+}
+
+// A runner over a pool of one connection, and a transaction holding it meanwhile.
+async fn saturated(
+    pool_options: PgPoolOptions,
+    connect_options: PgConnectOptions,
+    policy: Backpressure,
+) -> Result<(Arc<BoundedRunner<Postgres>>, Arc<AtomicBool>), sqlx::Error> {
+    let pool = pool_options
+        .max_connections(1)
+        .connect_with(connect_options)
+        .await?;
+    let runner = Arc::new(BoundedRunner::new(pool, policy));
+    let release = Arc::new(AtomicBool::new(false));
+    let holding = (runner.clone(), release.clone());
+    tokio::spawn(async move { holding.0.run(TxOptions::new(), hold(holding.1)).await });
+    while runner.pool().size() == 0 || runner.pool().num_idle() > 0 {
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+    Ok((runner, release))
+}
+
+#[sqlx::test]
+async fn sheds_the_load(
+    pool_options: PgPoolOptions,
+    connect_options: PgConnectOptions,
+) -> Result<(), TxError> {
+    let (runner, release) = saturated(pool_options, connect_options, Backpressure::shed()).await?;
+    let ran = Arc::new(Mutex::new(vec![]));
+    let result = runner
+        .run(TxOptions::new(), record(ran.clone(), "shed"))
+        .await;
+    assert!(
+        matches!(result, Err(TxError::PoolExhausted(e)) if e.waited.is_zero()),
+        "{:?}",
+        result
+    );
+    assert!(ran.lock().unwrap().is_empty());
+
+    release.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn waits_no_longer_than_allowed(
+    pool_options: PgPoolOptions,
+    connect_options: PgConnectOptions,
+) -> Result<(), TxError> {
+    let max_wait = Duration::from_millis(50);
+    let policy = Backpressure::new().max_wait(max_wait);
+    let (runner, release) = saturated(pool_options, connect_options, policy).await?;
+    let ran = Arc::new(Mutex::new(vec![]));
+    let result = runner
+        .run(TxOptions::new(), record(ran.clone(), "late"))
+        .await;
+    let Err(TxError::PoolExhausted(e)) = result else {
+        panic!("{:?}", result);
+    };
+    assert!(e.waited >= max_wait);
+    assert_eq!(TxError::<()>::from(e).error_kind(), ErrorKind::PoolTimedOut);
+    assert_eq!(runner.queued(), 0);
+
+    release.store(true, Ordering::SeqCst);
+    runner
+        .run(TxOptions::new(), record(ran.clone(), "in time"))
+        .await?;
+    assert_eq!(*ran.lock().unwrap(), ["in time"]);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn admits_the_queue_by_priority(
+    pool_options: PgPoolOptions,
+    connect_options: PgConnectOptions,
+) -> Result<(), TxError> {
+    let policy = Backpressure::new().max_queued(2);
+    let (runner, release) = saturated(pool_options, connect_options, policy).await?;
+    let ran = Arc::new(Mutex::new(vec![]));
+
+    let mut queued = vec![];
+    for (priority, name) in [
+        (Priority::Batch, "batch"),
+        (Priority::Interactive, "interactive"),
+    ] {
+        let (queuing, tx) = (runner.clone(), record(ran.clone(), name));
+        queued.push(tokio::spawn(async move {
+            let options = TxOptions::new().priority(priority);
+            queuing.run(options, tx).await
+        }));
+        while runner.queued() < queued.len() {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+    // the queue is full
+    let result = runner
+        .run(TxOptions::new(), record(ran.clone(), "over"))
+        .await;
+    assert!(matches!(result, Err(TxError::PoolExhausted(_))));
+
+    release.store(true, Ordering::SeqCst);
+    for queued in queued {
+        queued.await.unwrap()?;
+    }
+    assert_eq!(*ran.lock().unwrap(), ["interactive", "batch"]);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn keeps_the_batch_within_its_budget(
+    pool_options: PgPoolOptions,
+    connect_options: PgConnectOptions,
+) -> Result<(), TxError> {
+    let pool = pool_options
+        .max_connections(2)
+        .connect_with(connect_options)
+        .await?;
+    let policy = Backpressure::new().budget(Priority::Batch, 1);
+    let runner = Arc::new(BoundedRunner::new(pool, policy));
+    let batch = TxOptions::new().priority(Priority::Batch);
+    let release = Arc::new(AtomicBool::new(false));
+    let holding = (runner.clone(), release.clone());
+    tokio::spawn(async move { holding.0.run(batch, hold(holding.1)).await });
+    while runner.held(Priority::Batch) == 0 {
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+
+    // the second batch job waits, though a connection is free
+    let ran = Arc::new(Mutex::new(vec![]));
+    let (queuing, tx) = (runner.clone(), record(ran.clone(), "batch"));
+    let queued = tokio::spawn(async move { queuing.run(batch, tx).await });
+    while runner.queued() == 0 {
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+    // which is left to the interactive traffic
+    runner
+        .run(TxOptions::new(), record(ran.clone(), "interactive"))
+        .await?;
+    assert_eq!(*ran.lock().unwrap(), ["interactive"]);
+    assert_eq!(runner.queued(), 1);
+
+    release.store(true, Ordering::SeqCst);
+    queued.await.unwrap()?;
+    assert_eq!(*ran.lock().unwrap(), ["interactive", "batch"]);
+    assert_eq!(runner.held(Priority::Batch), 0);
+    Ok(())
+}
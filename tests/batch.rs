@@ -0,0 +1,76 @@
+#![cfg(feature = "postgres")]
+
+use sqlx::{PgPool, Postgres};
+
+use tx::prelude::*;
+use tx::runner::{self, batch, sql};
+
+// How many statements the chain issued through its context, i.e. its round trips.
+fn statements() -> impl Tx<PgCtx, Item = u32, Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(|ctx: &mut PgCtx| {
+        let statements = ctx.statements();
+        Box::pin(async move { Ok(statements) })
+    })
+}
+
+async fn descriptions(pool: &PgPool) -> Result<Vec<(i64, String)>, sqlx::Error> {
+    sqlx::query_as("SELECT id, description FROM todos ORDER BY id")
+        .fetch_all(pool)
+        .await
+}
+
+#[sqlx::test]
+async fn combines_the_writes_into_one_statement(pool: PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO todos (id, description) VALUES (1, 'old'), (2, 'gone')")
+        .execute(&pool)
+        .await?;
+    let writes = batch(vec![
+        sql::<Postgres>("INSERT INTO todos (id, description) VALUES ($1, '$1 ' || $2)")
+            .bind(3_i64)
+            .bind("new".to_string()),
+        sql::<Postgres>("UPDATE todos SET description = $1 WHERE id = $2;")
+            .bind("updated".to_string())
+            .bind(1_i64),
+        sql::<Postgres>("DELETE FROM todos WHERE id = $1 -- $2").bind(2_i64),
+        sql::<Postgres>("DELETE FROM todos WHERE id > 100"),
+    ]);
+    let chain = writes.join(statements());
+    let (rows, statements) = run_tx(&pool, chain).await?;
+
+    assert_eq!(rows, vec![1, 1, 1, 0]);
+    assert_eq!(statements, 1);
+    assert_eq!(
+        descriptions(&pool).await?,
+        vec![(1, "updated".to_string()), (3, "$1 new".to_string())]
+    );
+    Ok(())
+}
+
+#[sqlx::test]
+async fn runs_what_it_cannot_combine_one_by_one(pool: PgPool) -> Result<(), sqlx::Error> {
+    let writes = batch(vec![
+        sql::<Postgres>("INSERT INTO todos (id, description) VALUES ($1, 'a') RETURNING id")
+            .bind(1_i64),
+        sql::<Postgres>("INSERT INTO todos (id, description) VALUES ($1, $$b$$)").bind(2_i64),
+    ]);
+    let (rows, statements) = run_tx(&pool, writes.join(statements())).await?;
+
+    assert_eq!(rows, vec![1, 1]);
+    assert_eq!(statements, 2);
+    assert_eq!(descriptions(&pool).await?.len(), 2);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn lists_every_statement_in_a_dry_run(pool: PgPool) -> Result<(), sqlx::Error> {
+    let writes = batch(vec![
+        sql::<Postgres>("INSERT INTO todos (id, description) VALUES ($1, 'a')").bind(1_i64),
+        sql::<Postgres>("DELETE FROM todos WHERE id = $1").bind(2_i64),
+    ]);
+    let (rows, plan) = runner::run_tx_dry(&pool, TxOptions::new(), writes).await?;
+
+    assert_eq!(rows, vec![0, 0]);
+    assert_eq!(plan.statements.len(), 2);
+    assert!(descriptions(&pool).await?.is_empty());
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use tx::prelude::*;
+
+// A context which only logs the steps run.
+type Log = Vec<&'static str>;
+
+fn step<T>(
+    name: &'static str,
+    out: Result<T, &'static str>,
+) -> impl FnOnce(&mut Log) -> Result<T, &'static str> {
+    move |log: &mut Log| {
+        log.push(name);
+        out
+    }
+}
+
+fn run<X: Tx<Log, Mode = SyncMode>>(tx: X) -> (Result<X::Item, X::Err>, Log) {
+    let mut log = Log::new();
+    let result = tx.run(&mut log);
+    (result, log)
+}
+
+#[test]
+fn releases_after_the_use() {
+    let chain = bracket(
+        step("acquire", Ok(1)),
+        |r| step("use", Ok(r + 1)),
+        |_| step("release", Ok(())),
+    );
+    assert_eq!(chain.describe().to_string(), "bracket\n  closure\n");
+    assert_eq!(run(chain), (Ok(2), vec!["acquire", "use", "release"]));
+}
+
+#[test]
+fn releases_after_a_failed_use() {
+    let chain = bracket(
+        step("acquire", Ok(1)),
+        |_| step::<i32>("use", Err("use")),
+        |_| step::<()>("release", Err("release")),
+    );
+    // the error of the use wins over that of the release
+    assert_eq!(run(chain), (Err("use"), vec!["acquire", "use", "release"]));
+
+    let chain = bracket(
+        step("acquire", Ok(1)),
+        |r| step("use", Ok(r)),
+        |_| step::<()>("release", Err("release")),
+    );
+    assert_eq!(
+        run(chain),
+        (Err("release"), vec!["acquire", "use", "release"])
+    );
+}
+
+#[test]
+fn neither_uses_nor_releases_what_was_not_acquired() {
+    let chain = bracket(
+        step::<i32>("acquire", Err("acquire")),
+        |r| step("use", Ok(r)),
+        |_| step("release", Ok(())),
+    );
+    assert_eq!(run(chain), (Err("acquire"), vec!["acquire"]));
+}
+
+#[cfg(feature = "postgres")]
+mod postgres {
+    use sqlx::{PgPool, Postgres};
+
+    use tx::prelude::*;
+    use tx::runner::{self, Sql};
+
+    const KEY: i64 = 4242;
+
+    fn sql(sql: &str) -> Sql<Postgres> {
+        runner::sql(sql)
+    }
+
+    async fn locks(pool: &PgPool) -> Result<i64, sqlx::Error> {
+        let sql = "SELECT count(*) FROM pg_locks WHERE locktype = 'advisory' AND objid = $1";
+        sqlx::query_scalar(sql)
+            .bind(KEY as i32)
+            .fetch_one(pool)
+            .await
+    }
+
+    // A session advisory lock outlives the transaction: it has to be released even when the
+    // chain fails, or the pooled connection keeps it.
+    #[sqlx::test]
+    async fn releases_an_advisory_lock(pool: PgPool) -> Result<(), sqlx::Error> {
+        let chain = bracket(
+            sql("SELECT pg_advisory_lock($1)").bind(KEY).map(|_| KEY),
+            // a failed statement aborts the transaction on Postgres, unless in a savepoint
+            |_| savepoint(sql("SELECT 1 / 0")),
+            |key| sql("SELECT pg_advisory_unlock($1)").bind(key),
+        );
+        let result = run_tx(&pool, chain).await;
+        assert!(result.is_err());
+        assert_eq!(locks(&pool).await?, 0);
+
+        let chain = bracket(
+            sql("SELECT pg_advisory_lock($1)").bind(KEY).map(|_| KEY),
+            |_| {
+                sql("SELECT count(*) FROM pg_locks WHERE locktype = 'advisory'")
+                    .fetch_one::<(i64,), WriteTx>()
+            },
+            |key| sql("SELECT pg_advisory_unlock($1)").bind(key),
+        );
+        assert_eq!(run_tx(&pool, chain).await?, (1,));
+        assert_eq!(locks(&pool).await?, 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+#![cfg(feature = "postgres")]
+
+use sqlx::PgPool;
+
+use tx::chain::TxChain;
+use tx::prelude::*;
+
+fn insert(id: i64) -> impl Tx<PgCtx, Item = i64, Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(move |ctx: &mut PgCtx| {
+        Box::pin(async move {
+            sqlx::query("INSERT INTO todos (id, description) VALUES ($1, 'chained')")
+                .bind(id)
+                .execute(&mut **ctx)
+                .await?;
+            Ok(id)
+        })
+    })
+}
+
+fn count() -> impl Tx<PgCtx, Item = i64, Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(|ctx: &mut PgCtx| {
+        Box::pin(async move {
+            sqlx::query_scalar("SELECT count(*) FROM todos")
+                .fetch_one(&mut **ctx)
+                .await
+        })
+    })
+}
+
+#[sqlx::test]
+async fn runs_the_steps_in_order(pool: PgPool) -> Result<(), sqlx::Error> {
+    let chain = TxChain::new(insert(1))
+        .and_then(|id| insert(id + 1))
+        .map(|id| id + 1)
+        .and_then(insert)
+        .and_then(|_| count());
+    assert_eq!(
+        chain.describe().to_string(),
+        "chain\n  with_tx_async\n  and_then\n  map\n  and_then\n  and_then\n"
+    );
+    assert_eq!(run_tx(&pool, chain.named("chained")).await?, 3);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn stops_at_the_first_error(pool: PgPool) -> Result<(), sqlx::Error> {
+    // the second insert breaks the primary key
+    let chain = TxChain::new(insert(1))
+        .and_then(|_| insert(1))
+        .map(|_| -> i64 { panic!("run after an error") });
+    assert!(run_tx(&pool, chain).await.is_err());
+    assert_eq!(run_tx(&pool, count()).await?, 0);
+    Ok(())
+}
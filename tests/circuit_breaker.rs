@@ -0,0 +1,61 @@
+#![cfg(feature = "postgres")]
+
+use std::time::Duration;
+
+use sqlx::{PgPool, Postgres};
+
+use tx::prelude::*;
+use tx::runner::{self, BreakerState, CircuitBreaker, CircuitBreakerExt};
+
+// A call to a flaky resource, failing with a division by zero when `fails`.
+async fn call(pool: &PgPool, breaker: &CircuitBreaker, fails: bool) -> Result<i32, TxError> {
+    let sql = if fails { "SELECT 1 / 0" } else { "SELECT 1" };
+    let step = runner::sql::<Postgres>(sql).fetch_one::<(i32,), WriteTx>();
+    let step = step.circuit_breaker(breaker.clone());
+    Ok(runner::run_tx(pool, step).await?.0)
+}
+
+#[sqlx::test]
+async fn opens_on_failures_and_closes_after_a_trial(pool: PgPool) -> Result<(), TxError> {
+    let open_for = Duration::from_millis(50);
+    let breaker = CircuitBreaker::new()
+        .window(4)
+        .failure_rate(0.5)
+        .open_for(open_for);
+    for fails in [false, true, false] {
+        let _ = call(&pool, &breaker, fails).await;
+    }
+    assert_eq!(breaker.state(), BreakerState::Closed);
+    // two failures out of the last four
+    assert!(call(&pool, &breaker, true).await.is_err());
+    assert_eq!(breaker.state(), BreakerState::Open);
+
+    let result = call(&pool, &breaker, false).await;
+    let Err(TxError::CircuitOpen(open)) = result else {
+        panic!("{:?}", result);
+    };
+    assert!(open.retry_after > Duration::ZERO && open.retry_after <= open_for);
+
+    // the failing trial opens it again, the succeeding one closes it
+    tokio::time::sleep(open_for).await;
+    assert_eq!(breaker.state(), BreakerState::HalfOpen);
+    assert!(matches!(
+        call(&pool, &breaker, true).await,
+        Err(TxError::Db(_))
+    ));
+    assert_eq!(breaker.state(), BreakerState::Open);
+    tokio::time::sleep(open_for).await;
+    assert_eq!(call(&pool, &breaker, false).await?, 1);
+    assert_eq!(breaker.state(), BreakerState::Closed);
+    Ok(())
+}
+
+#[test]
+fn describes_the_guarded_step() {
+    let step = runner::sql::<Postgres>("SELECT 1").fetch_one::<(i32,), WriteTx>();
+    let chain = step.circuit_breaker(CircuitBreaker::new());
+    assert_eq!(
+        chain.describe().to_string().lines().next(),
+        Some("circuit_breaker")
+    );
+}
@@ -0,0 +1,78 @@
+#![cfg(feature = "diesel")]
+
+use diesel::result::Error;
+use diesel::sql_types::Text;
+use diesel::{sql_query, Connection, PgConnection, QueryableByName, RunQueryDsl};
+
+use tx::diesel::run_tx;
+use tx::prelude::*;
+
+#[derive(QueryableByName)]
+struct Note {
+    #[diesel(sql_type = Text)]
+    body: String,
+}
+
+// A connection of its own to the database of `DATABASE_URL`, with a temporary `notes` table
+// which goes away with it.
+fn notes_table() -> Result<PgConnection, Box<dyn std::error::Error>> {
+    let mut conn = PgConnection::establish(&std::env::var("DATABASE_URL")?)?;
+    sql_query("CREATE TEMPORARY TABLE notes (body TEXT PRIMARY KEY)").execute(&mut conn)?;
+    Ok(conn)
+}
+
+fn add_note(body: &'static str) -> impl Tx<PgConnection, Item = (), Err = Error, Mode = SyncMode> {
+    with_tx(move |conn: &mut PgConnection| {
+        sql_query("INSERT INTO notes (body) VALUES ($1)")
+            .bind::<Text, _>(body)
+            .execute(conn)?;
+        Ok(())
+    })
+}
+
+fn notes() -> impl Tx<PgConnection, Item = Vec<String>, Err = Error, Mode = SyncMode> {
+    with_tx(|conn: &mut PgConnection| {
+        let notes: Vec<Note> = sql_query("SELECT body FROM notes ORDER BY body").load(conn)?;
+        Ok(notes.into_iter().map(|note| note.body).collect())
+    })
+}
+
+#[test]
+fn commits_on_ok_and_rolls_back_on_err() -> Result<(), Box<dyn std::error::Error>> {
+    let mut conn = notes_table()?;
+    run_tx(
+        &mut conn,
+        add_note("kept").and_then(|()| add_note("kept too")),
+    )?;
+
+    let failing = add_note("lost").and_then(|()| add_note("kept"));
+    assert!(run_tx(&mut conn, failing).is_err());
+    let failing = add_note("lost")
+        .and_then(|()| with_tx(|_: &mut PgConnection| Err::<(), _>(Error::RollbackTransaction)));
+    assert!(matches!(
+        run_tx(&mut conn, failing),
+        Err(Error::RollbackTransaction)
+    ));
+    assert_eq!(run_tx(&mut conn, notes())?, ["kept", "kept too"]);
+    Ok(())
+}
+
+#[test]
+fn rolls_back_a_savepoint_only() -> Result<(), Box<dyn std::error::Error>> {
+    let mut conn = notes_table()?;
+    let chain = add_note("outer").and_then(|()| {
+        savepoint(add_note("inner").and_then(|()| add_note("outer")))
+            .map(|()| true)
+            .recover(|_| false)
+    });
+    assert!(!run_tx(&mut conn, chain)?);
+    assert_eq!(run_tx(&mut conn, notes())?, ["outer"]);
+
+    // a chain run within a transaction of diesel's own
+    let serializable = conn
+        .build_transaction()
+        .serializable()
+        .run(|conn| add_note("serializable").and_then(|()| notes()).run(conn))?;
+    assert_eq!(serializable, ["outer", "serializable"]);
+    Ok(())
+}
@@ -0,0 +1,86 @@
+#![cfg(feature = "postgres")]
+
+use std::sync::{Arc, Mutex, OnceLock};
+
+use sqlx::{PgPool, Postgres};
+
+use tx::observer::{add_observer, TxEvent, TxId, TxObserver};
+use tx::prelude::*;
+use tx::runner::{sql, ExplainExt};
+
+// The plans reported, with their statement. The observers are shared by the whole process, so
+// each test looks at its own statements only.
+
+#[derive(Clone, Default)]
+struct Plans(Arc<Mutex<Vec<(String, String)>>>);
+impl Plans {
+    fn of(&self, sql: &str) -> Vec<String> {
+        let plans = self.0.lock().unwrap();
+        let found = plans.iter().filter(|(explained, _)| explained == sql);
+        found.map(|(_, plan)| plan.clone()).collect()
+    }
+}
+impl TxObserver for Plans {
+    fn on_event(&self, _: TxId, event: &TxEvent<'_>) {
+        if let TxEvent::Explained { sql, plan } = event {
+            let explained = (sql.to_string(), plan.to_string());
+            self.0.lock().unwrap().push(explained);
+        }
+    }
+}
+
+fn plans() -> Plans {
+    static PLANS: OnceLock<Plans> = OnceLock::new();
+    let plans = PLANS.get_or_init(|| {
+        let plans = Plans::default();
+        add_observer(plans.clone());
+        plans
+    });
+    plans.clone()
+}
+
+const INSERT: &str = "INSERT INTO todos (id, description) VALUES ($1, $2)";
+const COUNT: &str = "SELECT count(*) FROM todos WHERE description = $1";
+
+#[sqlx::test]
+async fn reports_the_plans_and_writes_once(pool: PgPool) -> Result<(), sqlx::Error> {
+    let plans = plans();
+    let insert = sql::<Postgres>(INSERT)
+        .bind(1_i64)
+        .bind("explained".to_string());
+    let count = sql::<Postgres>(COUNT).bind("explained".to_string());
+    let chain = insert
+        .and_then(move |_| count.fetch_one::<(i64,), WriteTx>())
+        .explained();
+    assert_eq!(run_tx(&pool, chain).await?, (1,));
+
+    let [plan] = &plans.of(INSERT)[..] else {
+        panic!("one plan expected for the insert");
+    };
+    assert!(plan.starts_with("Insert on todos"), "{}", plan);
+    assert!(plan.contains("actual time"), "{}", plan);
+    assert!(plan.contains("Execution Time"), "{}", plan);
+    assert_eq!(plans.of(COUNT).len(), 1);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn leaves_the_other_steps_alone(pool: PgPool) -> Result<(), sqlx::Error> {
+    let plans = plans();
+    let unexplained = "SELECT count(*) FROM todos WHERE id = $1";
+    let explained = "SELECT count(*) FROM todos WHERE description <> $1";
+    let chain = sql::<Postgres>(unexplained)
+        .bind(1_i64)
+        .fetch_one::<(i64,), WriteTx>()
+        .and_then(|_| {
+            sql::<Postgres>(explained)
+                .bind("unexplained".to_string())
+                .fetch_one::<(i64,), WriteTx>()
+                .explained()
+        });
+    run_tx(&pool, chain).await?;
+
+    assert!(plans.of(unexplained).is_empty());
+    assert_eq!(plans.of(explained).len(), 1);
+    Ok(())
+}
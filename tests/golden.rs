@@ -0,0 +1,47 @@
+#![cfg(all(feature = "postgres", feature = "tracing"))]
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use serde_json::Value;
+
+// The reports of `sqlx-test report` against `tests/golden/<scenario>.json`.
+// `UPDATE_GOLDEN=1 cargo test --test golden` rewrites the files from the current output.
+#[test]
+fn scenarios_match_the_golden_files() {
+    let output = Command::new(env!("CARGO_BIN_EXE_sqlx-test"))
+        .arg("report")
+        .output()
+        .expect("cannot run the example binary");
+    assert!(
+        output.status.success(),
+        "report failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let reports: Vec<Value> = serde_json::from_slice(&output.stdout).expect("not a JSON report");
+    assert!(!reports.is_empty());
+
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    for report in reports {
+        let scenario = report["scenario"].as_str().expect("no scenario name");
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(format!("{}.json", scenario));
+        if update {
+            let json = serde_json::to_string_pretty(&report).unwrap() + "\n";
+            std::fs::write(&path, json).unwrap();
+            continue;
+        }
+
+        let golden =
+            std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        let golden: Value = serde_json::from_str(&golden).unwrap();
+        assert_eq!(
+            report,
+            golden,
+            "{} differs from {}",
+            scenario,
+            path.display()
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use tx::combinator::Description;
+
+// A tree with a node of each kind, as the combinators describe themselves.
+fn description() -> Description {
+    let import = Description::new("import", vec![Description::leaf("with_tx_async")]);
+    let notify = Description::new(
+        "join",
+        vec![Description::leaf("audit"), Description::leaf("notify")],
+    );
+    Description::new(
+        "import_todos",
+        vec![Description::new(
+            "then",
+            vec![Description::new("or_else", vec![import]), notify],
+        )],
+    )
+}
+
+#[test]
+fn renders_dot() {
+    assert_eq!(
+        description().to_dot(),
+        r#"digraph tx {
+    node [shape=box];
+    n0 [label="import_todos"];
+    n1 [label="then"];
+    n2 [label="or_else", shape=hexagon];
+    n3 [label="import"];
+    n4 [label="with_tx_async"];
+    n5 [label="…", style=dashed];
+    n6 [label="join", shape=diamond];
+    n7 [label="audit"];
+    n8 [label="notify"];
+    n9 [label="…", style=dashed];
+    n0 -> n1;
+    n1 -> n2;
+    n2 -> n3;
+    n3 -> n4;
+    n2 -> n5 [label="on error", style=dashed];
+    n1 -> n6;
+    n6 -> n7 [label="1"];
+    n6 -> n8 [label="2"];
+    n1 -> n9 [label="then", style=dashed];
+}
+"#
+    );
+}
+
+#[test]
+fn renders_mermaid() {
+    assert_eq!(
+        description().to_mermaid(),
+        r#"flowchart TD
+    n0["import_todos"]
+    n1["then"]
+    n2{{"or_else"}}
+    n3["import"]
+    n4["with_tx_async"]
+    n5["…"]
+    n6{"join"}
+    n7["audit"]
+    n8["notify"]
+    n9["…"]
+    n0 --> n1
+    n1 --> n2
+    n2 --> n3
+    n3 --> n4
+    n2 -.->|on error| n5
+    n1 --> n6
+    n6 -->|1| n7
+    n6 -->|2| n8
+    n1 -.->|then| n9
+"#
+    );
+}
+
+#[test]
+fn escapes_labels() {
+    let description = Description::leaf(r#"context: "quoted""#);
+    assert!(description
+        .to_dot()
+        .contains(r#"n0 [label="context: \"quoted\""];"#));
+    assert!(description
+        .to_mermaid()
+        .contains(r#"n0["context: #quot;quoted#quot;"]"#));
+}
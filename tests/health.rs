@@ -0,0 +1,53 @@
+#![cfg(feature = "postgres")]
+
+use std::time::Duration;
+
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::PgPool;
+
+use tx::prelude::*;
+use tx::runner::{self, health_check, warmup, ReadTx};
+
+#[sqlx::test]
+async fn warms_the_pool_up(
+    pool_options: PgPoolOptions,
+    connect_options: PgConnectOptions,
+) -> Result<(), sqlx::Error> {
+    let pool = pool_options
+        .max_connections(3)
+        .connect_with(connect_options)
+        .await?;
+    warmup(&pool, 2).await?;
+    assert_eq!(pool.size(), 2);
+    // no further than the pool may go
+    warmup(&pool, 5).await?;
+    assert_eq!(pool.size(), 3);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn checks_the_server_answers(pool: PgPool) -> Result<(), sqlx::Error> {
+    let check = health_check::<_, ReadTx>(Duration::from_secs(1));
+    runner::run_tx(&pool, check).await
+}
+
+#[sqlx::test]
+async fn tells_a_starved_pool_apart(
+    pool_options: PgPoolOptions,
+    connect_options: PgConnectOptions,
+) -> Result<(), sqlx::Error> {
+    let pool = pool_options
+        .max_connections(1)
+        .acquire_timeout(Duration::from_millis(50))
+        .connect_with(connect_options)
+        .await?;
+    let _held = pool.acquire().await?;
+
+    let check = health_check::<_, ReadTx>(Duration::from_secs(1));
+    let result: Result<(), TxError> = runner::run_tx(&pool, check.map_err(TxError::from)).await;
+    let e = result.unwrap_err();
+    assert!(matches!(e, TxError::PoolTimedOut), "{:?}", e);
+    assert_eq!(e.error_kind(), ErrorKind::PoolTimedOut);
+    assert!(e.error_kind().is_transient());
+    Ok(())
+}
@@ -0,0 +1,199 @@
+#![cfg(feature = "postgres")]
+
+use sqlx::PgPool;
+
+use tx::prelude::*;
+use tx::runner::IsolationLevel;
+use tx::testing::isolation::{run_pair, Schedule, Turn, Turn::*};
+
+// The anomalies of each isolation level, on a database of their own from `#[sqlx::test]`.
+
+fn description(id: i64) -> impl Tx<PgCtx, Item = String, Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(move |ctx: &mut PgCtx| {
+        Box::pin(async move {
+            sqlx::query_scalar("SELECT description FROM todos WHERE id = $1")
+                .bind(id)
+                .fetch_one(&mut **ctx)
+                .await
+        })
+    })
+}
+fn set_description(
+    id: i64,
+    description: String,
+) -> impl Tx<PgCtx, Item = (), Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(move |ctx: &mut PgCtx| {
+        Box::pin(async move {
+            sqlx::query("UPDATE todos SET description = $2 WHERE id = $1")
+                .bind(id)
+                .bind(description)
+                .execute(&mut **ctx)
+                .await?;
+            Ok(())
+        })
+    })
+}
+fn count_open() -> impl Tx<PgCtx, Item = i64, Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(|ctx: &mut PgCtx| {
+        Box::pin(async move {
+            sqlx::query_scalar("SELECT count(*) FROM todos WHERE NOT done")
+                .fetch_one(&mut **ctx)
+                .await
+        })
+    })
+}
+fn close(id: i64) -> impl Tx<PgCtx, Item = (), Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(move |ctx: &mut PgCtx| {
+        Box::pin(async move {
+            sqlx::query("UPDATE todos SET done = TRUE WHERE id = $1")
+                .bind(id)
+                .execute(&mut **ctx)
+                .await?;
+            Ok(())
+        })
+    })
+}
+
+async fn insert_todos(pool: &PgPool, ids: &[i64]) -> Result<(), sqlx::Error> {
+    for id in ids {
+        sqlx::query("INSERT INTO todos (id, description) VALUES ($1, 'original')")
+            .bind(id)
+            .execute(pool)
+            .await?;
+    }
+    Ok(())
+}
+
+fn options(level: IsolationLevel) -> TxOptions {
+    TxOptions::new().isolation_level(level)
+}
+
+// A reads a row twice, B updates it and commits in between.
+async fn read_twice(pool: &PgPool, level: IsolationLevel) -> (String, String) {
+    let schedule = Schedule::new([A("first"), B("update"), B("end"), A("second")]);
+    let a = schedule.at(A("first"), description(1)).and_then({
+        let schedule = schedule.clone();
+        move |first| {
+            schedule
+                .at(A("second"), description(1))
+                .map(move |second| (first, second))
+        }
+    });
+    let b = schedule.at(B("update"), set_description(1, "changed".to_string()));
+
+    let (a, b) = run_pair(pool, &schedule, (options(level), a), (options(level), b)).await;
+    b.unwrap();
+    a.unwrap()
+}
+
+#[sqlx::test]
+async fn read_committed_lets_reads_differ(pool: PgPool) -> Result<(), sqlx::Error> {
+    insert_todos(&pool, &[1]).await?;
+    let (first, second) = read_twice(&pool, IsolationLevel::ReadCommitted).await;
+    assert_eq!((first.as_str(), second.as_str()), ("original", "changed"));
+    Ok(())
+}
+
+#[sqlx::test]
+async fn repeatable_read_reads_the_same(pool: PgPool) -> Result<(), sqlx::Error> {
+    insert_todos(&pool, &[1]).await?;
+    let (first, second) = read_twice(&pool, IsolationLevel::RepeatableRead).await;
+    assert_eq!((first.as_str(), second.as_str()), ("original", "original"));
+    Ok(())
+}
+
+// Both read the row, then write it back with their suffix; B writes after A committed.
+fn append(
+    schedule: &Schedule,
+    read: Turn,
+    write: Turn,
+    suffix: &'static str,
+) -> impl Tx<PgCtx, Item = (), Err = sqlx::Error, Mode = AsyncMode> {
+    let schedule = schedule.clone();
+    schedule
+        .clone()
+        .at(read, description(1))
+        .and_then(move |d| schedule.at(write, set_description(1, format!("{} {}", d, suffix))))
+}
+
+async fn update_both(
+    pool: &PgPool,
+    level: IsolationLevel,
+) -> (Result<(), sqlx::Error>, Result<(), sqlx::Error>) {
+    let schedule = Schedule::new([A("read"), B("read"), A("write"), A("end"), B("write")]);
+    let a = append(&schedule, A("read"), A("write"), "a");
+    let b = append(&schedule, B("read"), B("write"), "b");
+    run_pair(pool, &schedule, (options(level), a), (options(level), b)).await
+}
+
+#[sqlx::test]
+async fn read_committed_loses_an_update(pool: PgPool) -> Result<(), sqlx::Error> {
+    insert_todos(&pool, &[1]).await?;
+    let (a, b) = update_both(&pool, IsolationLevel::ReadCommitted).await;
+    a?;
+    b?;
+    let description = run_tx(&pool, description(1)).await?;
+    assert_eq!(description, "original b");
+    Ok(())
+}
+
+#[sqlx::test]
+async fn repeatable_read_refuses_a_lost_update(pool: PgPool) -> Result<(), sqlx::Error> {
+    insert_todos(&pool, &[1]).await?;
+    let (a, b) = update_both(&pool, IsolationLevel::RepeatableRead).await;
+    a?;
+    assert_eq!(b.unwrap_err().error_kind(), ErrorKind::SerializationFailure);
+    let description = run_tx(&pool, description(1)).await?;
+    assert_eq!(description, "original a");
+    Ok(())
+}
+
+// Each closes a todo of its own when it sees both open, so that one is left open.
+fn close_if_both_open(
+    schedule: &Schedule,
+    count: Turn,
+    write: Turn,
+    id: i64,
+) -> impl Tx<PgCtx, Item = (), Err = sqlx::Error, Mode = AsyncMode> {
+    let schedule = schedule.clone();
+    schedule
+        .clone()
+        .at(count, count_open())
+        .and_then(move |open| {
+            schedule.at(
+                write,
+                close(id).map(move |()| assert_eq!(open, 2, "both are open")),
+            )
+        })
+}
+
+async fn close_both(
+    pool: &PgPool,
+    level: IsolationLevel,
+) -> (Result<(), sqlx::Error>, Result<(), sqlx::Error>) {
+    let schedule = Schedule::new([A("count"), B("count"), A("close"), B("close")]);
+    let a = close_if_both_open(&schedule, A("count"), A("close"), 1);
+    let b = close_if_both_open(&schedule, B("count"), B("close"), 2);
+    run_pair(pool, &schedule, (options(level), a), (options(level), b)).await
+}
+
+#[sqlx::test]
+async fn repeatable_read_lets_write_skew_through(pool: PgPool) -> Result<(), sqlx::Error> {
+    insert_todos(&pool, &[1, 2]).await?;
+    let (a, b) = close_both(&pool, IsolationLevel::RepeatableRead).await;
+    a?;
+    b?;
+    assert_eq!(run_tx(&pool, count_open()).await?, 0);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn serializable_refuses_write_skew(pool: PgPool) -> Result<(), sqlx::Error> {
+    insert_todos(&pool, &[1, 2]).await?;
+    let (a, b) = close_both(&pool, IsolationLevel::Serializable).await;
+    let failed: Vec<_> = vec![a, b].into_iter().filter_map(Result::err).collect();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].error_kind(), ErrorKind::SerializationFailure);
+    assert_eq!(run_tx(&pool, count_open()).await?, 1);
+    Ok(())
+}
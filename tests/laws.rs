@@ -0,0 +1,134 @@
+use proptest::prelude::*;
+
+use tx::prelude::*;
+
+// The algebraic laws the combinators keep, over a context which only logs the steps run:
+// both sides of each law must end the same way after running the same steps, in order.
+
+#[derive(Default)]
+struct Log(Vec<u8>);
+
+type Out = Result<i32, u8>;
+
+// A step logging `tag` and ending with `out`.
+fn step(tag: u8, out: Out) -> impl Tx<Log, Item = i32, Err = u8, Mode = SyncMode> {
+    with_tx(move |log: &mut Log| {
+        log.0.push(tag);
+        out
+    })
+}
+
+// A step depending on what it is given, to chain with `and_then`.
+fn next(tag: u8, out: Out, x: i32) -> impl Tx<Log, Item = i32, Err = u8, Mode = SyncMode> {
+    step(tag, out.map(|y| y.wrapping_add(x)))
+}
+
+fn run<X: Tx<Log, Mode = SyncMode>>(tx: X) -> (Result<X::Item, X::Err>, Vec<u8>) {
+    let mut log = Log::default();
+    let result = tx.run(&mut log);
+    (result, log.0)
+}
+
+fn out() -> impl Strategy<Value = Out> {
+    prop_oneof![any::<i32>().prop_map(Ok), any::<u8>().prop_map(Err)]
+}
+
+proptest! {
+    #[test]
+    fn map_identity(tag: u8, out in out()) {
+        prop_assert_eq!(run(step(tag, out).map(|x| x)), run(step(tag, out)));
+    }
+
+    #[test]
+    fn map_composition(tag: u8, out in out(), a: i32, b: i32) {
+        let f = move |x: i32| x.wrapping_add(a);
+        let g = move |x: i32| x.wrapping_mul(b);
+        prop_assert_eq!(
+            run(step(tag, out).map(f).map(g)),
+            run(step(tag, out).map(move |x| g(f(x))))
+        );
+    }
+
+    #[test]
+    fn and_then_left_identity(a: i32, tag: u8, out in out()) {
+        prop_assert_eq!(
+            run(ready(Ok(a)).and_then(|x| next(tag, out, x))),
+            run(next(tag, out, a))
+        );
+    }
+
+    #[test]
+    fn and_then_right_identity(tag: u8, out in out()) {
+        prop_assert_eq!(
+            run(step(tag, out).and_then(|x| Ok(x).into_tx())),
+            run(step(tag, out))
+        );
+    }
+
+    #[test]
+    fn and_then_associativity(
+        (t1, t2, t3) in (any::<u8>(), any::<u8>(), any::<u8>()),
+        (o1, o2, o3) in (out(), out(), out()),
+    ) {
+        let left = run(
+            step(t1, o1)
+                .and_then(|x| next(t2, o2, x))
+                .and_then(|y| next(t3, o3, y)),
+        );
+        let right = run(step(t1, o1).and_then(|x| next(t2, o2, x).and_then(move |y| next(t3, o3, y))));
+        prop_assert_eq!(left, right);
+    }
+
+    #[test]
+    fn or_else_left_identity(e: u8, tag: u8, out in out()) {
+        prop_assert_eq!(
+            run(ready(Err(e)).or_else(|e| step(tag, out.map(|y| y ^ i32::from(e))))),
+            run(step(tag, out.map(|y| y ^ i32::from(e))))
+        );
+    }
+
+    #[test]
+    fn or_else_right_identity(tag: u8, out in out()) {
+        prop_assert_eq!(
+            run(step(tag, out).or_else(|e| Err(e).into_tx())),
+            run(step(tag, out))
+        );
+    }
+
+    #[test]
+    fn recover_is_or_else_of_a_success(tag: u8, out in out(), c: i32) {
+        let f = move |e: u8| c.wrapping_add(i32::from(e));
+        prop_assert_eq!(
+            run(step(tag, out).recover(f)),
+            run(step(tag, out).or_else(move |e| Ok(f(e)).into_tx()))
+        );
+    }
+
+    #[test]
+    fn recover_keeps_a_success(tag: u8, x: i32, c: i32) {
+        prop_assert_eq!(run(step(tag, Ok(x)).recover(|_| c)), (Ok(x), vec![tag]));
+    }
+
+    // both steps run whatever the first ends with; the first error wins
+    #[test]
+    fn join_is_then_pairing(t1: u8, t2: u8, o1 in out(), o2 in out()) {
+        prop_assert_eq!(
+            run(step(t1, o1).join(step(t2, o2))),
+            run(step(t1, o1).then(move |r1| {
+                step(t2, o2).then(move |r2| ready(r1.and_then(|x| r2.map(|y| (x, y)))))
+            }))
+        );
+    }
+
+    #[test]
+    fn join_symmetry_of_effects(t1: u8, t2: u8, o1 in out(), o2 in out()) {
+        let (r12, mut log12) = run(step(t1, o1).join(step(t2, o2)));
+        let (r21, mut log21) = run(step(t2, o2).join(step(t1, o1)));
+        log12.sort_unstable();
+        log21.sort_unstable();
+        prop_assert_eq!(log12, log21);
+        if o1.is_ok() || o2.is_ok() {
+            prop_assert_eq!(r12, r21.map(|(y, x)| (x, y)));
+        }
+    }
+}
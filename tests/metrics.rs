@@ -0,0 +1,144 @@
+#![cfg(all(feature = "postgres", feature = "metrics"))]
+
+use std::time::Duration;
+
+use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+use sqlx::postgres::PgPoolOptions;
+use sqlx::PgPool;
+
+use tx::chaos::{Fault, FaultPolicy};
+use tx::prelude::*;
+use tx::runner::{self, RetryPolicy};
+
+// The metrics of the runner, as the recorder of `metrics-util` for tests sees them.
+
+fn insert_two() -> runner::Sql<sqlx::Postgres> {
+    runner::sql("INSERT INTO todos (id, description) VALUES (1, 'one'), (2, 'two')")
+}
+
+#[sqlx::test]
+async fn counts_and_times_transactions(pool: PgPool) -> Result<(), sqlx::Error> {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    recorder.install().unwrap();
+
+    runner::run_tx(&pool, insert_two().named("insert_two")).await?;
+    // the same rows again
+    let duplicate = runner::run_tx(&pool, insert_two().named("insert_two")).await;
+    assert!(duplicate.is_err());
+
+    let faults = FaultPolicy::new(1.0).faults([Fault::SerializationFailure]);
+    let policy = RetryPolicy::new()
+        .max_attempts(2)
+        .base_delay(Duration::from_millis(1))
+        .inject_faults(faults);
+    let retried = runner::run_tx_retry(&pool, TxOptions::new(), policy, || {
+        runner::sql::<sqlx::Postgres>("SELECT 1").fetch_one::<(i32,), runner::WriteTx>()
+    })
+    .await;
+    assert!(retried.is_err());
+
+    // two transactions of a tenant, whose pool is evicted then
+    let url = std::env::var("DATABASE_URL").unwrap();
+    let registry = runner::PoolRegistry::<sqlx::Postgres>::new(move |_: &str| url.clone())
+        .evict_after(Duration::ZERO);
+    for _ in 0..2 {
+        let select =
+            runner::sql::<sqlx::Postgres>("SELECT 1").fetch_one::<(i32,), runner::WriteTx>();
+        registry
+            .run_for_tenant("acme", select.named("tenant_select"))
+            .await?;
+    }
+    assert_eq!(registry.evict_idle().await, ["acme"]);
+
+    // no connection comes free of a pool of one
+    let small = PgPoolOptions::new()
+        .max_connections(1)
+        .acquire_timeout(Duration::from_millis(50))
+        .connect_with((*pool.connect_options()).clone())
+        .await?;
+    let _held = small.acquire().await?;
+    let starved = runner::run_tx(&small, insert_two().named("starved")).await;
+    assert!(matches!(starved, Err(sqlx::Error::PoolTimedOut)));
+
+    let mut counters = vec![];
+    let mut histograms = vec![];
+    let mut gauges = vec![];
+    for (key, _, _, value) in snapshotter.snapshot().into_vec() {
+        let key = key.key();
+        let labels: Vec<String> = key
+            .labels()
+            .map(|label| format!("{}={}", label.key(), label.value()))
+            .collect();
+        let labels = labels.join(",");
+        match value {
+            DebugValue::Counter(n) => counters.push((key.name().to_string(), labels, n)),
+            DebugValue::Histogram(values) => {
+                histograms.push((key.name().to_string(), labels, values.len() as u64))
+            }
+            DebugValue::Gauge(value) => gauges.push((key.name().to_string(), value.into_inner())),
+        }
+    }
+    counters.sort();
+    // of the last `BEGIN`, the one of the pool of one which timed out
+    assert!(gauges.contains(&("tx_pool_connections".to_string(), 1.0)));
+    assert!(gauges.contains(&("tx_pool_idle_connections".to_string(), 0.0)));
+    // closed with the eviction
+    assert!(gauges.contains(&("tx_tenant_pools".to_string(), 0.0)));
+    assert!(gauges.contains(&("tx_tenant_pool_connections".to_string(), 0.0)));
+    histograms.sort();
+
+    let counter = |name: &str, labels: &str, n| (name.to_string(), labels.to_string(), n);
+    assert_eq!(
+        counters,
+        [
+            counter("tx_pool_timeouts_total", "", 1),
+            counter("tx_tenant_pool_evictions_total", "tenant=acme", 1),
+            counter("tx_tenant_transactions_total", "tenant=acme", 2),
+            counter("tx_transaction_retries_total", "name=inject_fault", 1),
+            counter("tx_transactions_committed_total", "name=insert_two", 1),
+            counter("tx_transactions_committed_total", "name=tenant_select", 2),
+            counter("tx_transactions_rolled_back_total", "name=inject_fault", 2),
+            counter("tx_transactions_rolled_back_total", "name=insert_two", 1),
+            counter("tx_transactions_rolled_back_total", "name=starved", 1),
+            counter("tx_transactions_started_total", "name=inject_fault", 2),
+            counter("tx_transactions_started_total", "name=insert_two", 2),
+            counter("tx_transactions_started_total", "name=starved", 1),
+            counter("tx_transactions_started_total", "name=tenant_select", 2),
+        ]
+    );
+    // how many values each histogram got
+    assert_eq!(
+        histograms,
+        [
+            counter("tx_connection_wait_seconds", "", 7),
+            counter("tx_rows_affected", "name=insert_two", 1),
+            counter(
+                "tx_transaction_duration_seconds",
+                "name=inject_fault,outcome=rolled back",
+                2
+            ),
+            counter(
+                "tx_transaction_duration_seconds",
+                "name=insert_two,outcome=committed",
+                1
+            ),
+            counter(
+                "tx_transaction_duration_seconds",
+                "name=insert_two,outcome=rolled back",
+                1
+            ),
+            counter(
+                "tx_transaction_duration_seconds",
+                "name=starved,outcome=rolled back",
+                1
+            ),
+            counter(
+                "tx_transaction_duration_seconds",
+                "name=tenant_select,outcome=committed",
+                2
+            ),
+        ]
+    );
+    Ok(())
+}
@@ -0,0 +1,72 @@
+#![cfg(feature = "postgres")]
+
+use sqlx::PgPool;
+
+use tx::migrations::{self, require_version, MIGRATOR};
+use tx::prelude::*;
+use tx::runner;
+
+const CREATE_TODOS: i64 = 20240101000000;
+
+fn guard(version: i64) -> impl Tx<PgReadCtx, Item = (), Err = sqlx::Error, Mode = AsyncMode> {
+    require_version(version)
+}
+
+async fn todos(pool: &PgPool) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar("SELECT to_regclass('todos') IS NOT NULL")
+        .fetch_one(pool)
+        .await
+}
+
+#[sqlx::test(migrations = false)]
+async fn bootstraps_a_fresh_database(pool: PgPool) -> Result<(), sqlx::Error> {
+    let e = run_tx(&pool, guard(CREATE_TODOS)).await.unwrap_err();
+    assert!(SchemaTooOld::is(&e), "{:?}", e);
+    assert!(!todos(&pool).await?);
+
+    migrations::run(&pool).await?;
+    assert!(todos(&pool).await?);
+    run_tx(&pool, guard(CREATE_TODOS)).await?;
+    // nothing left to apply
+    migrations::run(&pool).await?;
+    Ok(())
+}
+
+#[sqlx::test(migrations = false)]
+async fn migrates_with_the_rest_of_the_chain(pool: PgPool) -> Result<(), sqlx::Error> {
+    let chain = migrations::migrate(&MIGRATOR)
+        .and_then(|_| runner::sql("INSERT INTO todos (description) VALUES ('first')"))
+        .and_then(|_| runner::sql("INSERT INTO missing (id) VALUES (1)"));
+    assert!(run_tx(&pool, chain).await.is_err());
+    // rolled back with it
+    assert!(!todos(&pool).await?);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn fails_fast_on_an_older_schema(pool: PgPool) -> Result<(), sqlx::Error> {
+    let counted = require_version(CREATE_TODOS)
+        .and_then(|_| runner::sql("SELECT count(*) FROM todos").fetch_one::<(i64,), ReadTx>());
+    runner::run_tx(&pool, counted).await?;
+
+    let e = run_tx(&pool, guard(CREATE_TODOS + 1)).await.unwrap_err();
+    assert!(SchemaTooOld::is(&e));
+    assert!(e
+        .to_string()
+        .ends_with("schema too old: migration 20240101000001 required, 20240101000000 applied"));
+    Ok(())
+}
+
+#[sqlx::test]
+async fn fails_with_its_own_tx_error(pool: PgPool) -> Result<(), sqlx::Error> {
+    let chain = guard(CREATE_TODOS + 1).map_err(TxError::<()>::from);
+    let result = run_tx(&pool, chain).await;
+    let Err(TxError::SchemaTooOld(e)) = result else {
+        panic!("not too old: {:?}", result);
+    };
+    assert_eq!(
+        (e.required, e.applied),
+        (CREATE_TODOS + 1, Some(CREATE_TODOS))
+    );
+    Ok(())
+}
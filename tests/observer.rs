@@ -0,0 +1,196 @@
+#![cfg(feature = "postgres")]
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use sqlx::PgPool;
+
+use tx::observer::{add_observer, RollbackReason, TxEvent, TxId, TxObserver};
+use tx::prelude::*;
+use tx::rt::CancellationToken;
+use tx::runner::{run_tx_cancellable, run_tx_pending, RoutingRunner};
+
+// The events of each transaction, as strings, under the name it began with. The observers are
+// shared by the whole process, so each test looks at its own chain only.
+
+#[derive(Clone, Default)]
+struct Events(Arc<Mutex<BTreeMap<TxId, Vec<String>>>>);
+impl Events {
+    fn of(&self, name: &str) -> Vec<String> {
+        let begin = format!("begin {} #1", name);
+        let events = self.0.lock().unwrap();
+        let mut found = events.values().filter(|events| events[0] == begin);
+        found.next().cloned().unwrap_or_default()
+    }
+}
+impl TxObserver for Events {
+    fn on_event(&self, tx: TxId, event: &TxEvent<'_>) {
+        let event = match event {
+            TxEvent::Begin { name, attempt } => format!("begin {} #{}", name, attempt),
+            TxEvent::StepStarted { step } => format!("start {}", step),
+            TxEvent::StepFinished { step, ok, .. } => format!("finish {} ok={}", step, ok),
+            TxEvent::Commit { .. } => "commit".to_string(),
+            TxEvent::Rollback { reason, .. } => match reason {
+                RollbackReason::Failed => "rollback: failed".to_string(),
+                RollbackReason::BeginFailed(e) => format!("rollback: begin failed: {}", e),
+                RollbackReason::CommitFailed(e) => format!("rollback: commit failed: {}", e),
+                RollbackReason::Requested => "rollback: requested".to_string(),
+                RollbackReason::Dropped => "rollback: dropped".to_string(),
+            },
+            TxEvent::Explained { sql, .. } => format!("explained {}", sql),
+            TxEvent::StatementBudgetExceeded { budget, statements } => {
+                format!("over budget: {} of {}", statements, budget)
+            }
+        };
+        self.0.lock().unwrap().entry(tx).or_default().push(event);
+    }
+}
+
+fn events() -> Events {
+    static EVENTS: OnceLock<Events> = OnceLock::new();
+    let events = EVENTS.get_or_init(|| {
+        let events = Events::default();
+        add_observer(events.clone());
+        events
+    });
+    events.clone()
+}
+
+fn select(value: i32) -> impl Tx<PgCtx, Item = i32, Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(move |ctx: &mut PgCtx| {
+        Box::pin(async move {
+            sqlx::query_scalar("SELECT $1")
+                .bind(value)
+                .fetch_one(&mut **ctx)
+                .await
+        })
+    })
+}
+
+#[sqlx::test]
+async fn reports_steps_and_commit(pool: PgPool) -> Result<(), sqlx::Error> {
+    let events = events();
+    let chain = select(1)
+        .named("first")
+        .and_then(|x| select(x + 1).named("second"))
+        .named("observed_commit");
+    assert_eq!(run_tx(&pool, chain).await?, 2);
+
+    assert_eq!(
+        events.of("observed_commit"),
+        [
+            "begin observed_commit #1",
+            "start observed_commit",
+            "start first",
+            "finish first ok=true",
+            "start second",
+            "finish second ok=true",
+            "finish observed_commit ok=true",
+            "commit",
+        ]
+    );
+    Ok(())
+}
+
+#[sqlx::test]
+async fn reports_the_failed_step_and_rollback(pool: PgPool) -> Result<(), sqlx::Error> {
+    let events = events();
+    let failing = with_tx_async(|ctx: &mut PgCtx| {
+        Box::pin(async move {
+            sqlx::query("SELECT 1 / 0").execute(&mut **ctx).await?;
+            Ok(())
+        })
+    });
+    let chain = select(1)
+        .named("first")
+        .and_then(move |_| failing.named("failing"))
+        .named("observed_rollback");
+    assert!(run_tx(&pool, chain).await.is_err());
+
+    assert_eq!(
+        events.of("observed_rollback"),
+        [
+            "begin observed_rollback #1",
+            "start observed_rollback",
+            "start first",
+            "finish first ok=true",
+            "start failing",
+            "finish failing ok=false",
+            "finish observed_rollback ok=false",
+            "rollback: failed",
+        ]
+    );
+    Ok(())
+}
+
+#[sqlx::test]
+async fn reports_a_cancelled_transaction(pool: PgPool) -> Result<(), sqlx::Error> {
+    let events = events();
+    let token = CancellationToken::new();
+    let cancel = token.clone();
+    // the chain completes right after the cancellation, so it is rolled back all the same
+    let chain = select(1)
+        .named("first")
+        .and_then(move |x| {
+            cancel.cancel();
+            ready(Ok(x + 1))
+        })
+        .named("observed_cancel");
+    let result = run_tx_cancellable(&pool, TxOptions::new(), &token, chain).await;
+    assert!(result.is_err());
+
+    assert_eq!(
+        events.of("observed_cancel"),
+        [
+            "begin observed_cancel #1",
+            "start observed_cancel",
+            "start first",
+            "finish first ok=true",
+            "finish observed_cancel ok=true",
+            "rollback: failed",
+        ]
+    );
+    Ok(())
+}
+
+#[sqlx::test]
+async fn reports_pending_transactions_as_they_end(pool: PgPool) -> Result<(), sqlx::Error> {
+    let events = events();
+    let options = TxOptions::new();
+    let pending = run_tx_pending(&pool, options, select(1).named("pending_commit")).await?;
+    assert_eq!(pending.commit().await?, 1);
+    let pending = run_tx_pending(&pool, options, select(1).named("pending_rollback")).await?;
+    pending.rollback().await?;
+    let pending = run_tx_pending(&pool, options, select(1).named("pending_drop")).await?;
+    drop(pending);
+
+    for (name, end) in [
+        ("pending_commit", "commit"),
+        ("pending_rollback", "rollback: requested"),
+        ("pending_drop", "rollback: dropped"),
+    ] {
+        let begin = format!("begin {} #1", name);
+        let start = format!("start {}", name);
+        let finish = format!("finish {} ok=true", name);
+        assert_eq!(events.of(name), [&*begin, &*start, &*finish, end]);
+    }
+    Ok(())
+}
+
+#[sqlx::test]
+async fn reports_routed_transactions(pool: PgPool) -> Result<(), sqlx::Error> {
+    let events = events();
+    let routing = RoutingRunner::new(pool.clone()).replica(pool.clone());
+    let read = select(1).named("routed_read");
+    assert_eq!(routing.run(TxOptions::new().read_only(), read).await?, 1);
+    let write = select(1).named("routed_write");
+    assert_eq!(routing.run(TxOptions::new(), write).await?, 1);
+
+    for name in ["routed_read", "routed_write"] {
+        let begin = format!("begin {} #1", name);
+        let start = format!("start {}", name);
+        let finish = format!("finish {} ok=true", name);
+        assert_eq!(events.of(name), [&*begin, &*start, &*finish, "commit"]);
+    }
+    Ok(())
+}
@@ -0,0 +1,84 @@
+#![cfg(feature = "postgres")]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use sqlx::PgPool;
+
+use tx::prelude::*;
+use tx::runner::{join_parallel, join_parallel3};
+
+// The backend pid of the connection the branch runs on, once `arrived` tells that every one of
+// the `branches` got that far: run one after the other, the first would wait forever.
+fn meet(
+    arrived: Arc<AtomicUsize>,
+    branches: usize,
+) -> impl Tx<PgReadCtx, Item = i32, Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(move |ctx: &mut PgReadCtx| {
+        Box::pin(async move {
+            let pid = sqlx::query_scalar("SELECT pg_backend_pid()")
+                .fetch_one(&mut **ctx)
+                .await?;
+            arrived.fetch_add(1, Ordering::SeqCst);
+            while arrived.load(Ordering::SeqCst) < branches {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+            Ok(pid)
+        })
+    })
+}
+
+fn fail() -> impl Tx<PgReadCtx, Item = i32, Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(|ctx: &mut PgReadCtx| {
+        Box::pin(async move {
+            sqlx::query_scalar("SELECT 1 / 0")
+                .fetch_one(&mut **ctx)
+                .await
+        })
+    })
+}
+
+#[sqlx::test]
+async fn runs_the_branches_side_by_side(pool: PgPool) -> Result<(), sqlx::Error> {
+    let arrived = Arc::new(AtomicUsize::new(0));
+    let joined = join_parallel(
+        &pool,
+        TxOptions::new(),
+        meet(arrived.clone(), 2),
+        meet(arrived.clone(), 2),
+    );
+    let (a, b) = tokio::time::timeout(Duration::from_secs(5), joined)
+        .await
+        .expect("the branches ran one after the other")?;
+    assert_ne!(a, b);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn runs_three_branches(pool: PgPool) -> Result<(), sqlx::Error> {
+    let arrived = Arc::new(AtomicUsize::new(0));
+    let joined = join_parallel3(
+        &pool,
+        TxOptions::new(),
+        meet(arrived.clone(), 3),
+        meet(arrived.clone(), 3),
+        meet(arrived.clone(), 3),
+    );
+    let (a, b, c) = tokio::time::timeout(Duration::from_secs(5), joined)
+        .await
+        .expect("the branches ran one after the other")?;
+    assert!(a != b && b != c && a != c);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn fails_with_the_first_failing_branch(pool: PgPool) {
+    let arrived = Arc::new(AtomicUsize::new(0));
+    // the meeting branch never sees the other arrive, and is dropped
+    let joined = join_parallel(&pool, TxOptions::new(), meet(arrived, 2), fail());
+    let result = tokio::time::timeout(Duration::from_secs(5), joined)
+        .await
+        .expect("the failure did not end the join");
+    assert_eq!(result.unwrap_err().sqlstate().as_deref(), Some("22012"));
+}
@@ -0,0 +1,53 @@
+#![cfg(feature = "postgres")]
+
+use std::time::{Duration, Instant};
+
+use sqlx::{PgPool, Postgres};
+
+use tx::prelude::*;
+use tx::runner::{self, RateLimitExt, RateLimiter};
+
+fn select_one() -> impl Tx<PgCtx, Item = (i32,), Err = sqlx::Error, Mode = AsyncMode> {
+    runner::sql::<Postgres>("SELECT 1").fetch_one::<(i32,), WriteTx>()
+}
+
+#[sqlx::test]
+async fn delays_the_transactions_over_the_rate(pool: PgPool) -> Result<(), sqlx::Error> {
+    let limiter = RateLimiter::new(20.0).burst(1);
+    let started = Instant::now();
+    for _ in 0..3 {
+        runner::run_tx(&pool, select_one().rate_limited(limiter.clone())).await?;
+    }
+    // the first one takes the token of the burst, the others wait 50ms each
+    assert!(started.elapsed() >= Duration::from_millis(95));
+    Ok(())
+}
+
+#[sqlx::test]
+async fn rejects_the_transactions_over_the_rate(pool: PgPool) -> Result<(), sqlx::Error> {
+    let limiter = RateLimiter::new(1.0).burst(2).reject();
+    for _ in 0..2 {
+        runner::run_tx(&pool, select_one().rate_limited(limiter.clone())).await?;
+    }
+    let started = Instant::now();
+    let result = runner::run_tx(&pool, select_one().rate_limited(limiter.clone())).await;
+    let e = result.unwrap_err();
+    assert!(RateLimited::is(&e), "{:?}", e);
+    assert!(started.elapsed() < Duration::from_millis(500));
+
+    let TxError::RateLimited(limited) = TxError::<()>::from(e) else {
+        panic!("not rate limited");
+    };
+    assert!(limited.retry_after > Duration::from_millis(500));
+    assert!(limited.retry_after <= Duration::from_secs(1));
+    Ok(())
+}
+
+#[test]
+fn describes_the_limited_step() {
+    let chain = select_one().rate_limited(RateLimiter::new(1.0));
+    assert_eq!(
+        chain.describe().to_string().lines().next(),
+        Some("rate_limited")
+    );
+}
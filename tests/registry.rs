@@ -0,0 +1,89 @@
+#![cfg(feature = "postgres")]
+
+use std::time::Duration;
+
+use sqlx::postgres::PgPoolOptions;
+use sqlx::Postgres;
+
+use tx::prelude::*;
+use tx::runner::PoolRegistry;
+
+// Every tenant on the database of `DATABASE_URL`, each pool telling its tenant apart with the
+// `application_name` of its connections.
+fn registry(evict_after: Duration) -> PoolRegistry<Postgres> {
+    let url = std::env::var("DATABASE_URL").unwrap();
+    PoolRegistry::new(move |tenant: &str| format!("{}?application_name={}", url, tenant))
+        .pool_options(PgPoolOptions::new().max_connections(2))
+        .evict_after(evict_after)
+}
+
+fn application_name(
+    sleep: f64,
+) -> impl Tx<PgReadCtx, Item = String, Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(move |ctx: &mut PgReadCtx| {
+        Box::pin(async move {
+            sqlx::query("SELECT pg_sleep($1)")
+                .bind(sleep)
+                .execute(&mut **ctx)
+                .await?;
+            sqlx::query_scalar("SELECT current_setting('application_name')")
+                .fetch_one(&mut **ctx)
+                .await
+        })
+    })
+}
+
+#[tokio::test]
+async fn runs_each_tenant_on_a_pool_of_its_own() -> Result<(), sqlx::Error> {
+    let registry = registry(Duration::from_secs(60));
+    assert!(registry.usage().is_empty());
+
+    for tenant in ["acme", "globex", "acme"] {
+        let name = registry
+            .run_for_tenant(tenant, application_name(0.0))
+            .await?;
+        assert_eq!(name, tenant);
+    }
+    let usage = registry.usage();
+    let tenants: Vec<&str> = usage.keys().map(String::as_str).collect();
+    assert_eq!(tenants, ["acme", "globex"]);
+    assert_eq!(usage["acme"].transactions, 2);
+    assert_eq!(usage["acme"].in_flight, 0);
+    assert_eq!(usage["acme"].connections, 1);
+    assert_eq!(usage["globex"].transactions, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn evicts_the_idle_tenants() -> Result<(), sqlx::Error> {
+    let registry = registry(Duration::from_millis(200));
+    registry
+        .run_for_tenant("acme", application_name(0.0))
+        .await?;
+    registry
+        .run_for_tenant("globex", application_name(0.0))
+        .await?;
+    tokio::time::sleep(Duration::from_millis(250)).await;
+
+    // globex runs a transaction meanwhile, acme has been idle for too long
+    let (name, evicted) = tokio::join!(
+        registry.run_for_tenant("globex", application_name(0.3)),
+        async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            registry.evict_idle().await
+        }
+    );
+    assert_eq!(name?, "globex");
+    assert_eq!(evicted, ["acme"]);
+    let usage = registry.usage();
+    assert!(!usage.contains_key("acme"));
+    assert_eq!(usage["globex"].transactions, 2);
+
+    // made again on its next transaction
+    registry
+        .run_for_tenant("acme", application_name(0.0))
+        .await?;
+    assert_eq!(registry.usage()["acme"].transactions, 1);
+    assert!(registry.evict_idle().await.is_empty());
+    Ok(())
+}
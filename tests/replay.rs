@@ -0,0 +1,73 @@
+#![cfg(feature = "postgres")]
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use sqlx::{PgPool, Postgres};
+
+use tx::prelude::*;
+use tx::runner::{self, ReadTx, RetryMetrics, RetryPolicy, WriteTx};
+
+// Reads `SELECT 1`, the first run only after killing its own connection.
+fn select_one<A: TxAccess>(
+    runs: &AtomicU32,
+) -> impl Tx<TxCtx<Postgres, A>, Item = (i32,), Err = sqlx::Error, Mode = AsyncMode> {
+    let sql = match runs.fetch_add(1, Ordering::SeqCst) {
+        0 => "SELECT pg_terminate_backend(pg_backend_pid())::int",
+        _ => "SELECT 1",
+    };
+    runner::sql::<Postgres>(sql).fetch_one::<(i32,), A>()
+}
+
+fn policy(metrics: Arc<RetryMetrics>) -> RetryPolicy {
+    RetryPolicy::new()
+        .base_delay(Duration::from_millis(1))
+        .metrics(metrics)
+}
+
+#[sqlx::test]
+async fn replays_a_read_chain_on_a_new_connection(pool: PgPool) -> Result<(), sqlx::Error> {
+    let runs = AtomicU32::new(0);
+    let metrics = Arc::new(RetryMetrics::new());
+    let policy = policy(metrics.clone()).replay_reads(1);
+    let row = runner::run_tx_retry(&pool, TxOptions::new(), policy, || {
+        select_one::<ReadTx>(&runs)
+    })
+    .await?;
+    assert_eq!(row, (1,));
+    assert_eq!(runs.load(Ordering::SeqCst), 2);
+    let stats = metrics.snapshot();
+    assert_eq!(stats.replays, 1);
+    assert_eq!(stats.attempts.get(&2), Some(&1));
+    Ok(())
+}
+
+#[sqlx::test]
+async fn replays_only_when_asked_to(pool: PgPool) -> Result<(), sqlx::Error> {
+    let runs = AtomicU32::new(0);
+    let metrics = Arc::new(RetryMetrics::new());
+    let result = runner::run_tx_retry(&pool, TxOptions::new(), policy(metrics.clone()), || {
+        select_one::<ReadTx>(&runs)
+    })
+    .await;
+    let e = result.unwrap_err();
+    assert_eq!(e.error_kind(), ErrorKind::ConnectionLost, "{:?}", e);
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+    assert_eq!(metrics.snapshot().replays, 0);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn never_replays_a_writing_chain(pool: PgPool) -> Result<(), sqlx::Error> {
+    let runs = AtomicU32::new(0);
+    let metrics = Arc::new(RetryMetrics::new());
+    let policy = policy(metrics.clone()).replay_reads(1);
+    let result = runner::run_tx_retry(&pool, TxOptions::new(), policy, || {
+        select_one::<WriteTx>(&runs)
+    })
+    .await;
+    assert_eq!(result.unwrap_err().error_kind(), ErrorKind::ConnectionLost);
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+    Ok(())
+}
@@ -0,0 +1,125 @@
+#![cfg(feature = "postgres")]
+
+use sqlx::postgres::PgPoolOptions;
+use sqlx::PgPool;
+
+use tx::prelude::*;
+
+// A `notes` table whose rows only their owner sees, through a role which does not own it: the
+// tests connect as a superuser, to whom policies do not apply. Roles belong to the cluster, so
+// the one of every test database is made once.
+async fn notes_table(pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "DO $$ BEGIN CREATE ROLE tx_rls_reader NOLOGIN; \
+         EXCEPTION WHEN duplicate_object OR unique_violation THEN NULL; END $$",
+    )
+    .execute(pool)
+    .await?;
+    for sql in [
+        "CREATE TABLE notes (owner TEXT NOT NULL, body TEXT NOT NULL)",
+        "INSERT INTO notes VALUES ('alice', 'alice''s'), ('bob', 'bob''s')",
+        "ALTER TABLE notes ENABLE ROW LEVEL SECURITY",
+        "CREATE POLICY own_notes ON notes USING (owner = current_setting('app.user_id', true))",
+        "GRANT SELECT ON notes TO tx_rls_reader",
+    ] {
+        sqlx::query(sql).execute(pool).await?;
+    }
+    Ok(())
+}
+
+fn notes<A: TxAccess>(
+) -> impl Tx<TxCtx<sqlx::Postgres, A>, Item = Vec<String>, Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(|ctx: &mut TxCtx<sqlx::Postgres, A>| {
+        Box::pin(async move {
+            sqlx::query_scalar("SELECT body FROM notes ORDER BY body")
+                .fetch_all(&mut **ctx)
+                .await
+        })
+    })
+}
+
+fn settings<A: TxAccess>(
+) -> impl Tx<TxCtx<sqlx::Postgres, A>, Item = (String, String), Err = sqlx::Error, Mode = AsyncMode>
+{
+    with_tx_async(|ctx: &mut TxCtx<sqlx::Postgres, A>| {
+        Box::pin(async move {
+            sqlx::query_as(
+                "SELECT coalesce(current_setting('app.user_id', true), ''), current_user::text",
+            )
+            .fetch_one(&mut **ctx)
+            .await
+        })
+    })
+}
+
+#[sqlx::test]
+async fn shows_each_user_their_own_rows(pool: PgPool) -> Result<(), sqlx::Error> {
+    notes_table(&pool).await?;
+    for user in ["alice", "bob"] {
+        let chain = as_user(user, notes::<ReadTx>()).role("tx_rls_reader");
+        assert_eq!(run_tx(&pool, chain).await?, [format!("{}'s", user)]);
+    }
+    // an unknown user sees nothing
+    let chain = as_user("mallory", notes::<ReadTx>()).role("tx_rls_reader");
+    assert!(run_tx(&pool, chain).await?.is_empty());
+    Ok(())
+}
+
+#[sqlx::test]
+async fn leaks_neither_to_the_next_steps_nor_transactions(pool: PgPool) -> Result<(), sqlx::Error> {
+    notes_table(&pool).await?;
+    // one connection, so that the next transaction gets the same one
+    let pool = PgPoolOptions::new()
+        .max_connections(1)
+        .connect_with((*pool.connect_options()).clone())
+        .await?;
+    let (_, owner) = run_tx(&pool, settings::<ReadTx>()).await?;
+
+    let chain = as_user("alice", settings::<ReadTx>())
+        .role("tx_rls_reader")
+        .and_then(|inside| settings().map(move |after| (inside, after)));
+    let (inside, after) = run_tx(&pool, chain).await?;
+    assert_eq!(inside, ("alice".to_string(), "tx_rls_reader".to_string()));
+    assert_eq!(after, (String::new(), owner.clone()));
+    assert_eq!(
+        run_tx(&pool, settings::<ReadTx>()).await?,
+        (String::new(), owner.clone())
+    );
+
+    // the transaction of a failing step rolls it back
+    let failing = as_user(
+        "bob",
+        with_tx_async(|ctx: &mut PgReadCtx| {
+            Box::pin(async move {
+                sqlx::query("SELECT 1 / 0").execute(&mut **ctx).await?;
+                Ok::<_, sqlx::Error>(())
+            })
+        }),
+    );
+    assert!(run_tx(&pool, failing).await.is_err());
+    assert_eq!(
+        run_tx(&pool, settings::<ReadTx>()).await?,
+        (String::new(), owner)
+    );
+    Ok(())
+}
+
+#[sqlx::test]
+async fn sets_them_back_when_the_step_fails(pool: PgPool) -> Result<(), sqlx::Error> {
+    notes_table(&pool).await?;
+    let (_, owner) = run_tx(&pool, settings::<ReadTx>()).await?;
+
+    // fails on the client, so the transaction carries on as whoever ran it
+    let failing = as_user(
+        "alice",
+        notes::<ReadTx>().and_then(|_| ready(Err::<Vec<String>, _>(sqlx::Error::RowNotFound))),
+    )
+    .role("tx_rls_reader");
+    let chain = failing
+        .recover(|_| vec![])
+        .and_then(|_| settings().join(notes()));
+    let (after, notes) = run_tx(&pool, chain).await?;
+    assert_eq!(after, (String::new(), owner));
+    assert_eq!(notes, ["alice's", "bob's"]);
+    Ok(())
+}
@@ -0,0 +1,122 @@
+#![cfg(feature = "postgres")]
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use sqlx::{PgPool, Postgres};
+
+use tx::observer::{TxEvent, TxId, TxObserver};
+use tx::prelude::*;
+use tx::runner::{IsolationLevel, RetryPolicy, TxRuntime, TxRuntimeConfig};
+
+fn show(
+    setting: &'static str,
+) -> impl Tx<PgReadCtx, Item = String, Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(move |ctx: &mut PgReadCtx| {
+        Box::pin(async move {
+            sqlx::query_scalar(&format!("SHOW {}", setting))
+                .fetch_one(&mut **ctx)
+                .await
+        })
+    })
+}
+
+#[derive(Clone, Default)]
+struct Commits(Arc<AtomicU32>);
+impl TxObserver for Commits {
+    fn on_event(&self, _: TxId, event: &TxEvent<'_>) {
+        if let TxEvent::Commit { .. } = event {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+// The only test of the file touching those variables, as they are shared by the process.
+#[test]
+fn reads_the_environment() {
+    std::env::set_var("DATABASE_MAX_CONNECTIONS", "3");
+    std::env::set_var("DATABASE_ISOLATION", "repeatable_read");
+    std::env::set_var("DATABASE_STATEMENT_TIMEOUT_MS", "250");
+    std::env::set_var("DATABASE_RETRY_ATTEMPTS", "2");
+    let config = TxRuntimeConfig::from_env().unwrap();
+    let options = TxOptions::new()
+        .isolation_level(IsolationLevel::RepeatableRead)
+        .statement_timeout(Duration::from_millis(250));
+    assert_eq!(config.tx_options(), options);
+    assert!(format!("{:?}", config).contains("max_connections: 3"));
+    assert!(format!("{:?}", config.retry_policy()).contains("max_attempts: 2"));
+
+    std::env::set_var("DATABASE_ISOLATION", "snapshot");
+    let e = TxRuntimeConfig::from_env().unwrap_err();
+    assert!(e
+        .to_string()
+        .contains("DATABASE_ISOLATION: invalid value \"snapshot\""));
+
+    for name in [
+        "DATABASE_MAX_CONNECTIONS",
+        "DATABASE_ISOLATION",
+        "DATABASE_STATEMENT_TIMEOUT_MS",
+        "DATABASE_RETRY_ATTEMPTS",
+    ] {
+        std::env::remove_var(name);
+    }
+}
+
+#[sqlx::test]
+async fn runs_with_the_options_and_observers_of_the_config(
+    pool: PgPool,
+) -> Result<(), sqlx::Error> {
+    let commits = Commits::default();
+    let config = TxRuntimeConfig::new()
+        .isolation_level(IsolationLevel::Serializable)
+        .statement_timeout(Duration::from_millis(1500))
+        .retry(RetryPolicy::new().max_attempts(2))
+        .observer(commits.clone());
+    // made twice over, the observers of the config are still told once
+    let _ = TxRuntime::new(pool.clone(), config.clone());
+    let runtime = TxRuntime::new(pool.clone(), config);
+
+    let shared = runtime.clone();
+    assert_eq!(
+        shared.run(show("transaction_isolation")).await?,
+        "serializable"
+    );
+    assert_eq!(
+        shared.run_retry(|| show("statement_timeout")).await?,
+        "1500ms"
+    );
+    // the options as given
+    let options = runtime
+        .options()
+        .isolation_level(IsolationLevel::ReadCommitted);
+    let level = runtime
+        .run_with(options, show("transaction_isolation"))
+        .await?;
+    assert_eq!(level, "read committed");
+    assert_eq!(commits.0.load(Ordering::Relaxed), 3);
+
+    // and only of the transactions run through the runtime
+    run_tx(&pool, show("transaction_isolation")).await?;
+    assert_eq!(commits.0.load(Ordering::Relaxed), 3);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn connects_a_pool_sized_by_the_config(_: PgPool) -> Result<(), sqlx::Error> {
+    let url = std::env::var("DATABASE_URL").unwrap();
+    let config = TxRuntimeConfig::new()
+        .url(url)
+        .max_connections(2)
+        .acquire_timeout(Duration::from_millis(200));
+    let runtime = TxRuntime::<Postgres>::connect(config).await?;
+    assert_eq!(runtime.pool().options().get_max_connections(), 2);
+    assert!(!runtime.run(show("max_connections")).await?.is_empty());
+
+    let e = TxRuntime::<Postgres>::connect(TxRuntimeConfig::new())
+        .await
+        .err()
+        .unwrap();
+    assert!(e.to_string().contains("no database URL"));
+    Ok(())
+}
@@ -0,0 +1,98 @@
+#![cfg(feature = "rusqlite")]
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use rusqlite::{Connection, TransactionBehavior};
+
+use tx::prelude::*;
+use tx::rusqlite::{run_tx, run_tx_with, sql};
+
+fn todos() -> Result<Connection, rusqlite::Error> {
+    let conn = Connection::open_in_memory()?;
+    conn.execute_batch(
+        "CREATE TABLE todos (id INTEGER PRIMARY KEY, description TEXT NOT NULL, done BOOLEAN NOT NULL DEFAULT FALSE)",
+    )?;
+    Ok(conn)
+}
+
+fn insert(
+    id: i64,
+    description: &str,
+) -> impl Tx<Connection, Item = (), Err = rusqlite::Error, Mode = SyncMode> {
+    sql("INSERT INTO todos (id, description) VALUES (?, ?)")
+        .bind(id)
+        .bind(description.to_string())
+        .map(|_| ())
+}
+
+fn descriptions() -> impl Tx<Connection, Item = Vec<String>, Err = rusqlite::Error, Mode = SyncMode>
+{
+    sql("SELECT description FROM todos ORDER BY id").fetch_all(|row| row.get(0))
+}
+
+#[test]
+fn commits_on_ok_and_rolls_back_on_err() -> Result<(), rusqlite::Error> {
+    let mut conn = todos()?;
+    run_tx(
+        &mut conn,
+        insert(1, "kept").and_then(|()| insert(2, "kept too")),
+    )?;
+
+    let failing = insert(3, "lost").and_then(|()| insert(1, "duplicate"));
+    assert!(run_tx(&mut conn, failing).is_err());
+    assert!(conn.is_autocommit());
+    assert_eq!(run_tx(&mut conn, descriptions())?, ["kept", "kept too"]);
+
+    // a panicking step rolls it back too, and leaves the connection usable
+    let panicking = insert(3, "lost").and_then(|()| {
+        with_tx(|_: &mut Connection| -> Result<(), rusqlite::Error> { panic!("step failed") })
+    });
+    let panicked = catch_unwind(AssertUnwindSafe(|| run_tx(&mut conn, panicking)));
+    assert!(panicked.is_err());
+    let chain = insert(3, "after the panic").and_then(|()| descriptions());
+    let written = run_tx_with(&mut conn, TransactionBehavior::Immediate, chain)?;
+    assert_eq!(written, ["kept", "kept too", "after the panic"]);
+    Ok(())
+}
+
+#[test]
+fn rolls_back_a_savepoint_only() -> Result<(), rusqlite::Error> {
+    let mut conn = todos()?;
+    let chain = insert(1, "outer").and_then(|()| {
+        savepoint(insert(2, "inner").and_then(|()| insert(1, "duplicate")))
+            .map(|()| true)
+            .recover(|_| false)
+    });
+    assert!(!run_tx(&mut conn, chain)?);
+    assert_eq!(run_tx(&mut conn, descriptions())?, ["outer"]);
+    Ok(())
+}
+
+#[test]
+fn fetches_rows_with_bound_values() -> Result<(), rusqlite::Error> {
+    let mut conn = todos()?;
+    let done = sql("UPDATE todos SET done = TRUE WHERE id = ?").bind(1);
+    let chain = insert(1, "first")
+        .and_then(|()| insert(2, "second"))
+        .and_then(|()| done)
+        .and_then(|updated| {
+            sql("SELECT id, description FROM todos WHERE done = ?")
+                .bind(true)
+                .fetch_one(|row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))
+                .map(move |todo| (updated, todo))
+        });
+    assert_eq!(run_tx(&mut conn, chain)?, (1, (1, "first".to_string())));
+
+    let missing = sql("SELECT description FROM todos WHERE id = ?")
+        .bind(3)
+        .fetch_optional(|row| row.get::<_, String>(0));
+    assert_eq!(run_tx(&mut conn, missing)?, None);
+    let missing = sql("SELECT description FROM todos WHERE id = ?")
+        .bind(3)
+        .fetch_one(|row| row.get::<_, String>(0));
+    assert!(matches!(
+        run_tx(&mut conn, missing),
+        Err(rusqlite::Error::QueryReturnedNoRows)
+    ));
+    Ok(())
+}
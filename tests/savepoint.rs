@@ -0,0 +1,83 @@
+#![cfg(feature = "postgres")]
+
+use std::time::Duration;
+
+use sqlx::PgPool;
+
+use tx::prelude::*;
+
+fn insert(id: i64) -> impl Tx<PgCtx, Item = (), Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(move |ctx: &mut PgCtx| {
+        Box::pin(async move {
+            sqlx::query("INSERT INTO todos (id, description) VALUES ($1, 'saved')")
+                .bind(id)
+                .execute(&mut **ctx)
+                .await?;
+            Ok(())
+        })
+    })
+}
+
+fn fail() -> impl Tx<PgCtx, Item = (), Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(|ctx: &mut PgCtx| {
+        Box::pin(async move {
+            sqlx::query("SELECT 1 / 0").execute(&mut **ctx).await?;
+            Ok(())
+        })
+    })
+}
+
+// How many savepoints deep the chain is where this step runs.
+fn depth() -> impl Tx<PgCtx, Item = usize, Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(|ctx: &mut PgCtx| Box::pin(async move { Ok(ctx.depth()) }))
+}
+
+async fn ids(pool: &PgPool) -> Result<Vec<i64>, sqlx::Error> {
+    sqlx::query_scalar("SELECT id FROM todos ORDER BY id")
+        .fetch_all(pool)
+        .await
+}
+
+#[sqlx::test]
+async fn commits_the_outer_chain_after_a_failed_savepoint(pool: PgPool) -> Result<(), sqlx::Error> {
+    let chain = insert(1)
+        .and_then(|_| savepoint(insert(2).and_then(|_| fail())).or_else(|_| ready(Ok(()))))
+        .and_then(|_| insert(3));
+    run_tx(&pool, chain).await?;
+    // the writes of the savepoint are rolled back with it, and only those
+    assert_eq!(ids(&pool).await?, [1, 3]);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn restores_the_depth_after_a_failed_savepoint(pool: PgPool) -> Result<(), sqlx::Error> {
+    let chain = savepoint(savepoint(fail()))
+        .or_else(|_| ready(Ok(())))
+        .and_then(|_| depth())
+        .and_then(|after| savepoint(depth()).map(move |inner| (after, inner)));
+    assert_eq!(run_tx(&pool, chain).await?, (0, 1));
+    Ok(())
+}
+
+#[sqlx::test]
+async fn restores_the_depth_when_the_savepoint_is_dropped(pool: PgPool) -> Result<(), sqlx::Error> {
+    let slow = with_tx_async(|ctx: &mut PgCtx| {
+        Box::pin(async move {
+            sqlx::query("SELECT pg_sleep(0.5)")
+                .execute(&mut **ctx)
+                .await?;
+            Ok(())
+        })
+    });
+    // the timeout drops the savepoint while its statement runs
+    let chain = savepoint(insert(1).and_then(move |_| slow))
+        .timeout(Duration::from_millis(50))
+        .or_else(|e| match e {
+            sqlx::Error::Io(e) if e.kind() == std::io::ErrorKind::TimedOut => ready(Ok(())),
+            e => ready(Err(e)),
+        })
+        .and_then(|_| depth())
+        .and_then(|after| savepoint(insert(2)).map(move |_| after));
+    assert_eq!(run_tx(&pool, chain).await?, 0);
+    Ok(())
+}
@@ -0,0 +1,57 @@
+#![cfg(feature = "postgres")]
+
+use sqlx::PgPool;
+
+use tx::prelude::*;
+use tx::runner;
+use tx::schema::{column_type, current_schema_version, table_exists};
+
+async fn read<T, X>(pool: &PgPool, tx: X) -> Result<T, sqlx::Error>
+where
+    X: Tx<PgReadCtx, Item = T, Err = sqlx::Error, Mode = AsyncMode>,
+{
+    run_tx(pool, tx).await
+}
+
+#[sqlx::test]
+async fn tells_the_tables_and_columns(pool: PgPool) -> Result<(), sqlx::Error> {
+    assert!(read(&pool, table_exists("todos")).await?);
+    assert!(read(&pool, table_exists("public.todos")).await?);
+    assert!(!read(&pool, table_exists("missing")).await?);
+
+    let description = read(&pool, column_type("todos", "description")).await?;
+    assert_eq!(description.as_deref(), Some("text"));
+    let id = read(&pool, column_type("todos", "id")).await?;
+    assert_eq!(id.as_deref(), Some("bigint"));
+    assert_eq!(read(&pool, column_type("todos", "missing")).await?, None);
+    assert_eq!(read(&pool, column_type("missing", "id")).await?, None);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn tells_the_schema_version(pool: PgPool) -> Result<(), sqlx::Error> {
+    let version = read(&pool, current_schema_version()).await?;
+    assert_eq!(version, Some(20240101000000));
+    Ok(())
+}
+
+#[sqlx::test(migrations = false)]
+async fn tells_an_unmigrated_schema(pool: PgPool) -> Result<(), sqlx::Error> {
+    assert_eq!(read(&pool, current_schema_version()).await?, None);
+    assert!(!read(&pool, table_exists("todos")).await?);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn guards_destructive_work(pool: PgPool) -> Result<(), sqlx::Error> {
+    // the column the cleanup counts on is not there: nothing is deleted
+    let cleanup = column_type("todos", "archived_at")
+        .try_abort(|found| match found {
+            Some(_) => Ok(found),
+            None => Err(sqlx::Error::ColumnNotFound("archived_at".to_string())),
+        })
+        .and_then(|_| runner::sql("DELETE FROM todos WHERE archived_at IS NOT NULL"));
+    let result = run_tx(&pool, cleanup).await;
+    assert!(matches!(result, Err(sqlx::Error::ColumnNotFound(_))));
+    Ok(())
+}
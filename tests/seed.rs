@@ -0,0 +1,83 @@
+#![cfg(feature = "postgres")]
+
+use serde_json::json;
+use sqlx::PgPool;
+
+use tx::prelude::*;
+use tx::seed::{Seed, SeedSet};
+
+fn demo() -> SeedSet {
+    let todos = Seed::new("todos").upsert(
+        "todos",
+        &["id"],
+        [
+            json!({"id": 1001, "description": "write the seeds"}),
+            json!({"id": 1002, "description": "run them twice"}),
+        ],
+    );
+    let tags = Seed::new("tags").after("todos").upsert(
+        "todo_tags",
+        &["todo_id", "tag"],
+        [
+            json!({"todo_id": 1001, "tag": "seed"}),
+            json!({"todo_id": 1002, "tag": "seed"}),
+        ],
+    );
+    // declared before the seed it depends on
+    SeedSet::new("demo").seed(tags).seed(todos)
+}
+
+async fn rows(pool: &PgPool) -> Result<Vec<(i64, String, String)>, sqlx::Error> {
+    let sql = "SELECT id, description, tag FROM todos JOIN todo_tags ON todo_id = id ORDER BY id";
+    sqlx::query_as(sql).fetch_all(pool).await
+}
+
+#[sqlx::test]
+async fn seeds_in_the_order_of_the_dependencies(pool: PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"CREATE TABLE todo_tags
+           (
+               todo_id BIGINT NOT NULL REFERENCES todos (id),
+               tag     TEXT   NOT NULL,
+               PRIMARY KEY (todo_id, tag)
+           )"#,
+    )
+    .execute(&pool)
+    .await?;
+    let set = demo();
+    let order: Vec<&str> = set.ordered()?.iter().map(|seed| seed.name()).collect();
+    assert_eq!(order, ["todos", "tags"]);
+    assert_eq!(
+        set.describe().to_string(),
+        "seed_set demo\n  seed tags\n  seed todos\n"
+    );
+
+    run_tx(&pool, set).await?;
+    let seeded = vec![
+        (1001, "write the seeds".to_string(), "seed".to_string()),
+        (1002, "run them twice".to_string(), "seed".to_string()),
+    ];
+    assert_eq!(rows(&pool).await?, seeded);
+
+    // upserted again, to the same rows
+    run_tx(&pool, demo()).await?;
+    assert_eq!(rows(&pool).await?, seeded);
+    Ok(())
+}
+
+#[test]
+fn rejects_unknown_and_circular_dependencies() {
+    let unknown = SeedSet::new("dev").seed(Seed::new("notes").after("todos"));
+    let e = unknown.ordered().unwrap_err();
+    assert!(e
+        .to_string()
+        .contains("seed set dev: notes depends on unknown todos"));
+
+    let circular = SeedSet::new("dev")
+        .seed(Seed::new("a").after("b"))
+        .seed(Seed::new("b").after("a"));
+    let e = circular.ordered().unwrap_err();
+    assert!(e
+        .to_string()
+        .contains("seed set dev: a, b depend on each other"));
+}
@@ -0,0 +1,41 @@
+#![cfg(feature = "postgres")]
+
+use sqlx::PgPool;
+
+use tx::prelude::*;
+use tx::runner::{self, WriteTx};
+
+// No trace source is set in this binary, so the comments only tell the name of the chain.
+
+async fn current_query(pool: &PgPool, options: TxOptions, sql: &str) -> String {
+    let (query,): (String,) = runner::run_tx_with(
+        pool,
+        options,
+        runner::sql(sql.to_string())
+            .fetch_one::<_, WriteTx>()
+            .named("it's a name"),
+    )
+    .await
+    .unwrap();
+    query
+}
+
+#[sqlx::test]
+async fn comments_are_encoded(pool: PgPool) {
+    let options = TxOptions::new().sql_comments(true);
+    assert_eq!(
+        current_query(&pool, options, "SELECT current_query();").await,
+        "SELECT current_query() /*tx='it%27s%20a%20name'*/;"
+    );
+}
+
+#[sqlx::test]
+async fn commented_statements_are_left_alone(pool: PgPool) {
+    let options = TxOptions::new().sql_comments(true);
+    let sql = "SELECT current_query() /* mine */";
+    assert_eq!(current_query(&pool, options, sql).await, sql);
+    assert_eq!(
+        current_query(&pool, TxOptions::new(), "SELECT current_query()").await,
+        "SELECT current_query()"
+    );
+}
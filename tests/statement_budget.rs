@@ -0,0 +1,108 @@
+#![cfg(feature = "postgres")]
+
+use std::sync::{Arc, Mutex, OnceLock};
+
+use sqlx::PgPool;
+
+use tx::observer::{add_observer, TxEvent, TxId, TxObserver};
+use tx::prelude::*;
+use tx::runner::{self, OverBudget};
+
+// A step inserting its todos one statement at a time, then telling how many statements the
+// chain issued so far.
+fn insert_one_by_one(
+    ids: Vec<i64>,
+) -> impl Tx<PgCtx, Item = u32, Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(move |ctx: &mut PgCtx| {
+        Box::pin(async move {
+            for id in ids {
+                sqlx::query("INSERT INTO todos (id, description) VALUES ($1, 'n+1')")
+                    .bind(id)
+                    .execute(&mut **ctx)
+                    .await?;
+            }
+            Ok(ctx.statements())
+        })
+    })
+}
+
+// The overruns reported, as (statements, budget). The observers are shared by the whole
+// process, so each test looks for a budget of its own.
+#[derive(Clone, Default)]
+struct Overruns(Arc<Mutex<Vec<(u32, u32)>>>);
+impl TxObserver for Overruns {
+    fn on_event(&self, _: TxId, event: &TxEvent<'_>) {
+        if let TxEvent::StatementBudgetExceeded { budget, statements } = event {
+            self.0.lock().unwrap().push((*statements, *budget));
+        }
+    }
+}
+
+fn overruns() -> Overruns {
+    static OVERRUNS: OnceLock<Overruns> = OnceLock::new();
+    let overruns = OVERRUNS.get_or_init(|| {
+        let overruns = Overruns::default();
+        add_observer(overruns.clone());
+        overruns
+    });
+    overruns.clone()
+}
+
+async fn count(pool: &PgPool) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("SELECT count(*) FROM todos")
+        .fetch_one(pool)
+        .await
+}
+
+#[sqlx::test]
+async fn counts_the_statements(pool: PgPool) -> Result<(), sqlx::Error> {
+    let options = TxOptions::new().statement_budget(3, OverBudget::Fail);
+    let statements = runner::run_tx_with(&pool, options, insert_one_by_one(vec![1, 2, 3])).await?;
+    assert_eq!(statements, 3);
+    assert_eq!(count(&pool).await?, 3);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn fails_over_budget(pool: PgPool) -> Result<(), sqlx::Error> {
+    let options = TxOptions::new().statement_budget(3, OverBudget::Fail);
+    let result = runner::run_tx_with(&pool, options, insert_one_by_one(vec![1, 2, 3, 4])).await;
+    let e = result.unwrap_err();
+    assert!(StatementBudgetExceeded::is(&e));
+    assert_eq!(
+        e.to_string(),
+        "error communicating with database: 4 statements issued, over the budget of 3"
+    );
+    assert_eq!(count(&pool).await?, 0);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn fails_with_its_own_tx_error(pool: PgPool) -> Result<(), sqlx::Error> {
+    let options = TxOptions::new().statement_budget(3, OverBudget::Fail);
+    let chain = insert_one_by_one(vec![1, 2, 3, 4]).map_err(TxError::<()>::from);
+    let result = runner::run_tx_with(&pool, options, chain).await;
+    let Err(TxError::StatementBudgetExceeded(e)) = result else {
+        panic!("not over budget: {:?}", result);
+    };
+    assert_eq!((e.budget, e.statements), (3, 4));
+    Ok(())
+}
+
+#[sqlx::test]
+async fn warns_over_budget(pool: PgPool) -> Result<(), sqlx::Error> {
+    let options = TxOptions::new().statement_budget(3, OverBudget::Warn);
+    runner::run_tx_with(&pool, options, insert_one_by_one(vec![1, 2, 3, 4])).await?;
+    assert_eq!(count(&pool).await?, 4);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn reports_the_overrun_to_the_observers(pool: PgPool) -> Result<(), sqlx::Error> {
+    let overruns = overruns();
+    let options = TxOptions::new().statement_budget(2, OverBudget::Warn);
+    runner::run_tx_with(&pool, options, insert_one_by_one(vec![1, 2, 3])).await?;
+    assert_eq!(count(&pool).await?, 3);
+    assert!(overruns.0.lock().unwrap().contains(&(3, 2)));
+    Ok(())
+}
@@ -0,0 +1,64 @@
+#![cfg(feature = "postgres")]
+
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::{PgPool, Postgres};
+
+use tx::prelude::*;
+use tx::runner::{self, sql, StatementCacheExt};
+
+fn cached() -> impl Tx<PgCtx, Item = usize, Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(|ctx: &mut PgCtx| {
+        let cached = ctx.cached_statements();
+        Box::pin(async move { Ok(cached) })
+    })
+}
+
+fn select(n: i64) -> impl Tx<PgCtx, Item = (i64,), Err = sqlx::Error, Mode = AsyncMode> {
+    sql::<Postgres>(format!("SELECT $1::bigint + {}", n))
+        .bind(n)
+        .fetch_one()
+}
+
+// How many statements `tx` left in the cache of the connection.
+async fn cached_by<X>(pool: &PgPool, options: TxOptions, tx: X) -> Result<usize, sqlx::Error>
+where
+    X: Tx<PgCtx, Err = sqlx::Error, Mode = AsyncMode> + Send,
+    X::Item: Send,
+{
+    let chain = cached()
+        .and_then(move |before| tx.and_then(move |_| cached().map(move |after| after - before)));
+    runner::run_tx_with(pool, options, chain).await
+}
+
+#[sqlx::test]
+async fn keeps_the_statements_by_default(pool: PgPool) -> Result<(), sqlx::Error> {
+    assert_eq!(cached_by(&pool, TxOptions::new(), select(1)).await?, 1);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn keeps_only_the_hot_statements(pool: PgPool) -> Result<(), sqlx::Error> {
+    let options = TxOptions::new().persistent_statements(false);
+    assert_eq!(cached_by(&pool, options, select(1)).await?, 0);
+    assert_eq!(cached_by(&pool, options, select(2).hot()).await?, 1);
+    let mixed = select(3).and_then(|_| select(4).hot());
+    assert_eq!(cached_by(&pool, options, mixed).await?, 1);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn holds_as_many_statements_as_its_capacity(
+    pool_options: PgPoolOptions,
+    connect_options: PgConnectOptions,
+) -> Result<(), sqlx::Error> {
+    let pool = pool_options
+        .max_connections(1)
+        .connect_with(connect_options.statement_cache_capacity(2))
+        .await?;
+    let chain = select(1)
+        .and_then(|_| select(2))
+        .and_then(|_| select(3))
+        .and_then(|_| cached());
+    assert_eq!(runner::run_tx(&pool, chain).await?, 2);
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use tx::prelude::*;
+
+// Sync chains run on the thread which builds them, so neither their context nor their closures
+// have to be `Send`.
+struct Local {
+    log: Rc<RefCell<Vec<&'static str>>>,
+}
+
+fn step(name: &'static str) -> impl Tx<Local, Item = i32, Err = String, Mode = SyncMode> {
+    with_tx(move |ctx: &mut Local| {
+        ctx.log.borrow_mut().push(name);
+        Ok(1)
+    })
+}
+
+#[test]
+fn chains_non_send_contexts_and_closures() {
+    let seen = Rc::new(RefCell::new(vec![]));
+    let (map_seen, release_seen) = (Rc::clone(&seen), Rc::clone(&seen));
+    let chain = step("first")
+        .map(move |n| {
+            map_seen.borrow_mut().push(n);
+            n + 1
+        })
+        .and_then(|n| step("second").map(move |m| n + m))
+        .or_else(|e| Err(e).into_tx())
+        .join3(step("third"), step("fourth"))
+        .map(|(a, b, c)| a + b + c);
+    let chain = bracket(
+        chain,
+        |n| ready(Ok(n * 10)),
+        move |n| {
+            release_seen.borrow_mut().push(n);
+            ready(Ok(()))
+        },
+    );
+
+    let mut ctx = Local {
+        log: Rc::new(RefCell::new(vec![])),
+    };
+    assert_eq!(chain.run(&mut ctx), Ok(50));
+    assert_eq!(*ctx.log.borrow(), ["first", "second", "third", "fourth"]);
+    assert_eq!(*seen.borrow(), [1, 5]);
+}
@@ -0,0 +1,223 @@
+#![cfg(feature = "postgres")]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use sqlx::PgPool;
+
+use tx::prelude::*;
+use tx::runner::{run_tenant_tx, savepoint, OverBudget, Tenant, TenantCtx};
+
+// A schema per tenant, each with a `notes` table of its own.
+async fn tenants(pool: &PgPool) -> Result<(), sqlx::Error> {
+    for schema in ["acme", "globex"] {
+        sqlx::query(&format!("CREATE SCHEMA {}", schema))
+            .execute(pool)
+            .await?;
+        sqlx::query(&format!(
+            "CREATE TABLE {}.notes (body TEXT NOT NULL)",
+            schema
+        ))
+        .execute(pool)
+        .await?;
+    }
+    Ok(())
+}
+
+fn add_note(
+    body: &'static str,
+) -> impl Tx<TenantCtx, Item = (), Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(move |ctx: &mut TenantCtx| {
+        Box::pin(async move {
+            sqlx::query("INSERT INTO notes (body) VALUES ($1)")
+                .bind(body)
+                .execute(&mut **ctx)
+                .await?;
+            Ok(())
+        })
+    })
+}
+
+fn notes<B, A: TxAccess>(
+) -> impl Tx<TenantCtx<B, A>, Item = Vec<String>, Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(|ctx: &mut TenantCtx<B, A>| {
+        Box::pin(async move {
+            sqlx::query_scalar("SELECT body FROM notes ORDER BY body")
+                .fetch_all(&mut **ctx)
+                .await
+        })
+    })
+}
+
+fn settings<B>(
+) -> impl Tx<TenantCtx<B, ReadTx>, Item = (String, String), Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(|ctx: &mut TenantCtx<B, ReadTx>| {
+        let tenant = ctx.tenant().tenant_id().to_string();
+        Box::pin(async move {
+            let (id, search_path): (String, String) = sqlx::query_as(
+                "SELECT current_setting('app.tenant_id'), current_setting('search_path')",
+            )
+            .fetch_one(&mut **ctx)
+            .await?;
+            assert_eq!(id, tenant);
+            Ok((id, search_path))
+        })
+    })
+}
+
+#[sqlx::test]
+async fn runs_each_tenant_in_its_schema(pool: PgPool) -> Result<(), sqlx::Error> {
+    tenants(&pool).await?;
+    let acme = || Tenant::schema("acme");
+    let globex = || Tenant::schema("globex").shared("public");
+
+    run_tenant_tx(&pool, TxOptions::new(), acme(), add_note("acme's")).await?;
+    run_tenant_tx(&pool, TxOptions::new(), globex(), add_note("globex's")).await?;
+    let acme_notes = run_tenant_tx(&pool, TxOptions::new(), acme(), notes::<_, ReadTx>()).await?;
+    assert_eq!(acme_notes, ["acme's"]);
+    let globex_notes =
+        run_tenant_tx(&pool, TxOptions::new(), globex(), notes::<_, ReadTx>()).await?;
+    assert_eq!(globex_notes, ["globex's"]);
+
+    let set = run_tenant_tx(&pool, TxOptions::new(), globex(), settings()).await?;
+    assert_eq!(
+        set,
+        ("globex".to_string(), "\"globex\", \"public\"".to_string())
+    );
+    Ok(())
+}
+
+#[sqlx::test]
+async fn sets_the_tenant_for_the_transaction_only(pool: PgPool) -> Result<(), sqlx::Error> {
+    tenants(&pool).await?;
+    // one connection, so that the next transaction gets the same one
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .max_connections(1)
+        .connect_with(pool.connect_options().as_ref().clone())
+        .await?;
+    let default_path: String = sqlx::query_scalar("SHOW search_path")
+        .fetch_one(&pool)
+        .await?;
+
+    let failing = add_note("lost").and_then(|()| {
+        with_tx_async(|_: &mut TenantCtx| {
+            Box::pin(async { Err::<(), _>(sqlx::Error::RowNotFound) })
+        })
+    });
+    let result = run_tenant_tx(&pool, TxOptions::new(), Tenant::schema("acme"), failing).await;
+    assert!(matches!(result, Err(sqlx::Error::RowNotFound)));
+    run_tenant_tx(
+        &pool,
+        TxOptions::new(),
+        Tenant::schema("acme"),
+        add_note("kept"),
+    )
+    .await?;
+
+    let (path, id): (String, String) = sqlx::query_as(
+        "SELECT current_setting('search_path'), current_setting('app.tenant_id', true)",
+    )
+    .fetch_one(&pool)
+    .await?;
+    assert_eq!(path, default_path);
+    assert_eq!(id, "");
+
+    // a tenant of a shared schema only gets its id
+    let tenant = Tenant::id("initech");
+    let (id, path) = run_tenant_tx(&pool, TxOptions::new(), tenant, settings()).await?;
+    assert_eq!((id.as_str(), path), ("initech", default_path));
+    let kept: Vec<String> = sqlx::query_scalar("SELECT body FROM acme.notes")
+        .fetch_all(&pool)
+        .await?;
+    assert_eq!(kept, ["kept"]);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn runs_as_the_other_transactions_do(pool: PgPool) -> Result<(), sqlx::Error> {
+    tenants(&pool).await?;
+    // the statements of the chain are counted against its budget, those setting it up are not
+    let options = || TxOptions::new().statement_budget(1, OverBudget::Fail);
+    run_tenant_tx(&pool, options(), Tenant::schema("acme"), add_note("one")).await?;
+    let two = add_note("two").and_then(|()| add_note("three"));
+    let e = run_tenant_tx(&pool, options(), Tenant::schema("acme"), two)
+        .await
+        .unwrap_err();
+    assert!(StatementBudgetExceeded::is(&e));
+
+    // and the deadline holds for it
+    let slow = with_tx_async(|ctx: &mut TenantCtx| {
+        Box::pin(async move {
+            sqlx::query("SELECT pg_sleep(1)")
+                .execute(&mut **ctx)
+                .await?;
+            Ok::<_, sqlx::Error>(())
+        })
+    });
+    let deadline = std::time::Instant::now() + std::time::Duration::from_millis(100);
+    let options = TxOptions::new().deadline(deadline);
+    let e = run_tenant_tx(&pool, options, Tenant::schema("acme"), slow)
+        .await
+        .unwrap_err();
+    assert!(DeadlineExceeded::is(&e));
+    let notes = run_tenant_tx(
+        &pool,
+        TxOptions::new(),
+        Tenant::schema("acme"),
+        notes::<_, ReadTx>(),
+    )
+    .await?;
+    assert_eq!(notes, ["one"]);
+    Ok(())
+}
+
+// The brand of the tenant `acme`.
+enum Acme {}
+
+fn add_acme_note(
+    body: &'static str,
+    committed: Arc<AtomicUsize>,
+) -> impl Tx<TenantCtx<Acme>, Item = (), Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(move |ctx: &mut TenantCtx<Acme>| {
+        ctx.after_commit(move || {
+            committed.fetch_add(1, Ordering::SeqCst);
+        });
+        Box::pin(async move {
+            sqlx::query("INSERT INTO notes (body) VALUES ($1)")
+                .bind(body)
+                .execute(&mut **ctx)
+                .await?;
+            Ok(())
+        })
+    })
+}
+
+#[sqlx::test]
+async fn runs_branded_tenants_with_hooks_and_savepoints(pool: PgPool) -> Result<(), sqlx::Error> {
+    tenants(&pool).await?;
+    let acme = || Tenant::schema("acme").branded::<Acme>();
+    let committed = Arc::new(AtomicUsize::new(0));
+
+    // the savepoint takes the note and the hook registered in it along when it is rolled back
+    let failing = add_acme_note("lost", committed.clone()).and_then(|()| {
+        with_tx_async(|_: &mut TenantCtx<Acme>| {
+            Box::pin(async { Err::<(), _>(sqlx::Error::RowNotFound) })
+        })
+    });
+    let chain = add_acme_note("kept", committed.clone())
+        .and_then(|()| savepoint(failing).or_else(|_| ready(Ok(()))))
+        .and_then(|()| {
+            savepoint(with_tx_async(|ctx: &mut TenantCtx<Acme>| {
+                let depth = ctx.depth();
+                Box::pin(async move { Ok(depth) })
+            }))
+        });
+    let depth = run_tenant_tx(&pool, TxOptions::new(), acme(), chain).await?;
+    assert_eq!(depth, 1);
+    assert_eq!(committed.load(Ordering::SeqCst), 1);
+
+    // steps generic over the brand fit in the chains of any
+    let notes = run_tenant_tx(&pool, TxOptions::new(), acme(), notes::<_, ReadTx>()).await?;
+    assert_eq!(notes, ["kept"]);
+    Ok(())
+}
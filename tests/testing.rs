@@ -0,0 +1,31 @@
+#![cfg(feature = "testing")]
+
+use tx::prelude::*;
+use tx::testing::{tx_test, TestDb};
+
+// Needs docker: `cargo test --features testing`.
+#[tokio::test]
+async fn todos_in_a_container() {
+    let db = TestDb::start(&sqlx::migrate!()).await;
+
+    let count = tx_test(db.pool(), |ctx: &mut PgCtx| {
+        Box::pin(async move {
+            sqlx::query("INSERT INTO todos (description) VALUES ('in a container')")
+                .execute(&mut **ctx)
+                .await?;
+            sqlx::query_scalar::<_, i64>("SELECT count(*) FROM todos")
+                .fetch_one(&mut **ctx)
+                .await
+        })
+    })
+    .await
+    .unwrap();
+    assert_eq!(count, 1);
+
+    // rolled back by `tx_test`
+    let count: i64 = sqlx::query_scalar("SELECT count(*) FROM todos")
+        .fetch_one(db.pool())
+        .await
+        .unwrap();
+    assert_eq!(count, 0);
+}
@@ -0,0 +1,40 @@
+use tx::prelude::*;
+use tx::trace::{Outcome, TraceExt, TxTrace};
+
+fn step(out: Result<i32, u8>) -> impl Tx<(), Item = i32, Err = u8, Mode = SyncMode> {
+    with_tx(move |_: &mut ()| out)
+}
+
+#[test]
+fn records_the_fallback_path() {
+    let trace = TxTrace::new();
+    let result = step(Err(1))
+        .named("primary")
+        .traced(&trace)
+        .or_else(|_| step(Ok(2)).named("fallback").traced(&trace))
+        .run(&mut ());
+
+    assert_eq!(result, Ok(2));
+    assert_eq!(trace.names(), ["primary", "fallback"]);
+    assert_eq!(trace.outcome("primary"), Some(Outcome::Err));
+    assert_eq!(trace.outcome("fallback"), Some(Outcome::Ok));
+    assert!(trace.steps().iter().all(|step| step.duration.is_some()));
+}
+
+#[test]
+fn orders_steps_by_start() {
+    let trace = TxTrace::new();
+    let inner = step(Ok(1)).named("inner").traced(&trace);
+    let result = inner
+        .and_then(|x| step(Ok(x + 1)).named("next").traced(&trace))
+        .named("outer")
+        .traced(&trace)
+        .run(&mut ());
+
+    assert_eq!(result, Ok(2));
+    // the outer step starts first, and ends last
+    assert_eq!(trace.names(), ["outer", "inner", "next"]);
+    assert!(trace.ran_before("inner", "next"));
+    assert!(!trace.ran_before("next", "inner"));
+    assert!(!trace.ran("missing"));
+}
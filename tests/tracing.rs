@@ -0,0 +1,174 @@
+#![cfg(all(feature = "postgres", feature = "tracing"))]
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use sqlx::PgPool;
+use tracing::field::{Field, Visit};
+use tracing::instrument::WithSubscriber;
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Event, Metadata, Subscriber};
+
+use tx::chaos::{Fault, FaultPolicy};
+use tx::prelude::*;
+use tx::runner::{self, IsolationLevel, RetryPolicy};
+
+// The spans of the `tracing` feature, as a subscriber collecting them sees them.
+
+#[derive(Debug, Clone, PartialEq)]
+struct Span {
+    name: &'static str,
+    fields: BTreeMap<&'static str, String>,
+    // the index of the span it was opened in
+    parent: Option<usize>,
+}
+
+#[derive(Clone, Default)]
+struct Spans(Arc<Mutex<State>>);
+#[derive(Default)]
+struct State {
+    spans: Vec<Span>,
+    entered: Vec<usize>,
+}
+impl Spans {
+    fn all(&self) -> Vec<Span> {
+        self.0.lock().unwrap().spans.clone()
+    }
+}
+impl Subscriber for Spans {
+    fn register_callsite(&self, _: &'static Metadata<'static>) -> Interest {
+        Interest::sometimes()
+    }
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.is_span() && metadata.target().starts_with("tx::")
+    }
+    fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+        let mut state = self.0.lock().unwrap();
+        let mut span = Span {
+            name: attributes.metadata().name(),
+            fields: BTreeMap::new(),
+            parent: state.entered.last().copied(),
+        };
+        attributes.record(&mut Fields(&mut span.fields));
+        state.spans.push(span);
+        Id::from_u64(state.spans.len() as u64)
+    }
+    fn record(&self, id: &Id, values: &Record<'_>) {
+        let mut state = self.0.lock().unwrap();
+        let span = &mut state.spans[id.into_u64() as usize - 1];
+        values.record(&mut Fields(&mut span.fields));
+    }
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+    fn event(&self, _: &Event<'_>) {}
+    fn enter(&self, id: &Id) {
+        let mut state = self.0.lock().unwrap();
+        state.entered.push(id.into_u64() as usize - 1);
+    }
+    fn exit(&self, _: &Id) {
+        self.0.lock().unwrap().entered.pop();
+    }
+}
+
+struct Fields<'f>(&'f mut BTreeMap<&'static str, String>);
+impl Visit for Fields<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name(), value.to_string());
+    }
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(field.name(), format!("{:?}", value));
+    }
+}
+
+fn fields(pairs: &[(&'static str, &str)]) -> BTreeMap<&'static str, String> {
+    pairs.iter().map(|(k, v)| (*k, v.to_string())).collect()
+}
+
+fn count() -> impl Tx<PgCtx, Item = i64, Err = sqlx::Error, Mode = AsyncMode> + Clone {
+    with_tx_async(|ctx: &mut PgCtx| {
+        Box::pin(async move {
+            sqlx::query_scalar("SELECT count(*) FROM todos")
+                .fetch_one(&mut **ctx)
+                .await
+        })
+    })
+}
+
+#[sqlx::test]
+async fn a_span_per_transaction_and_named_step(pool: PgPool) -> Result<(), sqlx::Error> {
+    let spans = Spans::default();
+    let chain = count()
+        .named("first")
+        .and_then(|_| count().named("second"))
+        .named("count_twice");
+    let options = TxOptions::new().isolation_level(IsolationLevel::Serializable);
+    runner::run_tx_with(&pool, options, chain)
+        .with_subscriber(spans.clone())
+        .await?;
+
+    let transaction = Span {
+        name: "transaction",
+        fields: fields(&[
+            ("name", "count_twice"),
+            ("isolation", "serializable"),
+            ("attempt", "1"),
+            ("outcome", "committed"),
+        ]),
+        parent: None,
+    };
+    let step = |name, parent| Span {
+        name: "step",
+        fields: fields(&[("name", name)]),
+        parent: Some(parent),
+    };
+    assert_eq!(
+        spans.all(),
+        [
+            transaction,
+            step("count_twice", 0),
+            step("first", 1),
+            step("second", 1)
+        ]
+    );
+    Ok(())
+}
+
+#[sqlx::test]
+async fn a_span_per_attempt(pool: PgPool) -> Result<(), sqlx::Error> {
+    let spans = Spans::default();
+    let faults = FaultPolicy::new(1.0).faults([Fault::SerializationFailure]);
+    let policy = RetryPolicy::new()
+        .max_attempts(2)
+        .base_delay(std::time::Duration::from_millis(1))
+        .inject_faults(faults);
+    let chain = count().named("count");
+    let result = runner::run_tx_retry(&pool, TxOptions::new(), policy, || chain.clone())
+        .with_subscriber(spans.clone())
+        .await;
+    assert!(result.is_err());
+
+    let attempts: Vec<_> = spans
+        .all()
+        .into_iter()
+        .filter(|span| span.name == "transaction")
+        .map(|span| span.fields)
+        .collect();
+    assert_eq!(
+        attempts,
+        [
+            fields(&[
+                ("name", "inject_fault"),
+                ("isolation", "default"),
+                ("attempt", "1"),
+                ("outcome", "rolled back"),
+            ]),
+            fields(&[
+                ("name", "inject_fault"),
+                ("isolation", "default"),
+                ("attempt", "2"),
+                ("outcome", "rolled back"),
+            ]),
+        ]
+    );
+    Ok(())
+}
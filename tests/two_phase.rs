@@ -0,0 +1,35 @@
+#![cfg(feature = "postgres")]
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use sqlx::PgPool;
+
+use tx::prelude::*;
+use tx::runner::prepare_tx;
+
+#[sqlx::test]
+async fn rolls_back_when_it_cannot_prepare(pool: PgPool) -> Result<(), sqlx::Error> {
+    let rolled_back = Arc::new(AtomicBool::new(false));
+    let flag = rolled_back.clone();
+    let chain = with_tx_async(move |ctx: &mut PgCtx| {
+        ctx.after_rollback(move || flag.store(true, Ordering::SeqCst));
+        Box::pin(async move {
+            sqlx::query("INSERT INTO todos (id, description) VALUES (1, 'lost')")
+                .execute(&mut **ctx)
+                .await?;
+            Ok::<_, sqlx::Error>(())
+        })
+    });
+
+    // over the 200 bytes a transaction identifier may have
+    let gid = "g".repeat(300);
+    let result = prepare_tx(&pool, TxOptions::new(), gid, chain).await;
+    assert!(result.is_err());
+    assert!(rolled_back.load(Ordering::SeqCst));
+    let count: i64 = sqlx::query_scalar("SELECT count(*) FROM todos")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(count, 0);
+    Ok(())
+}
@@ -0,0 +1,30 @@
+#![cfg(feature = "postgres")]
+
+use tx::prelude::*;
+use tx::testing::tx_test;
+
+// Each test gets a database of its own from sqlx, migrated with `migrations/`, and a
+// transaction in it which is rolled back afterwards.
+
+#[tx_test]
+async fn inserted_todo_is_found(ctx: &mut PgCtx) -> Result<(), sqlx::Error> {
+    let id: i64 = sqlx::query_scalar("INSERT INTO todos (description) VALUES ($1) RETURNING id")
+        .bind("found")
+        .fetch_one(&mut **ctx)
+        .await?;
+    let description: String = sqlx::query_scalar("SELECT description FROM todos WHERE id = $1")
+        .bind(id)
+        .fetch_one(&mut **ctx)
+        .await?;
+    assert_eq!(description, "found");
+    Ok(())
+}
+
+#[tx_test]
+async fn starts_without_todos(ctx: &mut PgCtx) -> Result<(), sqlx::Error> {
+    let count: i64 = sqlx::query_scalar("SELECT count(*) FROM todos")
+        .fetch_one(&mut **ctx)
+        .await?;
+    assert_eq!(count, 0);
+    Ok(())
+}
@@ -0,0 +1,100 @@
+#![cfg(feature = "postgres")]
+
+use sqlx::PgPool;
+
+use tx::prelude::*;
+use tx::runner::{self, run_unit_of_work, OverBudget, UowCtx};
+
+async fn descriptions(pool: &PgPool) -> Result<Vec<(i64, String)>, sqlx::Error> {
+    sqlx::query_as("SELECT id, description FROM todos ORDER BY id")
+        .fetch_all(pool)
+        .await
+}
+
+fn register(
+    f: impl FnOnce(&mut UowCtx) + Send,
+) -> impl Tx<UowCtx, Item = usize, Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(move |uow: &mut UowCtx| {
+        f(uow);
+        Box::pin(async { Ok::<_, sqlx::Error>(()) })
+    })
+    .and_then(|()| runner::flush())
+}
+
+#[sqlx::test]
+async fn sends_a_statement_per_run_of_changes_of_the_same_shape(
+    pool: PgPool,
+) -> Result<(), sqlx::Error> {
+    let chain = register(|uow| {
+        for id in 1..4_i64 {
+            uow.insert(
+                "todos",
+                vec![("id", id.into()), ("description", "todo".into())],
+            );
+        }
+        uow.update("todos", ("id", 2), vec![("description", "updated".into())]);
+        uow.delete("todos", ("id", 3));
+        assert_eq!(uow.pending(), 5);
+    });
+    assert_eq!(run_unit_of_work(&pool, TxOptions::new(), chain).await?, 3);
+    assert_eq!(
+        descriptions(&pool).await?,
+        [(1, "todo".to_string()), (2, "updated".to_string())]
+    );
+    Ok(())
+}
+
+#[sqlx::test]
+async fn updates_a_key_once_per_statement(pool: PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO todos (id, description) VALUES (1, 'old'), (2, 'old')")
+        .execute(&pool)
+        .await?;
+    let chain = register(|uow| {
+        uow.update("todos", ("id", 1), vec![("description", "first".into())]);
+        uow.update("todos", ("id", 2), vec![("description", "second".into())]);
+        // the later write wins, in a statement of its own
+        uow.update("todos", ("id", 1), vec![("description", "last".into())]);
+    });
+    assert_eq!(run_unit_of_work(&pool, TxOptions::new(), chain).await?, 2);
+    assert_eq!(
+        descriptions(&pool).await?,
+        [(1, "last".to_string()), (2, "second".to_string())]
+    );
+    Ok(())
+}
+
+#[sqlx::test]
+async fn leaves_out_updates_setting_nothing(pool: PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO todos (id, description) VALUES (1, 'old')")
+        .execute(&pool)
+        .await?;
+    let chain = register(|uow| {
+        uow.update("todos", ("id", 1), vec![]);
+        assert_eq!(uow.pending(), 0);
+        uow.update("todos", ("id", 1), vec![("done", true.into())]);
+    });
+    assert_eq!(run_unit_of_work(&pool, TxOptions::new(), chain).await?, 1);
+    let done: bool = sqlx::query_scalar("SELECT done FROM todos WHERE id = 1")
+        .fetch_one(&pool)
+        .await?;
+    assert!(done);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn counts_the_flushed_statements_against_the_budget(pool: PgPool) -> Result<(), sqlx::Error> {
+    // flushed by the runner once the chain is over, in two statements
+    let chain = with_tx_async(|uow: &mut UowCtx| {
+        uow.insert(
+            "todos",
+            vec![("id", 1.into()), ("description", "kept".into())],
+        );
+        uow.delete("todos", ("id", 2));
+        Box::pin(async { Ok::<_, sqlx::Error>(()) })
+    });
+    let options = TxOptions::new().statement_budget(1, OverBudget::Fail);
+    let e = run_unit_of_work(&pool, options, chain).await.unwrap_err();
+    assert!(StatementBudgetExceeded::is(&e));
+    assert!(descriptions(&pool).await?.is_empty());
+    Ok(())
+}
@@ -0,0 +1,97 @@
+#![cfg(feature = "postgres")]
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use sqlx::PgPool;
+
+use tx::prelude::*;
+use tx::watchdog::{run_tx_watched, SlowTx, Watchdog};
+
+fn sleep(seconds: f64) -> impl Tx<PgCtx, Item = (), Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(move |ctx: &mut PgCtx| {
+        Box::pin(async move {
+            sqlx::query("SELECT pg_sleep($1)")
+                .bind(seconds)
+                .execute(&mut **ctx)
+                .await?;
+            Ok(())
+        })
+    })
+}
+
+// `sleep` a statement at a time: a cancelled chain is dropped between two statements, while
+// the one in flight runs to its end
+fn sleep_in_steps(
+    seconds: f64,
+    steps: u32,
+) -> impl Tx<PgCtx, Item = (), Err = sqlx::Error, Mode = AsyncMode> {
+    with_tx_async(move |ctx: &mut PgCtx| {
+        Box::pin(async move {
+            for _ in 0..steps {
+                sleep(seconds).run(ctx).await?;
+            }
+            Ok(())
+        })
+    })
+}
+
+fn collect(reports: &Arc<Mutex<Vec<SlowTx>>>) -> impl Fn(&SlowTx) + Send + Sync + 'static {
+    let reports = reports.clone();
+    move |slow| reports.lock().unwrap().push(slow.clone())
+}
+
+#[sqlx::test]
+async fn reports_the_step_a_slow_transaction_is_in(pool: PgPool) -> Result<(), sqlx::Error> {
+    let reports = Arc::new(Mutex::new(vec![]));
+    let watchdog = Watchdog::new(Duration::from_millis(200)).on_slow(collect(&reports));
+    let chain = sleep(0.0)
+        .named("quick")
+        .and_then(|()| sleep(0.5).named("sleeping"))
+        .named("slow");
+    run_tx_watched(&pool, TxOptions::new(), &watchdog, chain).await?;
+
+    let reports = reports.lock().unwrap();
+    // at 200ms and 400ms
+    assert_eq!(reports.len(), 2);
+    for (i, report) in reports.iter().enumerate() {
+        assert_eq!(report.name, "slow");
+        assert_eq!(report.step, Some("sleeping"));
+        assert!(report.elapsed >= Duration::from_millis(200) * (i as u32 + 1));
+        assert!(!report.cancelled);
+    }
+    Ok(())
+}
+
+#[sqlx::test]
+async fn quick_transactions_are_not_reported(pool: PgPool) -> Result<(), sqlx::Error> {
+    let reports = Arc::new(Mutex::new(vec![]));
+    let watchdog = Watchdog::new(Duration::from_secs(1)).on_slow(collect(&reports));
+    run_tx_watched(&pool, TxOptions::new(), &watchdog, sleep(0.0)).await?;
+    assert!(reports.lock().unwrap().is_empty());
+    Ok(())
+}
+
+#[sqlx::test]
+async fn cancels_after_the_limit(pool: PgPool) -> Result<(), sqlx::Error> {
+    let reports = Arc::new(Mutex::new(vec![]));
+    let watchdog = Watchdog::new(Duration::from_millis(100))
+        .cancel_after(Duration::from_millis(250))
+        .on_slow(collect(&reports));
+    let started = Instant::now();
+    let result = run_tx_watched(
+        &pool,
+        TxOptions::new(),
+        &watchdog,
+        sleep_in_steps(0.05, 100).named("stuck"),
+    )
+    .await;
+    assert!(result.is_err());
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    let reports = reports.lock().unwrap();
+    let cancelled: Vec<bool> = reports.iter().map(|report| report.cancelled).collect();
+    assert_eq!(cancelled, [false, false, true]);
+    assert_eq!(reports[2].step, Some("stuck"));
+    Ok(())
+}
@@ -0,0 +1,135 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+
+use tx::prelude::*;
+
+// The allocations made on each thread, tests running side by side on threads of their own.
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn count() {
+    let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count();
+        System.alloc(layout)
+    }
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count();
+        System.alloc_zeroed(layout)
+    }
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count();
+        System.realloc(ptr, layout, new_size)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+// What `f` returns, and how many allocations it made.
+fn allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (result, ALLOCATIONS.with(Cell::get) - before)
+}
+
+// Polls `future` to its end; the steps here never wait.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+struct Counter(u64);
+
+fn incr(ctx: &mut Counter) -> Result<u64, String> {
+    ctx.0 += 1;
+    Ok(ctx.0)
+}
+
+// The chains are kept short: the type of a chain grows with each combinator, and so
+// does the time to compile it.
+#[test]
+fn sync_chains_do_not_allocate() {
+    let chain = with_tx(incr)
+        .map(|n| n * 10)
+        .and_then(|n| with_tx(move |ctx: &mut Counter| incr(ctx).map(|m| n + m)))
+        .then(|r| ready(r.map(|n| n + 1)))
+        .try_map(Ok)
+        .join(with_tx(incr))
+        .map(|(a, b)| a + b)
+        .join3(incr, ready(Ok(100)))
+        .map_err(|e: String| e.len())
+        .named("sync_chain");
+
+    let mut ctx = Counter(0);
+    let (result, allocations) = allocations(|| chain.run(&mut ctx));
+    assert_eq!(result, Ok((16, 4, 100)));
+    assert_eq!(allocations, 0);
+}
+
+#[test]
+fn sync_error_paths_do_not_allocate() {
+    let chain = with_tx(incr)
+        .map_err(|_| "failed")
+        .abort(|_| "aborted")
+        .recover(|_| 0)
+        .try_abort(|_| Err("aborted again"))
+        .or_else(|_| with_tx(|ctx: &mut Counter| incr(ctx).map_err(|_| "unreachable")))
+        .try_recover(Err)
+        .named("sync_error_paths");
+
+    let mut ctx = Counter(0);
+    let (result, allocations) = allocations(|| chain.run(&mut ctx));
+    assert_eq!(result, Ok(2));
+    assert_eq!(allocations, 0);
+}
+
+#[test]
+fn only_the_closures_allocate() {
+    let chain = with_tx(incr)
+        .map(|n| vec![n; 4])
+        .map(|ns| ns.len())
+        .named("allocating_closure");
+
+    let mut ctx = Counter(0);
+    let (result, allocations) = allocations(|| chain.run(&mut ctx));
+    assert_eq!(result, Ok(4));
+    assert_eq!(allocations, 1);
+}
+
+// Async chains box the future of each combinator and of each `with_tx_async` closure.
+#[test]
+fn async_chains_allocate_a_box_per_step() {
+    let step = || {
+        with_tx_async(|ctx: &mut Counter| {
+            Box::pin(async move {
+                ctx.0 += 1;
+                Ok::<_, Infallible>(ctx.0)
+            })
+        })
+    };
+    let chain = step().map(|n| n * 10).and_then(move |_| step());
+
+    let mut ctx = Counter(0);
+    let (result, allocations) = allocations(|| block_on(chain.run(&mut ctx)));
+    assert_eq!(result, Ok(2));
+    assert_eq!(allocations, 4);
+}
@@ -0,0 +1,245 @@
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, FnArg, GenericArgument, Ident, ItemFn, Pat, PathArguments, ReturnType,
+    Token, Type,
+};
+
+// Turns `async fn name(ctx: &mut Ctx, args...) -> Result<T, E>` into a step: a struct `Name`
+// holding the arguments, implementing `Tx<Ctx, Item = T, Err = E>` by running the body, and
+// a constructor `name(args...) -> Name` in place of the function.
+//
+// The generated code names the `tx` crate `::tx`; a crate which has it under another name
+// passes its path with `#[tx(crate = path)]`.
+#[proc_macro_attribute]
+pub fn tx(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as Args);
+    let item = parse_macro_input!(item as ItemFn);
+    expand(args, item)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+// Turns `async fn name(ctx: &mut Ctx) -> R` into a `#[sqlx::test]` run by `testing::tx_test`:
+// sqlx hands the test a fresh database, migrated and with the fixtures its arguments name,
+// which are passed on as they are, and the body runs in a transaction rolled back after it.
+#[proc_macro_attribute]
+pub fn tx_test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemFn);
+    expand_test(attr.into(), item)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+struct Args {
+    krate: syn::Path,
+}
+impl Parse for Args {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.is_empty() {
+            return Ok(Args {
+                krate: syn::parse_quote!(::tx),
+            });
+        }
+        input.parse::<Token![crate]>()?;
+        input.parse::<Token![=]>()?;
+        Ok(Args {
+            krate: input.parse()?,
+        })
+    }
+}
+
+fn expand(args: Args, item: ItemFn) -> syn::Result<TokenStream2> {
+    let krate = args.krate;
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = item;
+
+    if sig.asyncness.is_none() {
+        return Err(syn::Error::new(
+            sig.fn_token.span(),
+            "#[tx] takes an async fn",
+        ));
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            sig.generics.span(),
+            "#[tx] does not take generic fns",
+        ));
+    }
+
+    let mut inputs = sig.inputs.iter();
+    let (ctx, ctx_ty) = match inputs.next() {
+        Some(FnArg::Typed(arg)) => match &*arg.ty {
+            Type::Reference(r) if r.mutability.is_some() => (ident(&arg.pat)?, &*r.elem),
+            ty => {
+                return Err(syn::Error::new(
+                    ty.span(),
+                    "expected the context as `&mut Ctx`",
+                ))
+            }
+        },
+        Some(arg) => return Err(syn::Error::new(arg.span(), "#[tx] does not take methods")),
+        None => {
+            return Err(syn::Error::new(
+                sig.paren_token.span.join(),
+                "expected the context as the first argument",
+            ))
+        }
+    };
+    let mut names = vec![];
+    let mut types = vec![];
+    for arg in inputs {
+        match arg {
+            FnArg::Typed(arg) => {
+                names.push(ident(&arg.pat)?);
+                types.push(&*arg.ty);
+            }
+            FnArg::Receiver(arg) => {
+                return Err(syn::Error::new(arg.span(), "#[tx] does not take methods"))
+            }
+        }
+    }
+    let (item_ty, err_ty) = result_types(&sig.output)?;
+
+    let name = &sig.ident;
+    let step = Ident::new(&pascal_case(&name.to_string()), name.span());
+    let doc = format!("The step `{}` returns.", name);
+    let ctx_var = format_ident!("{}", ctx);
+
+    Ok(quote! {
+        #[doc = #doc]
+        #vis struct #step {
+            #(#names: #types,)*
+        }
+
+        #(#attrs)*
+        #vis fn #name(#(#names: #types),*) -> #step {
+            #step { #(#names,)* }
+        }
+
+        impl #krate::combinator::Tx<#ctx_ty> for #step {
+            type Item = #item_ty;
+            type Err = #err_ty;
+            type Mode = #krate::combinator::AsyncMode;
+
+            fn run<'a>(
+                self,
+                #ctx_var: &'a mut #ctx_ty,
+            ) -> #krate::combinator::BoxFuture<'a, ::std::result::Result<#item_ty, #err_ty>>
+            where
+                Self: 'a,
+            {
+                let #step { #(#names,)* } = self;
+                ::std::boxed::Box::pin(async move #block)
+            }
+        }
+    })
+}
+
+fn expand_test(args: TokenStream2, item: ItemFn) -> syn::Result<TokenStream2> {
+    let krate: syn::Path = syn::parse_quote!(::tx);
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = item;
+
+    if sig.asyncness.is_none() {
+        return Err(syn::Error::new(
+            sig.fn_token.span(),
+            "#[tx_test] takes an async fn",
+        ));
+    }
+    let (ctx, ctx_ty) = match (sig.inputs.first(), sig.inputs.len()) {
+        (Some(FnArg::Typed(arg)), 1) => match &*arg.ty {
+            Type::Reference(r) if r.mutability.is_some() => (ident(&arg.pat)?, &*r.elem),
+            ty => {
+                return Err(syn::Error::new(
+                    ty.span(),
+                    "expected the context as `&mut Ctx`",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new(
+                sig.inputs.span(),
+                "expected the context as the only argument",
+            ))
+        }
+    };
+    let name = &sig.ident;
+    let output = &sig.output;
+    let args = if args.is_empty() {
+        quote!()
+    } else {
+        quote!((#args))
+    };
+
+    Ok(quote! {
+        #(#attrs)*
+        #[::sqlx::test #args]
+        #vis async fn #name(
+            pool: ::sqlx::Pool<<#ctx_ty as #krate::testing::TestCtx>::Database>,
+        ) #output {
+            #krate::testing::tx_test(&pool, |#ctx: &mut #ctx_ty| {
+                ::std::boxed::Box::pin(async move #block)
+            })
+            .await
+        }
+    })
+}
+
+fn ident(pat: &Pat) -> syn::Result<Ident> {
+    match pat {
+        Pat::Ident(pat) if pat.by_ref.is_none() && pat.subpat.is_none() => Ok(pat.ident.clone()),
+        pat => Err(syn::Error::new(
+            pat.span(),
+            "#[tx] takes plain argument names",
+        )),
+    }
+}
+
+fn result_types(output: &ReturnType) -> syn::Result<(&Type, &Type)> {
+    let error = |span: Span| syn::Error::new(span, "expected the return type as `Result<T, E>`");
+    let ty = match output {
+        ReturnType::Type(_, ty) => ty,
+        ReturnType::Default => return Err(error(output.span())),
+    };
+    let segment = match &**ty {
+        Type::Path(path) => path.path.segments.last().ok_or_else(|| error(ty.span()))?,
+        _ => return Err(error(ty.span())),
+    };
+    let args = match &segment.arguments {
+        PathArguments::AngleBracketed(args) if segment.ident == "Result" => &args.args,
+        _ => return Err(error(ty.span())),
+    };
+    let mut types = args.iter().filter_map(|arg| match arg {
+        GenericArgument::Type(ty) => Some(ty),
+        _ => None,
+    });
+    match (types.next(), types.next(), types.next()) {
+        (Some(item), Some(err), None) => Ok((item, err)),
+        _ => Err(error(ty.span())),
+    }
+}
+
+fn pascal_case(name: &str) -> String {
+    name.split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}